@@ -40,6 +40,7 @@ pub enum PlacementCenterInterface {
 
     // placement inner interface
     ClusterStatus,
+    ClusterConfig,
     ListNode,
     RegisterNode,
     UnRegisterNode,
@@ -21,7 +21,7 @@ use prost::Message;
 use protocol::placement_center::generate::{
     common::CommonReply,
     placement::{
-        ClusterStatusReply, ClusterStatusRequest, DeleteIdempotentDataRequest, DeleteResourceConfigRequest, ExistsIdempotentDataReply, ExistsIdempotentDataRequest, GetResourceConfigReply, GetResourceConfigRequest, HeartbeatRequest, NodeListReply, NodeListRequest, RegisterNodeRequest, SendRaftConfChangeReply, SendRaftConfChangeRequest, SendRaftMessageReply, SendRaftMessageRequest, SetIdempotentDataRequest, SetResourceConfigRequest, UnRegisterNodeRequest
+        ClusterConfigReply, ClusterConfigRequest, ClusterStatusReply, ClusterStatusRequest, DeleteIdempotentDataRequest, DeleteResourceConfigRequest, ExistsIdempotentDataReply, ExistsIdempotentDataRequest, GetResourceConfigReply, GetResourceConfigRequest, HeartbeatRequest, NodeListReply, NodeListRequest, RegisterNodeRequest, SendRaftConfChangeReply, SendRaftConfChangeRequest, SendRaftMessageReply, SendRaftMessageRequest, SetIdempotentDataRequest, SetResourceConfigRequest, UnRegisterNodeRequest
     },
 };
 use std::sync::Arc;
@@ -51,6 +51,30 @@ pub async fn cluster_status(
     }
 }
 
+pub async fn cluster_config(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: ClusterConfigRequest,
+) -> Result<ClusterConfigReply, CommonError> {
+    let request_data = ClusterConfigRequest::encode_to_vec(&request);
+    match retry_call(
+        PlacementCenterService::Placement,
+        PlacementCenterInterface::ClusterConfig,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match ClusterConfigReply::decode(data.as_ref()) {
+            Ok(da) => return Ok(da),
+            Err(e) => return Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => {
+            return Err(e);
+        }
+    }
+}
 
 pub async fn node_list(
     client_poll: Arc<ClientPool>,
@@ -17,8 +17,8 @@ use prost::Message;
 use protocol::placement_center::generate::{
     common::CommonReply,
     placement::{
-        placement_center_service_client::PlacementCenterServiceClient, ClusterStatusReply,
-        ClusterStatusRequest, DeleteIdempotentDataRequest, DeleteResourceConfigRequest,
+        placement_center_service_client::PlacementCenterServiceClient, ClusterConfigReply,
+        ClusterConfigRequest, ClusterStatusReply, ClusterStatusRequest, DeleteIdempotentDataRequest, DeleteResourceConfigRequest,
         ExistsIdempotentDataReply, ExistsIdempotentDataRequest, GetResourceConfigReply,
         GetResourceConfigRequest, HeartbeatRequest, NodeListReply, NodeListRequest,
         RegisterNodeRequest, SendRaftConfChangeReply, SendRaftConfChangeRequest,
@@ -45,6 +45,23 @@ pub(crate) async fn inner_cluster_status(
     }
 }
 
+pub(crate) async fn inner_cluster_config(
+    mut client: PlacementCenterServiceClient<Channel>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match ClusterConfigRequest::decode(request.as_ref()) {
+        Ok(request) => match client.cluster_config(request).await {
+            Ok(result) => {
+                return Ok(ClusterConfigReply::encode_to_vec(&result.into_inner()));
+            }
+            Err(e) => return Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => {
+            return Err(CommonError::CommmonError(e.to_string()));
+        }
+    }
+}
+
 pub(crate) async fn inner_node_list(
     mut client: PlacementCenterServiceClient<Channel>,
     request: Vec<u8>,
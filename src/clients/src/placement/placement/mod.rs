@@ -14,9 +14,9 @@
 
 use common_base::error::common::CommonError;
 use inner::{
-    inner_cluster_status, inner_delete_idempotent, inner_delete_resource_config,
-    inner_exist_idempotent, inner_get_resource_config, inner_node_list, inner_set_idempotent,
-    inner_set_resource_config,
+    inner_cluster_config, inner_cluster_status, inner_delete_idempotent,
+    inner_delete_resource_config, inner_exist_idempotent, inner_get_resource_config,
+    inner_node_list, inner_set_idempotent, inner_set_resource_config,
 };
 use mobc::Manager;
 use protocol::placement_center::generate::placement::placement_center_service_client::PlacementCenterServiceClient;
@@ -47,6 +47,9 @@ pub(crate) async fn placement_interface_call(
                 PlacementCenterInterface::ClusterStatus => {
                     inner_cluster_status(client, request.clone()).await
                 }
+                PlacementCenterInterface::ClusterConfig => {
+                    inner_cluster_config(client, request.clone()).await
+                }
 
                 PlacementCenterInterface::ListNode => {
                     inner_node_list(client, request.clone()).await
@@ -14,7 +14,9 @@
 
 use clap::command;
 use clap::Parser;
-use common_base::config::placement_center::init_placement_center_conf_by_path;
+use common_base::config::placement_center::{
+    init_placement_center_conf_by_path, placement_center_conf,
+};
 use common_base::config::DEFAULT_PLACEMENT_CENTER_CONFIG;
 use common_base::logs::init_placement_center_log;
 use placement_center::PlacementCenter;
@@ -28,6 +30,10 @@ struct ArgsParams {
     /// MetaService Indicates the path of the configuration file
     #[arg(short, long, default_value_t=String::from(DEFAULT_PLACEMENT_CENTER_CONFIG))]
     conf: String,
+
+    /// Check that the storage round trips a canary key and a Raft entry before starting
+    #[arg(long)]
+    self_check: bool,
 }
 
 fn main() {
@@ -35,6 +41,15 @@ fn main() {
     init_placement_center_conf_by_path(&args.conf);
     init_placement_center_log();
     let (stop_send, _) = broadcast::channel(2);
-    let mut pc = PlacementCenter::new();
+    let mut pc = match PlacementCenter::new() {
+        Ok(pc) => pc,
+        Err(e) => panic!("{}", e),
+    };
+    // new() has already run the check when rocksdb.self_check is set
+    if args.self_check && !placement_center_conf().rocksdb.self_check {
+        if let Err(e) = pc.self_check() {
+            panic!("{}", e);
+        }
+    }
     pc.start(stop_send);
 }
@@ -14,7 +14,7 @@
 
 use super::{
     common::Log,
-    placement_center::{Heartbeat, Network, Node, Rocksdb, System},
+    placement_center::{Heartbeat, Network, Node, Raft, Rocksdb, System},
 };
 use toml::Table;
 
@@ -22,6 +22,10 @@ pub fn default_cluster_name() -> String {
     "placement-center".to_string()
 }
 
+pub fn default_bootstrap() -> bool {
+    true
+}
+
 pub fn default_node() -> Node {
     Node {
         node_id: default_node_id(),
@@ -42,6 +46,7 @@ pub fn default_network() -> Network {
     Network {
         grpc_port: default_grpc_port(),
         http_port: default_http_port(),
+        dns_cache_ttl_ms: default_dns_cache_ttl_ms(),
     }
 }
 
@@ -53,6 +58,10 @@ pub fn default_http_port() -> u32 {
     1227
 }
 
+pub fn default_dns_cache_ttl_ms() -> u64 {
+    30000
+}
+
 pub fn default_system() -> System {
     System {
         runtime_work_threads: default_runtime_work_threads(),
@@ -95,10 +104,99 @@ pub fn default_rocksdb() -> Rocksdb {
     Rocksdb {
         max_open_files: default_max_open_files(),
         data_path: default_data_path(),
-        ..Default::default()
+        profile: default_rocksdb_profile(),
+        write_buffer_size: default_write_buffer_size(),
+        max_write_buffer_number: default_max_write_buffer_number(),
+        target_file_size_base: default_target_file_size_base(),
+        level_zero_slowdown_writes_trigger: default_level_zero_slowdown_writes_trigger(),
+        level_zero_stop_writes_trigger: default_level_zero_stop_writes_trigger(),
+        compact_on_open: default_compact_on_open(),
+        repair_on_corruption: default_repair_on_corruption(),
+        enable_statistics: default_enable_statistics(),
+        value_codec: default_value_codec(),
+        flush_interval_ms: default_flush_interval_ms(),
+        open_retries: default_open_retries(),
+        open_retry_backoff_ms: default_open_retry_backoff_ms(),
+        universal_size_ratio: default_universal_size_ratio(),
+        universal_min_merge_width: default_universal_min_merge_width(),
+        universal_max_merge_width: default_universal_max_merge_width(),
+        universal_max_size_amplification_percent: default_universal_max_size_amplification_percent(),
+        self_check: default_self_check(),
     }
 }
 
+pub fn default_self_check() -> bool {
+    false
+}
+
+pub fn default_rocksdb_profile() -> String {
+    "default".to_string()
+}
+
+pub fn default_write_buffer_size() -> u64 {
+    64 * 1024 * 1024
+}
+
+pub fn default_max_write_buffer_number() -> i32 {
+    4
+}
+
+pub fn default_target_file_size_base() -> u64 {
+    64 * 1024 * 1024
+}
+
+pub fn default_level_zero_slowdown_writes_trigger() -> i32 {
+    20
+}
+
+pub fn default_level_zero_stop_writes_trigger() -> i32 {
+    36
+}
+
+pub fn default_compact_on_open() -> bool {
+    false
+}
+
+pub fn default_repair_on_corruption() -> bool {
+    false
+}
+
+pub fn default_enable_statistics() -> bool {
+    false
+}
+
+pub fn default_value_codec() -> String {
+    "json".to_string()
+}
+
+pub fn default_flush_interval_ms() -> u64 {
+    0
+}
+
+pub fn default_open_retries() -> u32 {
+    5
+}
+
+pub fn default_open_retry_backoff_ms() -> u64 {
+    100
+}
+
+pub fn default_universal_size_ratio() -> u32 {
+    1
+}
+
+pub fn default_universal_min_merge_width() -> u32 {
+    2
+}
+
+pub fn default_universal_max_merge_width() -> u32 {
+    0
+}
+
+pub fn default_universal_max_size_amplification_percent() -> u32 {
+    200
+}
+
 pub fn default_heartbeat() -> Heartbeat {
     Heartbeat {
         heartbeat_check_time_ms: default_heartbeat_check_time_ms(),
@@ -113,3 +211,108 @@ pub fn default_heartbeat_timeout_ms() -> u64 {
 pub fn default_heartbeat_check_time_ms() -> u64 {
     1000
 }
+
+pub fn default_raft() -> Raft {
+    Raft {
+        snapshot_entries: default_snapshot_entries(),
+        log_dir: default_raft_log_dir(),
+        log_max_size: default_raft_log_max_size(),
+        max_pending_proposals: default_max_pending_proposals(),
+        propose_timeout_ms: default_propose_timeout_ms(),
+        message_channel_size: default_message_channel_size(),
+        snapshot_chunk_size: default_snapshot_chunk_size(),
+        max_size_per_msg_bytes: default_max_size_per_msg_bytes(),
+        max_uncommitted_size_bytes: default_max_uncommitted_size_bytes(),
+        pre_vote: default_pre_vote(),
+        lease_read: default_lease_read(),
+        apply_batch_size: default_apply_batch_size(),
+        apply_queue_size: default_apply_queue_size(),
+        entry_cache_size: default_entry_cache_size(),
+        min_election_tick: default_min_election_tick(),
+        max_election_tick: default_max_election_tick(),
+        election_retries: default_election_retries(),
+        election_retry_backoff_ms: default_election_retry_backoff_ms(),
+        snapshot_retain_entries: default_snapshot_retain_entries(),
+        snapshot_path: default_snapshot_path(),
+    }
+}
+
+pub fn default_snapshot_entries() -> u64 {
+    1000
+}
+
+pub fn default_raft_log_dir() -> String {
+    "./logs/placement-center".to_string()
+}
+
+pub fn default_raft_log_max_size() -> u64 {
+    100 * 1024 * 1024
+}
+
+pub fn default_max_pending_proposals() -> u64 {
+    1024
+}
+
+pub fn default_propose_timeout_ms() -> u64 {
+    30000
+}
+
+pub fn default_message_channel_size() -> u64 {
+    1000
+}
+
+pub fn default_snapshot_chunk_size() -> u64 {
+    1024 * 1024
+}
+
+pub fn default_max_size_per_msg_bytes() -> u64 {
+    1024 * 1024
+}
+
+pub fn default_max_uncommitted_size_bytes() -> u64 {
+    0
+}
+
+pub fn default_pre_vote() -> bool {
+    true
+}
+
+pub fn default_lease_read() -> bool {
+    false
+}
+
+pub fn default_apply_batch_size() -> u64 {
+    100
+}
+
+pub fn default_apply_queue_size() -> u64 {
+    16
+}
+
+pub fn default_entry_cache_size() -> u64 {
+    1024
+}
+
+pub fn default_min_election_tick() -> u64 {
+    10
+}
+
+pub fn default_max_election_tick() -> u64 {
+    20
+}
+
+pub fn default_election_retries() -> u64 {
+    3
+}
+
+pub fn default_election_retry_backoff_ms() -> u64 {
+    200
+}
+
+pub fn default_snapshot_retain_entries() -> u64 {
+    0
+}
+
+pub fn default_snapshot_path() -> String {
+    "".to_string()
+}
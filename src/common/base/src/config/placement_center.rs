@@ -29,13 +29,32 @@
  */
 
 use super::default_placement_center::{
-    default_addr, default_cluster_name, default_data_path, default_grpc_port, default_heartbeat,
-    default_heartbeat_check_time_ms, default_heartbeat_timeout_ms, default_http_port, default_log,
-    default_max_open_files, default_network, default_node, default_node_id, default_nodes,
-    default_rocksdb, default_runtime_work_threads, default_system,
+    default_addr, default_apply_batch_size, default_apply_queue_size, default_bootstrap,
+    default_cluster_name, default_compact_on_open, default_data_path, default_dns_cache_ttl_ms,
+    default_election_retries, default_election_retry_backoff_ms, default_enable_statistics,
+    default_entry_cache_size, default_flush_interval_ms, default_grpc_port, default_heartbeat,
+    default_heartbeat_check_time_ms, default_heartbeat_timeout_ms, default_http_port,
+    default_lease_read, default_level_zero_slowdown_writes_trigger,
+    default_level_zero_stop_writes_trigger, default_log, default_max_election_tick,
+    default_max_open_files, default_max_pending_proposals, default_max_size_per_msg_bytes,
+    default_max_uncommitted_size_bytes, default_max_write_buffer_number,
+    default_message_channel_size, default_min_election_tick, default_network, default_node,
+    default_node_id, default_nodes, default_open_retries, default_open_retry_backoff_ms,
+    default_pre_vote, default_propose_timeout_ms, default_raft, default_raft_log_dir,
+    default_raft_log_max_size, default_repair_on_corruption, default_rocksdb,
+    default_rocksdb_profile, default_runtime_work_threads, default_self_check,
+    default_snapshot_chunk_size, default_snapshot_entries, default_snapshot_path,
+    default_snapshot_retain_entries, default_system, default_target_file_size_base,
+    default_universal_max_merge_width, default_universal_max_size_amplification_percent,
+    default_universal_min_merge_width, default_universal_size_ratio, default_value_codec,
+    default_write_buffer_size,
 };
+use crate::error::placement_center::PlacementCenterError;
 use crate::tools::{create_fold, read_file};
 use serde::{Deserialize, Serialize};
+use std::cmp;
+use std::collections::HashSet;
+use std::path::Path;
 use std::sync::OnceLock;
 use toml::Table;
 
@@ -45,6 +64,10 @@ use super::common::Log;
 pub struct PlacementCenterConfig {
     #[serde(default = "default_cluster_name")]
     pub cluster_name: String,
+    // Form a new cluster from `nodes` when there is no persisted Raft state,
+    // turn it off for nodes that join an existing cluster
+    #[serde(default = "default_bootstrap")]
+    pub bootstrap: bool,
     #[serde(default = "default_node")]
     pub node: Node,
     #[serde(default = "default_network")]
@@ -59,6 +82,168 @@ pub struct PlacementCenterConfig {
     pub rocksdb: Rocksdb,
     #[serde(default = "default_heartbeat")]
     pub heartbeat: Heartbeat,
+    #[serde(default = "default_raft")]
+    pub raft: Raft,
+}
+
+impl PlacementCenterConfig {
+    /// Address the local node advertises to its peers
+    pub fn local_addr(&self) -> String {
+        return format!("{}:{}", self.node.addr, self.network.grpc_port);
+    }
+
+    /// Check the invariants the Raft node relies on, so that misconfiguration is
+    /// reported before startup instead of panicking somewhere inside it.
+    pub fn validate(&self) -> Result<(), PlacementCenterError> {
+        if self.rocksdb.data_path.trim().is_empty() {
+            return Err(PlacementCenterError::DataPathNotConfigured);
+        }
+
+        if self.nodes.is_empty() {
+            return Err(PlacementCenterError::RaftNodesNotConfigured);
+        }
+
+        let mut node_ids = HashSet::new();
+        for (node_id, addr) in self.nodes.iter() {
+            let id = match node_id.trim().parse::<u64>() {
+                Ok(id) => id,
+                Err(_) => {
+                    return Err(PlacementCenterError::InvalidRaftNodeConfig(
+                        node_id.clone(),
+                        addr.to_string(),
+                    ));
+                }
+            };
+
+            let valid_addr = match addr.as_str() {
+                Some(addr) => addr.split_once(":").is_some(),
+                None => false,
+            };
+            if !valid_addr {
+                return Err(PlacementCenterError::InvalidRaftNodeConfig(
+                    node_id.clone(),
+                    addr.to_string(),
+                ));
+            }
+
+            if !node_ids.insert(id) {
+                return Err(PlacementCenterError::DuplicateRaftNodeId(id));
+            }
+        }
+
+        if node_ids.len() > 1 && !node_ids.contains(&self.node.node_id) {
+            return Err(PlacementCenterError::LocalNodeNotInRaftNodes(
+                self.node.node_id,
+            ));
+        }
+
+        // peers reach this node through the listed address, so it has to be the advertised one
+        if let Some(listed) = self.nodes.get(&self.node.node_id.to_string()) {
+            let listed = listed.as_str().unwrap_or_default().trim().to_string();
+            let advertised = self.local_addr();
+            if listed != advertised {
+                return Err(PlacementCenterError::LocalNodeAddrMismatch(
+                    self.node.node_id,
+                    listed,
+                    advertised,
+                ));
+            }
+        }
+
+        let max_size_per_msg = self.raft.max_size_per_msg_bytes;
+        if max_size_per_msg == 0 || max_size_per_msg > RAFT_MAX_SIZE_PER_MSG_CEILING {
+            return Err(PlacementCenterError::InvalidRaftMaxSizePerMsg(
+                max_size_per_msg,
+                RAFT_MAX_SIZE_PER_MSG_CEILING,
+            ));
+        }
+
+        let max_uncommitted_size = self.raft.max_uncommitted_size_bytes;
+        if max_uncommitted_size != 0 && max_uncommitted_size < max_size_per_msg {
+            return Err(PlacementCenterError::InvalidRaftMaxUncommittedSize(
+                max_uncommitted_size,
+                max_size_per_msg,
+            ));
+        }
+
+        if self.raft.propose_timeout_ms == 0 {
+            return Err(PlacementCenterError::InvalidRaftProposeTimeout);
+        }
+
+        if self.raft.message_channel_size == 0 {
+            return Err(PlacementCenterError::InvalidRaftMessageChannelSize);
+        }
+
+        // RocksDB owns the files of its data path
+        let snapshot_path = self.raft.snapshot_path.trim();
+        if !snapshot_path.is_empty()
+            && Path::new(snapshot_path) == Path::new(self.rocksdb.data_path.trim())
+        {
+            return Err(PlacementCenterError::InvalidRaftSnapshotPath(
+                self.raft.snapshot_path.clone(),
+            ));
+        }
+
+        let min_election_tick = self.raft.min_election_tick as usize;
+        let max_election_tick = self.raft.max_election_tick as usize;
+        if min_election_tick <= RAFT_HEARTBEAT_TICK
+            || min_election_tick < RAFT_ELECTION_TICK
+            || max_election_tick <= min_election_tick
+        {
+            return Err(PlacementCenterError::InvalidRaftElectionTick(
+                self.raft.min_election_tick,
+                self.raft.max_election_tick,
+            ));
+        }
+
+        // an empty codec keeps the json default
+        let value_codec = self.rocksdb.value_codec.as_str();
+        if !value_codec.is_empty()
+            && value_codec != ROCKSDB_VALUE_CODEC_JSON
+            && value_codec != ROCKSDB_VALUE_CODEC_BINCODE
+        {
+            return Err(PlacementCenterError::InvalidRocksDBValueCodec(
+                self.rocksdb.value_codec.clone(),
+            ));
+        }
+        self.validate_universal_compaction()?;
+        return Ok(());
+    }
+
+    fn validate_universal_compaction(&self) -> Result<(), PlacementCenterError> {
+        let rocksdb = &self.rocksdb;
+        for (name, value) in [
+            ("universal_size_ratio", rocksdb.universal_size_ratio),
+            ("universal_min_merge_width", rocksdb.universal_min_merge_width),
+            ("universal_max_merge_width", rocksdb.universal_max_merge_width),
+            (
+                "universal_max_size_amplification_percent",
+                rocksdb.universal_max_size_amplification_percent,
+            ),
+        ] {
+            if value > i32::MAX as u32 {
+                return Err(PlacementCenterError::InvalidRocksDBUniversalCompaction(
+                    format!("rocksdb.{} {} is larger than {}", name, value, i32::MAX),
+                ));
+            }
+        }
+
+        let min_merge_width = rocksdb.universal_min_merge_width;
+        if min_merge_width == 1 {
+            return Err(PlacementCenterError::InvalidRocksDBUniversalCompaction(
+                "rocksdb.universal_min_merge_width must be at least 2".to_string(),
+            ));
+        }
+        let max_merge_width = rocksdb.universal_max_merge_width;
+        if max_merge_width != 0 && max_merge_width < cmp::max(min_merge_width, 2) {
+            return Err(PlacementCenterError::InvalidRocksDBUniversalCompaction(format!(
+                "rocksdb.universal_max_merge_width {} is below the min merge width {}",
+                max_merge_width,
+                cmp::max(min_merge_width, 2)
+            )));
+        }
+        return Ok(());
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
@@ -77,6 +262,10 @@ pub struct Network {
     pub grpc_port: u32,
     #[serde(default = "default_http_port")]
     pub http_port: u32,
+    // Resolved addresses of the peers are cached this long, an address is also resolved
+    // again once the connection through it fails. 0 resolves on every new connection.
+    #[serde(default = "default_dns_cache_ttl_ms")]
+    pub dns_cache_ttl_ms: u64,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
@@ -99,8 +288,146 @@ pub struct Rocksdb {
     pub data_path: String,
     #[serde(default = "default_max_open_files")]
     pub max_open_files: Option<i32>,
+    // "default" uses the settings below, "high-throughput" ignores them in favour of
+    // large write buffers for dedicated machines
+    #[serde(default = "default_rocksdb_profile")]
+    pub profile: String,
+    #[serde(default = "default_write_buffer_size")]
+    pub write_buffer_size: u64,
+    #[serde(default = "default_max_write_buffer_number")]
+    pub max_write_buffer_number: i32,
+    #[serde(default = "default_target_file_size_base")]
+    pub target_file_size_base: u64,
+    #[serde(default = "default_level_zero_slowdown_writes_trigger")]
+    pub level_zero_slowdown_writes_trigger: i32,
+    #[serde(default = "default_level_zero_stop_writes_trigger")]
+    pub level_zero_stop_writes_trigger: i32,
+    // Run a full manual compaction of every column family right after opening,
+    // reclaiming the space left behind while auto compaction is disabled
+    #[serde(default = "default_compact_on_open")]
+    pub compact_on_open: bool,
+    // Try to repair the data directory when RocksDB fails to open it, data that
+    // cannot be recovered is dropped by the repair
+    #[serde(default = "default_repair_on_corruption")]
+    pub repair_on_corruption: bool,
+    // Collect the RocksDB internal statistics, this costs a few percent of throughput
+    #[serde(default = "default_enable_statistics")]
+    pub enable_statistics: bool,
+    // Encoding of the values written through the typed write and read calls, "json" or
+    // "bincode". Data written with one codec cannot be read back with the other.
+    #[serde(default = "default_value_codec")]
+    pub value_codec: String,
+    // Flush the WAL and memtables in the background every this many milliseconds,
+    // the writes of the state machine are not synced on their own. 0 disables it.
+    #[serde(default = "default_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+    // Retries when the data directory is still locked, e.g. by a process that is exiting.
+    // The wait before each retry starts at open_retry_backoff_ms and doubles every time.
+    #[serde(default = "default_open_retries")]
+    pub open_retries: u32,
+    #[serde(default = "default_open_retry_backoff_ms")]
+    pub open_retry_backoff_ms: u64,
+    // Options of the universal compaction, 0 keeps the RocksDB default of each of them.
+    // A merge takes at least universal_min_merge_width (>= 2) and at most
+    // universal_max_merge_width sorted runs.
+    #[serde(default = "default_universal_size_ratio")]
+    pub universal_size_ratio: u32,
+    #[serde(default = "default_universal_min_merge_width")]
+    pub universal_min_merge_width: u32,
+    #[serde(default = "default_universal_max_merge_width")]
+    pub universal_max_merge_width: u32,
+    #[serde(default = "default_universal_max_size_amplification_percent")]
+    pub universal_max_size_amplification_percent: u32,
+    // Write and read back a canary value in every column family and round trip a Raft
+    // entry at startup, the node does not start if any of it fails
+    #[serde(default = "default_self_check")]
+    pub self_check: bool,
 }
 
+pub const ROCKSDB_VALUE_CODEC_JSON: &str = "json";
+pub const ROCKSDB_VALUE_CODEC_BINCODE: &str = "bincode";
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct Raft {
+    // Take a snapshot and compact the log once this many entries have been applied
+    // since the last snapshot. 0 disables automatic snapshots.
+    #[serde(default = "default_snapshot_entries")]
+    pub snapshot_entries: u64,
+    // Directory of raft-node-{node_id}.log, the log of the Raft library itself.
+    #[serde(default = "default_raft_log_dir")]
+    pub log_dir: String,
+    // The Raft log is rolled to <file>.1 once it reaches this many bytes. 0 disables rotation.
+    #[serde(default = "default_raft_log_max_size")]
+    pub log_max_size: u64,
+    // Proposals waiting to be committed. Once this many are pending,
+    // new proposals are rejected until some of them are committed.
+    #[serde(default = "default_max_pending_proposals")]
+    pub max_pending_proposals: u64,
+    // Time a proposal may take to be committed and applied, the caller gets a timeout
+    // error once it has passed and the pending slot of the proposal is released.
+    #[serde(default = "default_propose_timeout_ms")]
+    pub propose_timeout_ms: u64,
+    // Messages queued for the Raft node, senders wait once this many are queued.
+    // Must be greater than 0.
+    #[serde(default = "default_message_channel_size")]
+    pub message_channel_size: u64,
+    // Snapshots larger than this are not sent inline with the Raft message,
+    // the follower pulls them through InstallSnapshot in chunks of this size.
+    #[serde(default = "default_snapshot_chunk_size")]
+    pub snapshot_chunk_size: u64,
+    // Limit of each appended Raft message, must be within (0, RAFT_MAX_SIZE_PER_MSG_CEILING].
+    #[serde(default = "default_max_size_per_msg_bytes")]
+    pub max_size_per_msg_bytes: u64,
+    // Bytes of uncommitted entries the leader accepts, proposals beyond it are dropped
+    // until some of them are committed. 0 disables the limit, otherwise it must be
+    // at least max_size_per_msg_bytes.
+    #[serde(default = "default_max_uncommitted_size_bytes")]
+    pub max_uncommitted_size_bytes: u64,
+    #[serde(default = "default_pre_vote")]
+    pub pre_vote: bool,
+    // Serve reads on the leader locally while its lease is valid
+    #[serde(default = "default_lease_read")]
+    pub lease_read: bool,
+    // Committed entries applied together, their Raft state is written to RocksDB in one batch
+    #[serde(default = "default_apply_batch_size")]
+    pub apply_batch_size: u64,
+    // Runs of apply_batch_size entries queued for the apply worker, the ready loop waits
+    // once this many are queued. 0 applies the entries in the ready loop itself.
+    #[serde(default = "default_apply_queue_size")]
+    pub apply_queue_size: u64,
+    // Most recently appended entries kept in memory for replication, 0 disables the cache
+    #[serde(default = "default_entry_cache_size")]
+    pub entry_cache_size: u64,
+    // Election timeouts are randomized within [min_election_tick, max_election_tick) ticks,
+    // min_election_tick must be at least RAFT_ELECTION_TICK and below max_election_tick.
+    #[serde(default = "default_min_election_tick")]
+    pub min_election_tick: u64,
+    #[serde(default = "default_max_election_tick")]
+    pub max_election_tick: u64,
+    // Rounds of asking the peers for the leader before giving up, the backoff between
+    // rounds starts at election_retry_backoff_ms and doubles each time, plus some jitter.
+    #[serde(default = "default_election_retries")]
+    pub election_retries: u64,
+    #[serde(default = "default_election_retry_backoff_ms")]
+    pub election_retry_backoff_ms: u64,
+    // Entries kept below the snapshot index when the log is compacted, a follower
+    // lagging by less than this catches up from the log instead of a full snapshot.
+    #[serde(default = "default_snapshot_retain_entries")]
+    pub snapshot_retain_entries: u64,
+    // Directory the snapshot data is written to as files, apart from the RocksDB data.
+    // Empty keeps the snapshot data in RocksDB.
+    #[serde(default = "default_snapshot_path")]
+    pub snapshot_path: String,
+}
+
+// Larger Raft messages cause huge memory spikes during replication
+pub const RAFT_MAX_SIZE_PER_MSG_CEILING: u64 = 64 * 1024 * 1024;
+
+// Ticks of the Raft node, the leader sends a heartbeat every RAFT_HEARTBEAT_TICK ticks
+// and a follower campaigns after RAFT_ELECTION_TICK ticks without hearing from it.
+pub const RAFT_ELECTION_TICK: usize = 10;
+pub const RAFT_HEARTBEAT_TICK: usize = 3;
+
 static PLACEMENT_CENTER_CONF: OnceLock<PlacementCenterConfig> = OnceLock::new();
 
 pub fn init_placement_center_conf_by_path(config_path: &String) -> &'static PlacementCenterConfig {
@@ -169,9 +496,32 @@ pub fn placement_center_conf() -> &'static PlacementCenterConfig {
 #[cfg(test)]
 mod tests {
     use super::{placement_center_conf, Log, PlacementCenterConfig};
-    use crate::config::placement_center::init_placement_center_conf_by_path;
+    use crate::config::default_placement_center::default_raft;
+    use crate::config::placement_center::{
+        init_placement_center_conf_by_path, RAFT_ELECTION_TICK, RAFT_MAX_SIZE_PER_MSG_CEILING,
+        ROCKSDB_VALUE_CODEC_BINCODE,
+    };
+    use crate::error::placement_center::PlacementCenterError;
     use toml::Table;
 
+    fn valid_config() -> PlacementCenterConfig {
+        let mut config = PlacementCenterConfig::default();
+        config.raft = default_raft();
+        config.node.node_id = 1;
+        config.node.addr = "127.0.0.1".to_string();
+        config.network.grpc_port = 1228;
+        config.rocksdb.data_path = "/tmp/robust/placement-center/data".to_string();
+        config.nodes.insert(
+            "1".to_string(),
+            toml::Value::String("127.0.0.1:1228".to_string()),
+        );
+        config.nodes.insert(
+            "2".to_string(),
+            toml::Value::String("127.0.0.1:2228".to_string()),
+        );
+        return config;
+    }
+
     #[test]
     fn meta_default() {
         let path = format!(
@@ -182,10 +532,12 @@ mod tests {
         let config: &PlacementCenterConfig = placement_center_conf();
         println!("{:?}", config);
         assert_eq!(config.cluster_name, "placement-test");
+        assert!(config.bootstrap);
         assert_eq!(config.node.node_id, 1);
         assert_eq!(config.node.addr, "127.0.0.1");
         assert_eq!(config.network.grpc_port, 1228);
         assert_eq!(config.network.http_port, 1227);
+        assert_eq!(config.network.dns_cache_ttl_ms, 30000);
         assert_eq!(config.system.runtime_work_threads, 100);
         println!("{}", config.rocksdb.data_path);
         println!("{}", "/tmp/robust/placement-center/data".to_string());
@@ -207,7 +559,270 @@ mod tests {
         );
         assert_eq!(config.nodes, nodes);
         assert_eq!(config.rocksdb.max_open_files, Some(10000 as i32));
+        assert_eq!(config.rocksdb.profile, "default".to_string());
+        assert_eq!(config.rocksdb.write_buffer_size, 67108864);
+        assert_eq!(config.rocksdb.max_write_buffer_number, 4);
+        assert_eq!(config.rocksdb.target_file_size_base, 67108864);
+        assert_eq!(config.rocksdb.level_zero_slowdown_writes_trigger, 20);
+        assert_eq!(config.rocksdb.level_zero_stop_writes_trigger, 36);
+        assert!(!config.rocksdb.compact_on_open);
+        assert!(!config.rocksdb.repair_on_corruption);
+        assert!(!config.rocksdb.enable_statistics);
+        assert_eq!(config.rocksdb.value_codec, "json".to_string());
+        assert_eq!(config.rocksdb.flush_interval_ms, 0);
+        assert_eq!(config.rocksdb.open_retries, 5);
+        assert_eq!(config.rocksdb.open_retry_backoff_ms, 100);
+        assert_eq!(config.rocksdb.universal_size_ratio, 1);
+        assert_eq!(config.rocksdb.universal_min_merge_width, 2);
+        assert_eq!(config.rocksdb.universal_max_merge_width, 0);
+        assert_eq!(config.rocksdb.universal_max_size_amplification_percent, 200);
+        assert!(!config.rocksdb.self_check);
         assert_eq!(config.heartbeat.heartbeat_timeout_ms, 30000);
         assert_eq!(config.heartbeat.heartbeat_check_time_ms, 1000);
+        assert_eq!(config.raft.snapshot_entries, 1000);
+        assert_eq!(config.raft.log_dir, "./logs/placement-center".to_string());
+        assert_eq!(config.raft.log_max_size, 104857600);
+        assert_eq!(config.raft.max_pending_proposals, 1024);
+        assert_eq!(config.raft.propose_timeout_ms, 30000);
+        assert_eq!(config.raft.message_channel_size, 1000);
+        assert_eq!(config.raft.snapshot_chunk_size, 1048576);
+        assert_eq!(config.raft.max_size_per_msg_bytes, 1048576);
+        assert_eq!(config.raft.max_uncommitted_size_bytes, 0);
+        assert!(config.raft.pre_vote);
+        assert!(!config.raft.lease_read);
+        assert_eq!(config.raft.apply_batch_size, 100);
+        assert_eq!(config.raft.apply_queue_size, 16);
+        assert_eq!(config.raft.entry_cache_size, 1024);
+        assert_eq!(config.raft.min_election_tick, 10);
+        assert_eq!(config.raft.max_election_tick, 20);
+        assert_eq!(config.raft.election_retries, 3);
+        assert_eq!(config.raft.election_retry_backoff_ms, 200);
+        assert_eq!(config.raft.snapshot_retain_entries, 0);
+        assert_eq!(config.raft.snapshot_path, "".to_string());
+    }
+
+    #[test]
+    fn validate_ok() {
+        assert!(valid_config().validate().is_ok());
+
+        // a single node cluster does not need to list the local node id
+        let mut config = valid_config();
+        config.nodes.remove("1");
+        config.node.node_id = 3;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_local_addr_mismatch() {
+        let mut config = valid_config();
+        config.network.grpc_port = 1229;
+        match config.validate() {
+            Err(PlacementCenterError::LocalNodeAddrMismatch(node_id, listed, advertised)) => {
+                assert_eq!(node_id, 1);
+                assert_eq!(listed, "127.0.0.1:1228".to_string());
+                assert_eq!(advertised, "127.0.0.1:1229".to_string());
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn validate_empty_data_path() {
+        let mut config = valid_config();
+        config.rocksdb.data_path = "".to_string();
+        match config.validate() {
+            Err(PlacementCenterError::DataPathNotConfigured) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn validate_empty_nodes() {
+        let mut config = valid_config();
+        config.nodes = Table::new();
+        match config.validate() {
+            Err(PlacementCenterError::RaftNodesNotConfigured) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn validate_invalid_node() {
+        let mut config = valid_config();
+        config.nodes.insert(
+            "node-3".to_string(),
+            toml::Value::String("127.0.0.1:3228".to_string()),
+        );
+        match config.validate() {
+            Err(PlacementCenterError::InvalidRaftNodeConfig(node_id, _)) => {
+                assert_eq!(node_id, "node-3")
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        let mut config = valid_config();
+        config
+            .nodes
+            .insert("3".to_string(), toml::Value::String("127.0.0.1".to_string()));
+        match config.validate() {
+            Err(PlacementCenterError::InvalidRaftNodeConfig(node_id, _)) => {
+                assert_eq!(node_id, "3")
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn validate_duplicate_node_id() {
+        let mut config = valid_config();
+        config.nodes.insert(
+            " 2".to_string(),
+            toml::Value::String("127.0.0.1:3228".to_string()),
+        );
+        match config.validate() {
+            Err(PlacementCenterError::DuplicateRaftNodeId(id)) => assert_eq!(id, 2),
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn validate_local_node_not_in_nodes() {
+        let mut config = valid_config();
+        config.node.node_id = 3;
+        match config.validate() {
+            Err(PlacementCenterError::LocalNodeNotInRaftNodes(id)) => assert_eq!(id, 3),
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn validate_election_tick() {
+        let config = valid_config();
+        assert_eq!(config.raft.min_election_tick, RAFT_ELECTION_TICK as u64);
+        assert!(config.validate().is_ok());
+
+        // below the election tick, equal bounds and an inverted range
+        for (min, max) in [(3, 20), (15, 15), (20, 10)] {
+            let mut config = valid_config();
+            config.raft.min_election_tick = min;
+            config.raft.max_election_tick = max;
+            match config.validate() {
+                Err(PlacementCenterError::InvalidRaftElectionTick(value_min, value_max)) => {
+                    assert_eq!(value_min, min);
+                    assert_eq!(value_max, max);
+                }
+                res => panic!("unexpected result {:?}", res),
+            }
+        }
+    }
+
+    #[test]
+    fn validate_max_size_per_msg() {
+        let config = valid_config();
+        assert_eq!(config.raft.max_size_per_msg_bytes, 1024 * 1024);
+        assert!(config.validate().is_ok());
+
+        for size in [0, 1024 * 1024 * 1024] {
+            let mut config = valid_config();
+            config.raft.max_size_per_msg_bytes = size;
+            match config.validate() {
+                Err(PlacementCenterError::InvalidRaftMaxSizePerMsg(value, ceiling)) => {
+                    assert_eq!(value, size);
+                    assert_eq!(ceiling, RAFT_MAX_SIZE_PER_MSG_CEILING);
+                }
+                res => panic!("unexpected result {:?}", res),
+            }
+        }
+    }
+
+    #[test]
+    fn validate_max_uncommitted_size() {
+        let mut config = valid_config();
+        config.raft.max_uncommitted_size_bytes = 1024 * 1024;
+        assert!(config.validate().is_ok());
+
+        config.raft.max_uncommitted_size_bytes = 1024;
+        match config.validate() {
+            Err(PlacementCenterError::InvalidRaftMaxUncommittedSize(value, max_size_per_msg)) => {
+                assert_eq!(value, 1024);
+                assert_eq!(max_size_per_msg, 1024 * 1024);
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn validate_propose_timeout() {
+        let mut config = valid_config();
+        config.raft.propose_timeout_ms = 0;
+        match config.validate() {
+            Err(PlacementCenterError::InvalidRaftProposeTimeout) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn validate_message_channel_size() {
+        let mut config = valid_config();
+        config.raft.message_channel_size = 0;
+        match config.validate() {
+            Err(PlacementCenterError::InvalidRaftMessageChannelSize) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn validate_snapshot_path() {
+        let mut config = valid_config();
+        config.raft.snapshot_path = format!("{}/snapshot", config.rocksdb.data_path);
+        assert!(config.validate().is_ok());
+
+        config.raft.snapshot_path = config.rocksdb.data_path.clone();
+        match config.validate() {
+            Err(PlacementCenterError::InvalidRaftSnapshotPath(path)) => {
+                assert_eq!(path, config.rocksdb.data_path);
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn validate_value_codec() {
+        let mut config = valid_config();
+        assert!(config.validate().is_ok());
+        config.rocksdb.value_codec = ROCKSDB_VALUE_CODEC_BINCODE.to_string();
+        assert!(config.validate().is_ok());
+
+        config.rocksdb.value_codec = "protobuf".to_string();
+        match config.validate() {
+            Err(PlacementCenterError::InvalidRocksDBValueCodec(codec)) => {
+                assert_eq!(codec, "protobuf".to_string());
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn validate_universal_compaction() {
+        let mut config = valid_config();
+        config.rocksdb.universal_size_ratio = 10;
+        config.rocksdb.universal_min_merge_width = 4;
+        config.rocksdb.universal_max_merge_width = 8;
+        config.rocksdb.universal_max_size_amplification_percent = 100;
+        assert!(config.validate().is_ok());
+
+        for (min, max) in [(1, 0), (4, 3), (0, 1)] {
+            let mut config = valid_config();
+            config.rocksdb.universal_min_merge_width = min;
+            config.rocksdb.universal_max_merge_width = max;
+            match config.validate() {
+                Err(PlacementCenterError::InvalidRocksDBUniversalCompaction(_)) => {}
+                res => panic!("unexpected result {:?}", res),
+            }
+        }
+
+        let mut config = valid_config();
+        config.rocksdb.universal_size_ratio = u32::MAX;
+        assert!(config.validate().is_err());
     }
 }
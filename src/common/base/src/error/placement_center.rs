@@ -18,4 +18,133 @@ use thiserror::Error;
 pub enum PlacementCenterError {
     #[error("Description The interface {0} submitted logs to the commit log")]
     RaftLogCommitTimeout(String),
+
+    #[error("Raft node id {0} is configured more than once")]
+    DuplicateRaftNodeId(u64),
+
+    #[error("No Raft nodes are configured, check the nodes section of the configuration file")]
+    RaftNodesNotConfigured,
+
+    #[error("Raft node configuration {0} = {1} is invalid, expected node_id = \"ip:port\"")]
+    InvalidRaftNodeConfig(String, String),
+
+    #[error("The local node id {0} is not present in the configured Raft nodes")]
+    LocalNodeNotInRaftNodes(u64),
+
+    #[error("Raft node {0} is listed at {1} but advertises {2}, check node.addr and network.grpc_port")]
+    LocalNodeAddrMismatch(u64, String, String),
+
+    #[error("rocksdb.data_path is not configured")]
+    DataPathNotConfigured,
+
+    #[error("Failed to start Raft node {0}, {1}")]
+    RaftNodeStartFailed(u64, String),
+
+    #[error("Failed to append the Raft log at index {0}, {1}")]
+    RaftLogAppendFailed(u64, String),
+
+    #[error("The Raft storage failed, the Raft node stops: {0}")]
+    RaftStorageFailed(String),
+
+    #[error("The Raft apply worker stopped, the Raft node stops")]
+    RaftApplyWorkerStopped,
+
+    #[error("Raft node {0} is not a voter of the Raft group")]
+    RaftNodeNotVoter(u64),
+
+    #[error("Raft node {0} is already a voter or learner of the Raft group")]
+    RaftNodeAlreadyMember(u64),
+
+    #[error("Raft node {0} is not a learner of the Raft group")]
+    RaftNodeNotLearner(u64),
+
+    #[error("Raft learner {0} has only replicated up to index {1}, the commit index is {2}")]
+    RaftLearnerNotCaughtUp(u64, u64, u64),
+
+    #[error("This Raft node is not the leader, the leader is node {leader_id} at {leader_addr}")]
+    NotLeader { leader_id: u64, leader_addr: String },
+
+    #[error("Raft node {0} is not the leader, only the leader can transfer the leadership")]
+    NotRaftLeader(u64),
+
+    #[error("Failed to transfer the Raft leadership to node {0}")]
+    RaftLeaderTransferFailed(u64),
+
+    #[error("Raft node failed to start a campaign, error message: {0}")]
+    RaftCampaignFailed(String),
+
+    #[error("Raft node {0} is the last voter of the Raft group and cannot be removed")]
+    RemoveLastRaftVoter(u64),
+
+    #[error("The Raft group is still in a joint configuration, the next membership change has to wait")]
+    RaftJointConsensusInProgress,
+
+    #[error("Too many pending Raft proposals, the interface {0} was rejected, retry later")]
+    RaftProposalQueueFull(String),
+
+    #[error("The proposal of interface {0} was dropped by Raft, the node may not be the leader")]
+    RaftProposalDropped(String),
+
+    #[error("The proposal of interface {0} was not applied within {1} ms")]
+    RaftProposalTimeout(String, u64),
+
+    #[error("The node is in read-only mode, the write of interface {0} was rejected")]
+    RaftReadOnly(String),
+
+    #[error("Failed to decode the Raft {0}, error message: {1}")]
+    RaftMessageDecodeFailed(String, String),
+
+    #[error("The Raft entries from index {0} to {1} have been compacted into the snapshot")]
+    RaftEntriesCompacted(u64, u64),
+
+    #[error("Storage self-check failed, {0}")]
+    StorageSelfCheckFailed(String),
+
+    #[error("Failed to open the storage, {0}")]
+    StorageOpenFailed(String),
+
+    #[error("Request {1} of client {0} is older than its last applied request {2}")]
+    RaftRequestOutdated(String, u64, u64),
+
+    #[error("Resetting the Raft storage wipes the Raft log and state of this node, it has to be confirmed")]
+    RaftStorageResetNotConfirmed,
+
+    #[error("The current value of key {0} does not match the expected value")]
+    CompareAndSwapMismatch(String),
+
+    #[error("Snapshot term {0} index {1} is not available on this node")]
+    SnapshotNotAvailable(u64, u64),
+
+    #[error("Snapshot chunk of term {0} index {1} does not belong to the snapshot being received")]
+    SnapshotChunkMismatch(u64, u64),
+
+    #[error("Snapshot chunk at offset {1} arrived out of order, expected offset {0}")]
+    SnapshotChunkOutOfOrder(u64, u64),
+
+    #[error("Failed to transfer snapshot index {0} from Raft node {1}")]
+    SnapshotTransferFailed(u64, u64),
+
+    #[error("raft.max_size_per_msg_bytes {0} is invalid, it must be greater than 0 and at most {1}")]
+    InvalidRaftMaxSizePerMsg(u64, u64),
+
+    #[error("raft.max_uncommitted_size_bytes {0} is invalid, it must be 0 or at least raft.max_size_per_msg_bytes {1}")]
+    InvalidRaftMaxUncommittedSize(u64, u64),
+
+    #[error("raft.min_election_tick {0} and raft.max_election_tick {1} are invalid, the election tick must be <= min < max")]
+    InvalidRaftElectionTick(u64, u64),
+
+    #[error("raft.propose_timeout_ms is invalid, it must be greater than 0")]
+    InvalidRaftProposeTimeout,
+
+    #[error("raft.message_channel_size is invalid, it must be greater than 0")]
+    InvalidRaftMessageChannelSize,
+
+    #[error("raft.snapshot_path {0} is invalid, it must not be the RocksDB data path")]
+    InvalidRaftSnapshotPath(String),
+
+    #[error("rocksdb.value_codec {0} is invalid, expected \"json\" or \"bincode\"")]
+    InvalidRocksDBValueCodec(String),
+
+    #[error("The universal compaction options are invalid, {0}")]
+    InvalidRocksDBUniversalCompaction(String),
 }
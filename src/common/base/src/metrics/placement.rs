@@ -0,0 +1,36 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use lazy_static::lazy_static;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+
+const ENTRY_TYPE: &str = "entry_type";
+
+lazy_static! {
+    static ref RAFT_UNDECODABLE_ENTRIES: IntCounterVec = register_int_counter_vec!(
+        "placement_raft_undecodable_entries",
+        "committed raft entries skipped because their data could not be decoded",
+        &[ENTRY_TYPE]
+    )
+    .unwrap();
+}
+
+pub fn metrics_raft_undecodable_entry(entry_type: &str) {
+    RAFT_UNDECODABLE_ENTRIES.with_label_values(&[entry_type]).inc();
+}
+
+pub fn raft_undecodable_entries(entry_type: &str) -> u64 {
+    return RAFT_UNDECODABLE_ENTRIES.with_label_values(&[entry_type]).get();
+}
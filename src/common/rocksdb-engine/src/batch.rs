@@ -0,0 +1,51 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// A write of RocksDBEngine::write_batch_multi_cf, the column family is given by name
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchOperation {
+    Put {
+        cf: String,
+        key: String,
+        value: Vec<u8>,
+    },
+    Delete {
+        cf: String,
+        key: String,
+    },
+}
+
+impl BatchOperation {
+    pub fn put(cf: &str, key: &str, value: Vec<u8>) -> Self {
+        return BatchOperation::Put {
+            cf: cf.to_string(),
+            key: key.to_string(),
+            value,
+        };
+    }
+
+    pub fn delete(cf: &str, key: &str) -> Self {
+        return BatchOperation::Delete {
+            cf: cf.to_string(),
+            key: key.to_string(),
+        };
+    }
+
+    pub fn cf(&self) -> &str {
+        match self {
+            BatchOperation::Put { cf, .. } => return cf,
+            BatchOperation::Delete { cf, .. } => return cf,
+        }
+    }
+}
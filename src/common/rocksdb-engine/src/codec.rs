@@ -0,0 +1,71 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::error::StorageError;
+use common_base::config::placement_center::{
+    ROCKSDB_VALUE_CODEC_BINCODE, ROCKSDB_VALUE_CODEC_JSON,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Encoding of the values written and read by the typed RocksDBEngine calls
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValueCodec {
+    // Human readable, the values can be inspected with read_all
+    #[default]
+    Json,
+    // Compact and faster to encode, the values are no longer valid UTF-8
+    Bincode,
+}
+
+impl ValueCodec {
+    /// Codec of a rocksdb.value_codec name, an empty name is the json default
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "" | ROCKSDB_VALUE_CODEC_JSON => return Some(ValueCodec::Json),
+            ROCKSDB_VALUE_CODEC_BINCODE => return Some(ValueCodec::Bincode),
+            _ => return None,
+        }
+    }
+
+    pub fn encode<T: Serialize + std::fmt::Debug>(
+        &self,
+        value: &T,
+    ) -> Result<Vec<u8>, StorageError> {
+        match self {
+            ValueCodec::Json => match serde_json::to_vec(value) {
+                Ok(data) => return Ok(data),
+                Err(err) => return Err(StorageError::Serialize(format!("{:?}", value), err)),
+            },
+            ValueCodec::Bincode => match bincode::serialize(value) {
+                Ok(data) => return Ok(data),
+                Err(err) => {
+                    return Err(StorageError::BincodeSerialize(format!("{:?}", value), err));
+                }
+            },
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, StorageError> {
+        match self {
+            ValueCodec::Json => match serde_json::from_slice::<T>(data) {
+                Ok(value) => return Ok(value),
+                Err(err) => return Err(StorageError::Deserialize(format!("{:?}", err))),
+            },
+            ValueCodec::Bincode => match bincode::deserialize::<T>(data) {
+                Ok(value) => return Ok(value),
+                Err(err) => return Err(StorageError::Deserialize(format!("{:?}", err))),
+            },
+        }
+    }
+}
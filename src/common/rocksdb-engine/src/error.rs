@@ -0,0 +1,71 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use common_base::error::common::CommonError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum StorageError {
+    #[error("{0}")]
+    Io(#[from] rocksdb::Error),
+
+    #[error("Failed to put to ColumnFamily:{0:?}")]
+    Write(rocksdb::Error),
+
+    #[error("Failed to get from ColumnFamily: {0:?}")]
+    Read(rocksdb::Error),
+
+    #[error("Failed to serialize to String. T: {0}, err: {1:?}")]
+    Serialize(String, serde_json::Error),
+
+    #[error("Failed to serialize with bincode. T: {0}, err: {1:?}")]
+    BincodeSerialize(String, bincode::Error),
+
+    #[error("Failed to deserialize: {0}")]
+    Deserialize(String),
+
+    #[error("ColumnFamily {0} does not exist")]
+    ColumnFamilyNotFound(String),
+
+    #[error("Failed to open RocksDB at {0}: {1}")]
+    OpenFailed(String, String),
+
+    #[error(
+        "RocksDB at {0} is still locked after {1} retries, another process may be using it: {2}"
+    )]
+    OpenRetriesExhausted(String, u32, String),
+
+    #[error("Failed to repair RocksDB at {0}: {1}")]
+    RepairFailed(String, String),
+
+    #[error("Checksum mismatch of the value stored at key {0}, the data may be corrupted")]
+    ChecksumMismatch(String),
+
+    #[error(
+        "The value stored at key {0} has the unknown format version {1}, it may have been written by a newer version"
+    )]
+    UnknownFormatVersion(String, u8),
+
+    #[error("Self-check of ColumnFamily {0} failed: {1}")]
+    SelfCheckFailed(String, String),
+
+    #[error("Failed to access the snapshot file {0}: {1}")]
+    SnapshotFile(String, std::io::Error),
+}
+
+impl From<StorageError> for CommonError {
+    fn from(e: StorageError) -> Self {
+        return CommonError::CommmonError(e.to_string());
+    }
+}
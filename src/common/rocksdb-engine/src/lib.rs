@@ -12,50 +12,327 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use common_base::error::common::CommonError;
-use log::error;
-use rocksdb::SliceTransform;
-use rocksdb::{ColumnFamily, DBCompactionStyle, Options, DB};
+use log::{error, info, warn};
+use rocksdb::{ErrorKind, SliceTransform};
+use rocksdb::{
+    ColumnFamily, ColumnFamilyDescriptor, DBCompactionStyle, IteratorMode, Options, ReadOptions, WriteOptions, DB,
+};
+pub use rocksdb::WriteBatch;
 use serde::{de::DeserializeOwned, Serialize};
-use serde_json;
+use std::cmp;
 use std::collections::HashMap;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub mod batch;
+pub mod codec;
+pub mod error;
+pub mod tuning;
+
+pub use batch::BatchOperation;
+pub use codec::ValueCodec;
+pub use error::StorageError;
+pub use tuning::{ColumnFamilyProfile, RocksDBTuning, UniversalCompaction};
 
 pub struct RocksDBEngine {
     pub db: DB,
     cf_list: Vec<String>,
+    // Number of write requests sent to RocksDB, a batch counts as one
+    write_calls: AtomicU64,
+    // Number of read requests sent to RocksDB, a multi read or prefix scan counts as one
+    read_calls: AtomicU64,
+    // Options the DB was opened with, they hold the statistics when enabled
+    opts: Options,
+    // Encoding of the values passed to write, write_sync, batch_put and read
+    codec: ValueCodec,
+}
+
+// Properties reported by RocksDBEngine::properties, summed over all column families
+pub const ROCKSDB_PROPERTIES: [&str; 6] = [
+    "rocksdb.estimate-num-keys",
+    "rocksdb.total-sst-files-size",
+    "rocksdb.num-files-at-level0",
+    "rocksdb.cur-size-all-mem-tables",
+    "rocksdb.estimate-live-data-size",
+    "rocksdb.estimate-pending-compaction-bytes",
+];
+
+// Key of the canary value written by RocksDBEngine::self_check
+const SELF_CHECK_KEY: &str = "/self_check/canary";
+
+// The LOCK file of the data directory is held by another process, or by another
+// instance in this process that has not been dropped yet
+fn is_lock_error(e: &rocksdb::Error) -> bool {
+    let message = e.to_string();
+    return e.kind() == ErrorKind::IOError
+        && (message.contains("While lock file")
+            || message.contains("lock hold by current process"));
 }
 
 impl RocksDBEngine {
-    /// Create a rocksdb instance
+    /// Create a rocksdb instance with the default tuning
     pub fn new(data_path: &str, max_open_files: i32, cf_list: Vec<String>) -> Self {
-        let opts: Options = Self::open_db_opts(max_open_files);
+        return RocksDBEngine::new_with_tuning(
+            data_path,
+            max_open_files,
+            cf_list,
+            &RocksDBTuning::default(),
+        );
+    }
+
+    /// Create a rocksdb instance, all column families share the same options
+    pub fn new_with_tuning(
+        data_path: &str,
+        max_open_files: i32,
+        cf_list: Vec<String>,
+        tuning: &RocksDBTuning,
+    ) -> Self {
+        return RocksDBEngine::new_with_cf_profiles(
+            data_path,
+            max_open_files,
+            cf_list,
+            tuning,
+            &HashMap::new(),
+        );
+    }
+
+    /// Create a rocksdb instance, the column families listed in cf_profiles get
+    /// their profile applied on top of the shared options. Panics when the instance
+    /// cannot be opened, it is meant for tests, servers call open and handle the error.
+    pub fn new_with_cf_profiles(
+        data_path: &str,
+        max_open_files: i32,
+        cf_list: Vec<String>,
+        tuning: &RocksDBTuning,
+        cf_profiles: &HashMap<String, ColumnFamilyProfile>,
+    ) -> Self {
+        match RocksDBEngine::open(data_path, max_open_files, cf_list, tuning, cf_profiles) {
+            Ok(engine) => return engine,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Open a rocksdb instance. When the data directory cannot be opened and
+    /// tuning.repair_on_corruption is set, it is repaired and opened once more.
+    pub fn open(
+        data_path: &str,
+        max_open_files: i32,
+        cf_list: Vec<String>,
+        tuning: &RocksDBTuning,
+        cf_profiles: &HashMap<String, ColumnFamilyProfile>,
+    ) -> Result<Self, StorageError> {
+        let opts: Options = Self::open_db_opts(max_open_files, tuning);
         let db_path = format!("{}/{}", data_path, "_storage_rocksdb");
 
+        let opened = Self::open_db_with_retry(&db_path, &opts, &cf_list, cf_profiles, tuning);
+        let instance = match opened {
+            Ok(instance) => instance,
+            Err(e) => {
+                // repairing cannot help while another instance holds the lock
+                if is_lock_error(&e) {
+                    return Err(StorageError::OpenRetriesExhausted(
+                        db_path,
+                        tuning.open_retries,
+                        e.to_string(),
+                    ));
+                }
+                if !tuning.repair_on_corruption {
+                    return Err(StorageError::OpenFailed(db_path, e.to_string()));
+                }
+                error!(
+                    "Failed to open RocksDB at {}, try to repair it. error message: {}",
+                    db_path, e
+                );
+                if let Err(e) = DB::repair(&opts, &db_path) {
+                    return Err(StorageError::RepairFailed(db_path, e.to_string()));
+                }
+                match Self::open_db(&db_path, &opts, &cf_list, cf_profiles) {
+                    Ok(instance) => {
+                        info!("RocksDB at {} has been repaired", db_path);
+                        instance
+                    }
+                    Err(e) => {
+                        return Err(StorageError::OpenFailed(db_path, e.to_string()));
+                    }
+                }
+            }
+        };
+
+        let engine = RocksDBEngine {
+            db: instance,
+            cf_list,
+            write_calls: AtomicU64::new(0),
+            read_calls: AtomicU64::new(0),
+            opts,
+            codec: tuning.value_codec,
+        };
+
+        if tuning.compact_on_open {
+            engine.compact_all();
+        }
+        return Ok(engine);
+    }
+
+    // Open the DB, retrying with a doubling backoff while its LOCK file is still held
+    fn open_db_with_retry(
+        db_path: &str,
+        opts: &Options,
+        cf_list: &[String],
+        cf_profiles: &HashMap<String, ColumnFamilyProfile>,
+        tuning: &RocksDBTuning,
+    ) -> Result<DB, rocksdb::Error> {
+        let mut backoff = Duration::from_millis(tuning.open_retry_backoff_ms);
+        let mut attempt = 0;
+        loop {
+            match Self::open_db(db_path, opts, cf_list, cf_profiles) {
+                Err(e) if is_lock_error(&e) && attempt < tuning.open_retries => {
+                    attempt += 1;
+                    warn!(
+                        "RocksDB at {} is locked, retry {}/{} in {:?}. error message: {}",
+                        db_path, attempt, tuning.open_retries, backoff, e
+                    );
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn open_db(
+        db_path: &str,
+        opts: &Options,
+        cf_list: &[String],
+        cf_profiles: &HashMap<String, ColumnFamilyProfile>,
+    ) -> Result<DB, rocksdb::Error> {
+        let cf_opts = |family: &str| -> Options {
+            let mut family_opts = opts.clone();
+            if let Some(profile) = cf_profiles.get(family) {
+                profile.apply(&mut family_opts);
+            }
+            return family_opts;
+        };
+
         // init RocksDB
-        if !Path::new(&db_path).exists() {
-            DB::open(&opts, db_path.clone()).unwrap();
+        if !Path::new(db_path).exists() {
+            DB::open(opts, db_path)?;
         }
 
         // init column family
-        let cf_list_existing = rocksdb::DB::list_cf(&opts, &db_path).unwrap();
-        let mut instance = DB::open_cf(&opts, db_path.clone(), &cf_list_existing).unwrap();
+        let cf_list_existing = DB::list_cf(opts, db_path)?;
+        let descriptors: Vec<ColumnFamilyDescriptor> = cf_list_existing
+            .iter()
+            .map(|family| ColumnFamilyDescriptor::new(family, cf_opts(family)))
+            .collect();
+        let mut instance = DB::open_cf_descriptors(opts, db_path, descriptors)?;
 
         for family in cf_list.iter() {
             if cf_list_existing.iter().find(|cf| cf == &family).is_none() {
-                match instance.create_cf(&family, &opts) {
-                    Ok(()) => {}
+                instance.create_cf(family, &cf_opts(family))?;
+            }
+        }
+        return Ok(instance);
+    }
+
+    /// Run a full manual compaction of the default and all configured column families
+    pub fn compact_all(&self) {
+        let start = Instant::now();
+        self.db.compact_range::<&[u8], &[u8]>(None, None);
+        for family in self.cf_list.iter() {
+            if let Some(cf) = self.db.cf_handle(family) {
+                self.db.compact_range_cf::<&[u8], &[u8]>(cf, None, None);
+            }
+        }
+        info!(
+            "RocksDB full compaction of {} column families finished in {}ms",
+            self.cf_list.len() + 1,
+            start.elapsed().as_millis()
+        );
+    }
+
+    /// Compact the whole key range of a single column family
+    pub fn compact_cf(&self, name: &str) -> Result<(), StorageError> {
+        let cf = self.cf_by_name(name)?;
+        let start = Instant::now();
+        self.db.compact_range_cf::<&[u8], &[u8]>(cf, None, None);
+        info!(
+            "RocksDB compaction of column family {} finished in {}ms",
+            name,
+            start.elapsed().as_millis()
+        );
+        return Ok(());
+    }
+
+    /// Sync the WAL to disk, then flush the memtables of the default and all configured
+    /// column families. The WAL goes first so the writes survive a failed memtable flush.
+    pub fn flush(&self) -> Result<(), StorageError> {
+        self.db.flush_wal(true)?;
+        self.db.flush()?;
+        for family in self.cf_list.iter() {
+            if let Some(cf) = self.db.cf_handle(family) {
+                self.db.flush_cf(cf)?;
+            }
+        }
+        return Ok(());
+    }
+
+    /// The RocksDB statistics dump, None unless tuning.enable_statistics is set
+    pub fn statistics_string(&self) -> Option<String> {
+        return self.opts.get_statistics();
+    }
+
+    /// Value of each of ROCKSDB_PROPERTIES over the default and all configured column families,
+    /// a property RocksDB does not report or that is not a number is left out.
+    pub fn properties(&self) -> HashMap<String, u64> {
+        let mut result = HashMap::new();
+        for name in ROCKSDB_PROPERTIES {
+            let mut values = vec![self.db.property_value(name)];
+            for family in self.cf_list.iter() {
+                if let Some(cf) = self.db.cf_handle(family) {
+                    values.push(self.db.property_value_cf(cf, name));
+                }
+            }
+
+            for value in values {
+                match value {
+                    Ok(Some(value)) => match value.trim().parse::<u64>() {
+                        Ok(val) => *result.entry(name.to_string()).or_insert(0) += val,
+                        Err(e) => {
+                            error!("RocksDB property {} is not a number: {}, {}", name, value, e);
+                        }
+                    },
+                    Ok(None) => {}
                     Err(e) => {
-                        panic!("{}", e);
+                        error!("Failed to read RocksDB property {}, error message: {}", name, e);
                     }
                 }
             }
         }
+        return result;
+    }
 
-        return RocksDBEngine {
-            db: instance,
-            cf_list,
-        };
+    /// Number of write requests sent to RocksDB since it was opened
+    pub fn write_calls(&self) -> u64 {
+        return self.write_calls.load(Ordering::Relaxed);
+    }
+
+    /// Number of read requests sent to RocksDB since it was opened
+    pub fn read_calls(&self) -> u64 {
+        return self.read_calls.load(Ordering::Relaxed);
+    }
+
+    fn count_write(&self) {
+        self.write_calls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn count_read(&self) {
+        self.read_calls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn codec(&self) -> ValueCodec {
+        return self.codec;
     }
 
     /// Write the data serialization to RocksDB
@@ -64,23 +341,117 @@ impl RocksDBEngine {
         cf: &ColumnFamily,
         key: &str,
         value: &T,
-    ) -> Result<(), String> {
-        match serde_json::to_string(&value) {
-            Ok(serialized) => self
-                .db
-                .put_cf(cf, key, serialized.into_bytes())
-                .map_err(|err| format!("Failed to put to ColumnFamily:{:?}", err)),
-            Err(err) => Err(format!(
-                "Failed to serialize to String. T: {:?}, err: {:?}",
-                value, err
-            )),
+    ) -> Result<(), StorageError> {
+        self.count_write();
+        let serialized = self.codec.encode(value)?;
+        self.db.put_cf(cf, key, serialized).map_err(StorageError::Write)
+    }
+
+    /// Write the data serialization to RocksDB and fsync the WAL before returning
+    pub fn write_sync<T: Serialize + std::fmt::Debug>(
+        &self,
+        cf: &ColumnFamily,
+        key: &str,
+        value: &T,
+    ) -> Result<(), StorageError> {
+        self.count_write();
+        let mut write_opts = WriteOptions::default();
+        write_opts.set_sync(true);
+        let serialized = self.codec.encode(value)?;
+        self.db
+            .put_cf_opt(cf, key, serialized, &write_opts)
+            .map_err(StorageError::Write)
+    }
+
+    /// Serialize the value into the batch, nothing reaches RocksDB until write_batch is called
+    pub fn batch_put<T: Serialize + std::fmt::Debug>(
+        &self,
+        batch: &mut WriteBatch,
+        cf: &ColumnFamily,
+        key: &str,
+        value: &T,
+    ) -> Result<(), StorageError> {
+        let serialized = self.codec.encode(value)?;
+        batch.put_cf(cf, key, serialized);
+        return Ok(());
+    }
+
+    /// Atomically apply every operation of the batch
+    pub fn write_batch(&self, batch: WriteBatch) -> Result<(), StorageError> {
+        self.count_write();
+        self.db.write(batch).map_err(StorageError::Write)
+    }
+
+    /// Atomically apply every operation of the batch and fsync the WAL before returning
+    pub fn write_batch_sync(&self, batch: WriteBatch) -> Result<(), StorageError> {
+        self.count_write();
+        let mut write_opts = WriteOptions::default();
+        write_opts.set_sync(true);
+        self.db
+            .write_opt(batch, &write_opts)
+            .map_err(StorageError::Write)
+    }
+
+    /// Atomically apply operations on several column families. Every column family is looked
+    /// up before anything is written, an unknown one fails the whole batch.
+    pub fn write_batch_multi_cf(&self, operations: &[BatchOperation]) -> Result<(), StorageError> {
+        let mut batch = WriteBatch::default();
+        for operation in operations {
+            let cf = self.cf_by_name(operation.cf())?;
+            match operation {
+                BatchOperation::Put { key, value, .. } => batch.put_cf(cf, key, value),
+                BatchOperation::Delete { key, .. } => batch.delete_cf(cf, key),
+            }
         }
+        return self.write_batch(batch);
     }
 
-    pub fn write_str(&self, cf: &ColumnFamily, key: &str, value: String) -> Result<(), String> {
+    pub fn write_str(
+        &self,
+        cf: &ColumnFamily,
+        key: &str,
+        value: String,
+    ) -> Result<(), StorageError> {
+        self.count_write();
         self.db
             .put_cf(cf, key, value.into_bytes())
-            .map_err(|err| format!("Failed to put to ColumnFamily:{:?}", err))
+            .map_err(StorageError::Write)
+    }
+
+    /// Write bytes as they are, for values that already have a compact encoding
+    pub fn write_raw(
+        &self,
+        cf: &ColumnFamily,
+        key: &str,
+        value: &[u8],
+    ) -> Result<(), StorageError> {
+        self.count_write();
+        self.db.put_cf(cf, key, value).map_err(StorageError::Write)
+    }
+
+    /// Write bytes as they are and fsync the WAL before returning
+    pub fn write_raw_sync(
+        &self,
+        cf: &ColumnFamily,
+        key: &str,
+        value: &[u8],
+    ) -> Result<(), StorageError> {
+        self.count_write();
+        let mut write_opts = WriteOptions::default();
+        write_opts.set_sync(true);
+        self.db
+            .put_cf_opt(cf, key, value, &write_opts)
+            .map_err(StorageError::Write)
+    }
+
+    // Read the bytes written by write_raw
+    pub fn read_raw(
+        &self,
+        cf: &ColumnFamily,
+        key: &str,
+    ) -> Result<Option<Vec<u8>>, StorageError> {
+        self.count_read();
+        self.db.get_cf(cf, key).map_err(StorageError::Read)
     }
 
     // Read data from the RocksDB
@@ -88,20 +459,35 @@ impl RocksDBEngine {
         &self,
         cf: &ColumnFamily,
         key: &str,
-    ) -> Result<Option<T>, String> {
+    ) -> Result<Option<T>, StorageError> {
+        self.count_read();
         match self.db.get_cf(cf, key) {
             Ok(opt) => match opt {
-                Some(found) => match String::from_utf8(found) {
-                    Ok(s) => match serde_json::from_str::<T>(&s) {
-                        Ok(t) => Ok(Some(t)),
-                        Err(err) => Err(format!("Failed to deserialize: {:?}", err)),
-                    },
-                    Err(err) => Err(format!("Failed to deserialize: {:?}", err)),
-                },
+                Some(found) => Ok(Some(self.codec.decode::<T>(&found)?)),
                 None => Ok(None),
             },
-            Err(err) => Err(format!("Failed to get from ColumnFamily: {:?}", err)),
+            Err(err) => Err(StorageError::Read(err)),
+        }
+    }
+
+    // Read the raw values of several keys in one batch, the result keeps the order of keys
+    pub fn multi_read(&self, cf: &ColumnFamily, keys: &[String]) -> Vec<Option<Vec<u8>>> {
+        self.count_read();
+        let values = self
+            .db
+            .multi_get_cf(keys.iter().map(|key| (cf, key.as_bytes())));
+
+        let mut result = Vec::with_capacity(keys.len());
+        for (key, value) in keys.iter().zip(values) {
+            match value {
+                Ok(data) => result.push(data),
+                Err(e) => {
+                    error!("Failed to read key {} from ColumnFamily: {:?}", key, e);
+                    result.push(None);
+                }
+            }
         }
+        return result;
     }
 
     // Search data by prefix
@@ -110,89 +496,213 @@ impl RocksDBEngine {
         cf: &ColumnFamily,
         search_key: &str,
     ) -> Vec<HashMap<String, Vec<u8>>> {
+        self.count_read();
         let mut iter = self.db.raw_iterator_cf(cf);
         iter.seek(search_key);
 
         let mut result = Vec::new();
         while iter.valid() {
-            let key = iter.key();
-            let value = iter.value();
+            // every branch falls through to iter.next(), so the loop always advances
+            if let (Some(key), Some(value)) = (iter.key(), iter.value()) {
+                if !key.starts_with(search_key.as_bytes()) {
+                    break;
+                }
+                match String::from_utf8(key.to_vec()) {
+                    Ok(result_key) => {
+                        let mut raw = HashMap::new();
+                        raw.insert(result_key, value.to_vec());
+                        result.push(raw);
+                    }
+                    Err(e) => {
+                        error!("Skip the key that is not valid UTF-8 in read_prefix: {}", e);
+                    }
+                }
+            }
+            iter.next();
+        }
+        return result;
+    }
+
+    /// Total size in bytes of the values of the keys starting with search_key,
+    /// the values are not copied out of RocksDB
+    pub fn prefix_value_size(&self, cf: &ColumnFamily, search_key: &str) -> u64 {
+        self.count_read();
+        let mut iter = self.db.raw_iterator_cf(cf);
+        iter.seek(search_key);
 
-            let mut raw = HashMap::new();
-            if key == None || value == None {
-                continue;
+        let mut size = 0;
+        while iter.valid() {
+            match iter.key() {
+                Some(key) if key.starts_with(search_key.as_bytes()) => {}
+                _ => break,
             }
-            let result_key = match String::from_utf8(key.unwrap().to_vec()) {
-                Ok(s) => s,
-                Err(_) => continue,
-            };
+            if let Some(value) = iter.value() {
+                size += value.len() as u64;
+            }
+            iter.next();
+        }
+        return size;
+    }
 
-            if !result_key.starts_with(search_key) {
-                break;
+    // Read at most limit key/value pairs in [start, end), in key order
+    pub fn scan_range(
+        &self,
+        cf: &ColumnFamily,
+        start: &str,
+        end: &str,
+        limit: usize,
+    ) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut result = Vec::new();
+        if limit == 0 || start >= end {
+            return result;
+        }
+
+        self.count_read();
+        let mut opts = ReadOptions::default();
+        opts.set_iterate_upper_bound(end.as_bytes().to_vec());
+        // the fixed prefix extractor would otherwise end the scan with the prefix of start
+        opts.set_total_order_seek(true);
+        let mut iter = self.db.raw_iterator_cf_opt(cf, opts);
+        iter.seek(start);
+        while iter.valid() && result.len() < limit {
+            if let (Some(key), Some(value)) = (iter.key(), iter.value()) {
+                result.push((key.to_vec(), value.to_vec()));
             }
-            raw.insert(result_key, value.unwrap().to_vec());
-            result.push(raw);
             iter.next();
         }
         return result;
     }
 
-    // Read data from all Columnfamiliy
-    pub fn read_all(&self) -> HashMap<String, Vec<HashMap<String, String>>> {
-        let mut result: HashMap<String, Vec<HashMap<String, String>>> = HashMap::new();
+    // Visit the key/value pairs in [start, end) in key order with a single forward
+    // iterator, until visit returns false
+    pub fn scan_range_while<F>(
+        &self,
+        cf: &ColumnFamily,
+        start: &str,
+        end: &str,
+        mut visit: F,
+    ) -> Result<(), StorageError>
+    where
+        F: FnMut(&[u8], &[u8]) -> bool,
+    {
+        if start >= end {
+            return Ok(());
+        }
+
+        self.count_read();
+        let mut opts = ReadOptions::default();
+        opts.set_iterate_upper_bound(end.as_bytes().to_vec());
+        opts.set_total_order_seek(true);
+        let mut iter = self.db.raw_iterator_cf_opt(cf, opts);
+        iter.seek(start);
+        while iter.valid() {
+            if let (Some(key), Some(value)) = (iter.key(), iter.value()) {
+                if !visit(key, value) {
+                    return Ok(());
+                }
+            }
+            iter.next();
+        }
+        return iter.status().map_err(StorageError::Read);
+    }
+
+    // Read the key/value pair with the greatest key that is less than or equal to key
+    pub fn read_le(&self, cf: &ColumnFamily, key: &str) -> Option<(Vec<u8>, Vec<u8>)> {
+        self.count_read();
+        let mut opts = ReadOptions::default();
+        // the fixed prefix extractor would otherwise stop the seek at the prefix of key
+        opts.set_total_order_seek(true);
+        let mut iter = self.db.raw_iterator_cf_opt(cf, opts);
+        iter.seek_for_prev(key);
+        if !iter.valid() {
+            return None;
+        }
+        match (iter.key(), iter.value()) {
+            (Some(found), Some(value)) => return Some((found.to_vec(), value.to_vec())),
+            _ => return None,
+        }
+    }
+
+    // Read the raw key/value pairs of every Columnfamiliy, the values are kept as they are
+    // stored whatever codec encoded them
+    pub fn read_all(&self) -> HashMap<String, Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut result: HashMap<String, Vec<(Vec<u8>, Vec<u8>)>> = HashMap::new();
         for family in self.cf_list.iter() {
-            let cf = self.get_column_family();
-            result.insert(family.to_string(), self.read_all_by_cf(cf));
+            if let Some(cf) = self.db.cf_handle(family) {
+                result.insert(family.to_string(), self.iter_cf(cf).collect());
+            }
         }
         return result;
     }
 
-    // Read all data in a ColumnFamily
+    // Read all data in a ColumnFamily, values that are not valid UTF-8 (such as
+    // bincode encoded ones) are logged and left out
     pub fn read_all_by_cf(&self, cf: &ColumnFamily) -> Vec<HashMap<String, String>> {
-        let mut iter = self.db.raw_iterator_cf(cf);
-        iter.seek_to_first();
-
         let mut result: Vec<HashMap<String, String>> = Vec::new();
-        while iter.valid() {
-            if let Some(key) = iter.key() {
-                if let Some(val) = iter.value() {
-                    match String::from_utf8(key.to_vec()) {
-                        Ok(key) => match String::from_utf8(val.to_vec()) {
-                            Ok(da) => {
-                                let mut raw: HashMap<String, String> = HashMap::new();
-                                raw.insert(key, da);
-                                result.push(raw);
-                            }
-                            Err(e) => {
-                                error!("{}", e);
-                            }
-                        },
-                        Err(e) => {
-                            error!("{}", e);
-                        }
+        for (key, val) in self.iter_cf(cf) {
+            match String::from_utf8(key) {
+                Ok(key) => match String::from_utf8(val) {
+                    Ok(da) => {
+                        let mut raw: HashMap<String, String> = HashMap::new();
+                        raw.insert(key, da);
+                        result.push(raw);
                     }
+                    Err(e) => {
+                        error!("{}", e);
+                    }
+                },
+                Err(e) => {
+                    error!("{}", e);
                 }
             }
-            iter.next();
         }
         return result;
     }
 
-    pub fn delete(&self, cf: &ColumnFamily, key: &str) -> Result<(), CommonError> {
+    // Lazily iterate over all key/value pairs in a ColumnFamily without buffering them.
+    // Iteration stops at the first read error.
+    pub fn iter_cf<'a>(
+        &'a self,
+        cf: &'a ColumnFamily,
+    ) -> impl Iterator<Item = (Vec<u8>, Vec<u8>)> + 'a {
+        return self
+            .db
+            .iterator_cf(cf, IteratorMode::Start)
+            .map_while(|item| match item {
+                Ok((key, value)) => Some((key.into_vec(), value.into_vec())),
+                Err(e) => {
+                    error!("Failed to iterate ColumnFamily: {:?}", e);
+                    None
+                }
+            });
+    }
+
+    pub fn delete(&self, cf: &ColumnFamily, key: &str) -> Result<(), StorageError> {
+        self.count_write();
         return Ok(self.db.delete_cf(cf, key)?);
     }
 
-    pub fn delete_prefix(&self, cf: &ColumnFamily, search_key: &str) -> Result<(), CommonError> {
-        let mut iter = self.db.raw_iterator_cf(cf);
+    // Delete all keys starting with search_key in a single batch, the keys after the
+    // prefix range are left untouched
+    pub fn delete_prefix(&self, cf: &ColumnFamily, search_key: &str) -> Result<(), StorageError> {
+        let mut opts = ReadOptions::default();
+        opts.set_total_order_seek(true);
+        let mut iter = self.db.raw_iterator_cf_opt(cf, opts);
         iter.seek(search_key);
 
+        let mut batch = WriteBatch::default();
         while iter.valid() {
-            if let Some(key) = iter.key() {
-                self.db.delete_cf(cf, key)?
+            match iter.key() {
+                Some(key) if key.starts_with(search_key.as_bytes()) => batch.delete_cf(cf, key),
+                _ => break,
             }
             iter.next();
         }
-        Ok(())
+        iter.status()?;
+        if batch.is_empty() {
+            return Ok(());
+        }
+        return self.write_batch(batch);
     }
 
     pub fn exist(&self, cf: &ColumnFamily, key: &str) -> bool {
@@ -207,7 +717,33 @@ impl RocksDBEngine {
         return self.db.cf_handle(&name);
     }
 
-    fn open_db_opts(max_open_files: i32) -> Options {
+    pub fn cf_by_name(&self, name: &str) -> Result<&ColumnFamily, StorageError> {
+        match self.db.cf_handle(&name) {
+            Some(cf) => return Ok(cf),
+            None => return Err(StorageError::ColumnFamilyNotFound(name.to_string())),
+        }
+    }
+
+    /// Write a canary value to each of the column families, read it back through the value
+    /// codec and delete it again. Fails on the first column family that does not round trip.
+    pub fn self_check(&self) -> Result<(), StorageError> {
+        for name in self.cf_list.iter() {
+            let cf = self.cf_by_name(name)?;
+            let canary = format!("self-check of {}", name);
+            self.write(cf, SELF_CHECK_KEY, &canary)?;
+            let value = self.read::<String>(cf, SELF_CHECK_KEY)?;
+            self.delete(cf, SELF_CHECK_KEY)?;
+            if value.as_ref() != Some(&canary) {
+                return Err(StorageError::SelfCheckFailed(
+                    name.clone(),
+                    format!("wrote {:?} but read back {:?}", canary, value),
+                ));
+            }
+        }
+        return Ok(());
+    }
+
+    fn open_db_opts(max_open_files: i32, tuning: &RocksDBTuning) -> Options {
         let mut opts = Options::default();
         opts.create_if_missing(true);
         opts.create_missing_column_families(true);
@@ -216,14 +752,18 @@ impl RocksDBEngine {
         opts.set_bytes_per_sync(8388608);
         opts.optimize_for_point_lookup(1024);
         opts.set_table_cache_num_shard_bits(6);
-        opts.set_max_write_buffer_number(32);
-        opts.set_write_buffer_size(536870912);
-        opts.set_target_file_size_base(1073741824);
-        opts.set_min_write_buffer_number_to_merge(4);
-        opts.set_level_zero_stop_writes_trigger(2000);
-        opts.set_level_zero_slowdown_writes_trigger(0);
+        opts.set_max_write_buffer_number(tuning.max_write_buffer_number);
+        opts.set_write_buffer_size(tuning.write_buffer_size);
+        opts.set_target_file_size_base(tuning.target_file_size_base);
+        opts.set_min_write_buffer_number_to_merge(cmp::min(4, tuning.max_write_buffer_number));
+        opts.set_level_zero_stop_writes_trigger(tuning.level_zero_stop_writes_trigger);
+        opts.set_level_zero_slowdown_writes_trigger(tuning.level_zero_slowdown_writes_trigger);
         opts.set_compaction_style(DBCompactionStyle::Universal);
+        tuning.universal_compaction.apply(&mut opts);
         opts.set_disable_auto_compactions(true);
+        if tuning.enable_statistics {
+            opts.enable_statistics();
+        }
 
         let transform = SliceTransform::create_fixed_prefix(10);
         opts.set_prefix_extractor(transform);
@@ -237,12 +777,25 @@ impl RocksDBEngine {
     }
 }
 
+// Writes are not synced one by one, flush them before the DB field is dropped and closed
+impl Drop for RocksDBEngine {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            error!("Failed to flush RocksDB before closing it, error message: {}", e);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::RocksDBEngine;
+    use super::{
+        BatchOperation, ColumnFamilyProfile, RocksDBEngine, RocksDBTuning, StorageError,
+        UniversalCompaction, ValueCodec, SELF_CHECK_KEY,
+    };
+    use common_base::config::default_placement_center::default_rocksdb;
     use common_base::{config::placement_center::PlacementCenterConfig, tools::unique_id};
     use serde::{Deserialize, Serialize};
-    use std::{sync::Arc, time::Duration};
+    use std::{collections::HashMap, sync::Arc, time::Duration};
     use tokio::{
         fs::{remove_dir, remove_dir_all},
         time::sleep,
@@ -409,4 +962,763 @@ mod tests {
 
         remove_dir_all(config.rocksdb.data_path).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn multi_read() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+        config.rocksdb.max_open_files = Some(10);
+
+        let rs = RocksDBEngine::new(
+            &config.rocksdb.data_path,
+            config.rocksdb.max_open_files.unwrap(),
+            vec!["cluster".to_string()],
+        );
+        let mut keys = Vec::new();
+        for i in 1..=5 {
+            let key = format!("/multi/{}", i);
+            rs.write_str(rs.cf_cluster(), &key, format!("v{}", i))
+                .unwrap();
+            keys.push(key);
+        }
+        keys.insert(2, "/multi/missing".to_string());
+
+        let result = rs.multi_read(rs.cf_cluster(), &keys);
+        assert_eq!(result.len(), 6);
+        assert_eq!(result[0], Some("v1".as_bytes().to_vec()));
+        assert_eq!(result[1], Some("v2".as_bytes().to_vec()));
+        assert_eq!(result[2], None);
+        assert_eq!(result[3], Some("v3".as_bytes().to_vec()));
+        assert_eq!(result[5], Some("v5".as_bytes().to_vec()));
+
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn storage_error_variants() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+        config.rocksdb.max_open_files = Some(10);
+
+        let rs = RocksDBEngine::new(
+            &config.rocksdb.data_path,
+            config.rocksdb.max_open_files.unwrap(),
+            vec!["cluster".to_string()],
+        );
+
+        rs.write_str(rs.cf_cluster(), "/user/1", "not a user".to_string())
+            .unwrap();
+        match rs.read::<User>(rs.cf_cluster(), "/user/1") {
+            Err(StorageError::Deserialize(msg)) => assert!(!msg.is_empty()),
+            _ => assert!(false),
+        }
+
+        match rs.cf_by_name("not_exist_cf") {
+            Err(StorageError::ColumnFamilyNotFound(name)) => assert_eq!(name, "not_exist_cf"),
+            _ => assert!(false),
+        }
+        assert!(rs.cf_by_name("cluster").is_ok());
+
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn iter_cf() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+        config.rocksdb.max_open_files = Some(10);
+
+        let rs = RocksDBEngine::new(
+            &config.rocksdb.data_path,
+            config.rocksdb.max_open_files.unwrap(),
+            vec!["cluster".to_string()],
+        );
+        assert_eq!(rs.iter_cf(rs.cf_cluster()).count(), 0);
+
+        for i in 0..10000 {
+            rs.write_str(rs.cf_cluster(), &format!("/key/{:05}", i), format!("{}", i))
+                .unwrap();
+        }
+
+        // keys come back in order, one pair at a time
+        let mut count = 0;
+        for (key, value) in rs.iter_cf(rs.cf_cluster()) {
+            assert_eq!(key, format!("/key/{:05}", count).into_bytes());
+            assert_eq!(value, format!("{}", count).into_bytes());
+            count += 1;
+        }
+        assert_eq!(count, 10000);
+        assert_eq!(rs.read_all_by_cf(rs.cf_cluster()).len(), 10000);
+
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn scan_range() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+
+        let rs = RocksDBEngine::new(&config.rocksdb.data_path, 10, vec!["cluster".to_string()]);
+        for i in 0..100 {
+            rs.write_str(rs.cf_cluster(), &format!("/offset/{:03}", i), format!("{}", i))
+                .unwrap();
+        }
+        rs.write_str(rs.cf_cluster(), "/other/key", "other".to_string())
+            .unwrap();
+
+        // end is exclusive
+        let result = rs.scan_range(rs.cf_cluster(), "/offset/010", "/offset/020", 100);
+        assert_eq!(result.len(), 10);
+        assert_eq!(result[0].0, b"/offset/010".to_vec());
+        assert_eq!(result[9].0, b"/offset/019".to_vec());
+        assert_eq!(result[9].1, b"19".to_vec());
+
+        // the limit is hit before the end of the range
+        let result = rs.scan_range(rs.cf_cluster(), "/offset/010", "/offset/090", 5);
+        assert_eq!(result.len(), 5);
+        assert_eq!(result[4].0, b"/offset/014".to_vec());
+
+        // the range may cross key prefixes
+        let result = rs.scan_range(rs.cf_cluster(), "/offset/098", "/z", 100);
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[2].0, b"/other/key".to_vec());
+
+        // empty ranges
+        assert!(rs
+            .scan_range(rs.cf_cluster(), "/offset/050", "/offset/050", 100)
+            .is_empty());
+        assert!(rs
+            .scan_range(rs.cf_cluster(), "/offset/060", "/offset/050", 100)
+            .is_empty());
+        assert!(rs
+            .scan_range(rs.cf_cluster(), "/none/", "/none/z", 100)
+            .is_empty());
+        assert!(rs
+            .scan_range(rs.cf_cluster(), "/offset/010", "/offset/020", 0)
+            .is_empty());
+
+        // the visit stops the scan
+        let mut keys = Vec::new();
+        rs.scan_range_while(rs.cf_cluster(), "/offset/010", "/offset/090", |key, _| {
+            keys.push(key.to_vec());
+            return keys.len() < 3;
+        })
+        .unwrap();
+        assert_eq!(keys.len(), 3);
+        assert_eq!(keys[2], b"/offset/012".to_vec());
+
+        let mut visited = 0;
+        rs.scan_range_while(rs.cf_cluster(), "/offset/095", "/offset/z", |_, _| {
+            visited += 1;
+            return true;
+        })
+        .unwrap();
+        assert_eq!(visited, 5);
+
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_le() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+
+        let rs = RocksDBEngine::new(&config.rocksdb.data_path, 10, vec!["cluster".to_string()]);
+        for key in ["10", "20", "30"] {
+            rs.write_str(rs.cf_cluster(), key, format!("value_{}", key))
+                .unwrap();
+        }
+
+        let (key, value) = rs.read_le(rs.cf_cluster(), "25").unwrap();
+        assert_eq!(key, b"20".to_vec());
+        assert_eq!(value, b"value_20".to_vec());
+
+        // an exact match is returned as it is
+        assert_eq!(rs.read_le(rs.cf_cluster(), "30").unwrap().0, b"30".to_vec());
+        assert_eq!(rs.read_le(rs.cf_cluster(), "99").unwrap().0, b"30".to_vec());
+        assert!(rs.read_le(rs.cf_cluster(), "05").is_none());
+
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn prefix_value_size() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+
+        let rs = RocksDBEngine::new(&config.rocksdb.data_path, 10, vec!["cluster".to_string()]);
+        assert_eq!(rs.prefix_value_size(rs.cf_cluster(), "/v1/"), 0);
+
+        rs.write_raw(rs.cf_cluster(), "/v1/a", &[0; 100]).unwrap();
+        rs.write_raw(rs.cf_cluster(), "/v1/b", &[0; 50]).unwrap();
+        rs.write_raw(rs.cf_cluster(), "/v2/a", &[0; 1000]).unwrap();
+        assert_eq!(rs.prefix_value_size(rs.cf_cluster(), "/v1/"), 150);
+        assert_eq!(rs.prefix_value_size(rs.cf_cluster(), "/v2/"), 1000);
+        assert_eq!(rs.prefix_value_size(rs.cf_cluster(), "/v3/"), 0);
+
+        drop(rs);
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_prefix_skip_invalid_key() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+        config.rocksdb.max_open_files = Some(10);
+
+        let rs = Arc::new(RocksDBEngine::new(
+            &config.rocksdb.data_path,
+            config.rocksdb.max_open_files.unwrap(),
+            vec!["cluster".to_string()],
+        ));
+        rs.write_str(rs.cf_cluster(), "/prefix/1", "1".to_string())
+            .unwrap();
+        // a key under the prefix that is not valid UTF-8
+        rs.db
+            .put_cf(rs.cf_cluster(), b"/prefix/\xff", b"invalid")
+            .unwrap();
+        rs.write_str(rs.cf_cluster(), "/prefix/2", "2".to_string())
+            .unwrap();
+        rs.write_str(rs.cf_cluster(), "/other/1", "1".to_string())
+            .unwrap();
+
+        let (sx, rx) = std::sync::mpsc::channel();
+        let engine = rs.clone();
+        std::thread::spawn(move || {
+            let _ = sx.send(engine.read_prefix(engine.cf_cluster(), "/prefix/"));
+        });
+        let result = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(result.len(), 2);
+        assert!(result[0].contains_key("/prefix/1"));
+        assert!(result[1].contains_key("/prefix/2"));
+
+        drop(rs);
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn open_with_minimal_tuning() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+        config.rocksdb.write_buffer_size = 4 * 1024 * 1024;
+        config.rocksdb.max_write_buffer_number = 2;
+        config.rocksdb.target_file_size_base = 4 * 1024 * 1024;
+        config.rocksdb.level_zero_slowdown_writes_trigger = 8;
+        config.rocksdb.level_zero_stop_writes_trigger = 12;
+
+        let tuning = RocksDBTuning::from(&config.rocksdb);
+        assert_eq!(tuning.write_buffer_size, 4 * 1024 * 1024);
+        assert_eq!(tuning.max_write_buffer_number, 2);
+        assert_eq!(tuning.level_zero_stop_writes_trigger, 12);
+
+        let rs = RocksDBEngine::new_with_tuning(
+            &config.rocksdb.data_path,
+            10,
+            vec!["cluster".to_string()],
+            &tuning,
+        );
+        rs.write(rs.cf_cluster(), "tuning", &"value".to_string())
+            .unwrap();
+        assert_eq!(
+            rs.read::<String>(rs.cf_cluster(), "tuning").unwrap(),
+            Some("value".to_string())
+        );
+
+        // the default tuning stays far below the high-throughput profile
+        let default_tuning = RocksDBTuning::default();
+        let high_throughput = RocksDBTuning::high_throughput();
+        assert!(
+            default_tuning.write_buffer_size * default_tuning.max_write_buffer_number as usize
+                <= 256 * 1024 * 1024
+        );
+        assert_eq!(high_throughput.write_buffer_size, 536870912);
+        assert_eq!(high_throughput.max_write_buffer_number, 32);
+
+        // the options the profile does not cover are still taken from the configuration
+        let mut high_throughput_conf = default_rocksdb();
+        high_throughput_conf.profile = "high-throughput".to_string();
+        assert_eq!(RocksDBTuning::from(&high_throughput_conf), high_throughput);
+
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn open_with_universal_compaction() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+        config.rocksdb.universal_size_ratio = 10;
+        config.rocksdb.universal_min_merge_width = 4;
+        config.rocksdb.universal_max_merge_width = 16;
+
+        let tuning = RocksDBTuning::from(&config.rocksdb);
+        assert_eq!(
+            tuning.universal_compaction,
+            UniversalCompaction {
+                size_ratio: Some(10),
+                min_merge_width: Some(4),
+                max_merge_width: Some(16),
+                max_size_amplification_percent: None,
+            }
+        );
+
+        let rs = RocksDBEngine::new_with_tuning(
+            &config.rocksdb.data_path,
+            10,
+            vec!["cluster".to_string()],
+            &tuning,
+        );
+        for i in 0..100 {
+            rs.write(rs.cf_cluster(), &format!("universal_{}", i), &i)
+                .unwrap();
+        }
+        rs.compact_all();
+        assert_eq!(
+            rs.read::<i32>(rs.cf_cluster(), "universal_99").unwrap(),
+            Some(99)
+        );
+
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn write_read_raw() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+
+        let rs = RocksDBEngine::new(&config.rocksdb.data_path, 10, vec!["cluster".to_string()]);
+        let value: Vec<u8> = vec![8, 1, 16, 2, 24, 3];
+        rs.write_raw(rs.cf_cluster(), "raw", &value).unwrap();
+        rs.write_raw_sync(rs.cf_cluster(), "raw_sync", &value).unwrap();
+        assert_eq!(rs.read_raw(rs.cf_cluster(), "raw").unwrap(), Some(value.clone()));
+        assert_eq!(rs.read_raw(rs.cf_cluster(), "raw_sync").unwrap(), Some(value));
+        assert!(rs.read_raw(rs.cf_cluster(), "not_exist").unwrap().is_none());
+        assert_eq!(rs.write_calls(), 2);
+        assert_eq!(rs.read_calls(), 3);
+
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn open_with_cf_profiles() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+        let cf_list = vec![
+            "cluster".to_string(),
+            "mqtt".to_string(),
+            "journal".to_string(),
+        ];
+        let mut cf_profiles = HashMap::new();
+        cf_profiles.insert(
+            "cluster".to_string(),
+            ColumnFamilyProfile::PointLookup { block_cache_mb: 16 },
+        );
+        cf_profiles.insert(
+            "mqtt".to_string(),
+            ColumnFamilyProfile::LargeBlockCache {
+                block_cache_size: 64 * 1024 * 1024,
+            },
+        );
+
+        // the first open creates the families, the second opens the existing ones
+        for round in 0..2 {
+            let rs = RocksDBEngine::new_with_cf_profiles(
+                &config.rocksdb.data_path,
+                10,
+                cf_list.clone(),
+                &RocksDBTuning::default(),
+                &cf_profiles,
+            );
+            for family in cf_list.iter() {
+                let cf = rs.cf_by_name(family).unwrap();
+                let key = format!("/{}/{}", family, round);
+                rs.write(cf, &key, &round).unwrap();
+                for i in 0..=round {
+                    let key = format!("/{}/{}", family, i);
+                    assert_eq!(rs.read::<u32>(cf, &key).unwrap(), Some(i));
+                }
+            }
+            drop(rs);
+        }
+
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn repair_on_corruption() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+        let cf_list = vec!["cluster".to_string()];
+
+        let rs = RocksDBEngine::new(&config.rocksdb.data_path, 10, cf_list.clone());
+        rs.write(rs.cf_cluster(), "/repair/1", &1u64).unwrap();
+        drop(rs);
+
+        // CURRENT names the manifest, without it the column families cannot be listed
+        let db_path = format!("{}/_storage_rocksdb", config.rocksdb.data_path);
+        std::fs::write(format!("{}/CURRENT", db_path), "MANIFEST-999999\n").unwrap();
+
+        let tuning = RocksDBTuning::from(&config.rocksdb);
+        assert!(!tuning.repair_on_corruption);
+        match RocksDBEngine::open(
+            &config.rocksdb.data_path,
+            10,
+            cf_list.clone(),
+            &tuning,
+            &HashMap::new(),
+        ) {
+            Err(StorageError::OpenFailed(path, _)) => assert_eq!(path, db_path),
+            _ => panic!("opening a corrupted RocksDB should fail"),
+        }
+
+        config.rocksdb.repair_on_corruption = true;
+        let tuning = RocksDBTuning::from(&config.rocksdb);
+        let rs = RocksDBEngine::open(
+            &config.rocksdb.data_path,
+            10,
+            cf_list,
+            &tuning,
+            &HashMap::new(),
+        )
+        .unwrap();
+        rs.write(rs.cf_cluster(), "/repair/2", &2u64).unwrap();
+        assert_eq!(rs.read::<u64>(rs.cf_cluster(), "/repair/2").unwrap(), Some(2));
+
+        drop(rs);
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn compact_on_open() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+        let cf_list = vec!["cluster".to_string(), "raft".to_string()];
+
+        let rs = RocksDBEngine::new(&config.rocksdb.data_path, 10, cf_list.clone());
+        for i in 0..2000 {
+            rs.write(rs.cf_cluster(), &format!("/compact/{}", i), &i)
+                .unwrap();
+        }
+        for i in 0..1900 {
+            rs.delete(rs.cf_cluster(), &format!("/compact/{}", i))
+                .unwrap();
+        }
+        drop(rs);
+
+        config.rocksdb.compact_on_open = true;
+        let tuning = RocksDBTuning::from(&config.rocksdb);
+        assert!(tuning.compact_on_open);
+        let rs = RocksDBEngine::new_with_tuning(&config.rocksdb.data_path, 10, cf_list, &tuning);
+        assert!(rs
+            .read::<u64>(rs.cf_cluster(), "/compact/10")
+            .unwrap()
+            .is_none());
+        for i in 1900..2000 {
+            assert_eq!(
+                rs.read::<u64>(rs.cf_cluster(), &format!("/compact/{}", i))
+                    .unwrap(),
+                Some(i)
+            );
+        }
+        rs.write(rs.cf_cluster(), "/compact/new", &1u64).unwrap();
+        assert_eq!(rs.read::<u64>(rs.cf_cluster(), "/compact/new").unwrap(), Some(1));
+
+        drop(rs);
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn compact_cf() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+        let cf_list = vec!["cluster".to_string(), "raft".to_string()];
+
+        let rs = RocksDBEngine::new(&config.rocksdb.data_path, 10, cf_list);
+        let raft_cf = rs.cf_by_name("raft").unwrap();
+        for i in 0..100 {
+            rs.write(raft_cf, &format!("/compact/{}", i), &i).unwrap();
+        }
+        for i in 0..50 {
+            rs.delete(raft_cf, &format!("/compact/{}", i)).unwrap();
+        }
+
+        rs.compact_cf("raft").unwrap();
+        assert!(rs.read::<u64>(raft_cf, "/compact/10").unwrap().is_none());
+        assert_eq!(rs.read::<u64>(raft_cf, "/compact/60").unwrap(), Some(60));
+        assert!(matches!(
+            rs.compact_cf("unknown"),
+            Err(StorageError::ColumnFamilyNotFound(_))
+        ));
+
+        drop(rs);
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn write_batch_multi_cf() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+        let cf_list = vec!["cluster".to_string(), "mqtt".to_string()];
+
+        let rs = RocksDBEngine::new(&config.rocksdb.data_path, 10, cf_list);
+        let mqtt_cf = rs.cf_by_name("mqtt").unwrap();
+        rs.write_raw(mqtt_cf, "/mqtt/session/2", b"old").unwrap();
+
+        // the unknown column family in the middle fails the batch, nothing is written
+        let operations = vec![
+            BatchOperation::put("cluster", "/raft/applied_index", b"10".to_vec()),
+            BatchOperation::put("unknown", "/unknown/1", b"1".to_vec()),
+            BatchOperation::put("mqtt", "/mqtt/session/1", b"session".to_vec()),
+            BatchOperation::delete("mqtt", "/mqtt/session/2"),
+        ];
+        assert!(matches!(
+            rs.write_batch_multi_cf(&operations),
+            Err(StorageError::ColumnFamilyNotFound(_))
+        ));
+        assert!(rs
+            .read_raw(rs.cf_cluster(), "/raft/applied_index")
+            .unwrap()
+            .is_none());
+        assert!(rs.read_raw(mqtt_cf, "/mqtt/session/1").unwrap().is_none());
+        assert!(rs.read_raw(mqtt_cf, "/mqtt/session/2").unwrap().is_some());
+
+        let operations: Vec<BatchOperation> = operations
+            .into_iter()
+            .filter(|operation| operation.cf() != "unknown")
+            .collect();
+        rs.write_batch_multi_cf(&operations).unwrap();
+        assert_eq!(
+            rs.read_raw(rs.cf_cluster(), "/raft/applied_index").unwrap(),
+            Some(b"10".to_vec())
+        );
+        assert_eq!(
+            rs.read_raw(mqtt_cf, "/mqtt/session/1").unwrap(),
+            Some(b"session".to_vec())
+        );
+        assert!(rs.read_raw(mqtt_cf, "/mqtt/session/2").unwrap().is_none());
+
+        drop(rs);
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn self_check() {
+        for codec in [ValueCodec::Json, ValueCodec::Bincode] {
+            let mut config = PlacementCenterConfig::default();
+            config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+            let cf_list = vec!["cluster".to_string(), "mqtt".to_string()];
+            let tuning = RocksDBTuning {
+                value_codec: codec,
+                ..Default::default()
+            };
+            let rs =
+                RocksDBEngine::new_with_tuning(&config.rocksdb.data_path, 10, cf_list, &tuning);
+
+            rs.self_check().unwrap();
+            // the canary values are gone again
+            for name in ["cluster", "mqtt"] {
+                let cf = rs.cf_by_name(name).unwrap();
+                assert!(!rs.exist(cf, SELF_CHECK_KEY));
+            }
+
+            drop(rs);
+            remove_dir_all(config.rocksdb.data_path).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn delete_prefix() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+        let cf_list = vec!["cluster".to_string()];
+
+        let rs = RocksDBEngine::new(&config.rocksdb.data_path, 10, cf_list);
+        for i in 0..10 {
+            rs.write(rs.cf_cluster(), &format!("/offset/topic1/{}", i), &i)
+                .unwrap();
+        }
+        // sorts right after the prefix range
+        rs.write(rs.cf_cluster(), "/offset/topic2/0", &0u64).unwrap();
+
+        rs.delete_prefix(rs.cf_cluster(), "/offset/topic1/").unwrap();
+        assert!(rs.read_prefix(rs.cf_cluster(), "/offset/topic1/").is_empty());
+        assert_eq!(
+            rs.read::<u64>(rs.cf_cluster(), "/offset/topic2/0").unwrap(),
+            Some(0)
+        );
+
+        // nothing left under the prefix
+        rs.delete_prefix(rs.cf_cluster(), "/offset/topic1/").unwrap();
+
+        drop(rs);
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn flush_on_drop() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+        let cf_list = vec!["cluster".to_string(), "raft".to_string()];
+
+        let rs = RocksDBEngine::new(&config.rocksdb.data_path, 10, cf_list.clone());
+        let raft_cf = rs.cf_by_name("raft").unwrap();
+        for i in 0..100 {
+            rs.write(rs.cf_cluster(), &format!("/flush/{}", i), &i)
+                .unwrap();
+            rs.write(raft_cf, &format!("/flush/{}", i), &i).unwrap();
+        }
+        drop(rs);
+
+        let rs = RocksDBEngine::new(&config.rocksdb.data_path, 10, cf_list);
+        let raft_cf = rs.cf_by_name("raft").unwrap();
+        for i in 0..100 {
+            let key = format!("/flush/{}", i);
+            assert_eq!(rs.read::<u64>(rs.cf_cluster(), &key).unwrap(), Some(i));
+            assert_eq!(rs.read::<u64>(raft_cf, &key).unwrap(), Some(i));
+        }
+        rs.flush().unwrap();
+
+        drop(rs);
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn statistics() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+        let cf_list = vec!["cluster".to_string()];
+
+        let rs = RocksDBEngine::new(&config.rocksdb.data_path, 10, cf_list.clone());
+        assert!(rs.statistics_string().is_none());
+        drop(rs);
+
+        config.rocksdb.enable_statistics = true;
+        let tuning = RocksDBTuning::from(&config.rocksdb);
+        let rs = RocksDBEngine::new_with_tuning(&config.rocksdb.data_path, 10, cf_list, &tuning);
+        for i in 0..100 {
+            rs.write(rs.cf_cluster(), &format!("/statistics/{}", i), &i)
+                .unwrap();
+        }
+        assert_eq!(rs.read::<u64>(rs.cf_cluster(), "/statistics/1").unwrap(), Some(1));
+
+        let stats = rs.statistics_string().unwrap();
+        assert!(!stats.is_empty());
+        assert!(stats.contains("rocksdb.block.cache.hit"));
+        assert!(stats.contains("rocksdb.bytes.written"));
+        assert!(stats.contains("rocksdb.compact.write.bytes"));
+
+        let properties = rs.properties();
+        assert!(properties["rocksdb.estimate-num-keys"] >= 1);
+        assert!(properties["rocksdb.cur-size-all-mem-tables"] > 0);
+        assert!(properties.contains_key("rocksdb.total-sst-files-size"));
+
+        drop(rs);
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn value_codec() {
+        let user = User {
+            name: "lobo".to_string(),
+            age: 18,
+        };
+        for codec in [ValueCodec::Json, ValueCodec::Bincode] {
+            let data = codec.encode(&user).unwrap();
+            assert_eq!(codec.decode::<User>(&data).unwrap(), user);
+        }
+        let json = ValueCodec::Json.encode(&user).unwrap();
+        let bincode = ValueCodec::Bincode.encode(&user).unwrap();
+        assert!(bincode.len() < json.len());
+        assert!(ValueCodec::Json.decode::<User>(&bincode).is_err());
+
+        let mut config = PlacementCenterConfig::default();
+        assert_eq!(ValueCodec::from_name(&config.rocksdb.value_codec), Some(ValueCodec::Json));
+        assert_eq!(ValueCodec::from_name("bincode"), Some(ValueCodec::Bincode));
+        assert_eq!(ValueCodec::from_name("protobuf"), None);
+
+        for name in ["json", "bincode"] {
+            config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+            config.rocksdb.value_codec = name.to_string();
+            let tuning = RocksDBTuning::from(&config.rocksdb);
+            let rs = RocksDBEngine::new_with_tuning(
+                &config.rocksdb.data_path,
+                10,
+                vec!["cluster".to_string()],
+                &tuning,
+            );
+            assert_eq!(Some(rs.codec()), ValueCodec::from_name(name));
+
+            rs.write(rs.cf_cluster(), "/codec/write", &user).unwrap();
+            rs.write_sync(rs.cf_cluster(), "/codec/write_sync", &user)
+                .unwrap();
+            let mut batch = super::WriteBatch::default();
+            rs.batch_put(&mut batch, rs.cf_cluster(), "/codec/batch_put", &user)
+                .unwrap();
+            rs.write_batch(batch).unwrap();
+            let mut batch = super::WriteBatch::default();
+            rs.batch_put(&mut batch, rs.cf_cluster(), "/codec/batch_put_sync", &user)
+                .unwrap();
+            rs.write_batch_sync(batch).unwrap();
+
+            for key in [
+                "/codec/write",
+                "/codec/write_sync",
+                "/codec/batch_put",
+                "/codec/batch_put_sync",
+            ] {
+                let found = rs.read::<User>(rs.cf_cluster(), key).unwrap();
+                assert_eq!(found.unwrap(), user);
+                let raw = rs.read_raw(rs.cf_cluster(), key).unwrap().unwrap();
+                assert_eq!(raw, rs.codec().encode(&user).unwrap());
+            }
+
+            drop(rs);
+            remove_dir_all(config.rocksdb.data_path.clone()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn open_retry_on_lock() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+        let cf_list = vec!["cluster".to_string()];
+        let rs = RocksDBEngine::new(&config.rocksdb.data_path, 10, cf_list.clone());
+        rs.write(rs.cf_cluster(), "/open_retry", &1).unwrap();
+
+        // the lock is held for longer than the retries last
+        let mut tuning = RocksDBTuning::default();
+        tuning.open_retries = 1;
+        tuning.open_retry_backoff_ms = 10;
+        let res = RocksDBEngine::open(
+            &config.rocksdb.data_path,
+            10,
+            cf_list.clone(),
+            &tuning,
+            &HashMap::new(),
+        );
+        match res {
+            Err(StorageError::OpenRetriesExhausted(_, retries, _)) => assert_eq!(retries, 1),
+            res => panic!("unexpected result {:?}", res.err()),
+        }
+
+        // the lock is released while retrying
+        let holder = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            drop(rs);
+        });
+        tuning.open_retries = 5;
+        tuning.open_retry_backoff_ms = 50;
+        let rs = RocksDBEngine::open(
+            &config.rocksdb.data_path,
+            10,
+            cf_list,
+            &tuning,
+            &HashMap::new(),
+        )
+        .unwrap();
+        holder.join().unwrap();
+        assert_eq!(rs.read::<u64>(rs.cf_cluster(), "/open_retry").unwrap(), Some(1));
+
+        drop(rs);
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
 }
@@ -0,0 +1,184 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::codec::ValueCodec;
+use common_base::config::default_placement_center::{
+    default_open_retries, default_open_retry_backoff_ms, default_rocksdb,
+};
+use common_base::config::placement_center::Rocksdb;
+use log::error;
+use rocksdb::{BlockBasedOptions, Cache, Options, UniversalCompactOptions};
+
+pub const ROCKSDB_PROFILE_HIGH_THROUGHPUT: &str = "high-throughput";
+
+/// Memory and compaction related RocksDB settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct RocksDBTuning {
+    pub write_buffer_size: usize,
+    pub max_write_buffer_number: i32,
+    pub target_file_size_base: u64,
+    pub level_zero_slowdown_writes_trigger: i32,
+    pub level_zero_stop_writes_trigger: i32,
+    pub compact_on_open: bool,
+    pub repair_on_corruption: bool,
+    pub enable_statistics: bool,
+    pub value_codec: ValueCodec,
+    // Retries while the data directory is locked, the backoff doubles after each of them
+    pub open_retries: u32,
+    pub open_retry_backoff_ms: u64,
+    pub universal_compaction: UniversalCompaction,
+}
+
+impl RocksDBTuning {
+    /// Large write buffers for dedicated machines, needs several GB of memory
+    pub fn high_throughput() -> Self {
+        return RocksDBTuning {
+            write_buffer_size: 536870912,
+            max_write_buffer_number: 32,
+            target_file_size_base: 1073741824,
+            level_zero_slowdown_writes_trigger: 0,
+            level_zero_stop_writes_trigger: 2000,
+            compact_on_open: false,
+            repair_on_corruption: false,
+            enable_statistics: false,
+            value_codec: ValueCodec::Json,
+            open_retries: default_open_retries(),
+            open_retry_backoff_ms: default_open_retry_backoff_ms(),
+            universal_compaction: UniversalCompaction::from(&default_rocksdb()),
+        };
+    }
+}
+
+impl Default for RocksDBTuning {
+    fn default() -> Self {
+        return RocksDBTuning::from(&default_rocksdb());
+    }
+}
+
+impl From<&Rocksdb> for RocksDBTuning {
+    fn from(conf: &Rocksdb) -> Self {
+        let value_codec = match ValueCodec::from_name(&conf.value_codec) {
+            Some(codec) => codec,
+            None => {
+                error!(
+                    "Unknown rocksdb.value_codec {}, values are encoded as json",
+                    conf.value_codec
+                );
+                ValueCodec::Json
+            }
+        };
+        if conf.profile == ROCKSDB_PROFILE_HIGH_THROUGHPUT {
+            return RocksDBTuning {
+                compact_on_open: conf.compact_on_open,
+                repair_on_corruption: conf.repair_on_corruption,
+                enable_statistics: conf.enable_statistics,
+                value_codec,
+                open_retries: conf.open_retries,
+                open_retry_backoff_ms: conf.open_retry_backoff_ms,
+                universal_compaction: UniversalCompaction::from(conf),
+                ..RocksDBTuning::high_throughput()
+            };
+        }
+        return RocksDBTuning {
+            write_buffer_size: conf.write_buffer_size as usize,
+            max_write_buffer_number: conf.max_write_buffer_number,
+            target_file_size_base: conf.target_file_size_base,
+            level_zero_slowdown_writes_trigger: conf.level_zero_slowdown_writes_trigger,
+            level_zero_stop_writes_trigger: conf.level_zero_stop_writes_trigger,
+            compact_on_open: conf.compact_on_open,
+            repair_on_corruption: conf.repair_on_corruption,
+            enable_statistics: conf.enable_statistics,
+            value_codec,
+            open_retries: conf.open_retries,
+            open_retry_backoff_ms: conf.open_retry_backoff_ms,
+            universal_compaction: UniversalCompaction::from(conf),
+        };
+    }
+}
+
+/// Options of the universal compaction style the DB is opened with, None keeps the
+/// RocksDB default
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UniversalCompaction {
+    pub size_ratio: Option<i32>,
+    pub min_merge_width: Option<i32>,
+    pub max_merge_width: Option<i32>,
+    pub max_size_amplification_percent: Option<i32>,
+}
+
+impl UniversalCompaction {
+    pub fn apply(&self, opts: &mut Options) {
+        let mut universal_opts = UniversalCompactOptions::default();
+        if let Some(size_ratio) = self.size_ratio {
+            universal_opts.set_size_ratio(size_ratio);
+        }
+        if let Some(min_merge_width) = self.min_merge_width {
+            universal_opts.set_min_merge_width(min_merge_width);
+        }
+        if let Some(max_merge_width) = self.max_merge_width {
+            universal_opts.set_max_merge_width(max_merge_width);
+        }
+        if let Some(percent) = self.max_size_amplification_percent {
+            universal_opts.set_max_size_amplification_percent(percent);
+        }
+        opts.set_universal_compaction_options(&universal_opts);
+    }
+}
+
+// 0 and values that do not fit an i32 keep the RocksDB default, PlacementCenterConfig
+// validate() rejects the latter
+fn universal_option(value: u32) -> Option<i32> {
+    if value == 0 {
+        return None;
+    }
+    return i32::try_from(value).ok();
+}
+
+impl From<&Rocksdb> for UniversalCompaction {
+    fn from(conf: &Rocksdb) -> Self {
+        return UniversalCompaction {
+            size_ratio: universal_option(conf.universal_size_ratio),
+            min_merge_width: universal_option(conf.universal_min_merge_width),
+            max_merge_width: universal_option(conf.universal_max_merge_width),
+            max_size_amplification_percent: universal_option(
+                conf.universal_max_size_amplification_percent,
+            ),
+        };
+    }
+}
+
+/// Settings of a single column family on top of the options shared by all of them
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnFamilyProfile {
+    // Small and hot data that is mostly read by key
+    PointLookup { block_cache_mb: u64 },
+    // Large data sets, a dedicated LRU block cache of this many bytes
+    LargeBlockCache { block_cache_size: usize },
+}
+
+impl ColumnFamilyProfile {
+    pub fn apply(&self, opts: &mut Options) {
+        match self {
+            ColumnFamilyProfile::PointLookup { block_cache_mb } => {
+                opts.optimize_for_point_lookup(*block_cache_mb);
+            }
+            ColumnFamilyProfile::LargeBlockCache { block_cache_size } => {
+                let cache = Cache::new_lru_cache(*block_cache_size);
+                let mut table_opts = BlockBasedOptions::default();
+                table_opts.set_block_cache(&cache);
+                opts.set_block_based_table_factory(&table_opts);
+            }
+        }
+    }
+}
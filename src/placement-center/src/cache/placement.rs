@@ -113,7 +113,13 @@ impl PlacementCacheManager {
         match node.list(None) {
             Ok(result) => {
                 for bn in result {
+                    let cluster_name = bn.cluster_name.clone();
+                    let node_id = bn.node_id;
                     self.add_node(bn);
+                    // nodes that died while the placement center was down still time out
+                    if let Ok(Some(time)) = node.last_heartbeat(&cluster_name, node_id) {
+                        self.heart_time(&cluster_name, node_id, time);
+                    }
                 }
             }
             Err(_) => {}
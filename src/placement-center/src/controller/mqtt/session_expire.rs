@@ -17,7 +17,7 @@ use crate::{
     cache::{mqtt::MqttCacheManager, placement::PlacementCacheManager},
     storage::{
         keys::storage_key_mqtt_session_cluster_prefix, mqtt::lastwill::MQTTLastWillStorage,
-        rocksdb::RocksDBEngine, StorageDataWrap,
+        placement::clock::ClusterClockStorage, rocksdb::RocksDBEngine, StorageDataWrap,
     },
 };
 use clients::poll::ClientPool;
@@ -190,15 +190,14 @@ impl SessionExpire {
     }
 
     fn is_session_expire(&self, session: &MQTTSession) -> bool {
-        if session.connection_id.is_none() && session.broker_id.is_none() {
-            if let Some(distinct_time) = session.distinct_time {
-                if now_second() >= (session.session_expiry + distinct_time) {
-                    return true;
-                }
+        let clock_storage = ClusterClockStorage::new(self.rocksdb_engine_handler.clone());
+        match clock_storage.now() {
+            Ok(now) => return is_session_expired(session, now),
+            Err(e) => {
+                error!("Failed to read the cluster clock, error message: {}", e);
+                return false;
             }
         }
-
-        return false;
     }
 
     fn is_send_last_will(&self, lastwill: &ExpireLastWill) -> bool {
@@ -209,6 +208,23 @@ impl SessionExpire {
     }
 }
 
+// Evaluated against the replicated cluster clock, every node comes to the same decision
+// for the same session
+pub fn is_session_expired(session: &MQTTSession, now: u64) -> bool {
+    // The clock is unset until the first entry stamped by a leader is applied
+    if now == 0 {
+        return false;
+    }
+    if session.connection_id.is_none() && session.broker_id.is_none() {
+        if let Some(distinct_time) = session.distinct_time {
+            if now >= (session.session_expiry + distinct_time) {
+                return true;
+            }
+        }
+    }
+    return false;
+}
+
 #[cfg(test)]
 mod tests {
     use std::{fs::remove_dir_all, sync::Arc, time::Duration};
@@ -227,6 +243,7 @@ mod tests {
         cache::{mqtt::MqttCacheManager, placement::PlacementCacheManager},
         storage::{
             mqtt::session::MQTTSessionStorage,
+            placement::clock::ClusterClockStorage,
             rocksdb::{column_family_list, RocksDBEngine},
         },
     };
@@ -251,7 +268,7 @@ mod tests {
         let client_poll = Arc::new(ClientPool::new(10));
 
         let session_expire = SessionExpire::new(
-            rocksdb_engine_handler,
+            rocksdb_engine_handler.clone(),
             mqtt_cache_manager,
             placement_cache,
             client_poll,
@@ -261,6 +278,11 @@ mod tests {
         let mut session = MQTTSession::default();
         session.session_expiry = now_second() - 100;
         session.distinct_time = Some(5);
+        // the cluster clock is unset, no session expires
+        assert!(!session_expire.is_session_expire(&session));
+
+        let clock_storage = ClusterClockStorage::new(rocksdb_engine_handler);
+        clock_storage.advance(now_second()).unwrap();
         assert!(session_expire.is_session_expire(&session));
 
         let mut session = MQTTSession::default();
@@ -312,8 +334,11 @@ mod tests {
             .save(&cluster_name, &client_id, session)
             .unwrap();
 
+        let clock_storage = ClusterClockStorage::new(rocksdb_engine_handler.clone());
         let start = now_second();
         loop {
+            // stands in for the entries the leader stamps with its time
+            clock_storage.advance(now_second()).unwrap();
             let expire_list = session_expire.get_expire_session_list().await;
 
             if expire_list.len() > 0 {
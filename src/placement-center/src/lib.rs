@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use self::raft::peer::{PeerMessage, PeersManager};
+use self::raft::peer::{DnsResolver, PeerClients, PeerMessage, PeersManager};
 use crate::raft::metadata::RaftGroupMetadata;
 use crate::server::http::server::{start_http_server, HttpServerState};
 use cache::journal::JournalCacheManager;
@@ -20,11 +20,12 @@ use cache::mqtt::MqttCacheManager;
 use cache::placement::PlacementCacheManager;
 use clients::poll::ClientPool;
 use common_base::config::placement_center::placement_center_conf;
+use common_base::error::placement_center::PlacementCenterError;
 use common_base::runtime::create_runtime;
 use controller::journal::controller::StorageEngineController;
 use controller::mqtt::MQTTController;
 use controller::placement::controller::ClusterController;
-use log::info;
+use log::{error, info};
 use protocol::placement_center::generate::journal::engine_service_server::EngineServiceServer;
 use protocol::placement_center::generate::kv::kv_service_server::KvServiceServer;
 use protocol::placement_center::generate::mqtt::mqtt_service_server::MqttServiceServer;
@@ -36,10 +37,12 @@ use server::grpc::service_journal::GrpcEngineService;
 use server::grpc::service_kv::GrpcKvService;
 use server::grpc::service_mqtt::GrpcMqttService;
 use server::grpc::service_placement::GrpcPlacementService;
+use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 use std::time::Duration;
 use storage::placement::raft::RaftMachineStorage;
-use storage::rocksdb::{column_family_list, RocksDBEngine};
+use storage::rocksdb::{column_family_list, RocksDBEngine, RocksDBTuning};
+use storage::self_check::storage_self_check;
 use tokio::runtime::Runtime;
 use tokio::signal;
 use tokio::sync::mpsc::{Receiver, Sender};
@@ -53,6 +56,9 @@ mod raft;
 mod server;
 mod storage;
 
+// How long to wait before starting the Raft node again after it failed to start
+const RAFT_START_RETRY_INTERVAL: Duration = Duration::from_secs(3);
+
 pub struct PlacementCenter {
     server_runtime: Runtime,
     daemon_runtime: Runtime,
@@ -69,20 +75,34 @@ pub struct PlacementCenter {
     rocksdb_engine_handler: Arc<RocksDBEngine>,
     // Global GRPC client connection pool
     client_poll: Arc<ClientPool>,
+    // GRPC clients of the other Raft nodes
+    peer_clients: Arc<PeerClients>,
 }
 
 impl PlacementCenter {
-    pub fn new() -> PlacementCenter {
+    pub fn new() -> Result<PlacementCenter, PlacementCenterError> {
         let config = placement_center_conf();
         let server_runtime = create_runtime("server-runtime", config.system.runtime_work_threads);
         let daemon_runtime = create_runtime("daemon-runtime", config.system.runtime_work_threads);
 
         let client_poll = Arc::new(ClientPool::new(100));
-        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+        let peer_clients = Arc::new(PeerClients::new_with_resolver(
+            Arc::new(DnsResolver),
+            Duration::from_millis(config.network.dns_cache_ttl_ms),
+        ));
+        let rocksdb_engine_handler = match RocksDBEngine::open(
             &config.rocksdb.data_path,
             config.rocksdb.max_open_files.unwrap(),
             column_family_list(),
-        ));
+            &RocksDBTuning::from(&config.rocksdb),
+            &HashMap::new(),
+        ) {
+            Ok(engine) => Arc::new(engine),
+            Err(e) => return Err(PlacementCenterError::StorageOpenFailed(e.to_string())),
+        };
+        if config.rocksdb.self_check {
+            storage_self_check(&rocksdb_engine_handler)?;
+        }
 
         let engine_cache = Arc::new(JournalCacheManager::new());
         let cluster_cache: Arc<PlacementCacheManager> =
@@ -93,11 +113,15 @@ impl PlacementCenter {
         ));
         let placement_cache = Arc::new(RwLock::new(RaftGroupMetadata::new()));
 
-        let raft_machine_storage = Arc::new(RwLock::new(RaftMachineStorage::new(
+        let mut raft_storage = RaftMachineStorage::new_with_entry_cache(
             rocksdb_engine_handler.clone(),
-        )));
+            config.raft.entry_cache_size,
+        );
+        raft_storage.set_snapshot_retain_entries(config.raft.snapshot_retain_entries);
+        raft_storage.set_snapshot_path(&config.raft.snapshot_path);
+        let raft_machine_storage = Arc::new(RwLock::new(raft_storage));
 
-        return PlacementCenter {
+        return Ok(PlacementCenter {
             server_runtime,
             daemon_runtime,
             cluster_cache,
@@ -107,20 +131,24 @@ impl PlacementCenter {
             raft_machine_storage,
             rocksdb_engine_handler,
             client_poll,
-        };
+            peer_clients,
+        });
+    }
+
+    /// Round trips a canary key in each column family and a Raft entry through the storage
+    pub fn self_check(&self) -> Result<(), PlacementCenterError> {
+        return storage_self_check(&self.rocksdb_engine_handler);
     }
 
     pub fn start(&mut self, stop_send: broadcast::Sender<bool>) {
-        let (raft_message_send, raft_message_recv) = mpsc::channel::<RaftMessage>(1000);
         let (peer_message_send, peer_message_recv) = mpsc::channel::<PeerMessage>(1000);
+        let raft_message_send = self.start_raft_machine(peer_message_send, stop_send.subscribe());
         let placement_center_storage = Arc::new(RaftMachineApply::new(raft_message_send));
 
         self.start_controller(placement_center_storage.clone(), stop_send.clone());
 
         self.start_peers_manager(peer_message_recv);
 
-        self.start_raft_machine(peer_message_send, raft_message_recv, stop_send.subscribe());
-
         self.start_http_server();
 
         self.start_grpc_server(placement_center_storage.clone());
@@ -153,6 +181,8 @@ impl PlacementCenter {
             self.cluster_cache.clone(),
             self.rocksdb_engine_handler.clone(),
             self.client_poll.clone(),
+            self.raft_machine_storage.clone(),
+            self.peer_clients.clone(),
         );
 
         let kv_handler = GrpcKvService::new(
@@ -217,35 +247,48 @@ impl PlacementCenter {
         });
     }
 
-    // Start Raft Status Machine
+    // Start the Raft node, returns the sender of the messages to it
     pub fn start_raft_machine(
         &self,
         peer_message_send: Sender<PeerMessage>,
-        raft_message_recv: Receiver<RaftMessage>,
         stop_recv: broadcast::Receiver<bool>,
-    ) {
+    ) -> Sender<RaftMessage> {
         let data_route = Arc::new(DataRoute::new(
             self.rocksdb_engine_handler.clone(),
             self.cluster_cache.clone(),
             self.engine_cache.clone(),
         ));
 
-        let mut raft: RaftMachine = RaftMachine::new(
+        let (mut raft, raft_message_send) = match RaftMachine::new_with_channel(
             self.placement_cache.clone(),
             data_route,
             peer_message_send,
-            raft_message_recv,
             stop_recv,
             self.raft_machine_storage.clone(),
-        );
+            self.peer_clients.clone(),
+        ) {
+            Ok(raft) => raft,
+            Err(e) => {
+                panic!("Failed to start the Raft node, {}", e);
+            }
+        };
         self.daemon_runtime.spawn(async move {
-            raft.run().await;
+            loop {
+                match raft.run().await {
+                    Ok(()) => break,
+                    Err(e) => {
+                        error!("{}, retry in {} seconds", e, RAFT_START_RETRY_INTERVAL.as_secs());
+                        sleep(RAFT_START_RETRY_INTERVAL).await;
+                    }
+                }
+            }
         });
+        return raft_message_send;
     }
 
     // Start Raft Node Peer Manager
     pub fn start_peers_manager(&self, peer_message_recv: Receiver<PeerMessage>) {
-        let mut peers_manager = PeersManager::new(peer_message_recv, self.client_poll.clone());
+        let mut peers_manager = PeersManager::new(peer_message_recv, self.peer_clients.clone());
         self.daemon_runtime.spawn(async move {
             peers_manager.start().await;
         });
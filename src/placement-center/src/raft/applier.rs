@@ -0,0 +1,315 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::apply::{MetaEvent, RaftResponseMesage, StorageData};
+use super::route::DataRoute;
+use crate::storage::collect_writes;
+use crate::storage::placement::raft::{AppliedError, AppliedRequest, RaftMachineStorage};
+use common_base::config::placement_center::placement_center_conf;
+use common_base::error::common::CommonError;
+use common_base::error::placement_center::PlacementCenterError;
+use common_base::metrics::placement::metrics_raft_undecodable_entry;
+use log::{debug, error, info};
+use raft::eraftpb::{Entry, EntryType};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+#[cfg(test)]
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::{spawn_blocking, JoinHandle};
+
+/// The entries of a run applied to the state machine, reported back to the ready loop
+pub struct AppliedRun {
+    pub last_index: u64,
+    // The context of each entry, which carries the seq of its proposer, and its response
+    pub responses: Vec<(Vec<u8>, RaftResponseMesage)>,
+}
+
+/// Applies the service data of committed entries to the state machine, either in the
+/// ready loop or in the apply worker
+pub struct EntryApplier {
+    data_route: Arc<DataRoute>,
+    raft_storage: Arc<RwLock<RaftMachineStorage>>,
+    event_sender: broadcast::Sender<MetaEvent>,
+    // Makes every run slow to apply, to test that the ready loop does not wait for it
+    #[cfg(test)]
+    pub apply_delay: Duration,
+}
+
+impl EntryApplier {
+    pub fn new(
+        data_route: Arc<DataRoute>,
+        raft_storage: Arc<RwLock<RaftMachineStorage>>,
+        event_sender: broadcast::Sender<MetaEvent>,
+    ) -> Self {
+        return EntryApplier {
+            data_route,
+            raft_storage,
+            event_sender,
+            #[cfg(test)]
+            apply_delay: Duration::ZERO,
+        };
+    }
+
+    /// Apply a run of normal entries, the Raft state is persisted once for the whole run
+    /// before the proposers are answered.
+    pub fn apply_run(&self, run: &[Entry]) -> Option<AppliedRun> {
+        #[cfg(test)]
+        std::thread::sleep(self.apply_delay);
+
+        return self.apply_entries(run, |entry| {
+            error!(
+                "Conf change at index {} can only be applied by the Raft node, skip it",
+                entry.get_index()
+            );
+            return Ok(());
+        });
+    }
+
+    /// Apply a run of committed entries, the conf changes in it are handed to
+    /// `apply_conf_change`. The Raft state is persisted once for the whole run before
+    /// the proposers are answered.
+    pub fn apply_entries<F>(&self, run: &[Entry], mut apply_conf_change: F) -> Option<AppliedRun>
+    where
+        F: FnMut(&Entry) -> Result<(), CommonError>,
+    {
+        let last = run.last()?;
+
+        // Entries the state machine rejected, the proposer is told why instead of Applied
+        let mut failed: HashMap<u64, CommonError> = HashMap::new();
+        // Requests applied before, the proposer gets the result of the first time
+        let mut duplicates: HashMap<u64, RaftResponseMesage> = HashMap::new();
+        for entry in run {
+            if entry.get_entry_type() != EntryType::EntryNormal {
+                if let Err(err) = apply_conf_change(entry) {
+                    error!("{}", err);
+                    failed.insert(entry.get_index(), err);
+                }
+                continue;
+            }
+            // A leader appends an entry without data when it is elected. There is nothing to
+            // apply, but its index is still part of the applied index saved below.
+            if entry.data.is_empty() {
+                debug!("Skip the empty entry at index {}", entry.get_index());
+                continue;
+            }
+            match self.apply_normal_entry(entry) {
+                Ok(Some(resp)) => {
+                    duplicates.insert(entry.get_index(), resp);
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    error!("{}", err);
+                    failed.insert(entry.get_index(), err);
+                }
+            }
+        }
+
+        // every entry of the run counts as applied, the skipped empty ones included
+        let indexes: Vec<u64> = run.iter().map(|entry| entry.get_index()).collect();
+        self.commit_run(&indexes);
+
+        let mut responses = Vec::with_capacity(run.len());
+        for entry in run {
+            let index = entry.get_index();
+            let resp = match duplicates.remove(&index) {
+                Some(resp) => resp,
+                None => match failed.remove(&index) {
+                    Some(err) => RaftResponseMesage::ApplyFailed(err),
+                    None => RaftResponseMesage::Applied(index),
+                },
+            };
+            responses.push((entry.get_context().to_vec(), resp));
+        }
+        return Some(AppliedRun {
+            last_index: last.get_index(),
+            responses,
+        });
+    }
+
+    /// Record a run of applied entries as committed. Once more than `snapshot_entries`
+    /// entries have been applied since the last snapshot, a new snapshot is taken so that
+    /// the entries below it can be compacted. Both happen under one lock of the storage,
+    /// the snapshot then covers exactly the entries applied.
+    pub fn commit_run(&self, indexes: &[u64]) {
+        let applied_index = match indexes.last() {
+            Some(index) => *index,
+            None => return,
+        };
+        let mut raft_storage = self.raft_storage.write().unwrap();
+        if let Err(e) = raft_storage.commit_entries(indexes) {
+            error!(
+                "Failed to commit the entries up to index {}, error message: {}",
+                applied_index, e
+            );
+            return;
+        }
+
+        let snapshot_entries = placement_center_conf().raft.snapshot_entries;
+        if snapshot_entries == 0 {
+            return;
+        }
+        let snapshot_index = raft_storage.snapshot_metadata.index;
+        if applied_index.saturating_sub(snapshot_index) < snapshot_entries {
+            return;
+        }
+        raft_storage.create_snapshot();
+    }
+
+    // Saves the service data sent by the client. A request of a client that has been
+    // applied before is skipped, the returned response is the one it got the first time.
+    pub fn apply_normal_entry(
+        &self,
+        entry: &Entry,
+    ) -> Result<Option<RaftResponseMesage>, CommonError> {
+        let storage_data = match StorageData::decode(entry.get_data()) {
+            Ok(data) => data,
+            Err(e) => return Err(undecodable_entry(entry, e)),
+        };
+        if let Some(resp) = self.duplicate_request(&storage_data) {
+            info!(
+                "Request {} of client {} at index {} has already been applied, skip it",
+                storage_data.request_id,
+                storage_data.client_id,
+                entry.get_index()
+            );
+            return Ok(Some(resp));
+        }
+
+        let client_id = storage_data.client_id.clone();
+        let request_id = storage_data.request_id;
+        // The writes of the command, the request of its client and the applied index go in
+        // one batch, a crash in between leaves all of them or none.
+        let (result, mut operations) =
+            collect_writes(|| self.data_route.route_storage_data(storage_data));
+        // a command that failed halfway leaves nothing behind
+        if result.is_err() {
+            operations.clear();
+        }
+        let raft_storage = self.raft_storage.read().unwrap();
+        if !client_id.is_empty() {
+            let request = AppliedRequest {
+                request_id,
+                index: entry.get_index(),
+                error: result.as_ref().err().map(AppliedError::from_error),
+            };
+            operations.push(raft_storage.applied_request_operation(&client_id, &request)?);
+        }
+        operations.push(raft_storage.applied_index_operation(entry.get_index())?);
+        raft_storage
+            .rocksdb_engine_handler
+            .write_batch_multi_cf(&operations)?;
+        drop(raft_storage);
+
+        // An error only means there is no subscriber
+        let _ = self.event_sender.send(MetaEvent {
+            data_type: result?,
+            index: entry.get_index(),
+        });
+        return Ok(None);
+    }
+
+    // The response of a request that is not newer than the last applied request of its client
+    fn duplicate_request(&self, storage_data: &StorageData) -> Option<RaftResponseMesage> {
+        if storage_data.client_id.is_empty() {
+            return None;
+        }
+        let applied = self
+            .raft_storage
+            .read()
+            .unwrap()
+            .applied_request(&storage_data.client_id)?;
+        if storage_data.request_id > applied.request_id {
+            return None;
+        }
+        if storage_data.request_id < applied.request_id {
+            let err = PlacementCenterError::RaftRequestOutdated(
+                storage_data.client_id.clone(),
+                storage_data.request_id,
+                applied.request_id,
+            );
+            return Some(RaftResponseMesage::ApplyFailed(err.into()));
+        }
+        match applied.error {
+            Some(error) => return Some(RaftResponseMesage::ApplyFailed(error.into_error())),
+            None => return Some(RaftResponseMesage::Applied(applied.index)),
+        }
+    }
+}
+
+/// Applies the committed entries in a task of its own, so that the ready loop only persists
+/// and advances while they are applied. At most queue_size runs are queued, sending the next
+/// one waits for the worker to catch up.
+pub struct ApplyWorker {
+    sender: mpsc::Sender<Vec<Entry>>,
+    applied_recv: mpsc::UnboundedReceiver<AppliedRun>,
+    task: JoinHandle<()>,
+}
+
+impl ApplyWorker {
+    pub fn start(applier: Arc<EntryApplier>, queue_size: usize) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<Vec<Entry>>(queue_size);
+        let (applied_send, applied_recv) = mpsc::unbounded_channel();
+        // the state machine is written synchronously, keep it off the runtime threads
+        let task = spawn_blocking(move || {
+            while let Some(run) = receiver.blocking_recv() {
+                if let Some(applied) = applier.apply_run(&run) {
+                    if applied_send.send(applied).is_err() {
+                        break;
+                    }
+                }
+            }
+            info!("Raft apply worker stopped");
+        });
+        return ApplyWorker {
+            sender,
+            applied_recv,
+            task,
+        };
+    }
+
+    pub async fn send(&self, run: Vec<Entry>) -> Result<(), PlacementCenterError> {
+        if self.sender.send(run).await.is_err() {
+            return Err(PlacementCenterError::RaftApplyWorkerStopped);
+        }
+        return Ok(());
+    }
+
+    /// The next run the worker has applied, None once the worker has stopped
+    pub async fn applied(&mut self) -> Option<AppliedRun> {
+        return self.applied_recv.recv().await;
+    }
+
+    /// Apply the runs already queued and wait for the worker to stop
+    pub async fn stop(self) {
+        drop(self.sender);
+        if let Err(e) = self.task.await {
+            error!("Raft apply worker did not stop cleanly, error message: {}", e);
+        }
+    }
+}
+
+// A committed entry whose data cannot be decoded was written by a version with another
+// format. Panicking would stop the node on every restart, so the entry is counted and
+// skipped, and the entries behind it are still applied.
+pub fn undecodable_entry(entry: &Entry, e: impl std::fmt::Display) -> CommonError {
+    let entry_type = format!("{:?}", entry.get_entry_type());
+    metrics_raft_undecodable_entry(&entry_type);
+    return CommonError::CommmonError(format!(
+        "Skip the committed {} at index {} whose data cannot be decoded, error message: {}",
+        entry_type,
+        entry.get_index(),
+        e
+    ));
+}
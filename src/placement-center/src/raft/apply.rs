@@ -12,30 +12,137 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use bincode::serialize;
+use super::peer::PeerHealth;
+use crate::storage::engine_get_by_cf;
+use crate::storage::rocksdb::RocksDBEngine;
+use bincode::{deserialize, serialize};
+use common_base::config::placement_center::placement_center_conf;
 use common_base::error::placement_center::PlacementCenterError;
 use common_base::error::common::CommonError;
+use common_base::tools::now_second;
+use metadata_struct::placement::broker_node::BrokerNode;
+use prost::Message as _;
+use protocol::placement_center::generate::kv::CompareAndSwapRequest;
 use raft::eraftpb::ConfChange;
+use raft::eraftpb::ConfChangeSingle;
+use raft::eraftpb::ConfChangeTransition;
+use raft::eraftpb::ConfChangeType;
+use raft::eraftpb::ConfChangeV2;
+use raft::eraftpb::ConfState;
 use raft::eraftpb::Message as raftPreludeMessage;
+use raft::StateRole;
 use serde::Deserialize;
 use serde::Serialize;
 use std::fmt;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::oneshot;
 use tokio::sync::oneshot::Receiver;
 use tokio::sync::oneshot::Sender;
 use tokio::time::timeout;
 
+// Extra wait of the proposer past its deadline, the Raft node answers with Timeout on
+// its next tick after the deadline
+const PROPOSE_TIMEOUT_GRACE: Duration = Duration::from_millis(500);
+
+fn default_propose_timeout() -> Duration {
+    return Duration::from_millis(placement_center_conf().raft.propose_timeout_ms);
+}
+
 pub enum RaftResponseMesage {
     Success,
+    // The entry has been committed and applied at this log index
+    Applied(u64),
     Fail,
+    // The proposal queue is full, the proposal was not accepted
+    ResourceExhausted,
+    Status(RaftNodeStatus),
+    ClusterConfig(RaftClusterConfig),
+    // A snapshot has been taken and the log compacted up to this index
+    SnapshotCreated(u64),
+    // The entry was committed but the state machine rejected it when applying
+    ApplyFailed(CommonError),
+    // Only the leader accepts proposals, leader_id is 0 when no leader is known
+    NotLeader { leader_id: u64, leader_addr: String },
+    // The deadline of the proposal passed before its entry was applied
+    Timeout,
+    // The node is in read-only mode and does not accept proposals
+    ReadOnly,
+    ReplicationStatus(ReplicationStatus),
+    // Only the voters of the Raft group can campaign
+    NotVoter(u64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RaftNodeStatus {
+    pub node_id: u64,
+    pub role: StateRole,
+    pub term: u64,
+    pub leader_id: u64,
+    pub commit_index: u64,
+    pub last_index: u64,
+    pub applied_index: u64,
+    pub peers: Vec<PeerHealth>,
+    // Time since a quorum of voters last acknowledged this node, only set on the leader
+    pub quorum_ack_age: Option<Duration>,
+    // Times this node stepped down as leader after losing contact with a quorum
+    pub quorum_loss_step_downs: u64,
+    // Proposals and conf changes waiting for their entries to be applied
+    pub pending_proposals: usize,
+    // Entries appended to the local log that have not been committed yet
+    pub uncommitted_entries: usize,
+    // Proposals are rejected while set, reads are still served
+    pub read_only: bool,
+}
+
+// Replication progress of one node as tracked by the leader
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerProgress {
+    pub node_id: u64,
+    // Highest log index known to be replicated on the node
+    pub matched: u64,
+    // Index of the next entry the leader sends to the node
+    pub next_index: u64,
+    pub learner: bool,
 }
+
+// Replication progress of the Raft group, only the leader tracks it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplicationStatus {
+    pub term: u64,
+    pub commit_index: u64,
+    // Highest index matched by a quorum of the voters, the commit index catches up with
+    // it once an entry of the current term is in the quorum
+    pub quorum_commit_index: u64,
+    pub peers: Vec<PeerProgress>,
+}
+
+// Voters and learners of the Raft group as persisted by this node
+#[derive(Debug, Clone, PartialEq)]
+pub struct RaftClusterConfig {
+    pub conf_state: ConfState,
+    pub leader_id: u64,
+}
+
+// Outcome of a proposal that has been applied to the state machine
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProposeResult {
+    pub index: u64,
+}
+
 pub enum RaftMessage {
     ConfChange {
         change: ConfChange,
         chan: Sender<RaftResponseMesage>,
     },
 
+    // Several membership changes at once through a joint configuration,
+    // answered once the joint configuration has been left
+    ConfChangeV2 {
+        change: ConfChangeV2,
+        chan: Sender<RaftResponseMesage>,
+    },
+
     // Received a message from another node
     Raft {
         message: raftPreludeMessage,
@@ -51,10 +158,55 @@ pub enum RaftMessage {
     Propose {
         data: Vec<u8>,
         chan: Sender<RaftResponseMesage>,
+        // The proposal is answered with Timeout once this has passed
+        deadline: Instant,
+    },
+
+    // Query the role, term and log indices of the local Raft node
+    Status {
+        chan: Sender<RaftResponseMesage>,
+    },
+
+    // Query the membership of the Raft group and its current leader
+    ClusterConfig {
+        chan: Sender<RaftResponseMesage>,
+    },
+
+    // Query the membership of the Raft group as the leader sees it, followers
+    // confirm with the leader before they answer from the local state machine
+    GetMembership {
+        chan: Sender<RaftResponseMesage>,
+    },
+
+    // Wait until the local state machine can serve a linearizable read
+    ReadIndex {
+        chan: Sender<RaftResponseMesage>,
+    },
+
+    // Take a snapshot now instead of waiting for snapshot_entries entries to be applied
+    Snapshot {
+        chan: Sender<RaftResponseMesage>,
+    },
+
+    // Reject proposals during maintenance while still serving reads, not persisted
+    SetReadOnly {
+        read_only: bool,
+        chan: Sender<RaftResponseMesage>,
+    },
+
+    // Query the matched and next indices of every node, answered by the leader only
+    ReplicationStatus {
+        chan: Sender<RaftResponseMesage>,
+    },
+
+    // Start an election on this node now instead of waiting for the election timeout
+    Campaign {
+        chan: Sender<RaftResponseMesage>,
     },
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+// Committed entries store the index of the variant, new variants go at the end
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub enum StorageDataType {
     // Cluster
     ClusterRegisterNode,
@@ -89,12 +241,37 @@ pub enum StorageDataType {
     MQTTDeleteAcl,
     MQTTCreateBlacklist,
     MQTTDeleteBlacklist,
+
+    // kv
+    KvCompareAndSwap,
+
+    // Advances the cluster clock to the time proposed by the leader. It is not proposed any
+    // more, the clock moves with the propose time of every entry. Kept for the logged entries.
+    ClusterClockTick,
+}
+
+// Entries written before client_id and request_id were added hold the bincode of
+// StorageDataV1 without a header. Its first byte is the low byte of the index of the data
+// type, far below the version byte newer entries start with.
+const STORAGE_DATA_VERSION_2: u8 = 0xf2;
+
+#[derive(Debug, Deserialize)]
+struct StorageDataV1 {
+    data_type: StorageDataType,
+    value: Vec<u8>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct StorageData {
     pub data_type: StorageDataType,
     pub value: Vec<u8>,
+    // Client that sent the request, empty when the proposal is not deduplicated
+    pub client_id: String,
+    // Increases with every request of the client, a retry reuses the id of the request
+    pub request_id: u64,
+    // Seconds on the leader when the data was proposed, every node applies this time instead
+    // of its own clock. 0 for the entries written before it was recorded.
+    pub propose_time: u64,
 }
 
 impl StorageData {
@@ -102,8 +279,56 @@ impl StorageData {
         return StorageData {
             data_type,
             value: value,
+            client_id: "".to_string(),
+            request_id: 0,
+            propose_time: now_second(),
+        };
+    }
+
+    /// A request that is applied at most once, proposing it again returns the first result
+    pub fn new_request(
+        data_type: StorageDataType,
+        value: Vec<u8>,
+        client_id: String,
+        request_id: u64,
+    ) -> StorageData {
+        return StorageData {
+            data_type,
+            value,
+            client_id,
+            request_id,
+            propose_time: now_second(),
         };
     }
+
+    /// The data of the entry proposed for it, led by the format version
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = vec![STORAGE_DATA_VERSION_2];
+        data.extend_from_slice(&serialize(self).unwrap());
+        return data;
+    }
+
+    /// Decode the data of an entry, the entries written before the format had a version too
+    pub fn decode(data: &[u8]) -> Result<StorageData, bincode::Error> {
+        if let Some((&STORAGE_DATA_VERSION_2, data)) = data.split_first() {
+            return deserialize(data);
+        }
+        let data: StorageDataV1 = deserialize(data)?;
+        return Ok(StorageData {
+            data_type: data.data_type,
+            value: data.value,
+            client_id: "".to_string(),
+            request_id: 0,
+            propose_time: 0,
+        });
+    }
+}
+
+// Emitted after an entry has been applied to the state machine
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetaEvent {
+    pub data_type: StorageDataType,
+    pub index: u64,
 }
 
 impl fmt::Display for StorageData {
@@ -122,9 +347,11 @@ impl RaftMachineApply {
         };
     }
 
+    /// Transfer the leadership to a voter of the Raft group,
+    /// returns once the target node has become the leader.
     pub async fn transfer_leader(&self, node_id: u64) -> Result<(), CommonError> {
         let (sx, rx) = oneshot::channel::<RaftResponseMesage>();
-        return Ok(self
+        match self
             .apply_raft_status_machine_message(
                 RaftMessage::TransferLeader {
                     node_id: node_id,
@@ -133,7 +360,424 @@ impl RaftMachineApply {
                 "transfer_leader".to_string(),
                 rx,
             )
-            .await?);
+            .await?
+        {
+            RaftResponseMesage::Success => return Ok(()),
+            _ => return Err(PlacementCenterError::RaftLeaderTransferFailed(node_id).into()),
+        }
+    }
+
+    /// Start an election on this node, for tests and controlled failover. Answered once the
+    /// campaign has started, the caller waits for the node status to see it become leader.
+    /// Nothing happens on the leader, followers and candidates have to be voters.
+    pub async fn campaign(&self) -> Result<(), CommonError> {
+        let (sx, rx) = oneshot::channel::<RaftResponseMesage>();
+        match self
+            .apply_raft_status_machine_message(
+                RaftMessage::Campaign { chan: sx },
+                "campaign".to_string(),
+                rx,
+            )
+            .await?
+        {
+            RaftResponseMesage::Success => return Ok(()),
+            RaftResponseMesage::NotVoter(node_id) => {
+                return Err(PlacementCenterError::RaftNodeNotVoter(node_id).into())
+            }
+            _ => {
+                return Err(PlacementCenterError::RaftCampaignFailed(
+                    "the Raft node did not start the campaign".to_string(),
+                )
+                .into())
+            }
+        }
+    }
+
+    // Add a node as a learner, it replicates the log without counting towards the quorum
+    pub async fn add_learner(&self, node: BrokerNode) -> Result<(), CommonError> {
+        let node_id = node.node_id;
+        let mut change = ConfChange::default();
+        change.set_node_id(node_id);
+        change.set_change_type(ConfChangeType::AddLearnerNode);
+        change.set_context(serialize(&node).unwrap());
+
+        let (sx, rx) = oneshot::channel::<RaftResponseMesage>();
+        match self
+            .apply_raft_status_machine_message(
+                RaftMessage::ConfChange { change, chan: sx },
+                "add_learner".to_string(),
+                rx,
+            )
+            .await?
+        {
+            RaftResponseMesage::Applied(_) => return Ok(()),
+            _ => {
+                return Err(CommonError::CommmonError(format!(
+                    "Failed to add Raft learner {}",
+                    node_id
+                )))
+            }
+        }
+    }
+
+    // Promote a learner that has caught up with the leader to a voter
+    pub async fn promote_learner(&self, node_id: u64) -> Result<(), CommonError> {
+        let mut change = ConfChange::default();
+        change.set_node_id(node_id);
+        change.set_change_type(ConfChangeType::AddNode);
+
+        let (sx, rx) = oneshot::channel::<RaftResponseMesage>();
+        match self
+            .apply_raft_status_machine_message(
+                RaftMessage::ConfChange { change, chan: sx },
+                "promote_learner".to_string(),
+                rx,
+            )
+            .await?
+        {
+            RaftResponseMesage::Applied(_) => return Ok(()),
+            _ => {
+                return Err(CommonError::CommmonError(format!(
+                    "Failed to promote Raft learner {}",
+                    node_id
+                )))
+            }
+        }
+    }
+
+    // Remove a node from the Raft group, the last remaining voter cannot be removed
+    pub async fn remove_node(&self, node_id: u64) -> Result<(), CommonError> {
+        let mut change = ConfChange::default();
+        change.set_node_id(node_id);
+        change.set_change_type(ConfChangeType::RemoveNode);
+
+        let (sx, rx) = oneshot::channel::<RaftResponseMesage>();
+        match self
+            .apply_raft_status_machine_message(
+                RaftMessage::ConfChange { change, chan: sx },
+                "remove_node".to_string(),
+                rx,
+            )
+            .await?
+        {
+            RaftResponseMesage::Applied(_) => return Ok(()),
+            _ => {
+                return Err(CommonError::CommmonError(format!(
+                    "Failed to remove Raft node {}",
+                    node_id
+                )))
+            }
+        }
+    }
+
+    // Add the nodes as voters and remove the given voters in a single joint consensus change,
+    // a quorum of both the old and the new voters is required until the change completes.
+    pub async fn change_membership(
+        &self,
+        add_voters: Vec<BrokerNode>,
+        remove_voters: Vec<u64>,
+    ) -> Result<(), CommonError> {
+        let mut changes = Vec::new();
+        for node in add_voters.iter() {
+            let mut single = ConfChangeSingle::default();
+            single.set_node_id(node.node_id);
+            single.set_change_type(ConfChangeType::AddNode);
+            changes.push(single);
+        }
+        for node_id in remove_voters.iter() {
+            let mut single = ConfChangeSingle::default();
+            single.set_node_id(*node_id);
+            single.set_change_type(ConfChangeType::RemoveNode);
+            changes.push(single);
+        }
+
+        let mut change = ConfChangeV2::default();
+        change.set_transition(ConfChangeTransition::Auto);
+        change.set_changes(changes.into());
+        change.set_context(serialize(&add_voters).unwrap());
+
+        let (sx, rx) = oneshot::channel::<RaftResponseMesage>();
+        match self
+            .apply_raft_status_machine_message(
+                RaftMessage::ConfChangeV2 { change, chan: sx },
+                "change_membership".to_string(),
+                rx,
+            )
+            .await?
+        {
+            RaftResponseMesage::Applied(_) => return Ok(()),
+            RaftResponseMesage::ApplyFailed(e) => return Err(e),
+            _ => {
+                return Err(CommonError::CommmonError(format!(
+                    "Failed to add Raft voters {:?} and remove Raft voters {:?}",
+                    add_voters.iter().map(|node| node.node_id).collect::<Vec<u64>>(),
+                    remove_voters
+                )))
+            }
+        }
+    }
+
+    pub async fn status(&self) -> Result<RaftNodeStatus, CommonError> {
+        let (sx, rx) = oneshot::channel::<RaftResponseMesage>();
+        match self
+            .apply_raft_status_machine_message(
+                RaftMessage::Status { chan: sx },
+                "status".to_string(),
+                rx,
+            )
+            .await?
+        {
+            RaftResponseMesage::Status(status) => return Ok(status),
+            _ => {
+                return Err(CommonError::CommmonError(
+                    "Raft state machine did not return the node status".to_string(),
+                ))
+            }
+        }
+    }
+
+    pub async fn cluster_config(&self) -> Result<RaftClusterConfig, CommonError> {
+        let (sx, rx) = oneshot::channel::<RaftResponseMesage>();
+        match self
+            .apply_raft_status_machine_message(
+                RaftMessage::ClusterConfig { chan: sx },
+                "cluster_config".to_string(),
+                rx,
+            )
+            .await?
+        {
+            RaftResponseMesage::ClusterConfig(config) => return Ok(config),
+            _ => {
+                return Err(CommonError::CommmonError(
+                    "Raft state machine did not return the cluster configuration".to_string(),
+                ))
+            }
+        }
+    }
+
+    /// Matched and next index of every node and the commit index they add up to. Only the
+    /// leader tracks the progress of the other nodes, followers answer with NotLeader.
+    pub async fn replication_status(&self) -> Result<ReplicationStatus, CommonError> {
+        let (sx, rx) = oneshot::channel::<RaftResponseMesage>();
+        match self
+            .apply_raft_status_machine_message(
+                RaftMessage::ReplicationStatus { chan: sx },
+                "replication_status".to_string(),
+                rx,
+            )
+            .await?
+        {
+            RaftResponseMesage::ReplicationStatus(status) => return Ok(status),
+            RaftResponseMesage::NotLeader {
+                leader_id,
+                leader_addr,
+            } => {
+                return Err(PlacementCenterError::NotLeader {
+                    leader_id,
+                    leader_addr,
+                }
+                .into());
+            }
+            _ => {
+                return Err(CommonError::CommmonError(
+                    "Raft state machine did not return the replication status".to_string(),
+                ))
+            }
+        }
+    }
+
+    /// Voters, learners and leader of the Raft group as the leader sees them. On a follower
+    /// the answer includes every membership change the leader had committed when asked.
+    pub async fn membership(&self) -> Result<RaftClusterConfig, CommonError> {
+        let (sx, rx) = oneshot::channel::<RaftResponseMesage>();
+        match self
+            .apply_raft_status_machine_message(
+                RaftMessage::GetMembership { chan: sx },
+                "get_membership".to_string(),
+                rx,
+            )
+            .await?
+        {
+            RaftResponseMesage::ClusterConfig(config) => return Ok(config),
+            RaftResponseMesage::NotLeader {
+                leader_id,
+                leader_addr,
+            } => {
+                return Err(PlacementCenterError::NotLeader {
+                    leader_id,
+                    leader_addr,
+                }
+                .into());
+            }
+            _ => {
+                return Err(CommonError::CommmonError(
+                    "Raft state machine did not return the membership".to_string(),
+                ))
+            }
+        }
+    }
+
+    /// Take a snapshot of the state machine and compact the log, returns the snapshot index
+    pub async fn trigger_snapshot(&self) -> Result<u64, CommonError> {
+        let (sx, rx) = oneshot::channel::<RaftResponseMesage>();
+        match self
+            .apply_raft_status_machine_message(
+                RaftMessage::Snapshot { chan: sx },
+                "trigger_snapshot".to_string(),
+                rx,
+            )
+            .await?
+        {
+            RaftResponseMesage::SnapshotCreated(index) => return Ok(index),
+            _ => {
+                return Err(CommonError::CommmonError(
+                    "There are no committed entries to take a snapshot of".to_string(),
+                ))
+            }
+        }
+    }
+
+    /// Reject the proposals to this node while read_only is set, linearizable reads are
+    /// still served. The flag only lives in memory, a restarted node accepts writes again.
+    pub async fn set_read_only(&self, read_only: bool) -> Result<(), CommonError> {
+        let (sx, rx) = oneshot::channel::<RaftResponseMesage>();
+        match self
+            .apply_raft_status_machine_message(
+                RaftMessage::SetReadOnly {
+                    read_only,
+                    chan: sx,
+                },
+                "set_read_only".to_string(),
+                rx,
+            )
+            .await?
+        {
+            RaftResponseMesage::Success => return Ok(()),
+            _ => {
+                return Err(CommonError::CommmonError(
+                    "Failed to change the read-only mode of the Raft node".to_string(),
+                ))
+            }
+        }
+    }
+
+    /// Returns once reads from the local state machine are linearizable. A leader holding
+    /// a valid lease returns immediately, otherwise this goes through Raft ReadIndex.
+    pub async fn read_index(&self) -> Result<(), CommonError> {
+        let (sx, rx) = oneshot::channel::<RaftResponseMesage>();
+        match self
+            .apply_raft_status_machine_message(
+                RaftMessage::ReadIndex { chan: sx },
+                "read_index".to_string(),
+                rx,
+            )
+            .await?
+        {
+            RaftResponseMesage::Success => return Ok(()),
+            _ => {
+                return Err(CommonError::CommmonError(
+                    "Raft read index was not confirmed by the leader".to_string(),
+                ))
+            }
+        }
+    }
+
+    /// Read the value of key from the local state machine once every write committed before
+    /// the call has been applied to it, the wait goes through read_index.
+    pub async fn linearizable_get(
+        &self,
+        rocksdb_engine_handler: Arc<RocksDBEngine>,
+        cf: &str,
+        key: String,
+    ) -> Result<Option<Vec<u8>>, CommonError> {
+        self.read_index().await?;
+        let data = engine_get_by_cf(rocksdb_engine_handler, cf, key)?;
+        return Ok(data.map(|wrap| wrap.data));
+    }
+
+    /// Propose the data and wait until it has been committed and applied,
+    /// the result carries the log index of the entry. Gives up after raft.propose_timeout_ms.
+    pub async fn propose(&self, data: StorageData) -> Result<ProposeResult, CommonError> {
+        return self.propose_with_timeout(data, default_propose_timeout()).await;
+    }
+
+    /// Like propose, but gives up once propose_timeout has passed. The entry may still
+    /// be committed later, the timeout only means its outcome is unknown to the caller.
+    pub async fn propose_with_timeout(
+        &self,
+        data: StorageData,
+        propose_timeout: Duration,
+    ) -> Result<ProposeResult, CommonError> {
+        let action = format!("{:?}", data.data_type);
+        let (sx, rx) = oneshot::channel::<RaftResponseMesage>();
+        let message = RaftMessage::Propose {
+            data: data.encode(),
+            chan: sx,
+            deadline: Instant::now() + propose_timeout,
+        };
+        let _ = self.raft_status_machine_sender.send(message).await;
+
+        // The Raft node answers on the first tick after the deadline, only wait longer
+        // than that when the node itself is stuck.
+        let resp = match timeout(propose_timeout + PROPOSE_TIMEOUT_GRACE, rx).await {
+            Ok(Ok(resp)) => resp,
+            Ok(Err(_)) => RaftResponseMesage::Fail,
+            Err(_) => RaftResponseMesage::Timeout,
+        };
+        match resp {
+            RaftResponseMesage::Applied(index) => return Ok(ProposeResult { index }),
+            RaftResponseMesage::ApplyFailed(e) => return Err(e),
+            RaftResponseMesage::NotLeader {
+                leader_id,
+                leader_addr,
+            } => {
+                return Err(PlacementCenterError::NotLeader {
+                    leader_id,
+                    leader_addr,
+                }
+                .into());
+            }
+            RaftResponseMesage::ResourceExhausted => {
+                return Err(PlacementCenterError::RaftProposalQueueFull(action).into());
+            }
+            RaftResponseMesage::Timeout => {
+                return Err(PlacementCenterError::RaftProposalTimeout(
+                    action,
+                    propose_timeout.as_millis() as u64,
+                )
+                .into());
+            }
+            RaftResponseMesage::ReadOnly => {
+                return Err(PlacementCenterError::RaftReadOnly(action).into());
+            }
+            _ => return Err(PlacementCenterError::RaftProposalDropped(action).into()),
+        }
+    }
+
+    // Write new to the key only if its current value is expected, or if it does not
+    // exist when expected is None. The comparison happens when the entry is applied,
+    // so every node sees the same outcome. Returns false when the value did not match.
+    pub async fn compare_and_swap(
+        &self,
+        cf: String,
+        key: String,
+        expected: Option<String>,
+        new: String,
+    ) -> Result<bool, CommonError> {
+        let req = CompareAndSwapRequest {
+            cf,
+            key,
+            expected,
+            new,
+        };
+        let data = StorageData::new(StorageDataType::KvCompareAndSwap, req.encode_to_vec());
+        match self.propose(data).await {
+            Ok(_) => return Ok(true),
+            Err(CommonError::PlacementCenterError(
+                PlacementCenterError::CompareAndSwapMismatch(_),
+            )) => return Ok(false),
+            Err(e) => return Err(e),
+        }
     }
 
     pub async fn apply_propose_message(
@@ -142,16 +786,59 @@ impl RaftMachineApply {
         action: String,
     ) -> Result<(), CommonError> {
         let (sx, rx) = oneshot::channel::<RaftResponseMesage>();
-        return Ok(self
+        let propose_timeout = default_propose_timeout();
+        match self
             .apply_raft_status_machine_message(
                 RaftMessage::Propose {
-                    data: serialize(&data).unwrap(),
+                    data: data.encode(),
                     chan: sx,
+                    deadline: Instant::now() + propose_timeout,
                 },
-                action,
+                action.clone(),
                 rx,
             )
-            .await?);
+            .await?
+        {
+            RaftResponseMesage::NotLeader {
+                leader_id,
+                leader_addr,
+            } => {
+                return Err(PlacementCenterError::NotLeader {
+                    leader_id,
+                    leader_addr,
+                }
+                .into());
+            }
+            RaftResponseMesage::ResourceExhausted => {
+                return Err(PlacementCenterError::RaftProposalQueueFull(action).into());
+            }
+            RaftResponseMesage::Timeout => {
+                return Err(PlacementCenterError::RaftProposalTimeout(
+                    action,
+                    propose_timeout.as_millis() as u64,
+                )
+                .into());
+            }
+            RaftResponseMesage::ReadOnly => {
+                return Err(PlacementCenterError::RaftReadOnly(action).into());
+            }
+            RaftResponseMesage::Success | RaftResponseMesage::Applied(_) => return Ok(()),
+            // the state machine rejected the entry, e.g. a CAS mismatch or an outdated request
+            RaftResponseMesage::ApplyFailed(e) => return Err(e),
+            RaftResponseMesage::Fail => {
+                return Err(PlacementCenterError::RaftProposalDropped(action).into());
+            }
+            RaftResponseMesage::Status(_)
+            | RaftResponseMesage::ClusterConfig(_)
+            | RaftResponseMesage::SnapshotCreated(_)
+            | RaftResponseMesage::ReplicationStatus(_)
+            | RaftResponseMesage::NotVoter(_) => {
+                return Err(CommonError::CommmonError(format!(
+                    "Raft state machine returned an unexpected response to the proposal {}",
+                    action
+                )));
+            }
+        }
     }
 
     pub async fn apply_raft_message(
@@ -160,16 +847,16 @@ impl RaftMachineApply {
         action: String,
     ) -> Result<(), CommonError> {
         let (sx, rx) = oneshot::channel::<RaftResponseMesage>();
-        return Ok(self
-            .apply_raft_status_machine_message(
-                RaftMessage::Raft {
-                    message: message,
-                    chan: sx,
-                },
-                action,
-                rx,
-            )
-            .await?);
+        self.apply_raft_status_machine_message(
+            RaftMessage::Raft {
+                message: message,
+                chan: sx,
+            },
+            action,
+            rx,
+        )
+        .await?;
+        return Ok(());
     }
 
     pub async fn apply_conf_raft_message(
@@ -178,13 +865,13 @@ impl RaftMachineApply {
         action: String,
     ) -> Result<(), CommonError> {
         let (sx, rx) = oneshot::channel::<RaftResponseMesage>();
-        return Ok(self
-            .apply_raft_status_machine_message(
-                RaftMessage::ConfChange { change, chan: sx },
-                action,
-                rx,
-            )
-            .await?);
+        self.apply_raft_status_machine_message(
+            RaftMessage::ConfChange { change, chan: sx },
+            action,
+            rx,
+        )
+        .await?;
+        return Ok(());
     }
 
     async fn apply_raft_status_machine_message(
@@ -192,15 +879,18 @@ impl RaftMachineApply {
         message: RaftMessage,
         action: String,
         rx: Receiver<RaftResponseMesage>,
-    ) -> Result<(), PlacementCenterError> {
+    ) -> Result<RaftResponseMesage, PlacementCenterError> {
         let _ = self.raft_status_machine_sender.send(message).await;
-        if !self.wait_recv_chan_resp(rx).await {
-            return Err(PlacementCenterError::RaftLogCommitTimeout(action));
+        match self.wait_recv_chan_resp(rx).await {
+            Some(resp) => return Ok(resp),
+            None => return Err(PlacementCenterError::RaftLogCommitTimeout(action)),
         }
-        return Ok(());
     }
 
-    async fn wait_recv_chan_resp(&self, rx: Receiver<RaftResponseMesage>) -> bool {
+    async fn wait_recv_chan_resp(
+        &self,
+        rx: Receiver<RaftResponseMesage>,
+    ) -> Option<RaftResponseMesage> {
         let res = timeout(Duration::from_secs(30), async {
             match rx.await {
                 Ok(val) => {
@@ -212,9 +902,9 @@ impl RaftMachineApply {
             }
         });
         match res.await {
-            Ok(_) => return true,
+            Ok(val) => return Some(val),
             Err(_) => {
-                return false;
+                return None;
             }
         }
     }
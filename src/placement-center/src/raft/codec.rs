@@ -0,0 +1,86 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use common_base::error::placement_center::PlacementCenterError;
+use raft::{Error, Result as RaftResult, StorageError};
+
+/// Encode a message of the Raft library, the entries and the Raft state included,
+/// the way every node and the storage expect it
+pub fn encode_message<M: prost::Message>(message: &M) -> Vec<u8> {
+    return message.encode_to_vec();
+}
+
+/// Decode a message of the Raft library, `name` tells what was expected in the error
+pub fn decode_message<M: prost::Message + Default>(name: &str, data: &[u8]) -> RaftResult<M> {
+    return M::decode(data).map_err(|e| {
+        Error::Store(StorageError::Other(Box::new(
+            PlacementCenterError::RaftMessageDecodeFailed(name.to_string(), e.to_string()),
+        )))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_message, encode_message};
+    use raft::eraftpb::{ConfChange, ConfChangeType, HardState, Message as raftPreludeMessage};
+    use raft::prelude::{ConfState, Entry};
+
+    #[test]
+    fn round_trip_test() {
+        let mut entry = Entry::default();
+        entry.index = 7;
+        entry.term = 3;
+        entry.data = b"data".to_vec();
+        let data = encode_message(&entry);
+        assert_eq!(decode_message::<Entry>("Entry", &data).unwrap(), entry);
+
+        let mut hs = HardState::default();
+        hs.term = 3;
+        hs.vote = 1;
+        hs.commit = 7;
+        let data = encode_message(&hs);
+        assert_eq!(decode_message::<HardState>("HardState", &data).unwrap(), hs);
+
+        let mut cs = ConfState::default();
+        cs.voters = vec![1, 2, 3];
+        cs.learners = vec![4];
+        let data = encode_message(&cs);
+        assert_eq!(decode_message::<ConfState>("ConfState", &data).unwrap(), cs);
+
+        let mut change = ConfChange::default();
+        change.set_change_type(ConfChangeType::AddLearnerNode);
+        change.node_id = 4;
+        let data = encode_message(&change);
+        assert_eq!(decode_message::<ConfChange>("ConfChange", &data).unwrap(), change);
+
+        // an empty message decodes to the defaults
+        assert_eq!(decode_message::<HardState>("HardState", &[]).unwrap(), HardState::default());
+    }
+
+    #[test]
+    fn malformed_input_test() {
+        // a length delimited field that is longer than the data
+        let err = decode_message::<Entry>("Entry", &[0x22, 0x10, 0x01]).unwrap_err();
+        assert!(err.to_string().contains("Entry"));
+
+        let mut data = encode_message(&raftPreludeMessage::default());
+        data.push(0xff);
+        let err = decode_message::<raftPreludeMessage>("Message", &data).unwrap_err();
+        assert!(err.to_string().contains("Message"));
+
+        // an invalid wire type
+        assert!(decode_message::<ConfState>("ConfState", &[0x0f]).is_err());
+    }
+}
@@ -0,0 +1,341 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::peer::PeerClients;
+use common_base::error::placement_center::PlacementCenterError;
+use log::{debug, warn};
+use protocol::placement_center::generate::placement::{ClusterStatusReply, ClusterStatusRequest};
+use std::cmp;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::{sleep, timeout};
+
+// The backoff stops doubling after this many rounds
+const MAX_BACKOFF_SHIFT: u32 = 10;
+
+// Backoff before the next election round, base * 2^round plus a jitter of up to half of it,
+// so that nodes started together do not keep asking each other at the same moments.
+fn retry_backoff(base: Duration, round: u64, seed: u64) -> Duration {
+    let backoff = base * 2u32.pow(cmp::min(round, MAX_BACKOFF_SHIFT as u64) as u32);
+    let jitter_range = backoff.as_millis() as u64 / 2 + 1;
+    return backoff + Duration::from_millis(seed % jitter_range);
+}
+
+fn jitter_seed() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(now) => return now.subsec_nanos() as u64,
+        Err(_) => return 0,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElectionLeader {
+    pub node_id: u64,
+    pub addr: String,
+    pub term: u64,
+}
+
+pub struct Election {
+    nodes: HashMap<u64, String>,
+    peer_clients: Arc<PeerClients>,
+    peer_timeout: Duration,
+    retries: u64,
+    retry_backoff: Duration,
+}
+
+impl Election {
+    pub fn new(
+        meta_nodes: Vec<(u64, String)>,
+        peer_clients: Arc<PeerClients>,
+        peer_timeout: Duration,
+        retries: u64,
+        retry_backoff: Duration,
+    ) -> Result<Self, PlacementCenterError> {
+        let mut nodes = HashMap::new();
+        for (node_id, addr) in meta_nodes {
+            if nodes.insert(node_id, addr).is_some() {
+                return Err(PlacementCenterError::DuplicateRaftNodeId(node_id));
+            }
+        }
+        return Ok(Election {
+            nodes,
+            peer_clients,
+            peer_timeout,
+            retries,
+            retry_backoff,
+        });
+    }
+
+    /// Ask every configured node for its view of the cluster and return the leader
+    /// reported with the highest term. Each peer is given at most `peer_timeout`.
+    pub async fn leader_election(&self) -> Option<ElectionLeader> {
+        let mut responses = Vec::new();
+        for (node_id, addr) in self.nodes.iter() {
+            let mut client = match self.peer_clients.get(*node_id, addr) {
+                Ok(client) => client,
+                Err(e) => {
+                    debug!("Failed to create client for node {}: {}", node_id, e);
+                    responses.push((*node_id, None));
+                    continue;
+                }
+            };
+            let call = client.cluster_status(ClusterStatusRequest::default());
+            match timeout(self.peer_timeout, call).await {
+                Ok(Ok(reply)) => responses.push((*node_id, Some(reply.into_inner()))),
+                Ok(Err(e)) => {
+                    self.peer_clients.remove(*node_id);
+                    debug!("Node {} failed to return cluster status: {}", node_id, e);
+                    responses.push((*node_id, None));
+                }
+                Err(_) => {
+                    warn!("Node {} did not return cluster status in time", node_id);
+                    responses.push((*node_id, None));
+                }
+            }
+        }
+        return self.select_leader(responses);
+    }
+
+    /// Repeat the election until a leader is found, peers that are still starting
+    /// may not answer the first rounds. A failed election is retried up to `retries`
+    /// times, see raft.election_retries. Returns None only once every retry failed,
+    /// the caller may then fall back to bootstrapping on its own.
+    pub async fn leader_election_with_retry(&self) -> Option<ElectionLeader> {
+        return self.retry(move || self.leader_election()).await;
+    }
+
+    async fn retry<F, Fut>(&self, mut election: F) -> Option<ElectionLeader>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Option<ElectionLeader>>,
+    {
+        for round in 0..=self.retries {
+            if let Some(leader) = election().await {
+                return Some(leader);
+            }
+            if round < self.retries {
+                let backoff = retry_backoff(self.retry_backoff, round, jitter_seed());
+                debug!("No leader found in election round {}, retry in {:?}", round + 1, backoff);
+                sleep(backoff).await;
+            }
+        }
+        warn!("No leader found after {} election rounds", self.retries + 1);
+        return None;
+    }
+
+    fn select_leader(
+        &self,
+        responses: Vec<(u64, Option<ClusterStatusReply>)>,
+    ) -> Option<ElectionLeader> {
+        let mut leader: Option<ElectionLeader> = None;
+        for (_, reply) in responses {
+            let reply = match reply {
+                Some(reply) => reply,
+                None => continue,
+            };
+
+            // Node ids are resolved through the configured map so that a peer
+            // advertising an unknown id cannot redirect us to a random address.
+            if reply.leader_id == 0 {
+                continue;
+            }
+            let addr = match self.nodes.get(&reply.leader_id) {
+                Some(addr) => addr.clone(),
+                None => continue,
+            };
+
+            if let Some(current) = &leader {
+                if current.term >= reply.term {
+                    continue;
+                }
+            }
+            leader = Some(ElectionLeader {
+                node_id: reply.leader_id,
+                addr,
+                term: reply.term,
+            });
+        }
+        return leader;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{retry_backoff, Election, ElectionLeader};
+    use crate::raft::peer::PeerClients;
+    use common_base::error::placement_center::PlacementCenterError;
+    use protocol::placement_center::generate::placement::ClusterStatusReply;
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn election() -> Election {
+        let nodes = vec![
+            (1, "127.0.0.1:1228".to_string()),
+            (2, "127.0.0.1:2228".to_string()),
+            (3, "127.0.0.1:3228".to_string()),
+        ];
+        Election::new(
+            nodes,
+            Arc::new(PeerClients::new()),
+            Duration::from_millis(100),
+            3,
+            Duration::from_millis(1),
+        )
+        .unwrap()
+    }
+
+    fn reply(leader_id: u64, term: u64) -> ClusterStatusReply {
+        let mut reply = ClusterStatusReply::default();
+        reply.leader_id = leader_id;
+        reply.term = term;
+        return reply;
+    }
+
+    #[test]
+    fn duplicate_node_id_test() {
+        let nodes = vec![
+            (1, "127.0.0.1:1228".to_string()),
+            (1, "127.0.0.1:2228".to_string()),
+        ];
+        let peer_clients = Arc::new(PeerClients::new());
+        match Election::new(nodes, peer_clients, Duration::from_millis(100), 0, Duration::ZERO) {
+            Err(PlacementCenterError::DuplicateRaftNodeId(id)) => assert_eq!(id, 1),
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn single_reachable_peer_test() {
+        let election = election();
+        let leader = election.select_leader(vec![(1, None), (2, Some(reply(3, 4))), (3, None)]);
+        assert_eq!(
+            leader,
+            Some(ElectionLeader {
+                node_id: 3,
+                addr: "127.0.0.1:3228".to_string(),
+                term: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn no_reachable_peer_test() {
+        let election = election();
+        let leader = election.select_leader(vec![(1, None), (2, None), (3, None)]);
+        assert!(leader.is_none());
+    }
+
+    #[test]
+    fn conflicting_responses_test() {
+        let election = election();
+        let leader = election.select_leader(vec![
+            (1, Some(reply(1, 2))),
+            (2, Some(reply(2, 5))),
+            (3, Some(reply(1, 3))),
+        ]);
+        let leader = leader.unwrap();
+        assert_eq!(leader.node_id, 2);
+        assert_eq!(leader.term, 5);
+        assert_eq!(leader.addr, "127.0.0.1:2228".to_string());
+    }
+
+    #[tokio::test]
+    async fn unreachable_peers_election_test() {
+        let nodes = vec![(1, "127.0.0.1:1".to_string())];
+        let peer_clients = Arc::new(PeerClients::new());
+        let election =
+            Election::new(nodes, peer_clients, Duration::from_millis(100), 0, Duration::ZERO)
+                .unwrap();
+        assert!(election.leader_election().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn election_reuses_peer_channel_test() {
+        // the peer accepts connections but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let nodes = vec![(1, listener.local_addr().unwrap().to_string())];
+        let peer_clients = Arc::new(PeerClients::new());
+        let election = Election::new(
+            nodes,
+            peer_clients.clone(),
+            Duration::from_millis(100),
+            0,
+            Duration::ZERO,
+        )
+        .unwrap();
+
+        // each round times out, the channel to the peer is kept for the next one
+        for _ in 0..3 {
+            assert!(election.leader_election().await.is_none());
+        }
+        assert_eq!(peer_clients.channel_num(), 1);
+    }
+
+    #[test]
+    fn retry_backoff_test() {
+        let base = Duration::from_millis(100);
+        assert_eq!(retry_backoff(base, 0, 0), Duration::from_millis(100));
+        assert_eq!(retry_backoff(base, 2, 0), Duration::from_millis(400));
+        // the jitter adds at most half of the backoff
+        assert_eq!(retry_backoff(base, 1, 100), Duration::from_millis(300));
+        assert_eq!(retry_backoff(base, 1, 101), Duration::from_millis(200));
+        for seed in 0..1000 {
+            let backoff = retry_backoff(base, 3, seed);
+            assert!(backoff >= Duration::from_millis(800));
+            assert!(backoff <= Duration::from_millis(1200));
+        }
+        // the doubling is capped
+        assert_eq!(retry_backoff(base, 50, 0), retry_backoff(base, 10, 0));
+    }
+
+    #[tokio::test]
+    async fn election_retry_test() {
+        let election = election();
+        let leader = ElectionLeader {
+            node_id: 2,
+            addr: "127.0.0.1:2228".to_string(),
+            term: 3,
+        };
+
+        // the peers answer from the third round on
+        let rounds = AtomicU64::new(0);
+        let (counter, expected) = (&rounds, &leader);
+        let result = election
+            .retry(move || async move {
+                if counter.fetch_add(1, Ordering::Relaxed) < 2 {
+                    return None;
+                }
+                return Some(expected.clone());
+            })
+            .await;
+        assert_eq!(result, Some(leader.clone()));
+        assert_eq!(rounds.load(Ordering::Relaxed), 3);
+
+        // peers that never answer exhaust the retries
+        let rounds = AtomicU64::new(0);
+        let counter = &rounds;
+        let result = election
+            .retry(move || async move {
+                counter.fetch_add(1, Ordering::Relaxed);
+                return None;
+            })
+            .await;
+        assert!(result.is_none());
+        assert_eq!(rounds.load(Ordering::Relaxed), 4);
+    }
+}
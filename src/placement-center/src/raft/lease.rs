@@ -0,0 +1,158 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Lease of the Raft leader. While a quorum of voters has heard from the leader within
+/// the lease duration, no other node can win an election, so reads can be served locally.
+pub struct LeaderLease {
+    duration: Duration,
+    acks: HashMap<u64, Instant>,
+    expire_at: Option<Instant>,
+    // Last time a quorum of voters acknowledged the leader, its election counts as the first
+    quorum_heard_at: Option<Instant>,
+}
+
+impl LeaderLease {
+    pub fn new(duration: Duration) -> Self {
+        return LeaderLease {
+            duration,
+            acks: HashMap::new(),
+            expire_at: None,
+            quorum_heard_at: None,
+        };
+    }
+
+    /// Record that node_id answered a heartbeat or append of the leader at `at`
+    pub fn record_ack(&mut self, node_id: u64, at: Instant) {
+        self.acks.insert(node_id, at);
+    }
+
+    /// Extend the lease to the last time a quorum of voters was heard from,
+    /// the local node always counts as heard from at `now`.
+    pub fn renew(&mut self, local_id: u64, voters: &[u64], now: Instant) {
+        if voters.is_empty() {
+            return;
+        }
+
+        let mut ack_times: Vec<Instant> = voters
+            .iter()
+            .filter_map(|id| {
+                if *id == local_id {
+                    return Some(now);
+                }
+                return self.acks.get(id).copied();
+            })
+            .collect();
+
+        let quorum = voters.len() / 2 + 1;
+        if ack_times.len() < quorum {
+            return;
+        }
+
+        // the quorum-th most recent ack is the time a quorum was last heard from
+        ack_times.sort_by(|a, b| b.cmp(a));
+        let heard_at = ack_times[quorum - 1];
+        if self.quorum_heard_at.map_or(true, |current| heard_at > current) {
+            self.quorum_heard_at = Some(heard_at);
+        }
+        let expire_at = heard_at + self.duration;
+        if self.expire_at.map_or(true, |current| expire_at > current) {
+            self.expire_at = Some(expire_at);
+        }
+    }
+
+    /// The votes that made this node leader at `at` are its first quorum acknowledgement
+    pub fn elected(&mut self, at: Instant) {
+        self.quorum_heard_at = Some(at);
+    }
+
+    pub fn quorum_heard_at(&self) -> Option<Instant> {
+        return self.quorum_heard_at;
+    }
+
+    pub fn is_valid(&self, now: Instant) -> bool {
+        match self.expire_at {
+            Some(expire_at) => return now < expire_at,
+            None => return false,
+        }
+    }
+
+    /// Drop the lease and all acks, a new leader has to earn its own lease
+    pub fn reset(&mut self) {
+        self.acks.clear();
+        self.expire_at = None;
+        self.quorum_heard_at = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LeaderLease;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn lease_expire_test() {
+        let start = Instant::now();
+        let mut lease = LeaderLease::new(Duration::from_millis(700));
+        assert!(!lease.is_valid(start));
+
+        // only the local node, no quorum of three voters yet
+        lease.renew(1, &[1, 2, 3], start);
+        assert!(!lease.is_valid(start));
+
+        lease.record_ack(2, start);
+        lease.renew(1, &[1, 2, 3], start);
+        assert!(lease.is_valid(start + Duration::from_millis(500)));
+        assert!(!lease.is_valid(start + Duration::from_millis(700)));
+
+        // a later quorum ack extends the lease
+        lease.record_ack(3, start + Duration::from_millis(400));
+        lease.renew(1, &[1, 2, 3], start + Duration::from_millis(400));
+        assert!(lease.is_valid(start + Duration::from_millis(1000)));
+        assert!(!lease.is_valid(start + Duration::from_millis(1100)));
+
+        lease.reset();
+        assert!(!lease.is_valid(start));
+    }
+
+    #[test]
+    fn quorum_heard_at_test() {
+        let start = Instant::now();
+        let mut lease = LeaderLease::new(Duration::from_millis(700));
+        assert_eq!(lease.quorum_heard_at(), None);
+
+        lease.elected(start);
+        lease.renew(1, &[1, 2, 3], start + Duration::from_millis(300));
+        assert_eq!(lease.quorum_heard_at(), Some(start));
+
+        // the quorum is heard from only once the second voter answers
+        lease.record_ack(2, start + Duration::from_millis(200));
+        lease.renew(1, &[1, 2, 3], start + Duration::from_millis(400));
+        assert_eq!(lease.quorum_heard_at(), Some(start + Duration::from_millis(200)));
+
+        lease.reset();
+        assert_eq!(lease.quorum_heard_at(), None);
+    }
+
+    #[test]
+    fn lease_single_voter_test() {
+        let start = Instant::now();
+        let mut lease = LeaderLease::new(Duration::from_millis(700));
+        lease.renew(1, &[1], start);
+        assert!(lease.is_valid(start + Duration::from_millis(100)));
+        assert!(!lease.is_valid(start + Duration::from_millis(800)));
+    }
+}
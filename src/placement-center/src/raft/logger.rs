@@ -0,0 +1,144 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the log file of the Raft library, nodes sharing a log directory write to their own file
+pub fn raft_log_file_name(node_id: u64) -> String {
+    return format!("raft-node-{}.log", node_id);
+}
+
+/// Tag attached to every record of the Raft library log
+pub fn raft_log_tag(node_id: u64) -> String {
+    return format!("meta-node-id={}", node_id);
+}
+
+/// Writer of the Raft log. Once the file reaches `max_size` bytes it is rolled to
+/// `<file>.1` (replacing the previous one) and a new file is started.
+pub struct RotatingFileWriter {
+    path: PathBuf,
+    rolled_path: PathBuf,
+    max_size: u64,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFileWriter {
+    pub fn new(dir: &str, file_name: &str, max_size: u64) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let path = Path::new(dir).join(file_name);
+        let rolled_path = Path::new(dir).join(format!("{}.1", file_name));
+        let file = RotatingFileWriter::open(&path)?;
+        let size = file.metadata()?.len();
+        return Ok(RotatingFileWriter {
+            path,
+            rolled_path,
+            max_size,
+            file,
+            size,
+        });
+    }
+
+    fn open(path: &PathBuf) -> io::Result<File> {
+        return OpenOptions::new().create(true).append(true).open(path);
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        fs::rename(&self.path, &self.rolled_path)?;
+        self.file = RotatingFileWriter::open(&self.path)?;
+        self.size = 0;
+        return Ok(());
+    }
+}
+
+impl Write for RotatingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.max_size > 0 && self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.size += n as u64;
+        return Ok(n);
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return self.file.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{raft_log_file_name, raft_log_tag, RotatingFileWriter};
+    use common_base::tools::unique_id;
+    use std::fs::{metadata, read_to_string, remove_dir_all};
+    use std::io::Write;
+    use std::path::Path;
+
+    #[test]
+    fn rotate_test() {
+        let dir = format!("/tmp/robustmq_{}/logs", unique_id());
+        let mut writer = RotatingFileWriter::new(&dir, "raft.log", 1024).unwrap();
+        assert!(Path::new(&dir).exists());
+
+        for i in 0..100 {
+            writeln!(writer, "raft log line {}", i).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let rolled = format!("{}/raft.log.1", dir);
+        assert!(Path::new(&rolled).exists());
+        assert!(metadata(&rolled).unwrap().len() <= 1024);
+        assert!(metadata(format!("{}/raft.log", dir)).unwrap().len() <= 1024);
+
+        remove_dir_all(Path::new(&dir).parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn rotate_disabled_test() {
+        let dir = format!("/tmp/robustmq_{}/logs", unique_id());
+        let mut writer = RotatingFileWriter::new(&dir, "raft.log", 0).unwrap();
+        for i in 0..100 {
+            writeln!(writer, "raft log line {}", i).unwrap();
+        }
+        writer.flush().unwrap();
+
+        assert!(!Path::new(&format!("{}/raft.log.1", dir)).exists());
+
+        remove_dir_all(Path::new(&dir).parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn node_log_identity_test() {
+        assert_ne!(raft_log_tag(1), raft_log_tag(2));
+        assert_eq!(raft_log_tag(2), "meta-node-id=2".to_string());
+        assert_eq!(raft_log_file_name(2), "raft-node-2.log".to_string());
+
+        // two nodes logging to the same directory do not share a file
+        let dir = format!("/tmp/robustmq_{}/logs", unique_id());
+        for node_id in [1, 2] {
+            let mut writer = RotatingFileWriter::new(&dir, &raft_log_file_name(node_id), 0).unwrap();
+            writeln!(writer, "{}", raft_log_tag(node_id)).unwrap();
+            writer.flush().unwrap();
+        }
+        for node_id in [1, 2] {
+            let path = format!("{}/{}", dir, raft_log_file_name(node_id));
+            assert_eq!(read_to_string(path).unwrap().trim(), raft_log_tag(node_id));
+        }
+
+        remove_dir_all(Path::new(&dir).parent().unwrap()).unwrap();
+    }
+}
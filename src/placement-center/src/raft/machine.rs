@@ -12,47 +12,182 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::apply::{RaftMessage, RaftResponseMesage};
+use super::applier::{undecodable_entry, AppliedRun, ApplyWorker, EntryApplier};
+use super::apply::{
+    MetaEvent, PeerProgress, RaftClusterConfig, RaftMessage, RaftNodeStatus, RaftResponseMesage,
+    ReplicationStatus, StorageData,
+};
+use super::codec::{decode_message, encode_message};
+use super::election::{Election, ElectionLeader};
 use super::route::DataRoute;
 use super::storage::RaftRocksDBStorage;
+use crate::raft::lease::LeaderLease;
+use crate::raft::logger::{raft_log_file_name, raft_log_tag, RotatingFileWriter};
 use crate::raft::metadata::RaftGroupMetadata;
-use crate::raft::peer::PeerMessage;
+use crate::raft::peer::{PeerClients, PeerMessage};
+use crate::raft::tail::tail;
 use crate::storage::placement::raft::RaftMachineStorage;
 use bincode::{deserialize, serialize};
-use common_base::config::placement_center::placement_center_conf;
-use log::{debug, error, info};
+use common_base::config::placement_center::{
+    placement_center_conf, RAFT_ELECTION_TICK, RAFT_HEARTBEAT_TICK,
+};
+use common_base::error::common::CommonError;
+use common_base::error::placement_center::PlacementCenterError;
+use futures::Stream;
+use log::{debug, error, info, warn};
 use metadata_struct::placement::broker_node::BrokerNode;
-use prost::Message as _;
 use raft::eraftpb::{
-    ConfChange, ConfChangeType, Entry, EntryType, Message as raftPreludeMessage, MessageType,
-    Snapshot,
+    ConfChange, ConfChangeType, ConfChangeV2, Entry, EntryType, Message as raftPreludeMessage,
+    MessageType, Snapshot,
 };
-use raft::{Config, RawNode};
+use raft::{Config, RawNode, StateRole, INVALID_ID};
+use rocksdb_engine::StorageError as EngineError;
 use slog::o;
 use slog::Drain;
-use std::collections::HashMap;
-use std::fs::OpenOptions;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::future::pending;
 use std::sync::atomic::AtomicUsize;
 use std::sync::{Arc, RwLock};
 use std::time::Duration;
 use std::time::Instant;
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::mpsc::{Receiver, Sender};
-use tokio::sync::{broadcast, oneshot};
-use tokio::time::timeout;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::{spawn_blocking, JoinHandle};
+use tokio::time::{interval, MissedTickBehavior};
 
 pub struct RaftMachine {
     placement_cluster: Arc<RwLock<RaftGroupMetadata>>,
     receiver: Receiver<RaftMessage>,
     seqnum: AtomicUsize,
     resp_channel: HashMap<usize, oneshot::Sender<RaftResponseMesage>>,
-    data_route: Arc<DataRoute>,
-    entry_num: AtomicUsize,
+    // Deadlines of the proposals in resp_channel, conf changes have none
+    proposal_deadlines: HashMap<usize, Instant>,
     peer_message_send: Sender<PeerMessage>,
     stop_recv: broadcast::Receiver<bool>,
     raft_storage: Arc<RwLock<RaftMachineStorage>>,
+    peer_clients: Arc<PeerClients>,
+    max_pending_proposals: usize,
+    max_uncommitted_size: u64,
+    apply_batch_size: usize,
+    apply_queue_size: usize,
+    applier: Arc<EntryApplier>,
+    // Started by run when apply_queue_size is not 0, otherwise entries are applied inline
+    apply_worker: Option<ApplyWorker>,
+    // Last index applied to the state machine, the Raft node is told once it is reached
+    applied_index: u64,
+    // Last index handed to the apply worker or applied inline
+    dispatched_index: u64,
+    min_election_tick: usize,
+    max_election_tick: usize,
+    event_sender: broadcast::Sender<MetaEvent>,
+    bootstrap: bool,
+    lease: LeaderLease,
+    read_index_channel: HashMap<usize, oneshot::Sender<RaftResponseMesage>>,
+    // Confirmed reads waiting for the state machine to apply up to their index
+    pending_reads: Vec<(u64, oneshot::Sender<RaftResponseMesage>)>,
+    // ReadIndex requests of GetMembership, answered with the configuration instead of Success
+    membership_reads: HashSet<usize>,
+    pending_membership: Vec<(u64, oneshot::Sender<RaftResponseMesage>)>,
+    pending_transfer: Option<LeaderTransfer>,
+    // Proposer of the joint conf change in progress, answered once the joint configuration is left
+    pending_joint_change: Option<oneshot::Sender<RaftResponseMesage>>,
+    quorum_loss_step_downs: u64,
+    // Proposals are rejected with ReadOnly while set, see set_read_only
+    read_only: bool,
+    // Period of the background RocksDB flush, zero when it is disabled
+    flush_interval: Duration,
+    // Interval between two ticks of the Raft node, RAFT_TICK_INTERVAL outside of tests
+    tick_interval: Duration,
+    flush_task: Option<JoinHandle<()>>,
+}
+
+struct LeaderTransfer {
+    target: u64,
+    deadline: Instant,
+    chan: oneshot::Sender<RaftResponseMesage>,
+}
+
+// Events kept for slow subscribers, beyond this they get a Lagged error
+const META_EVENT_CAPACITY: usize = 1024;
+
+const RAFT_TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+// Ticks made up for at once after a stalled iteration, more than an election
+// timeout would only make the node campaign several times in a row.
+const MAX_COALESCED_TICKS: u32 = RAFT_ELECTION_TICK as u32;
+
+// Number of tick intervals that have passed, capped at MAX_COALESCED_TICKS
+fn elapsed_ticks(elapsed: Duration, tick_interval: Duration) -> u32 {
+    let ticks = elapsed.as_nanos() / tick_interval.as_nanos();
+    return cmp::min(ticks, MAX_COALESCED_TICKS as u128) as u32;
+}
+
+// The lease is one heartbeat shorter than the election timeout,
+// which absorbs the message delay and clock drift between nodes.
+fn leader_lease_duration() -> Duration {
+    return RAFT_TICK_INTERVAL * (RAFT_ELECTION_TICK - RAFT_HEARTBEAT_TICK) as u32;
+}
+
+// A leader that has not heard from a quorum of voters for an election timeout steps down,
+// by then the others may have elected a new leader and its reads would be stale.
+fn quorum_loss_timeout() -> Duration {
+    return RAFT_TICK_INTERVAL * RAFT_ELECTION_TICK as u32;
+}
+
+// A learner is promoted only once it has replicated the log up to this many entries
+// below the commit index, so that it does not stall the quorum while catching up.
+const PROMOTE_LEARNER_MAX_LAG: u64 = 100;
+
+// Time each peer is given to answer the leader discovery of a fresh node
+const ELECTION_PEER_TIMEOUT: Duration = Duration::from_secs(3);
+
+// The leader aborts a transfer after an election timeout, the target may still be
+// campaigning by then, so the caller waits for two.
+fn leader_transfer_timeout() -> Duration {
+    return RAFT_TICK_INTERVAL * (RAFT_ELECTION_TICK * 2) as u32;
+}
+
+// Highest index matched by a majority of the voters, 0 without voters
+fn quorum_index(mut matched: Vec<u64>) -> u64 {
+    if matched.is_empty() {
+        return 0;
+    }
+    matched.sort_unstable_by(|a, b| b.cmp(a));
+    return matched[matched.len() / 2];
 }
 
 impl RaftMachine {
+    /// Create the node together with the channel it receives its messages on, bounded at
+    /// raft.message_channel_size. Once that many messages are queued the senders wait,
+    /// which is the backpressure the proposers see. Returns the sender of the channel.
+    pub fn new_with_channel(
+        placement_cluster: Arc<RwLock<RaftGroupMetadata>>,
+        data_route: Arc<DataRoute>,
+        peer_message_send: Sender<PeerMessage>,
+        stop_recv: broadcast::Receiver<bool>,
+        raft_storage: Arc<RwLock<RaftMachineStorage>>,
+        peer_clients: Arc<PeerClients>,
+    ) -> Result<(Self, Sender<RaftMessage>), PlacementCenterError> {
+        let conf = placement_center_conf();
+        conf.validate()?;
+
+        let capacity = conf.raft.message_channel_size as usize;
+        let (sender, receiver) = mpsc::channel::<RaftMessage>(capacity);
+        let machine = RaftMachine::new(
+            placement_cluster,
+            data_route,
+            peer_message_send,
+            receiver,
+            stop_recv,
+            raft_storage,
+            peer_clients,
+        )?;
+        return Ok((machine, sender));
+    }
+
     pub fn new(
         placement_cluster: Arc<RwLock<RaftGroupMetadata>>,
         data_route: Arc<DataRoute>,
@@ -60,28 +195,233 @@ impl RaftMachine {
         receiver: Receiver<RaftMessage>,
         stop_recv: broadcast::Receiver<bool>,
         raft_storage: Arc<RwLock<RaftMachineStorage>>,
-    ) -> Self {
+        peer_clients: Arc<PeerClients>,
+    ) -> Result<Self, PlacementCenterError> {
+        let conf = placement_center_conf();
+        conf.validate()?;
+
         let seqnum = AtomicUsize::new(1);
-        let entry_num = AtomicUsize::new(1);
         let resp_channel = HashMap::new();
-        return Self {
+        let (event_sender, _) = broadcast::channel(META_EVENT_CAPACITY);
+        let applier = Arc::new(EntryApplier::new(
+            data_route,
+            raft_storage.clone(),
+            event_sender.clone(),
+        ));
+        return Ok(Self {
             placement_cluster,
             receiver,
             seqnum,
             resp_channel,
-            data_route,
-            entry_num,
+            proposal_deadlines: HashMap::new(),
             peer_message_send,
             stop_recv,
             raft_storage,
+            peer_clients,
+            max_pending_proposals: conf.raft.max_pending_proposals as usize,
+            // 0 disables the limit, which is u64::MAX (NO_LIMIT) for raft-rs
+            max_uncommitted_size: match conf.raft.max_uncommitted_size_bytes {
+                0 => u64::MAX,
+                size => size,
+            },
+            apply_batch_size: conf.raft.apply_batch_size as usize,
+            apply_queue_size: conf.raft.apply_queue_size as usize,
+            applier,
+            apply_worker: None,
+            applied_index: 0,
+            dispatched_index: 0,
+            min_election_tick: conf.raft.min_election_tick as usize,
+            max_election_tick: conf.raft.max_election_tick as usize,
+            event_sender,
+            bootstrap: conf.bootstrap,
+            lease: LeaderLease::new(leader_lease_duration()),
+            read_index_channel: HashMap::new(),
+            pending_reads: Vec::new(),
+            membership_reads: HashSet::new(),
+            pending_membership: Vec::new(),
+            pending_transfer: None,
+            pending_joint_change: None,
+            quorum_loss_step_downs: 0,
+            read_only: false,
+            flush_interval: Duration::from_millis(conf.rocksdb.flush_interval_ms),
+            flush_task: None,
+            tick_interval: RAFT_TICK_INTERVAL,
+        });
+    }
+
+    /// Subscribe to the entries applied to the state machine. Sending never waits for
+    /// subscribers, one that falls behind gets `RecvError::Lagged` instead.
+    pub fn subscribe(&self) -> broadcast::Receiver<MetaEvent> {
+        return self.event_sender.subscribe();
+    }
+
+    /// Stream the service data committed from `from_index` on, see `tail::tail`
+    pub fn tail(
+        &self,
+        from_index: u64,
+    ) -> impl Stream<Item = Result<(u64, StorageData), PlacementCenterError>> {
+        return tail(self.raft_storage.clone(), self.subscribe(), from_index);
+    }
+
+    /// Whether the leader can serve a read from the local state machine without ReadIndex.
+    /// The lease is only trusted with check_quorum, otherwise followers may still vote
+    /// for a new leader while it is held.
+    pub fn can_serve_local_read(&self, raft_node: &RawNode<RaftRocksDBStorage>) -> bool {
+        let raft = &raft_node.raft;
+        return placement_center_conf().raft.lease_read
+            && raft.state == StateRole::Leader
+            && raft.check_quorum
+            && raft.commit_to_current_term()
+            && raft.raft_log.applied >= raft.raft_log.committed
+            && self.lease.is_valid(Instant::now());
+    }
+
+    /// Tick once for every interval that has elapsed, so that a stalled loop
+    /// does not slow down the election and heartbeat timers. Returns the ticks applied.
+    fn advance_ticks(
+        &mut self,
+        raft_node: &mut RawNode<RaftRocksDBStorage>,
+        elapsed: Duration,
+    ) -> u32 {
+        let ticks = elapsed_ticks(elapsed, self.tick_interval);
+        if ticks == 0 {
+            return 0;
+        }
+        if ticks > 1 {
+            debug!("Raft loop stalled for {:?}, applying {} ticks", elapsed, ticks);
+        }
+        for _ in 0..ticks {
+            raft_node.tick();
+        }
+        self.renew_lease(raft_node);
+        return ticks;
+    }
+
+    fn renew_lease(&mut self, raft_node: &RawNode<RaftRocksDBStorage>) {
+        if raft_node.raft.state != StateRole::Leader {
+            return;
+        }
+        let voters = raft_node.raft.prs().conf().to_conf_state().voters;
+        self.lease.renew(raft_node.raft.id, &voters, Instant::now());
+    }
+
+    fn quorum_ack_age(&self, raft_node: &RawNode<RaftRocksDBStorage>) -> Option<Duration> {
+        if raft_node.raft.state != StateRole::Leader {
+            return None;
+        }
+        return self.lease.quorum_heard_at().map(|heard_at| heard_at.elapsed());
+    }
+
+    // With check_quorum raft steps the leader down when a quorum of voters has not been
+    // active for an election timeout. A leader that became follower without hearing from
+    // a quorum for that long has stepped down for it, not for a higher term. Raft counts
+    // the timeout in ticks that may run a little late, one tick of it is left as slack.
+    fn record_quorum_loss(&mut self, from: StateRole, raft_node: &RawNode<RaftRocksDBStorage>) {
+        if from != StateRole::Leader || raft_node.raft.state != StateRole::Follower {
+            return;
+        }
+        let age = match self.lease.quorum_heard_at() {
+            Some(heard_at) => heard_at.elapsed(),
+            None => return,
         };
+        if age < quorum_loss_timeout() - RAFT_TICK_INTERVAL {
+            return;
+        }
+        warn!(
+            "Raft leader {} has not heard from a quorum of voters for {:?}, stepped down at term {}",
+            raft_node.raft.id, age, raft_node.raft.term
+        );
+        self.quorum_loss_step_downs += 1;
+    }
+
+    /// Flush RocksDB every flush_interval until the node stops. The flush waits for the
+    /// memtables to be written out, so it runs on the blocking pool instead of a runtime
+    /// thread the ready loop may need, and is skipped when nothing has been written.
+    fn start_flush_task(&mut self) {
+        if self.flush_interval.is_zero() {
+            return;
+        }
+        // a restarted run keeps the task of the previous one
+        if let Some(task) = &self.flush_task {
+            if !task.is_finished() {
+                return;
+            }
+        }
+
+        let engine = self.raft_storage.read().unwrap().rocksdb_engine_handler.clone();
+        let mut stop_recv = self.stop_recv.resubscribe();
+        let flush_interval = self.flush_interval;
+        self.flush_task = Some(tokio::spawn(async move {
+            let mut flush_timer = interval(flush_interval);
+            flush_timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            let mut flushed_writes = engine.write_calls();
+            loop {
+                select! {
+                    val = stop_recv.recv() => match val {
+                        Ok(true) | Err(RecvError::Closed) => break,
+                        _ => continue,
+                    },
+                    _ = flush_timer.tick() => {}
+                }
+
+                let writes = engine.write_calls();
+                if writes == flushed_writes {
+                    continue;
+                }
+                flushed_writes = writes;
+
+                let flush_engine = engine.clone();
+                match spawn_blocking(move || flush_engine.flush()).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => {
+                        error!("Background flush of RocksDB failed, error message: {}", e);
+                    }
+                    Err(e) => {
+                        error!(
+                            "Background flush of RocksDB did not complete, error message: {}",
+                            e
+                        );
+                    }
+                }
+            }
+            info!("RocksDB background flush stopped");
+        }));
+    }
+
+    /// Returns an error when the Raft node cannot be created from the persisted state,
+    /// the caller may log it and call run again.
+    pub async fn run(&mut self) -> Result<(), PlacementCenterError> {
+        let mut raft_node: RawNode<RaftRocksDBStorage> = self.new_node().await?;
+        self.start_flush_task();
+        self.start_apply_worker(&raft_node);
+
+        let result = self.ready_loop(&mut raft_node).await;
+        // the entries handed to the worker are applied before the node may be started again
+        if let Some(worker) = self.apply_worker.take() {
+            worker.stop().await;
+        }
+        return result;
     }
 
-    pub async fn run(&mut self) {
-        let mut raft_node: RawNode<RaftRocksDBStorage> = self.new_node().await;
+    fn start_apply_worker(&mut self, raft_node: &RawNode<RaftRocksDBStorage>) {
+        self.applied_index = raft_node.raft.raft_log.applied;
+        self.dispatched_index = self.applied_index;
+        if self.apply_queue_size == 0 {
+            return;
+        }
+        self.apply_worker = Some(ApplyWorker::start(self.applier.clone(), self.apply_queue_size));
+    }
 
-        let heartbeat = Duration::from_millis(100);
+    async fn ready_loop(
+        &mut self,
+        raft_node: &mut RawNode<RaftRocksDBStorage>,
+    ) -> Result<(), PlacementCenterError> {
+        let heartbeat = self.tick_interval;
         let mut now = Instant::now();
+        // Only wakes the loop so that ticks are not late when no message arrives,
+        // how many ticks are due is still decided by advance_ticks.
+        let mut tick_timer = interval(heartbeat);
+        tick_timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
         loop {
             match self.stop_recv.try_recv() {
                 Ok(val) => {
@@ -93,26 +433,96 @@ impl RaftMachine {
                 Err(_) => {}
             }
 
-            match timeout(heartbeat, self.receiver.recv()).await {
+            let message = select! {
+                message = self.receiver.recv() => Ok(message),
+                _ = tick_timer.tick() => Err(None),
+                run = next_applied_run(&mut self.apply_worker) => Err(Some(run)),
+            };
+            let message = match message {
+                Err(Some(Some(run))) => {
+                    self.on_applied(run);
+                    self.advance_applied(raft_node);
+                    Err(None)
+                }
+                Err(Some(None)) => return Err(PlacementCenterError::RaftApplyWorkerStopped),
+                message => message,
+            };
+
+            match message {
                 Ok(Some(RaftMessage::ConfChange { change, chan })) => {
-                    let seq = self
-                        .seqnum
-                        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    match self.check_conf_change(raft_node, &change) {
+                        Ok(()) => {
+                            let seq = self
+                                .seqnum
+                                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
+                            match raft_node.propose_conf_change(serialize(&seq).unwrap(), change)
+                            {
+                                Ok(_) => {
+                                    self.resp_channel.insert(seq, chan);
+                                }
+                                Err(e) => {
+                                    error!("{}", e,);
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            error!("{}", e);
+                            match chan.send(RaftResponseMesage::Fail) {
+                                Ok(_) => {}
+                                Err(_) => {
+                                    error!("{}","conf change Fails to return data to chan. chan may have been closed");
+                                }
+                            }
+                        }
+                    }
+                }
 
-                    match raft_node.propose_conf_change(serialize(&seq).unwrap(), change) {
-                        Ok(_) => {
-                            self.resp_channel.insert(seq, chan);
+                Ok(Some(RaftMessage::ConfChangeV2 { change, chan })) => {
+                    match self.check_conf_change_v2(raft_node, &change) {
+                        Ok(()) => {
+                            let seq = self
+                                .seqnum
+                                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
+                            match raft_node.propose_conf_change(serialize(&seq).unwrap(), change)
+                            {
+                                Ok(_) => {
+                                    self.resp_channel.insert(seq, chan);
+                                }
+                                Err(e) => {
+                                    error!("{}", e);
+                                    let _ = chan.send(RaftResponseMesage::Fail);
+                                }
+                            }
                         }
                         Err(e) => {
-                            error!("{}", e,);
+                            error!("{}", e);
+                            match chan.send(RaftResponseMesage::Fail) {
+                                Ok(_) => {}
+                                Err(_) => {
+                                    error!("{}","conf change Fails to return data to chan. chan may have been closed");
+                                }
+                            }
                         }
                     }
                 }
 
                 Ok(Some(RaftMessage::Raft { message, chan })) => {
-                    // Step advances the state machine using the given message.
+                    // A response of a follower proves it still accepts this node as leader.
+                    let msg_type = message.get_msg_type();
+                    if raft_node.raft.state == StateRole::Leader
+                        && message.get_term() == raft_node.raft.term
+                        && (msg_type == MessageType::MsgHeartbeatResponse
+                            || msg_type == MessageType::MsgAppendResponse)
+                    {
+                        self.lease.record_ack(message.get_from(), Instant::now());
+                    }
 
-                    match raft_node.step(message) {
+                    // Step advances the state machine using the given message.
+                    let result = raft_node.step(message);
+                    self.renew_lease(raft_node);
+                    match result {
                         // After the step message succeeds, you can return success directly
                         Ok(_) => match chan.send(RaftResponseMesage::Success) {
                             Ok(_) => {}
@@ -127,66 +537,251 @@ impl RaftMachine {
                 }
 
                 Ok(Some(RaftMessage::TransferLeader { node_id, chan })) => {
-                    // Step advances the state machine using the given message.
                     info!("transfer_leader {}", node_id);
-                    raft_node.transfer_leader(node_id);
-                    match chan.send(RaftResponseMesage::Success) {
+                    match self.check_transfer_leader(raft_node, node_id) {
+                        Ok(()) => {
+                            if node_id == raft_node.raft.id {
+                                let _ = chan.send(RaftResponseMesage::Success);
+                            } else {
+                                raft_node.transfer_leader(node_id);
+                                // A newer request replaces the transfer still in progress
+                                if let Some(previous) = self.pending_transfer.take() {
+                                    let _ = previous.chan.send(RaftResponseMesage::Fail);
+                                }
+                                self.pending_transfer = Some(LeaderTransfer {
+                                    target: node_id,
+                                    deadline: Instant::now() + leader_transfer_timeout(),
+                                    chan,
+                                });
+                            }
+                        }
+                        Err(e) => {
+                            error!("{}", e);
+                            if chan.send(RaftResponseMesage::Fail).is_err() {
+                                error!("{}","transfer leader Fails to return data to chan. chan may have been closed");
+                            }
+                        }
+                    }
+                }
+
+                Ok(Some(RaftMessage::Propose {
+                    data,
+                    chan,
+                    deadline,
+                })) => {
+                    if self.read_only {
+                        if chan.send(RaftResponseMesage::ReadOnly).is_err() {
+                            error!("{}","propose Fails to return data to chan. chan may have been closed");
+                        }
+                    } else if raft_node.raft.state != StateRole::Leader {
+                        // A follower would forward the proposal and never learn its outcome,
+                        // tell the caller where the leader is instead.
+                        let leader_id = raft_node.raft.leader_id;
+                        let resp = RaftResponseMesage::NotLeader {
+                            leader_id,
+                            leader_addr: self.node_addr(leader_id),
+                        };
+                        if chan.send(resp).is_err() {
+                            error!("{}","propose Fails to return data to chan. chan may have been closed");
+                        }
+                    } else if self.resp_channel.len() >= self.max_pending_proposals {
+                        // Reject the proposal instead of queueing it without bound.
+                        match chan.send(RaftResponseMesage::ResourceExhausted) {
+                            Ok(_) => {}
+                            Err(_) => {
+                                error!("{}","propose Fails to return data to chan. chan may have been closed");
+                            }
+                        }
+                    } else {
+                        // Propose proposes data be appended to the raft log.
+                        let seq = self
+                            .seqnum
+                            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        match raft_node.propose(serialize(&seq).unwrap(), data) {
+                            Ok(_) => {
+                                self.resp_channel.insert(seq, chan);
+                                self.proposal_deadlines.insert(seq, deadline);
+                            }
+                            Err(e) => {
+                                error!("{}", e);
+                                if chan.send(RaftResponseMesage::Fail).is_err() {
+                                    error!("{}","propose Fails to return data to chan. chan may have been closed");
+                                }
+                            }
+                        }
+                    }
+                }
+
+                Ok(Some(RaftMessage::Snapshot { chan })) => {
+                    // the snapshot must not race with the worker writing the state machine
+                    self.drain_apply_worker().await?;
+                    let resp = match raft_node.mut_store().create_snapshot() {
+                        Some(index) => {
+                            info!("Raft snapshot created at index {} on demand", index);
+                            RaftResponseMesage::SnapshotCreated(index)
+                        }
+                        None => RaftResponseMesage::Fail,
+                    };
+                    if chan.send(resp).is_err() {
+                        error!("{}","snapshot Fails to return data to chan. chan may have been closed");
+                    }
+                }
+
+                Ok(Some(RaftMessage::SetReadOnly { read_only, chan })) => {
+                    self.set_read_only(read_only);
+                    if chan.send(RaftResponseMesage::Success).is_err() {
+                        error!("{}","set read-only Fails to return data to chan. chan may have been closed");
+                    }
+                }
+
+                Ok(Some(RaftMessage::Campaign { chan })) => {
+                    let resp = self.campaign(raft_node);
+                    if chan.send(resp).is_err() {
+                        error!("{}","campaign Fails to return data to chan. chan may have been closed");
+                    }
+                }
+
+                Ok(Some(RaftMessage::ReplicationStatus { chan })) => {
+                    let resp = if raft_node.raft.state == StateRole::Leader {
+                        RaftResponseMesage::ReplicationStatus(self.replication_status(raft_node))
+                    } else {
+                        let leader_id = raft_node.raft.leader_id;
+                        RaftResponseMesage::NotLeader {
+                            leader_id,
+                            leader_addr: self.node_addr(leader_id),
+                        }
+                    };
+                    if chan.send(resp).is_err() {
+                        error!("{}","replication status Fails to return data to chan. chan may have been closed");
+                    }
+                }
+
+                Ok(Some(RaftMessage::Status { chan })) => {
+                    let status = self.node_status(raft_node);
+                    match chan.send(RaftResponseMesage::Status(status)) {
                         Ok(_) => {}
                         Err(_) => {
-                            error!("{}","commit entry Fails to return data to chan. chan may have been closed");
+                            error!("{}","Raft node status Fails to return data to chan. chan may have been closed");
                         }
                     }
                 }
 
-                Ok(Some(RaftMessage::Propose { data, chan })) => {
-                    // Propose proposes data be appended to the raft log.
-                    let seq = self
-                        .seqnum
-                        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    match raft_node.propose(serialize(&seq).unwrap(), data) {
-                        Ok(_) => {
-                            self.resp_channel.insert(seq, chan);
+                Ok(Some(RaftMessage::ClusterConfig { chan })) => {
+                    let config = self.cluster_config(raft_node);
+                    match chan.send(RaftResponseMesage::ClusterConfig(config)) {
+                        Ok(_) => {}
+                        Err(_) => {
+                            error!("{}","Raft cluster config Fails to return data to chan. chan may have been closed");
                         }
-                        Err(e) => {
-                            error!("{}", e);
+                    }
+                }
+                Ok(Some(RaftMessage::GetMembership { chan })) => {
+                    if raft_node.raft.state == StateRole::Leader {
+                        let config = self.cluster_config(raft_node);
+                        if chan.send(RaftResponseMesage::ClusterConfig(config)).is_err() {
+                            error!("{}","get membership Fails to return data to chan. chan may have been closed");
+                        }
+                    } else if raft_node.raft.leader_id == INVALID_ID {
+                        let resp = RaftResponseMesage::NotLeader {
+                            leader_id: INVALID_ID,
+                            leader_addr: "".to_string(),
+                        };
+                        if chan.send(resp).is_err() {
+                            error!("{}","get membership Fails to return data to chan. chan may have been closed");
+                        }
+                    } else {
+                        // the ReadIndex is confirmed by the leader, once applied up to its index
+                        // the local configuration has every conf change the leader committed
+                        let seq = self
+                            .seqnum
+                            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        raft_node.read_index(serialize(&seq).unwrap());
+                        self.read_index_channel.insert(seq, chan);
+                        self.membership_reads.insert(seq);
+                    }
+                }
+                Ok(Some(RaftMessage::ReadIndex { chan })) => {
+                    if self.can_serve_local_read(raft_node) {
+                        match chan.send(RaftResponseMesage::Success) {
+                            Ok(_) => {}
+                            Err(_) => {
+                                error!("{}","read index Fails to return data to chan. chan may have been closed");
+                            }
                         }
+                    } else {
+                        let seq = self
+                            .seqnum
+                            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        raft_node.read_index(serialize(&seq).unwrap());
+                        self.read_index_channel.insert(seq, chan);
                     }
                 }
                 Ok(None) => continue,
                 Err(_) => {}
             }
 
-            let elapsed = now.elapsed();
-
-            if elapsed >= heartbeat {
-                raft_node.tick();
-                now = Instant::now();
+            let ticks = self.advance_ticks(raft_node, now.elapsed());
+            if ticks > 0 {
+                self.expire_proposals(Instant::now());
+                // keep the remainder of the interval unless ticks were dropped by the cap
+                if ticks < MAX_COALESCED_TICKS {
+                    now += heartbeat * ticks;
+                } else {
+                    now = Instant::now();
+                }
             }
 
-            if self.placement_cluster.read().unwrap().raft_role != raft_node.raft.state {
+            let from = self.placement_cluster.read().unwrap().raft_role;
+            if from != raft_node.raft.state {
                 info!(
                     "Node Raft Role changes from  【{:?}】 to 【{:?}】",
-                    self.placement_cluster.read().unwrap().raft_role,
-                    raft_node.raft.state
+                    from, raft_node.raft.state
                 );
+                // the lease still holds the last time the former leader heard from a quorum
+                self.record_quorum_loss(from, raft_node);
                 self.placement_cluster
                     .write()
                     .unwrap()
                     .set_role(raft_node.raft.state);
 
+                // Proposals of a former leader may never be committed, drop their channels
+                // so that the callers fail instead of holding a pending slot forever.
+                if raft_node.raft.state != StateRole::Leader {
+                    self.resp_channel.clear();
+                    self.proposal_deadlines.clear();
+                }
+                self.lease.reset();
+                if raft_node.raft.state == StateRole::Leader {
+                    self.lease.elected(Instant::now());
+                }
+                self.read_index_channel.clear();
+                self.pending_reads.clear();
+                self.membership_reads.clear();
+                self.pending_membership.clear();
+
                 let local_node = self.placement_cluster.read().unwrap().local.clone();
                 self.placement_cluster
                     .write()
                     .unwrap()
                     .set_leader(local_node);
             }
-            self.on_ready(&mut raft_node).await;
+            // entries that cannot be persisted must not be acknowledged, stop the node
+            // and let the caller start it again from what has been persisted
+            if let Err(e) = self.on_ready(raft_node).await {
+                error!("{}", e);
+                return Err(e);
+            }
+            self.check_leader_transfer(raft_node);
         }
+        return Ok(());
     }
 
-    async fn on_ready(&mut self, raft_node: &mut RawNode<RaftRocksDBStorage>) {
+    async fn on_ready(
+        &mut self,
+        raft_node: &mut RawNode<RaftRocksDBStorage>,
+    ) -> Result<(), PlacementCenterError> {
         if !raft_node.has_ready() {
-            return;
+            return Ok(());
         }
 
         let mut ready = raft_node.ready();
@@ -196,34 +791,63 @@ impl RaftMachine {
             self.send_message(ready.take_messages()).await;
         }
 
-        // If the snapshot is not empty, save the snapshot to Storage, and apply
-        // the data in the snapshot to the State Machine asynchronously.
-        // (Although synchronous apply can also be applied here,
-        // but the snapshot is usually large. Synchronization blocks threads).
+        // If the snapshot is not empty, it is persisted along with the Raft state it implies and
+        // the log is truncated, only then its data is restored into the State Machine. Raft is
+        // told it has been applied when the ready is advanced below.
         if *ready.snapshot() != Snapshot::default() {
+            // the entries still queued are older than the snapshot, they must not be
+            // applied on top of it
+            self.drain_apply_worker().await?;
             let s = ready.snapshot().clone();
             info!(
                 "save snapshot,term:{},index:{}",
                 s.get_metadata().get_term(),
                 s.get_metadata().get_index()
             );
-            raft_node.mut_store().apply_snapshot(s).unwrap();
+            let snapshot_index = s.get_metadata().get_index();
+            if let Err(e) = raft_node.mut_store().apply_snapshot(s) {
+                return Err(PlacementCenterError::RaftStorageFailed(e.to_string()));
+            }
+            self.applied_index = snapshot_index;
+            self.dispatched_index = snapshot_index;
         }
 
         // messages need to be stored to Storage before they can be sent.Save entries to Storage.
         if !ready.entries().is_empty() {
             let entries = ready.entries();
-            raft_node.mut_store().append(entries).unwrap();
+            if let Err(e) = raft_node.mut_store().append(entries) {
+                return Err(PlacementCenterError::RaftStorageFailed(e.to_string()));
+            }
         }
 
         // The committed raft log can be applied to the State Machine.
-        self.handle_committed_entries(raft_node, ready.take_committed_entries());
+        self.handle_committed_entries(raft_node, ready.take_committed_entries()).await?;
+
+        // ReadIndex requests confirmed by the leader, they are answered once applied.
+        for rs in ready.take_read_states() {
+            match deserialize::<usize>(&rs.request_ctx) {
+                Ok(seq) => {
+                    if let Some(chan) = self.read_index_channel.remove(&seq) {
+                        if self.membership_reads.remove(&seq) {
+                            self.pending_membership.push((rs.index, chan));
+                        } else {
+                            self.pending_reads.push((rs.index, chan));
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to parse the read index context, error message: {}", e);
+                }
+            }
+        }
 
         // If there is a change in HardState, such as a revote,
         // term is increased, the hs will not be empty.Persist non-empty hs.
         if let Some(hs) = ready.hs() {
             debug!("save hardState!!!,len:{:?}", hs);
-            raft_node.mut_store().set_hard_state(hs.clone()).unwrap();
+            if let Err(e) = raft_node.mut_store().set_hard_state(hs.clone()) {
+                return Err(PlacementCenterError::RaftStorageFailed(e.to_string()));
+            }
         }
 
         // Persisted Messages specifies outbound messages to be sent AFTER the HardState,
@@ -232,77 +856,30 @@ impl RaftMachine {
             self.send_message(ready.take_persisted_messages()).await;
         }
 
-        // A call to advance tells Raft that it is ready for processing.
-        let mut light_rd = raft_node.advance(ready);
+        // A call to advance tells Raft that it is ready for processing. The entries are
+        // applied asynchronously, Raft only learns the applied index once they are.
+        let mut light_rd = raft_node.advance_append(ready);
         if let Some(commit) = light_rd.commit_index() {
             debug!("save light rd!!!,commit:{:?}", commit);
-            raft_node.mut_store().set_hard_state_comit(commit).unwrap();
+            if let Err(e) = raft_node.mut_store().set_hard_state_commit(commit) {
+                return Err(PlacementCenterError::RaftStorageFailed(e.to_string()));
+            }
         }
 
         self.send_message(light_rd.take_messages()).await;
 
-        self.handle_committed_entries(raft_node, light_rd.take_committed_entries());
+        self.handle_committed_entries(raft_node, light_rd.take_committed_entries()).await?;
 
-        raft_node.advance_apply();
+        self.advance_applied(raft_node);
+        return Ok(());
     }
 
-    fn handle_committed_entries(
-        &mut self,
-        raft_node: &mut RawNode<RaftRocksDBStorage>,
-        entrys: Vec<Entry>,
-    ) {
-        for entry in entrys {
-            if !entry.data.is_empty() {
-                debug!("ready entrys entry type:{:?}", entry.get_entry_type());
-                match entry.get_entry_type() {
-                    EntryType::EntryNormal => {
-                        // Saves the service data sent by the client
-                        match self.data_route.route(entry.get_data().to_vec()) {
-                            Ok(_) => {}
-                            Err(err) => {
-                                error!("{}", err);
-                            }
-                        }
-                    }
-                    EntryType::EntryConfChange => {
-                        let change = ConfChange::decode(entry.get_data())
-                            .map_err(|e| tonic::Status::invalid_argument(e.to_string()))
-                            .unwrap();
-                        let id = change.get_node_id();
-                        let change_type = change.get_change_type();
-                        match change_type {
-                            ConfChangeType::AddNode => {
-                                match deserialize::<BrokerNode>(change.get_context()) {
-                                    Ok(node) => {
-                                        let mut cls = self.placement_cluster.write().unwrap();
-                                        cls.add_peer(id, node);
-                                    }
-                                    Err(e) => {
-                                        error!("Failed to parse Node data from context with error message {:?}", e);
-                                    }
-                                }
-                            }
-                            ConfChangeType::RemoveNode => {
-                                let mut cls = self.placement_cluster.write().unwrap();
-                                cls.remove_peer(id);
-                            }
-                            _ => unimplemented!(),
-                        }
-
-                        if let Ok(cs) = raft_node.apply_conf_change(&change) {
-                            let _ = raft_node.mut_store().set_conf_state(cs);
-                        }
-                    }
-                    EntryType::EntryConfChangeV2 => {}
-                }
-            }
-
-            let idx: u64 = entry.get_index();
-            let _ = raft_node.mut_store().commmit_index(idx);
-
-            match deserialize(entry.get_context()) {
-                Ok(seq) => match self.resp_channel.remove(&seq) {
-                    Some(chan) => match chan.send(RaftResponseMesage::Success) {
+    // Answer the proposers of the entries of a run the state machine has applied
+    fn on_applied(&mut self, run: AppliedRun) {
+        for (context, resp) in run.responses {
+            match deserialize(&context) {
+                Ok(seq) => match self.remove_proposal(seq) {
+                    Some(chan) => match chan.send(resp) {
                         Ok(_) => {}
                         Err(_) => {
                             error!("commit entry Fails to return data to chan. chan may have been closed");
@@ -312,78 +889,783 @@ impl RaftMachine {
                 },
                 Err(_) => {}
             }
+        }
+        self.applied_index = cmp::max(self.applied_index, run.last_index);
+    }
+
+    fn remove_proposal(&mut self, seq: usize) -> Option<oneshot::Sender<RaftResponseMesage>> {
+        self.proposal_deadlines.remove(&seq);
+        return self.resp_channel.remove(&seq);
+    }
+
+    // Answer the proposals whose deadline has passed with Timeout, their pending slots are
+    // released. An entry that is still committed later is applied without a caller.
+    fn expire_proposals(&mut self, now: Instant) {
+        let expired: Vec<usize> = self
+            .proposal_deadlines
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(seq, _)| *seq)
+            .collect();
+        for seq in expired {
+            if let Some(chan) = self.remove_proposal(seq) {
+                if chan.send(RaftResponseMesage::Timeout).is_err() {
+                    error!("{}","propose Fails to return data to chan. chan may have been closed");
+                }
+            }
+        }
+    }
 
-            self.create_snapshot(raft_node);
+    // Tell Raft how far the state machine has applied, and answer the reads waiting for it
+    fn advance_applied(&mut self, raft_node: &mut RawNode<RaftRocksDBStorage>) {
+        if self.applied_index > raft_node.raft.raft_log.applied {
+            raft_node.advance_apply_to(self.applied_index);
         }
+        self.complete_reads(raft_node.raft.raft_log.applied);
+        self.complete_membership_reads(raft_node);
     }
 
-    async fn send_message(&self, messages: Vec<raftPreludeMessage>) {
-        for msg in messages {
-            let to = msg.get_to();
-            if msg.get_msg_type() != MessageType::MsgHeartbeat
-                && msg.get_msg_type() != MessageType::MsgHeartbeatResponse
-            {
-                info!("ready message:{:?}", msg);
+    // Wait until the apply worker has applied every run handed to it, Raft is told about
+    // the applied index at the end of on_ready
+    async fn drain_apply_worker(&mut self) -> Result<(), PlacementCenterError> {
+        while self.applied_index < self.dispatched_index {
+            let run = match self.apply_worker.as_mut() {
+                Some(worker) => worker.applied().await,
+                None => return Ok(()),
+            };
+            match run {
+                Some(run) => self.on_applied(run),
+                None => return Err(PlacementCenterError::RaftApplyWorkerStopped),
             }
-            let data: Vec<u8> = raftPreludeMessage::encode_to_vec(&msg);
-            self.send_peer_message(to, data).await;
         }
+        return Ok(());
     }
 
-    pub async fn new_node(&self) -> RawNode<RaftRocksDBStorage> {
-        let cluster = self.placement_cluster.read().unwrap();
-        let storage = RaftRocksDBStorage::new(self.raft_storage.clone());
+    fn complete_membership_reads(&mut self, raft_node: &RawNode<RaftRocksDBStorage>) {
+        if self.pending_membership.is_empty() {
+            return;
+        }
+        let applied_index = raft_node.raft.raft_log.applied;
+        let (ready, waiting): (Vec<_>, Vec<_>) = self
+            .pending_membership
+            .drain(..)
+            .partition(|(index, _)| *index <= applied_index);
+        self.pending_membership = waiting;
+        for (_, chan) in ready {
+            let config = self.cluster_config(raft_node);
+            if chan.send(RaftResponseMesage::ClusterConfig(config)).is_err() {
+                error!("get membership Fails to return data to chan. chan may have been closed");
+            }
+        }
+    }
 
-        // build config
-        let hs = storage.read_lock().hard_state();
-        let conf = self.build_config(hs.commit);
+    fn complete_reads(&mut self, applied_index: u64) {
+        if self.pending_reads.is_empty() {
+            return;
+        }
+        let (ready, waiting): (Vec<_>, Vec<_>) = self
+            .pending_reads
+            .drain(..)
+            .partition(|(index, _)| *index <= applied_index);
+        self.pending_reads = waiting;
+        for (_, chan) in ready {
+            match chan.send(RaftResponseMesage::Success) {
+                Ok(_) => {}
+                Err(_) => {
+                    error!("read index Fails to return data to chan. chan may have been closed");
+                }
+            }
+        }
+    }
 
-        // init voters && learns
-        let mut cs = storage.read_lock().conf_state();
-        cs.voters = cluster.node_ids();
-        let _ = storage.write_lock().save_conf_state(cs);
+    async fn handle_committed_entries(
+        &mut self,
+        raft_node: &mut RawNode<RaftRocksDBStorage>,
+        entrys: Vec<Entry>,
+    ) -> Result<(), PlacementCenterError> {
+        // Entries are applied strictly in log order, so of two writes to the same key the one
+        // committed last wins on every node. An entry at or below the dispatched index has been
+        // applied or queued before, applying it again would overwrite the writes committed
+        // after it.
+        let mut last_index = self.dispatched_index;
+        let entrys: Vec<Entry> = entrys
+            .into_iter()
+            .filter(|entry| {
+                if entry.get_index() <= last_index {
+                    warn!(
+                        "Skip the committed entry at index {}, entries up to index {} have been applied",
+                        entry.get_index(),
+                        last_index
+                    );
+                    return false;
+                }
+                last_index = entry.get_index();
+                return true;
+            })
+            .collect();
 
-        let logger = self.build_slog();
-        let node = RawNode::new(&conf, storage, &logger).unwrap();
-        return node;
+        let batch_size = cmp::max(self.apply_batch_size, 1);
+        for run in entrys.chunks(batch_size) {
+            let last_index = run[run.len() - 1].get_index();
+            let has_conf_change = run
+                .iter()
+                .any(|entry| entry.get_entry_type() != EntryType::EntryNormal);
+            if has_conf_change {
+                // conf changes are applied through the RawNode, after the entries before them
+                self.drain_apply_worker().await?;
+                self.apply_entries(raft_node, run);
+            } else if let Some(worker) = &self.apply_worker {
+                worker.send(run.to_vec()).await?;
+            } else if let Some(applied) = self.applier.apply_run(run) {
+                self.on_applied(applied);
+            }
+            self.dispatched_index = last_index;
+        }
+        return Ok(());
     }
 
-    fn build_config(&self, apply: u64) -> Config {
-        let conf = placement_center_conf();
-        Config {
-            // The unique ID for the Raft node.
-            // id: self.config.node_id,
-            id: conf.node.node_id,
-            // Election tick is for how long the follower may campaign again after
-            // it doesn't receive any message from the leader.
-            election_tick: 10,
-            // Heartbeat tick is for how long the leader needs to send
-            // a heartbeat to keep alive.
-            heartbeat_tick: 3,
-            // The max size limits the max size of each appended message. Mostly, 1 MB is enough.
-            max_size_per_msg: 1024 * 1024 * 1024,
-            // Max inflight msgs that the leader sends messages to follower without
-            // receiving ACKs.
-            max_inflight_msgs: 256,
-            // The Raft applied index.
-            // You need to save your applied index when you apply the committed Raft logs.
-            applied: apply,
-            // check_quorum: true,
-            ..Default::default()
+    // Apply a run of committed entries that has conf changes in it, the conf changes
+    // go through the RawNode and the normal entries through the EntryApplier.
+    fn apply_entries(&mut self, raft_node: &mut RawNode<RaftRocksDBStorage>, run: &[Entry]) {
+        let applier = self.applier.clone();
+        let applied =
+            applier.apply_entries(run, |entry| self.apply_conf_change_entry(raft_node, entry));
+        if let Some(applied) = applied {
+            self.on_applied(applied);
+        }
+    }
+
+    fn apply_conf_change_entry(
+        &mut self,
+        raft_node: &mut RawNode<RaftRocksDBStorage>,
+        entry: &Entry,
+    ) -> Result<(), CommonError> {
+        // an empty ConfChangeV2 leaves the joint configuration
+        if entry.data.is_empty() && entry.get_entry_type() != EntryType::EntryConfChangeV2 {
+            debug!("Skip the empty entry at index {}", entry.get_index());
+            return Ok(());
+        }
+        debug!("ready entrys entry type:{:?}", entry.get_entry_type());
+        match entry.get_entry_type() {
+            EntryType::EntryConfChange => {
+                let change = match decode_message::<ConfChange>("ConfChange", entry.get_data()) {
+                    Ok(change) => change,
+                    Err(e) => return Err(undecodable_entry(entry, e)),
+                };
+                let id = change.get_node_id();
+                let change_type = change.get_change_type();
+                match change_type {
+                    // a promoted learner carries no context, its node is already known
+                    ConfChangeType::AddNode if change.get_context().is_empty() => {}
+                    ConfChangeType::AddNode | ConfChangeType::AddLearnerNode => {
+                        match deserialize::<BrokerNode>(change.get_context()) {
+                            Ok(node) => {
+                                let mut cls = self.placement_cluster.write().unwrap();
+                                cls.add_peer(id, node);
+                            }
+                            Err(e) => {
+                                error!("Failed to parse Node data from context with error message {:?}", e);
+                            }
+                        }
+                    }
+                    ConfChangeType::RemoveNode => {
+                        let mut cls = self.placement_cluster.write().unwrap();
+                        cls.remove_peer(id);
+                        // drop the connection to the removed node
+                        self.peer_clients.remove_peer(id);
+                    }
+                }
+
+                if let Ok(cs) = raft_node.apply_conf_change(&change) {
+                    let _ = raft_node.mut_store().set_conf_state(cs);
+                }
+            }
+            EntryType::EntryConfChangeV2 => {
+                let change =
+                    match decode_message::<ConfChangeV2>("ConfChangeV2", entry.get_data()) {
+                        Ok(change) => change,
+                        Err(e) => return Err(undecodable_entry(entry, e)),
+                    };
+                // the nodes joining the group, the change leaving the joint configuration has none
+                if !change.get_context().is_empty() {
+                    match deserialize::<Vec<BrokerNode>>(change.get_context()) {
+                        Ok(nodes) => {
+                            let mut cls = self.placement_cluster.write().unwrap();
+                            for node in nodes {
+                                cls.add_peer(node.node_id, node);
+                            }
+                        }
+                        Err(e) => {
+                            error!("Failed to parse Node data from context with error message {:?}", e);
+                        }
+                    }
+                }
+
+                let mut removed = raft_node.raft.prs().conf().to_conf_state().voters_outgoing;
+                for single in change.get_changes() {
+                    if single.get_change_type() == ConfChangeType::RemoveNode {
+                        removed.push(single.get_node_id());
+                    }
+                }
+
+                match raft_node.apply_conf_change(&change) {
+                    Ok(cs) => {
+                        if cs.voters_outgoing.is_empty() {
+                            // the removed voters count towards the joint quorum,
+                            // they are only dropped once it has been left
+                            for id in removed {
+                                if !cs.voters.contains(&id) && !cs.learners.contains(&id) {
+                                    self.placement_cluster.write().unwrap().remove_peer(id);
+                                    self.peer_clients.remove_peer(id);
+                                }
+                            }
+                            if let Some(chan) = self.pending_joint_change.take() {
+                                if chan.send(RaftResponseMesage::Applied(entry.get_index())).is_err() {
+                                    error!("conf change Fails to return data to chan. chan may have been closed");
+                                }
+                            }
+                        } else if let Ok(seq) = deserialize::<usize>(entry.get_context()) {
+                            // raft leaves the joint configuration on its own, see ConfChangeTransition::Auto
+                            if let Some(chan) = self.resp_channel.remove(&seq) {
+                                self.pending_joint_change = Some(chan);
+                            }
+                        }
+                        let _ = raft_node.mut_store().set_conf_state(cs);
+                    }
+                    Err(e) => {
+                        return Err(CommonError::CommmonError(format!(
+                            "Failed to apply the conf change at index {}, error message: {}",
+                            entry.get_index(),
+                            e
+                        )));
+                    }
+                }
+            }
+            EntryType::EntryNormal => {}
+        }
+        return Ok(());
+    }
+
+    fn check_transfer_leader(
+        &self,
+        raft_node: &RawNode<RaftRocksDBStorage>,
+        node_id: u64,
+    ) -> Result<(), PlacementCenterError> {
+        if raft_node.raft.state != StateRole::Leader {
+            return Err(PlacementCenterError::NotRaftLeader(raft_node.raft.id));
+        }
+        let voters = raft_node.raft.prs().conf().to_conf_state().voters;
+        if !voters.contains(&node_id) {
+            return Err(PlacementCenterError::RaftNodeNotVoter(node_id));
+        }
+        return Ok(());
+    }
+
+    // Answer the pending leadership transfer once the target is known as the leader,
+    // or once the leader has aborted it or the deadline has passed.
+    fn check_leader_transfer(&mut self, raft_node: &RawNode<RaftRocksDBStorage>) {
+        let transfer = match self.pending_transfer.take() {
+            Some(transfer) => transfer,
+            None => return,
+        };
+
+        let raft = &raft_node.raft;
+        let resp = if raft.leader_id == transfer.target {
+            RaftResponseMesage::Success
+        } else if raft.state == StateRole::Leader && raft.lead_transferee.is_none() {
+            RaftResponseMesage::Fail
+        } else if Instant::now() >= transfer.deadline {
+            RaftResponseMesage::Fail
+        } else {
+            self.pending_transfer = Some(transfer);
+            return;
+        };
+
+        if let RaftResponseMesage::Fail = resp {
+            warn!("Failed to transfer the Raft leadership to node {}", transfer.target);
+        }
+        if transfer.chan.send(resp).is_err() {
+            error!("transfer leader Fails to return data to chan. chan may have been closed");
+        }
+    }
+
+    fn check_conf_change(
+        &self,
+        raft_node: &RawNode<RaftRocksDBStorage>,
+        change: &ConfChange,
+    ) -> Result<(), PlacementCenterError> {
+        let id = change.get_node_id();
+        let cs = raft_node.raft.prs().conf().to_conf_state();
+        match change.get_change_type() {
+            ConfChangeType::AddLearnerNode => {
+                if cs.voters.contains(&id) || cs.learners.contains(&id) {
+                    return Err(PlacementCenterError::RaftNodeAlreadyMember(id));
+                }
+            }
+            ConfChangeType::AddNode => {
+                // without a context the change promotes a learner
+                if !change.get_context().is_empty() {
+                    return Ok(());
+                }
+                if !cs.learners.contains(&id) {
+                    return Err(PlacementCenterError::RaftNodeNotLearner(id));
+                }
+                let matched = match raft_node.raft.prs().get(id) {
+                    Some(progress) => progress.matched,
+                    None => 0,
+                };
+                let committed = raft_node.raft.raft_log.committed;
+                if matched + PROMOTE_LEARNER_MAX_LAG < committed {
+                    return Err(PlacementCenterError::RaftLearnerNotCaughtUp(
+                        id, matched, committed,
+                    ));
+                }
+            }
+            ConfChangeType::RemoveNode => {
+                if !cs.voters.contains(&id) {
+                    return Err(PlacementCenterError::RaftNodeNotVoter(id));
+                }
+                if cs.voters.len() == 1 {
+                    return Err(PlacementCenterError::RemoveLastRaftVoter(id));
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    // Every change of a joint conf change is checked against the configuration it starts from
+    fn check_conf_change_v2(
+        &self,
+        raft_node: &RawNode<RaftRocksDBStorage>,
+        change: &ConfChangeV2,
+    ) -> Result<(), PlacementCenterError> {
+        let cs = raft_node.raft.prs().conf().to_conf_state();
+        if !cs.voters_outgoing.is_empty() {
+            return Err(PlacementCenterError::RaftJointConsensusInProgress);
+        }
+
+        let nodes: Vec<BrokerNode> = if change.get_context().is_empty() {
+            Vec::new()
+        } else {
+            deserialize(change.get_context()).unwrap_or_default()
+        };
+        let mut voters = cs.voters.clone();
+        let mut last_removed = None;
+        for single in change.get_changes() {
+            let id = single.get_node_id();
+            match single.get_change_type() {
+                ConfChangeType::AddNode => {
+                    if cs.voters.contains(&id) {
+                        return Err(PlacementCenterError::RaftNodeAlreadyMember(id));
+                    }
+                    // a node that is not yet a learner has to come with its address
+                    if !cs.learners.contains(&id) && !nodes.iter().any(|node| node.node_id == id)
+                    {
+                        return Err(PlacementCenterError::RaftNodeNotLearner(id));
+                    }
+                    voters.push(id);
+                }
+                ConfChangeType::AddLearnerNode => {
+                    if cs.voters.contains(&id) || cs.learners.contains(&id) {
+                        return Err(PlacementCenterError::RaftNodeAlreadyMember(id));
+                    }
+                }
+                ConfChangeType::RemoveNode => {
+                    if !cs.voters.contains(&id) {
+                        return Err(PlacementCenterError::RaftNodeNotVoter(id));
+                    }
+                    voters.retain(|voter| *voter != id);
+                    last_removed = Some(id);
+                }
+            }
+        }
+
+        if voters.is_empty() {
+            if let Some(id) = last_removed {
+                return Err(PlacementCenterError::RemoveLastRaftVoter(id));
+            }
+        }
+        return Ok(());
+    }
+
+    async fn send_message(&self, messages: Vec<raftPreludeMessage>) {
+        let snapshot_chunk_size = placement_center_conf().raft.snapshot_chunk_size as usize;
+        for mut msg in messages {
+            // A large snapshot does not fit into one message, only its metadata is sent
+            // and the receiver pulls the data through InstallSnapshot.
+            if msg.get_msg_type() == MessageType::MsgSnapshot
+                && msg.get_snapshot().get_data().len() > snapshot_chunk_size
+            {
+                msg.mut_snapshot().data.clear();
+            }
+
+            let to = msg.get_to();
+            if msg.get_msg_type() != MessageType::MsgHeartbeat
+                && msg.get_msg_type() != MessageType::MsgHeartbeatResponse
+            {
+                info!("ready message:{:?}", msg);
+            }
+            let data: Vec<u8> = encode_message(&msg);
+            self.send_peer_message(to, data).await;
+        }
+    }
+
+    pub async fn new_node(&self) -> Result<RawNode<RaftRocksDBStorage>, PlacementCenterError> {
+        let storage = RaftRocksDBStorage::new(self.raft_storage.clone());
+
+        // a snapshot persisted before the node stopped may not have been applied yet
+        if let Err(e) = storage.write_lock().recover_snapshot() {
+            return Err(PlacementCenterError::RaftNodeStartFailed(
+                self.placement_cluster.read().unwrap().local.node_id,
+                format!("failed to restore the persisted snapshot, {}", e),
+            ));
+        }
+
+        // rebuild the state machine before serving
+        let node_id = self.placement_cluster.read().unwrap().local.node_id;
+        let applied_index = match self.recover(&storage) {
+            Ok(index) => index,
+            Err(e) => {
+                return Err(PlacementCenterError::RaftNodeStartFailed(
+                    node_id,
+                    format!("failed to recover the state machine, {}", e),
+                ));
+            }
+        };
+        let conf = self.build_config(applied_index);
+
+        // RawNode panics instead of failing when asked to start beyond the commit index
+        let commit_index = match storage.read_lock().hard_state() {
+            Ok(hs) => hs.commit,
+            Err(e) => {
+                return Err(PlacementCenterError::RaftNodeStartFailed(
+                    conf.id,
+                    format!("failed to read the HardState, {}", e),
+                ));
+            }
+        };
+        if applied_index > commit_index {
+            return Err(PlacementCenterError::RaftNodeStartFailed(
+                conf.id,
+                format!(
+                    "applied index {} is beyond the commit index {}",
+                    applied_index, commit_index
+                ),
+            ));
+        }
+
+        // init voters && learns, a saved ConfState already reflects the applied conf changes
+        let mut cs = match storage.read_lock().conf_state() {
+            Ok(cs) => cs,
+            Err(e) => {
+                return Err(PlacementCenterError::RaftNodeStartFailed(
+                    conf.id,
+                    format!("failed to read the ConfState, {}", e),
+                ));
+            }
+        };
+        if cs.voters.is_empty() {
+            if self.is_fresh_node(&storage) {
+                // a cluster that already has a leader adds this node, bootstrapping a
+                // second one next to it would split the group
+                if let Some(leader) = self.discover_leader().await {
+                    info!(
+                        "Node {} at {} leads the Raft group at term {}, wait for it to add this node",
+                        leader.node_id, leader.addr, leader.term
+                    );
+                } else {
+                    cs.voters = self.placement_cluster.read().unwrap().node_ids();
+                    if let Err(e) = storage.write_lock().bootstrap(cs) {
+                        return Err(PlacementCenterError::RaftNodeStartFailed(
+                            conf.id,
+                            format!("failed to bootstrap the Raft state, {}", e),
+                        ));
+                    }
+                }
+            } else {
+                info!("No Raft voters are known yet, wait for the cluster to add this node");
+            }
+        }
+
+        let logger = self.build_slog();
+        match RawNode::new(&conf, storage, &logger) {
+            Ok(node) => return Ok(node),
+            Err(e) => return Err(PlacementCenterError::RaftNodeStartFailed(conf.id, e.to_string())),
+        }
+    }
+
+    /// Ask the other configured nodes for the leader of the Raft group, the leader found is
+    /// recorded in the group metadata. None when no peer knows of a leader.
+    async fn discover_leader(&self) -> Option<ElectionLeader> {
+        let nodes: Vec<(u64, String)> = {
+            let cluster = self.placement_cluster.read().unwrap();
+            cluster
+                .peers
+                .iter()
+                .filter(|(id, _)| **id != cluster.local.node_id)
+                .map(|(id, node)| (*id, node.node_inner_addr.clone()))
+                .collect()
+        };
+        if nodes.is_empty() {
+            return None;
+        }
+
+        let raft_conf = &placement_center_conf().raft;
+        let election = match Election::new(
+            nodes,
+            self.peer_clients.clone(),
+            ELECTION_PEER_TIMEOUT,
+            raft_conf.election_retries,
+            Duration::from_millis(raft_conf.election_retry_backoff_ms),
+        ) {
+            Ok(election) => election,
+            Err(e) => {
+                error!("Failed to start the leader discovery, error message: {}", e);
+                return None;
+            }
+        };
+        let leader = election.leader_election_with_retry().await?;
+        let mut cluster = self.placement_cluster.write().unwrap();
+        if let Some(node) = cluster.get_node_by_id(leader.node_id).cloned() {
+            cluster.set_leader(node);
+        }
+        return Some(leader);
+    }
+
+    /// Only a node without any persisted Raft state may form a new cluster,
+    /// and only when bootstrap is enabled.
+    fn is_fresh_node(&self, storage: &RaftRocksDBStorage) -> bool {
+        if !self.bootstrap {
+            return false;
+        }
+        let fresh = !storage.read_lock().is_bootstrapped();
+        if !fresh {
+            info!("Raft state already exists, recover from it instead of bootstrapping");
+        }
+        return fresh;
+    }
+
+    /// Rebuild the state machine from RocksDB before the Raft node starts serving.
+    /// Entries committed but not yet applied before the restart are re-applied here,
+    /// returns the applied index the RawNode should start from.
+    fn recover(&self, storage: &RaftRocksDBStorage) -> Result<u64, EngineError> {
+        let store = storage.read_lock();
+        let hs = store.hard_state()?;
+        let cs = store.conf_state()?;
+        let first_index = store.first_index();
+        let last_index = store.last_index();
+        let mut applied_index = store.applied_index();
+        info!(
+            "Raft node recovery, hard state:{:?}, conf state:{:?}, first index:{}, last index:{}, applied index:{}",
+            hs, cs, first_index, last_index, applied_index
+        );
+
+        let commit_index = cmp::min(hs.commit, last_index);
+        let mut run = Vec::new();
+        for idx in applied_index + 1..=commit_index {
+            let entry = match store.entry_by_idx(idx) {
+                Some(entry) => entry,
+                None => {
+                    error!(
+                        "Raft node recovery stopped, entry {} does not exist, commit index:{}",
+                        idx, commit_index
+                    );
+                    break;
+                }
+            };
+            // Conf changes must be applied through the RawNode,
+            // leave this entry and the rest of the log to be applied by Raft.
+            if entry.get_entry_type() != EntryType::EntryNormal {
+                break;
+            }
+            run.push(entry);
+        }
+        // the applier takes the lock of the storage itself
+        drop(store);
+
+        // The entries go through the same path as in the ready loop, so a request applied
+        // before the restart is not applied twice and the subscribers see the events.
+        if let Some(applied) = self.applier.apply_run(&run) {
+            applied_index = applied.last_index;
+        }
+
+        info!("Raft node recovery completed, applied index:{}", applied_index);
+        return Ok(applied_index);
+    }
+
+    /// Reject proposals while read_only is set, reads and membership changes still go
+    /// through. This is a runtime flag only, it is not persisted.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        if self.read_only != read_only {
+            info!("Raft node read-only mode set to {}", read_only);
+        }
+        self.read_only = read_only;
+    }
+
+    // The inner address of a Raft node, empty when the node is unknown
+    fn node_addr(&self, node_id: u64) -> String {
+        match self.placement_cluster.read().unwrap().get_node_by_id(node_id) {
+            Some(node) => return node.node_inner_addr.clone(),
+            None => return "".to_string(),
+        }
+    }
+
+    fn node_status(&self, raft_node: &RawNode<RaftRocksDBStorage>) -> RaftNodeStatus {
+        let raft = &raft_node.raft;
+        return RaftNodeStatus {
+            node_id: raft.id,
+            role: raft.state,
+            term: raft.term,
+            leader_id: raft.leader_id,
+            commit_index: raft.raft_log.committed,
+            last_index: raft.raft_log.last_index(),
+            applied_index: raft.raft_log.applied,
+            peers: self.peer_clients.peer_health(),
+            quorum_ack_age: self.quorum_ack_age(raft_node),
+            quorum_loss_step_downs: self.quorum_loss_step_downs,
+            pending_proposals: self.resp_channel.len(),
+            uncommitted_entries: self.raft_storage.read().unwrap().uncommit_index.len(),
+            read_only: self.read_only,
+        };
+    }
+
+    // With check_quorum the voters that heard from the leader within the lease ignore a
+    // plain campaign. The election is run the way raft runs a leadership transfer to this
+    // node, its vote requests are then not ignored, a voter missing entries still refuses.
+    fn campaign(&self, raft_node: &mut RawNode<RaftRocksDBStorage>) -> RaftResponseMesage {
+        let id = raft_node.raft.id;
+        if !raft_node.raft.prs().conf().to_conf_state().voters.contains(&id) {
+            error!("{}", PlacementCenterError::RaftNodeNotVoter(id));
+            return RaftResponseMesage::NotVoter(id);
+        }
+        if raft_node.raft.state == StateRole::Leader {
+            return RaftResponseMesage::Success;
+        }
+
+        info!("Raft node {} campaigns at term {}", id, raft_node.raft.term);
+        let mut msg = raftPreludeMessage::default();
+        msg.set_msg_type(MessageType::MsgTimeoutNow);
+        msg.from = id;
+        msg.to = id;
+        msg.term = raft_node.raft.term;
+        match raft_node.step(msg) {
+            Ok(_) => return RaftResponseMesage::Success,
+            Err(e) => {
+                error!("{}", PlacementCenterError::RaftCampaignFailed(e.to_string()));
+                return RaftResponseMesage::Fail;
+            }
+        }
+    }
+
+    // The progress the leader tracks for every voter and learner, sorted by node id. In a
+    // joint configuration an index needs a majority of both the incoming and outgoing voters.
+    fn replication_status(&self, raft_node: &RawNode<RaftRocksDBStorage>) -> ReplicationStatus {
+        let raft = &raft_node.raft;
+        let cs = raft.prs().conf().to_conf_state();
+        let mut peers: Vec<PeerProgress> = raft
+            .prs()
+            .iter()
+            .map(|(id, progress)| PeerProgress {
+                node_id: *id,
+                matched: progress.matched,
+                next_index: progress.next_idx,
+                learner: cs.learners.contains(id) || cs.learners_next.contains(id),
+            })
+            .collect();
+        peers.sort_by_key(|peer| peer.node_id);
+
+        let matched = |voters: &[u64]| -> Vec<u64> {
+            return voters
+                .iter()
+                .map(|id| match raft.prs().get(*id) {
+                    Some(progress) => progress.matched,
+                    None => 0,
+                })
+                .collect();
+        };
+        let mut quorum_commit_index = quorum_index(matched(&cs.voters));
+        if !cs.voters_outgoing.is_empty() {
+            quorum_commit_index =
+                cmp::min(quorum_commit_index, quorum_index(matched(&cs.voters_outgoing)));
+        }
+
+        return ReplicationStatus {
+            term: raft.term,
+            commit_index: raft.raft_log.committed,
+            quorum_commit_index,
+            peers,
+        };
+    }
+
+    // The ConfState saved with the last applied conf change, a joint configuration
+    // shows the voters being replaced in voters_outgoing.
+    pub fn cluster_config(&self, raft_node: &RawNode<RaftRocksDBStorage>) -> RaftClusterConfig {
+        let conf_state = match self.raft_storage.read().unwrap().conf_state() {
+            Ok(cs) => cs,
+            Err(e) => {
+                error!("Failed to read the ConfState, error message: {}", e);
+                raft_node.raft.prs().conf().to_conf_state()
+            }
+        };
+        return RaftClusterConfig {
+            conf_state,
+            leader_id: raft_node.raft.leader_id,
+        };
+    }
+
+    fn build_config(&self, apply: u64) -> Config {
+        let node_id = self.placement_cluster.read().unwrap().local.node_id;
+        Config {
+            // The unique ID for the Raft node.
+            id: node_id,
+            // Election tick is for how long the follower may campaign again after
+            // it doesn't receive any message from the leader.
+            election_tick: RAFT_ELECTION_TICK,
+            // Heartbeat tick is for how long the leader needs to send
+            // a heartbeat to keep alive.
+            heartbeat_tick: RAFT_HEARTBEAT_TICK,
+            // Each election timeout is picked at random within [min, max) to avoid split votes.
+            min_election_tick: self.min_election_tick,
+            max_election_tick: self.max_election_tick,
+            // The max size limits the max size of each appended message. Mostly, 1 MB is enough.
+            max_size_per_msg: placement_center_conf().raft.max_size_per_msg_bytes,
+            // Max inflight msgs that the leader sends messages to follower without
+            // receiving ACKs.
+            max_inflight_msgs: 256,
+            // Proposals are dropped once the uncommitted entries of the leader reach this
+            // many bytes, a stalled follower then cannot make the log grow without bound.
+            max_uncommitted_size: self.max_uncommitted_size,
+            // The Raft applied index.
+            // You need to save your applied index when you apply the committed Raft logs.
+            applied: apply,
+            // Pre-vote keeps a node rejoining after a partition from bumping the term
+            // and forcing an unnecessary election.
+            pre_vote: placement_center_conf().raft.pre_vote,
+            // The leader steps down when it has not heard from a quorum of voters for an
+            // election timeout, and a follower that heard from the leader within it refuses
+            // to vote for anyone else, which lease reads rely on.
+            check_quorum: true,
+            ..Default::default()
         }
     }
 
     fn build_slog(&self) -> slog::Logger {
         let conf = placement_center_conf();
-        let path = format!("{}/raft.log", conf.log.log_path.clone());
-        let file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(false)
-            .open(path)
-            .unwrap();
+        let tag = o!("tag" => raft_log_tag(conf.node.node_id));
+        let file_name = raft_log_file_name(conf.node.node_id);
+        let writer =
+            match RotatingFileWriter::new(&conf.raft.log_dir, &file_name, conf.raft.log_max_size) {
+                Ok(writer) => writer,
+                Err(e) => {
+                    error!(
+                        "Failed to open {} in {}, raft logs will be discarded. error message: {}",
+                        file_name, conf.raft.log_dir, e
+                    );
+                    return slog::Logger::root(slog::Discard, tag);
+                }
+            };
 
-        let decorator = slog_term::PlainDecorator::new(file);
+        let decorator = slog_term::PlainDecorator::new(writer);
         // let decorator = slog_term::TermDecorator::new().build();
         let drain = slog_term::FullFormat::new(decorator).build().fuse();
         let drain = slog_async::Async::new(drain)
@@ -391,19 +1673,10 @@ impl RaftMachine {
             .overflow_strategy(slog_async::OverflowStrategy::Block)
             .build()
             .fuse();
-        let logger = slog::Logger::root(drain, o!("tag" => format!("meta-node-id={}", 1)));
+        let logger = slog::Logger::root(drain, tag);
         return logger;
     }
 
-    fn create_snapshot(&self, raft_node: &mut RawNode<RaftRocksDBStorage>) {
-        let num = self
-            .entry_num
-            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        if num % 1000 == 0 {
-            raft_node.mut_store().create_snapshot().unwrap();
-        }
-    }
-
     pub async fn send_peer_message(&self, id: u64, msg: Vec<u8>) {
         if let Some(node) = self.placement_cluster.read().unwrap().get_node_by_id(id) {
             let send = self.peer_message_send.clone();
@@ -411,6 +1684,7 @@ impl RaftMachine {
             tokio::spawn(async move {
                 match send
                     .send(PeerMessage {
+                        node_id: id,
                         to: node_c.node_inner_addr,
                         data: msg,
                     })
@@ -428,3 +1702,2598 @@ impl RaftMachine {
         }
     }
 }
+
+// The next run applied by the worker, never ready when entries are applied inline
+async fn next_applied_run(worker: &mut Option<ApplyWorker>) -> Option<AppliedRun> {
+    match worker {
+        Some(worker) => return worker.applied().await,
+        None => return pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        elapsed_ticks, leader_lease_duration, quorum_index, quorum_loss_timeout, RaftMachine,
+        MAX_COALESCED_TICKS, RAFT_TICK_INTERVAL,
+    };
+    use crate::cache::journal::JournalCacheManager;
+    use crate::cache::placement::PlacementCacheManager;
+    use crate::raft::apply::{
+        RaftMachineApply, RaftMessage, RaftNodeStatus, RaftResponseMesage, StorageData,
+        StorageDataType,
+    };
+    use crate::raft::codec::decode_message;
+    use crate::raft::metadata::RaftGroupMetadata;
+    use crate::raft::peer::{PeerClients, PeerMessage};
+    use crate::raft::route::DataRoute;
+    use crate::raft::storage::RaftRocksDBStorage;
+    use crate::server::grpc::service_placement::GrpcPlacementService;
+    use crate::controller::mqtt::session_expire::is_session_expired;
+    use crate::storage::collect_writes;
+    use crate::storage::placement::clock::ClusterClockStorage;
+    use crate::storage::placement::kv::KvStorage;
+    use crate::storage::placement::raft::RaftMachineStorage;
+    use crate::storage::rocksdb::{column_family_list, RocksDBEngine, DB_COLUMN_FAMILY_CLUSTER};
+    use bincode::serialize;
+    use clients::poll::ClientPool;
+    use common_base::config::default_placement_center::{
+        default_addr, default_network, default_nodes, default_raft,
+    };
+    use common_base::config::placement_center::{
+        init_placement_center_conf_by_config, placement_center_conf, PlacementCenterConfig,
+    };
+    use common_base::error::common::CommonError;
+    use common_base::error::placement_center::PlacementCenterError;
+    use common_base::metrics::placement::raft_undecodable_entries;
+    use common_base::tools::{now_second, unique_id};
+    use futures::StreamExt;
+    use metadata_struct::mqtt::session::MQTTSession;
+    use metadata_struct::placement::broker_node::BrokerNode;
+    use prost::Message as _;
+    use protocol::placement_center::generate::kv::{
+        CompareAndSwapRequest, DeleteRequest, SetRequest,
+    };
+    use protocol::placement_center::generate::placement::placement_center_service_client::PlacementCenterServiceClient;
+    use protocol::placement_center::generate::placement::placement_center_service_server::PlacementCenterServiceServer;
+    use raft::eraftpb::{Entry, EntryType, HardState, Message as raftPreludeMessage};
+    use raft::StateRole;
+    use std::collections::HashMap;
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, RwLock};
+    use std::time::{Duration, Instant};
+    use tokio::fs::remove_dir_all;
+    use tokio::sync::mpsc::error::TrySendError;
+    use tokio::sync::{broadcast, mpsc, oneshot};
+    use tokio::time::{sleep, timeout};
+    use tonic::transport::Server;
+
+    fn init_test_conf() {
+        let mut config = PlacementCenterConfig::default();
+        config.bootstrap = true;
+        config.node.node_id = 1;
+        config.node.addr = default_addr();
+        config.network = default_network();
+        config.nodes = default_nodes();
+        config.raft = default_raft();
+        config.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        config.log.log_path = format!("/tmp/robustmq_{}/logs", unique_id());
+        config.raft.log_dir = config.log.log_path.clone();
+        // the peers of the test clusters are either up or never come up
+        config.raft.election_retries = 0;
+        init_placement_center_conf_by_config(config);
+    }
+
+    fn build_machine(
+        data_path: &String,
+        metadata: RaftGroupMetadata,
+    ) -> (
+        RaftMachine,
+        mpsc::Sender<RaftMessage>,
+        broadcast::Sender<bool>,
+        mpsc::Receiver<PeerMessage>,
+        Arc<RocksDBEngine>,
+    ) {
+        let rocksdb_engine_handler = Arc::new(RocksDBEngine::new(
+            data_path,
+            10,
+            column_family_list(),
+        ));
+        let cluster_cache = Arc::new(PlacementCacheManager::new(rocksdb_engine_handler.clone()));
+        let engine_cache = Arc::new(JournalCacheManager::new());
+        let data_route = Arc::new(DataRoute::new(
+            rocksdb_engine_handler.clone(),
+            cluster_cache,
+            engine_cache,
+        ));
+        let raft_storage = Arc::new(RwLock::new(RaftMachineStorage::new(
+            rocksdb_engine_handler.clone(),
+        )));
+        let placement_cluster = Arc::new(RwLock::new(metadata));
+
+        let (peer_message_send, peer_message_recv) = mpsc::channel::<PeerMessage>(1000);
+        let (stop_send, _) = broadcast::channel(2);
+
+        let (machine, raft_message_send) = RaftMachine::new_with_channel(
+            placement_cluster,
+            data_route,
+            peer_message_send,
+            stop_send.subscribe(),
+            raft_storage,
+            Arc::new(PeerClients::new()),
+        )
+        .unwrap();
+        return (
+            machine,
+            raft_message_send,
+            stop_send,
+            peer_message_recv,
+            rocksdb_engine_handler,
+        );
+    }
+
+    fn start_single_node(
+        data_path: &String,
+    ) -> (
+        Arc<RaftMachineApply>,
+        broadcast::Sender<bool>,
+        mpsc::Receiver<PeerMessage>,
+    ) {
+        init_test_conf();
+        let (mut machine, raft_message_send, stop_send, peer_message_recv, _) =
+            build_machine(data_path, RaftGroupMetadata::new());
+        tokio::spawn(async move {
+            machine.run().await.unwrap();
+        });
+
+        return (
+            Arc::new(RaftMachineApply::new(raft_message_send)),
+            stop_send,
+            peer_message_recv,
+        );
+    }
+
+    fn cluster_metadata(local_id: u64, node_ids: &[u64]) -> RaftGroupMetadata {
+        let mut metadata = RaftGroupMetadata::new();
+        metadata.local.node_id = local_id;
+        metadata.peers.clear();
+        for id in node_ids {
+            let mut node = BrokerNode::default();
+            node.node_id = *id;
+            node.node_inner_addr = format!("127.0.0.1:{}", 1228 + id);
+            metadata.peers.insert(*id, node);
+        }
+        return metadata;
+    }
+
+    fn start_cluster_node(
+        local_id: u64,
+        node_ids: &[u64],
+        data_path: &String,
+    ) -> (
+        Arc<RaftMachineApply>,
+        broadcast::Sender<bool>,
+        mpsc::Receiver<PeerMessage>,
+        Arc<RwLock<RaftMachineStorage>>,
+        Arc<RocksDBEngine>,
+    ) {
+        let (mut machine, raft_message_send, stop_send, peer_message_recv, rocksdb_engine_handler) =
+            build_machine(data_path, cluster_metadata(local_id, node_ids));
+        let raft_storage = machine.raft_storage.clone();
+        tokio::spawn(async move {
+            machine.run().await.unwrap();
+        });
+        return (
+            Arc::new(RaftMachineApply::new(raft_message_send)),
+            stop_send,
+            peer_message_recv,
+            raft_storage,
+            rocksdb_engine_handler,
+        );
+    }
+
+    // Deliver the Raft messages sent by a node to the target nodes, in place of the gRPC PeersManager
+    // while `connected` is false the messages are dropped, as if the network were partitioned.
+    fn forward_peer_messages(
+        mut peer_message_recv: mpsc::Receiver<PeerMessage>,
+        nodes: HashMap<u64, Arc<RaftMachineApply>>,
+        connected: Arc<AtomicBool>,
+    ) {
+        tokio::spawn(async move {
+            while let Some(msg) = peer_message_recv.recv().await {
+                if !connected.load(Ordering::Relaxed) {
+                    continue;
+                }
+                if let Some(apply) = nodes.get(&msg.node_id) {
+                    let message =
+                        decode_message::<raftPreludeMessage>("Message", msg.data.as_ref()).unwrap();
+                    let _ = apply
+                        .apply_raft_message(message, "send_raft_message".to_string())
+                        .await;
+                }
+            }
+        });
+    }
+
+    async fn wait_for_leader(apply: &RaftMachineApply) -> RaftNodeStatus {
+        for _ in 0..100 {
+            let status = apply.status().await.unwrap();
+            if status.role == StateRole::Leader {
+                return status;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        panic!("single node did not become leader in time");
+    }
+
+    #[tokio::test]
+    async fn single_node_status_test() {
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (apply, stop_send, _peer_recv) = start_single_node(&data_path);
+
+        let status = wait_for_leader(&apply).await;
+        assert_eq!(status.role, StateRole::Leader);
+        assert_eq!(status.node_id, 1);
+        assert_eq!(status.leader_id, 1);
+        assert!(status.term >= 1);
+        assert!(status.commit_index <= status.last_index);
+
+        stop_send.send(true).unwrap();
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn propose_latency_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (mut machine, raft_message_send, stop_send, _peer_recv, _) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+        // the first tick is right at the start, the next one is far beyond the test
+        let tick_interval = Duration::from_secs(60);
+        machine.tick_interval = tick_interval;
+        let started = Instant::now();
+        tokio::spawn(async move {
+            machine.run().await.unwrap();
+        });
+        let apply = RaftMachineApply::new(raft_message_send);
+
+        // without ticks the node has to be made leader by a campaign
+        apply.campaign().await.unwrap();
+        wait_for_leader(&apply).await;
+
+        // A proposal wakes the loop at once instead of waiting for the next tick.
+        for idx in 0..10 {
+            let req = SetRequest {
+                key: format!("propose_latency_test-{}", idx),
+                value: "value".to_string(),
+            };
+            let data = StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+            apply.propose(data).await.unwrap();
+        }
+        assert!(started.elapsed() < tick_interval);
+
+        stop_send.send(true).unwrap();
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn recover_committed_entries_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        {
+            let rocksdb_engine_handler = Arc::new(RocksDBEngine::new(
+                &data_path,
+                10,
+                column_family_list(),
+            ));
+            let mut rds = RaftMachineStorage::new(rocksdb_engine_handler);
+            let mut entries = Vec::new();
+            for idx in 1..=3 {
+                let req = SetRequest {
+                    key: format!("key-{}", idx),
+                    value: format!("value-{}", idx),
+                };
+                let data = StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+                let mut entry = Entry::default();
+                entry.index = idx;
+                entry.term = 1;
+                entry.data = data.encode();
+                entries.push(entry);
+            }
+            // an entry written by another version, it is skipped instead of stopping the node
+            let mut entry = Entry::default();
+            entry.index = 4;
+            entry.term = 1;
+            entry.data = vec![0xff; 3];
+            entries.push(entry);
+            rds.append(&entries).unwrap();
+
+            let mut hs = HardState::default();
+            hs.term = 1;
+            hs.commit = 4;
+            rds.save_hard_state(hs).unwrap();
+        }
+
+        // The previous instance has been dropped, recreate the node on the same data path.
+        let (machine, _raft_send, _stop_send, _peer_recv, rocksdb_engine_handler) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+        let mut events = machine.subscribe();
+        let storage = RaftRocksDBStorage::new(machine.raft_storage.clone());
+        assert_eq!(machine.recover(&storage).unwrap(), 4);
+        assert_eq!(storage.read_lock().applied_index(), 4);
+        assert_eq!(storage.read_lock().hard_state().unwrap().commit, 4);
+        for idx in 1..=3 {
+            let event = events.try_recv().unwrap();
+            assert_eq!(event.index, idx);
+            assert_eq!(event.data_type, StorageDataType::KvSet);
+        }
+        assert!(events.try_recv().is_err());
+
+        let kv_storage = KvStorage::new(rocksdb_engine_handler);
+        for idx in 1..=3 {
+            let value = kv_storage.get(format!("key-{}", idx)).unwrap();
+            assert_eq!(value, Some(format!("value-{}", idx)));
+        }
+
+        // Everything has been applied, a second recovery has nothing left to do.
+        assert_eq!(machine.recover(&storage).unwrap(), 4);
+
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn remove_node_test() {
+        init_test_conf();
+        let node_ids = [1, 2];
+        let data_path_1 = format!("/tmp/robustmq_{}", unique_id());
+        let data_path_2 = format!("/tmp/robustmq_{}", unique_id());
+        let (apply_1, stop_send_1, peer_recv_1, raft_storage_1, rocksdb_engine_1) =
+            start_cluster_node(1, &node_ids, &data_path_1);
+        let (apply_2, stop_send_2, peer_recv_2, raft_storage_2, rocksdb_engine_2) =
+            start_cluster_node(2, &node_ids, &data_path_2);
+
+        let mut nodes = HashMap::new();
+        nodes.insert(1, apply_1.clone());
+        nodes.insert(2, apply_2.clone());
+        let connected = Arc::new(AtomicBool::new(true));
+        forward_peer_messages(peer_recv_1, nodes.clone(), connected.clone());
+        forward_peer_messages(peer_recv_2, nodes, connected);
+
+        let mut leader_id = 0;
+        for _ in 0..100 {
+            let status = apply_1.status().await.unwrap();
+            if status.leader_id != 0 {
+                leader_id = status.leader_id;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert_ne!(leader_id, 0);
+
+        let (leader, leader_stop, leader_storage, leader_engine, removed_stop) = if leader_id == 1 {
+            (apply_1, stop_send_1, raft_storage_1, rocksdb_engine_1, stop_send_2)
+        } else {
+            (apply_2, stop_send_2, raft_storage_2, rocksdb_engine_2, stop_send_1)
+        };
+        let removed_id = if leader_id == 1 { 2 } else { 1 };
+
+        leader.remove_node(removed_id).await.unwrap();
+        assert_eq!(
+            leader_storage.read().unwrap().conf_state().unwrap().voters,
+            vec![leader_id]
+        );
+
+        // The removed node is gone, the remaining node still commits entries on its own.
+        removed_stop.send(true).unwrap();
+        let req = SetRequest {
+            key: "remove_node_test".to_string(),
+            value: "progress".to_string(),
+        };
+        let data = StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        leader
+            .apply_propose_message(data, "set".to_string())
+            .await
+            .unwrap();
+        let kv_storage = KvStorage::new(leader_engine);
+        assert_eq!(
+            kv_storage.get("remove_node_test".to_string()).unwrap(),
+            Some("progress".to_string())
+        );
+
+        // The last voter cannot be removed.
+        assert!(leader.remove_node(leader_id).await.is_err());
+
+        leader_stop.send(true).unwrap();
+        remove_dir_all(data_path_1).await.unwrap();
+        remove_dir_all(data_path_2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn max_uncommitted_size_test() {
+        init_test_conf();
+        let node_ids = [1, 2];
+        let data_path_1 = format!("/tmp/robustmq_{}", unique_id());
+        let data_path_2 = format!("/tmp/robustmq_{}", unique_id());
+
+        let mut nodes = HashMap::new();
+        let mut peer_recvs = Vec::new();
+        let mut stops = Vec::new();
+        for (id, data_path) in [(1, &data_path_1), (2, &data_path_2)] {
+            let (mut machine, raft_message_send, stop_send, peer_message_recv, _) =
+                build_machine(data_path, cluster_metadata(id, &node_ids));
+            machine.max_uncommitted_size = 1024 * 1024;
+            tokio::spawn(async move {
+                machine.run().await.unwrap();
+            });
+            nodes.insert(id, Arc::new(RaftMachineApply::new(raft_message_send)));
+            peer_recvs.push(peer_message_recv);
+            stops.push(stop_send);
+        }
+        let connected = Arc::new(AtomicBool::new(true));
+        for peer_recv in peer_recvs {
+            forward_peer_messages(peer_recv, nodes.clone(), connected.clone());
+        }
+
+        let mut leader_id = 0;
+        for _ in 0..100 {
+            let status = nodes[&1].status().await.unwrap();
+            if status.leader_id != 0 {
+                leader_id = status.leader_id;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert_ne!(leader_id, 0);
+        let leader = nodes[&leader_id].clone();
+
+        // Without the follower nothing is committed, the uncommitted entries only grow
+        // until the limit is reached. Each proposal carries 256 KB.
+        connected.store(false, Ordering::Relaxed);
+        let build_data = |i: u64| {
+            let req = SetRequest {
+                key: format!("uncommitted-{}", i),
+                value: "v".repeat(256 * 1024),
+            };
+            return StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        };
+        // raft counts the data of the entries, the first one is always accepted
+        let entry_size = build_data(0).encode().len() as u64;
+        let accepted = (1024 * 1024 / entry_size).max(1);
+        assert_eq!(accepted, 3);
+
+        // the accepted proposals wait for a commit that never comes, each one is pending
+        // on the leader before the next is sent
+        for i in 0..accepted {
+            let proposer = leader.clone();
+            let data = build_data(i);
+            tokio::spawn(async move { proposer.propose(data).await });
+            let mut pending = 0;
+            for _ in 0..100 {
+                pending = leader.status().await.unwrap().pending_proposals;
+                if pending as u64 == i + 1 {
+                    break;
+                }
+                sleep(Duration::from_millis(10)).await;
+            }
+            assert_eq!(pending as u64, i + 1);
+        }
+
+        match leader.propose(build_data(accepted)).await {
+            Err(CommonError::PlacementCenterError(PlacementCenterError::RaftProposalDropped(
+                _,
+            ))) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert_eq!(leader.status().await.unwrap().pending_proposals as u64, accepted);
+
+        for stop_send in stops {
+            stop_send.send(true).unwrap();
+        }
+        remove_dir_all(data_path_1).await.unwrap();
+        remove_dir_all(data_path_2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn propose_backpressure_test() {
+        init_test_conf();
+        let node_ids = [1, 2];
+        let data_path_1 = format!("/tmp/robustmq_{}", unique_id());
+        let data_path_2 = format!("/tmp/robustmq_{}", unique_id());
+
+        let mut applies = Vec::new();
+        let mut nodes = HashMap::new();
+        let mut peer_recvs = Vec::new();
+        let mut stops = Vec::new();
+        for (id, data_path) in [(1, &data_path_1), (2, &data_path_2)] {
+            let (mut machine, raft_message_send, stop_send, peer_message_recv, _) =
+                build_machine(data_path, cluster_metadata(id, &node_ids));
+            machine.max_pending_proposals = 2;
+            tokio::spawn(async move {
+                machine.run().await.unwrap();
+            });
+            let apply = Arc::new(RaftMachineApply::new(raft_message_send));
+            nodes.insert(id, apply.clone());
+            applies.push(apply);
+            peer_recvs.push(peer_message_recv);
+            stops.push(stop_send);
+        }
+        let connected = Arc::new(AtomicBool::new(true));
+        for peer_recv in peer_recvs {
+            forward_peer_messages(peer_recv, nodes.clone(), connected.clone());
+        }
+
+        let mut leader_id = 0;
+        for _ in 0..100 {
+            let status = applies[0].status().await.unwrap();
+            if status.leader_id != 0 {
+                leader_id = status.leader_id;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert_ne!(leader_id, 0);
+        let leader = nodes.get(&leader_id).unwrap().clone();
+
+        // Without the follower nothing can be committed, so the proposals stay pending.
+        connected.store(false, Ordering::Relaxed);
+        for i in 0..2 {
+            let leader = leader.clone();
+            tokio::spawn(async move {
+                let req = SetRequest {
+                    key: format!("pending-{}", i),
+                    value: "value".to_string(),
+                };
+                let data =
+                    StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+                let _ = leader.apply_propose_message(data, "set".to_string()).await;
+            });
+        }
+        sleep(Duration::from_millis(500)).await;
+
+        let req = SetRequest {
+            key: "rejected".to_string(),
+            value: "value".to_string(),
+        };
+        let data = StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        let start = Instant::now();
+        let res = leader.apply_propose_message(data, "set".to_string()).await;
+        assert!(res.is_err());
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        for stop_send in stops {
+            stop_send.send(true).unwrap();
+        }
+        remove_dir_all(data_path_1).await.unwrap();
+        remove_dir_all(data_path_2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn subscribe_applied_event_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (mut machine, raft_message_send, stop_send, _peer_recv, _) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+        let mut events = machine.subscribe();
+        tokio::spawn(async move {
+            machine.run().await.unwrap();
+        });
+        let apply = RaftMachineApply::new(raft_message_send);
+        wait_for_leader(&apply).await;
+
+        let req = SetRequest {
+            key: "subscribe_test".to_string(),
+            value: "value".to_string(),
+        };
+        let data = StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        apply
+            .apply_propose_message(data, "set".to_string())
+            .await
+            .unwrap();
+
+        let event = timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.data_type, StorageDataType::KvSet);
+        assert!(event.index > 0);
+        assert!(apply.status().await.unwrap().applied_index >= event.index);
+
+        stop_send.send(true).unwrap();
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn tail_from_start_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (mut machine, raft_message_send, stop_send, _peer_recv, _) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+        let mut stream = Box::pin(machine.tail(0));
+        tokio::spawn(async move {
+            machine.run().await.unwrap();
+        });
+        let apply = RaftMachineApply::new(raft_message_send);
+        wait_for_leader(&apply).await;
+
+        let set = |key: &str| {
+            let req = SetRequest {
+                key: key.to_string(),
+                value: "value".to_string(),
+            };
+            return StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        };
+        for key in ["tail_1", "tail_2"] {
+            apply
+                .apply_propose_message(set(key), "set".to_string())
+                .await
+                .unwrap();
+        }
+
+        // The entries committed before the stream is polled are replayed from the log
+        let mut last_index = 0;
+        for key in ["tail_1", "tail_2"] {
+            let (index, data) = timeout(Duration::from_secs(5), stream.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            assert!(index > last_index);
+            let req = SetRequest::decode(data.value.as_ref()).unwrap();
+            assert_eq!(req.key, key);
+            last_index = index;
+        }
+
+        apply
+            .apply_propose_message(set("tail_3"), "set".to_string())
+            .await
+            .unwrap();
+        let (index, data) = timeout(Duration::from_secs(5), stream.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(index > last_index);
+        assert_eq!(SetRequest::decode(data.value.as_ref()).unwrap().key, "tail_3");
+
+        stop_send.send(true).unwrap();
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn learner_promote_test() {
+        init_test_conf();
+        let data_path_1 = format!("/tmp/robustmq_{}", unique_id());
+        let data_path_2 = format!("/tmp/robustmq_{}", unique_id());
+        let (apply_1, stop_send_1, peer_recv_1, raft_storage_1, _) =
+            start_cluster_node(1, &[1], &data_path_1);
+
+        // node 2 knows the leader but does not form a cluster of its own
+        let (mut machine, raft_message_send, stop_send_2, peer_recv_2, _) =
+            build_machine(&data_path_2, cluster_metadata(2, &[1, 2]));
+        machine.bootstrap = false;
+        let raft_storage_2 = machine.raft_storage.clone();
+        tokio::spawn(async move {
+            machine.run().await.unwrap();
+        });
+        let apply_2 = Arc::new(RaftMachineApply::new(raft_message_send));
+
+        let mut nodes = HashMap::new();
+        nodes.insert(1, apply_1.clone());
+        nodes.insert(2, apply_2.clone());
+        let connected = Arc::new(AtomicBool::new(true));
+        forward_peer_messages(peer_recv_1, nodes.clone(), connected.clone());
+        forward_peer_messages(peer_recv_2, nodes, connected);
+        wait_for_leader(&apply_1).await;
+
+        // a node that is not a learner cannot be promoted
+        assert!(apply_1.promote_learner(2).await.is_err());
+
+        let mut learner = BrokerNode::default();
+        learner.node_id = 2;
+        learner.node_inner_addr = "127.0.0.1:1230".to_string();
+        apply_1.add_learner(learner.clone()).await.unwrap();
+        let cs = raft_storage_1.read().unwrap().conf_state().unwrap();
+        assert_eq!(cs.voters, vec![1]);
+        assert_eq!(cs.learners, vec![2]);
+        assert!(apply_1.add_learner(learner).await.is_err());
+
+        // the learner catches up with the leader before it is promoted
+        let mut promoted = false;
+        for _ in 0..50 {
+            if apply_1.promote_learner(2).await.is_ok() {
+                promoted = true;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert!(promoted);
+        let mut cs = raft_storage_1.read().unwrap().conf_state().unwrap();
+        cs.voters.sort();
+        assert_eq!(cs.voters, vec![1, 2]);
+        assert!(cs.learners.is_empty());
+
+        // the promoted node replicates the conf change as well
+        for _ in 0..50 {
+            if raft_storage_2.read().unwrap().conf_state().unwrap().voters.len() == 2 {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        let cs = raft_storage_2.read().unwrap().conf_state().unwrap();
+        assert_eq!(cs.voters.len(), 2);
+        assert!(cs.learners.is_empty());
+
+        stop_send_1.send(true).unwrap();
+        stop_send_2.send(true).unwrap();
+        remove_dir_all(data_path_1).await.unwrap();
+        remove_dir_all(data_path_2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn follower_membership_test() {
+        init_test_conf();
+        let node_ids = [1, 2];
+        let data_path_1 = format!("/tmp/robustmq_{}", unique_id());
+        let data_path_2 = format!("/tmp/robustmq_{}", unique_id());
+        let (apply_1, stop_send_1, peer_recv_1, _, _) =
+            start_cluster_node(1, &node_ids, &data_path_1);
+        let (apply_2, stop_send_2, peer_recv_2, _, _) =
+            start_cluster_node(2, &node_ids, &data_path_2);
+
+        let mut nodes = HashMap::new();
+        nodes.insert(1, apply_1.clone());
+        nodes.insert(2, apply_2.clone());
+        let connected = Arc::new(AtomicBool::new(true));
+        forward_peer_messages(peer_recv_1, nodes.clone(), connected.clone());
+        forward_peer_messages(peer_recv_2, nodes.clone(), connected);
+
+        let mut leader_id = 0;
+        for _ in 0..100 {
+            let status = apply_1.status().await.unwrap();
+            if status.leader_id != 0 && status.leader_id == apply_2.status().await.unwrap().leader_id
+            {
+                leader_id = status.leader_id;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert_ne!(leader_id, 0);
+        let leader = nodes.get(&leader_id).unwrap().clone();
+        let follower = if leader_id == 1 { apply_2.clone() } else { apply_1.clone() };
+
+        let mut membership = leader.membership().await.unwrap();
+        membership.conf_state.voters.sort();
+        assert_eq!(membership.conf_state.voters, vec![1, 2]);
+        assert_eq!(membership.leader_id, leader_id);
+        let mut forwarded = follower.membership().await.unwrap();
+        forwarded.conf_state.voters.sort();
+        assert_eq!(forwarded, membership);
+
+        // a follower asked right after a membership change already knows about it
+        let mut learner = BrokerNode::default();
+        learner.node_id = 3;
+        learner.node_inner_addr = "127.0.0.1:1231".to_string();
+        leader.add_learner(learner).await.unwrap();
+        let forwarded = timeout(Duration::from_secs(5), follower.membership())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(forwarded.conf_state.learners, vec![3]);
+        assert_eq!(forwarded.leader_id, leader_id);
+
+        stop_send_1.send(true).unwrap();
+        stop_send_2.send(true).unwrap();
+        remove_dir_all(data_path_1).await.unwrap();
+        remove_dir_all(data_path_2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn cluster_config_test() {
+        init_test_conf();
+        let data_path_1 = format!("/tmp/robustmq_{}", unique_id());
+        let data_path_2 = format!("/tmp/robustmq_{}", unique_id());
+        let (apply_1, stop_send_1, peer_recv_1, _, _) = start_cluster_node(1, &[1], &data_path_1);
+
+        let (mut machine, raft_message_send, stop_send_2, peer_recv_2, _) =
+            build_machine(&data_path_2, cluster_metadata(2, &[1, 2]));
+        machine.bootstrap = false;
+        tokio::spawn(async move {
+            machine.run().await.unwrap();
+        });
+        let apply_2 = Arc::new(RaftMachineApply::new(raft_message_send));
+
+        let mut nodes = HashMap::new();
+        nodes.insert(1, apply_1.clone());
+        nodes.insert(2, apply_2.clone());
+        let connected = Arc::new(AtomicBool::new(true));
+        forward_peer_messages(peer_recv_1, nodes.clone(), connected.clone());
+        forward_peer_messages(peer_recv_2, nodes, connected);
+        wait_for_leader(&apply_1).await;
+
+        let config = apply_1.cluster_config().await.unwrap();
+        assert_eq!(config.conf_state.voters, vec![1]);
+        assert!(config.conf_state.learners.is_empty());
+        assert_eq!(config.leader_id, 1);
+
+        let mut learner = BrokerNode::default();
+        learner.node_id = 2;
+        learner.node_inner_addr = "127.0.0.1:1230".to_string();
+        apply_1.add_learner(learner).await.unwrap();
+        let config = apply_1.cluster_config().await.unwrap();
+        assert_eq!(config.conf_state.voters, vec![1]);
+        assert_eq!(config.conf_state.learners, vec![2]);
+
+        let mut promoted = false;
+        for _ in 0..50 {
+            if apply_1.promote_learner(2).await.is_ok() {
+                promoted = true;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert!(promoted);
+        let mut config = apply_1.cluster_config().await.unwrap();
+        config.conf_state.voters.sort();
+        assert_eq!(config.conf_state.voters, vec![1, 2]);
+        assert!(config.conf_state.learners.is_empty());
+        assert_eq!(config.leader_id, 1);
+
+        stop_send_1.send(true).unwrap();
+        stop_send_2.send(true).unwrap();
+        remove_dir_all(data_path_1).await.unwrap();
+        remove_dir_all(data_path_2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn joint_conf_change_test() {
+        init_test_conf();
+        let voter_ids = [1, 2, 3];
+        let all_ids = [1, 2, 3, 4, 5];
+        let mut data_paths = Vec::new();
+        let mut nodes = HashMap::new();
+        let mut stops = HashMap::new();
+        let mut storages = HashMap::new();
+        let mut engines = HashMap::new();
+        let mut peer_recvs = Vec::new();
+        for id in all_ids {
+            let data_path = format!("/tmp/robustmq_{}", unique_id());
+            let (apply, stop_send, peer_recv, raft_storage, engine) = if voter_ids.contains(&id) {
+                start_cluster_node(id, &voter_ids, &data_path)
+            } else {
+                // the new voters know the group but wait to be added to it
+                let (mut machine, raft_message_send, stop_send, peer_recv, engine) =
+                    build_machine(&data_path, cluster_metadata(id, &all_ids));
+                machine.bootstrap = false;
+                let raft_storage = machine.raft_storage.clone();
+                tokio::spawn(async move {
+                    machine.run().await.unwrap();
+                });
+                let apply = Arc::new(RaftMachineApply::new(raft_message_send));
+                (apply, stop_send, peer_recv, raft_storage, engine)
+            };
+            nodes.insert(id, apply);
+            stops.insert(id, stop_send);
+            storages.insert(id, raft_storage);
+            engines.insert(id, engine);
+            peer_recvs.push(peer_recv);
+            data_paths.push(data_path);
+        }
+        let connected = Arc::new(AtomicBool::new(true));
+        for peer_recv in peer_recvs {
+            forward_peer_messages(peer_recv, nodes.clone(), connected.clone());
+        }
+
+        let mut leader_id = 0;
+        for _ in 0..100 {
+            let status = nodes[&1].status().await.unwrap();
+            if status.leader_id != 0
+                && status.leader_id == nodes[&2].status().await.unwrap().leader_id
+                && status.leader_id == nodes[&3].status().await.unwrap().leader_id
+            {
+                leader_id = status.leader_id;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert_ne!(leader_id, 0);
+        let leader = nodes[&leader_id].clone();
+        let removed: Vec<u64> = voter_ids.iter().copied().filter(|id| *id != leader_id).collect();
+
+        // the new nodes start from a snapshot that carries the current configuration
+        leader.trigger_snapshot().await.unwrap();
+
+        // keep writing during the change, every proposal needs a quorum of the old
+        // and of the new voters while the group is in the joint configuration
+        let writing = Arc::new(AtomicBool::new(true));
+        let writer = {
+            let leader = leader.clone();
+            let writing = writing.clone();
+            tokio::spawn(async move {
+                let mut written = 0;
+                while writing.load(Ordering::Relaxed) {
+                    let req = SetRequest {
+                        key: format!("joint_conf_change_test-{}", written),
+                        value: "value".to_string(),
+                    };
+                    let data =
+                        StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+                    leader.propose(data).await.unwrap();
+                    written += 1;
+                }
+                written
+            })
+        };
+
+        let mut new_voters = Vec::new();
+        for id in [4, 5] {
+            let mut node = BrokerNode::default();
+            node.node_id = id;
+            node.node_inner_addr = format!("127.0.0.1:{}", 1228 + id);
+            new_voters.push(node);
+        }
+        leader
+            .change_membership(new_voters.clone(), removed.clone())
+            .await
+            .unwrap();
+        writing.store(false, Ordering::Relaxed);
+        assert!(writer.await.unwrap() > 0);
+
+        // the joint configuration has been left automatically
+        let mut cs = storages[&leader_id].read().unwrap().conf_state().unwrap();
+        cs.voters.sort();
+        let mut expected = vec![leader_id, 4, 5];
+        expected.sort();
+        assert_eq!(cs.voters, expected);
+        assert!(cs.voters_outgoing.is_empty());
+        assert!(!cs.auto_leave);
+
+        // members cannot be added twice
+        assert!(leader.change_membership(new_voters, vec![]).await.is_err());
+
+        // the old voters are gone, the new ones form the quorum
+        for id in removed.iter() {
+            stops[id].send(true).unwrap();
+        }
+        let req = SetRequest {
+            key: "joint_conf_change_test".to_string(),
+            value: "after".to_string(),
+        };
+        let data = StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        leader.propose(data).await.unwrap();
+        for id in [4, 5] {
+            let kv_storage = KvStorage::new(engines[&id].clone());
+            let mut value = None;
+            for _ in 0..50 {
+                value = kv_storage.get("joint_conf_change_test".to_string()).unwrap();
+                if value.is_some() {
+                    break;
+                }
+                sleep(Duration::from_millis(100)).await;
+            }
+            assert_eq!(value, Some("after".to_string()));
+        }
+
+        for id in [leader_id, 4, 5] {
+            stops[&id].send(true).unwrap();
+        }
+        for data_path in data_paths {
+            remove_dir_all(data_path).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn linearizable_get_test() {
+        init_test_conf();
+        let node_ids = [1, 2];
+        let data_path_1 = format!("/tmp/robustmq_{}", unique_id());
+        let data_path_2 = format!("/tmp/robustmq_{}", unique_id());
+        let (apply_1, stop_send_1, peer_recv_1, _, engine_1) =
+            start_cluster_node(1, &node_ids, &data_path_1);
+        let (apply_2, stop_send_2, peer_recv_2, _, engine_2) =
+            start_cluster_node(2, &node_ids, &data_path_2);
+
+        let mut nodes = HashMap::new();
+        nodes.insert(1, apply_1.clone());
+        nodes.insert(2, apply_2.clone());
+        let connected = Arc::new(AtomicBool::new(true));
+        forward_peer_messages(peer_recv_1, nodes.clone(), connected.clone());
+        forward_peer_messages(peer_recv_2, nodes, connected);
+
+        let mut leader_id = 0;
+        for _ in 0..100 {
+            let status = apply_1.status().await.unwrap();
+            if status.leader_id != 0 {
+                leader_id = status.leader_id;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert_ne!(leader_id, 0);
+        let (leader, leader_engine, target, target_engine, target_id) = if leader_id == 1 {
+            (apply_1.clone(), engine_1, apply_2.clone(), engine_2, 2)
+        } else {
+            (apply_2.clone(), engine_2, apply_1.clone(), engine_1, 1)
+        };
+
+        let key = "linearizable_get_test".to_string();
+        let set = |value: &str| {
+            let req = SetRequest {
+                key: key.clone(),
+                value: value.to_string(),
+            };
+            return StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        };
+        let read = |apply: Arc<RaftMachineApply>, engine: Arc<RocksDBEngine>| {
+            let key = key.clone();
+            return async move {
+                let data = apply
+                    .linearizable_get(engine, DB_COLUMN_FAMILY_CLUSTER, key)
+                    .await
+                    .unwrap();
+                return data.map(|data| serde_json::from_slice::<String>(&data).unwrap());
+            };
+        };
+
+        leader.propose(set("first")).await.unwrap();
+        assert_eq!(
+            read(leader.clone(), leader_engine.clone()).await,
+            Some("first".to_string())
+        );
+        // the follower waits until it has applied the write as well
+        assert_eq!(
+            read(target.clone(), target_engine.clone()).await,
+            Some("first".to_string())
+        );
+
+        // right after the leader changed, the reads on both nodes see the last write
+        leader.transfer_leader(target_id).await.unwrap();
+        assert_eq!(
+            read(target.clone(), target_engine.clone()).await,
+            Some("first".to_string())
+        );
+        target.propose(set("second")).await.unwrap();
+        assert_eq!(
+            read(leader.clone(), leader_engine).await,
+            Some("second".to_string())
+        );
+        assert_eq!(
+            read(target.clone(), target_engine).await,
+            Some("second".to_string())
+        );
+
+        stop_send_1.send(true).unwrap();
+        stop_send_2.send(true).unwrap();
+        remove_dir_all(data_path_1).await.unwrap();
+        remove_dir_all(data_path_2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn cluster_clock_session_expiry_test() {
+        init_test_conf();
+        let node_ids = [1, 2];
+        let data_path_1 = format!("/tmp/robustmq_{}", unique_id());
+        let data_path_2 = format!("/tmp/robustmq_{}", unique_id());
+        let (apply_1, stop_send_1, peer_recv_1, _, engine_1) =
+            start_cluster_node(1, &node_ids, &data_path_1);
+        let (apply_2, stop_send_2, peer_recv_2, _, engine_2) =
+            start_cluster_node(2, &node_ids, &data_path_2);
+
+        let mut nodes = HashMap::new();
+        nodes.insert(1, apply_1.clone());
+        nodes.insert(2, apply_2.clone());
+        let connected = Arc::new(AtomicBool::new(true));
+        forward_peer_messages(peer_recv_1, nodes.clone(), connected.clone());
+        forward_peer_messages(peer_recv_2, nodes, connected);
+
+        let mut leader_id = 0;
+        for _ in 0..100 {
+            let status = apply_1.status().await.unwrap();
+            if status.leader_id != 0 {
+                leader_id = status.leader_id;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert_ne!(leader_id, 0);
+        let (leader, follower, follower_id) = if leader_id == 1 {
+            (apply_1.clone(), apply_2.clone(), 2)
+        } else {
+            (apply_2.clone(), apply_1.clone(), 1)
+        };
+
+        // every entry moves the clock to the time it was proposed at on the leader
+        let stamped = |time: u64| {
+            let req = SetRequest {
+                key: "clock".to_string(),
+                value: time.to_string(),
+            };
+            let mut data =
+                StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+            data.propose_time = time;
+            return data;
+        };
+        let expired_on = |engine: &Arc<RocksDBEngine>, session: &MQTTSession| {
+            let clock_storage = ClusterClockStorage::new(engine.clone());
+            return is_session_expired(session, clock_storage.now().unwrap());
+        };
+
+        // expired by the local time of the nodes, but not by the cluster clock
+        let local = now_second();
+        let mut session = MQTTSession::default();
+        session.session_expiry = 10;
+        session.distinct_time = Some(local - 50);
+        assert!(is_session_expired(&session, local));
+
+        // no session expires before the clock is set
+        for engine in [&engine_1, &engine_2] {
+            assert_eq!(ClusterClockStorage::new(engine.clone()).now().unwrap(), 0);
+            assert!(!expired_on(engine, &session));
+        }
+
+        leader.propose(stamped(local - 100)).await.unwrap();
+        follower.read_index().await.unwrap();
+        for engine in [&engine_1, &engine_2] {
+            assert_eq!(ClusterClockStorage::new(engine.clone()).now().unwrap(), local - 100);
+            assert!(!expired_on(engine, &session));
+        }
+
+        // a new leader with a late wall clock does not move the clock back
+        leader.transfer_leader(follower_id).await.unwrap();
+        follower.propose(stamped(local - 200)).await.unwrap();
+        leader.read_index().await.unwrap();
+        for engine in [&engine_1, &engine_2] {
+            assert_eq!(ClusterClockStorage::new(engine.clone()).now().unwrap(), local - 100);
+            assert!(!expired_on(engine, &session));
+        }
+
+        follower.propose(stamped(local)).await.unwrap();
+        leader.read_index().await.unwrap();
+        for engine in [&engine_1, &engine_2] {
+            assert!(expired_on(engine, &session));
+        }
+
+        stop_send_1.send(true).unwrap();
+        stop_send_2.send(true).unwrap();
+        remove_dir_all(data_path_1).await.unwrap();
+        remove_dir_all(data_path_2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn transfer_leader_test() {
+        init_test_conf();
+        let node_ids = [1, 2];
+        let data_path_1 = format!("/tmp/robustmq_{}", unique_id());
+        let data_path_2 = format!("/tmp/robustmq_{}", unique_id());
+        let (apply_1, stop_send_1, peer_recv_1, _, _) =
+            start_cluster_node(1, &node_ids, &data_path_1);
+        let (apply_2, stop_send_2, peer_recv_2, _, _) =
+            start_cluster_node(2, &node_ids, &data_path_2);
+
+        let mut nodes = HashMap::new();
+        nodes.insert(1, apply_1.clone());
+        nodes.insert(2, apply_2.clone());
+        let connected = Arc::new(AtomicBool::new(true));
+        forward_peer_messages(peer_recv_1, nodes.clone(), connected.clone());
+        forward_peer_messages(peer_recv_2, nodes, connected);
+
+        let mut leader_id = 0;
+        for _ in 0..100 {
+            let status = apply_1.status().await.unwrap();
+            if status.leader_id != 0 {
+                leader_id = status.leader_id;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert_ne!(leader_id, 0);
+
+        let (leader, target, target_id) = if leader_id == 1 {
+            (apply_1.clone(), apply_2.clone(), 2)
+        } else {
+            (apply_2.clone(), apply_1.clone(), 1)
+        };
+
+        // only voters can take over the leadership, and only the leader can hand it over
+        assert!(leader.transfer_leader(3).await.is_err());
+        assert!(target.transfer_leader(leader_id).await.is_err());
+
+        leader.transfer_leader(target_id).await.unwrap();
+        let status = target.status().await.unwrap();
+        assert_eq!(status.role, StateRole::Leader);
+        assert_eq!(status.leader_id, target_id);
+
+        stop_send_1.send(true).unwrap();
+        stop_send_2.send(true).unwrap();
+        remove_dir_all(data_path_1).await.unwrap();
+        remove_dir_all(data_path_2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn replication_status_test() {
+        init_test_conf();
+        let node_ids = [1, 2];
+        let data_path_1 = format!("/tmp/robustmq_{}", unique_id());
+        let data_path_2 = format!("/tmp/robustmq_{}", unique_id());
+        let (apply_1, stop_send_1, peer_recv_1, _, _) =
+            start_cluster_node(1, &node_ids, &data_path_1);
+        let (apply_2, stop_send_2, peer_recv_2, _, _) =
+            start_cluster_node(2, &node_ids, &data_path_2);
+
+        let mut nodes = HashMap::new();
+        nodes.insert(1, apply_1.clone());
+        nodes.insert(2, apply_2.clone());
+        let connected = Arc::new(AtomicBool::new(true));
+        forward_peer_messages(peer_recv_1, nodes.clone(), connected.clone());
+        forward_peer_messages(peer_recv_2, nodes, connected);
+
+        let mut leader_id = 0;
+        for _ in 0..100 {
+            let status = apply_1.status().await.unwrap();
+            if status.leader_id != 0 && status.leader_id == apply_2.status().await.unwrap().leader_id
+            {
+                leader_id = status.leader_id;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert_ne!(leader_id, 0);
+        let (leader, follower, follower_id) = if leader_id == 1 {
+            (apply_1.clone(), apply_2.clone(), 2)
+        } else {
+            (apply_2.clone(), apply_1.clone(), 1)
+        };
+
+        // only the leader tracks the progress of the other nodes
+        match follower.replication_status().await {
+            Err(CommonError::PlacementCenterError(PlacementCenterError::NotLeader {
+                leader_id: hint_id,
+                ..
+            })) => assert_eq!(hint_id, leader_id),
+            _ => panic!("a follower should not report the replication status"),
+        }
+
+        let before = leader.replication_status().await.unwrap();
+        let before_matched = before
+            .peers
+            .iter()
+            .find(|peer| peer.node_id == follower_id)
+            .unwrap()
+            .matched;
+
+        let req = SetRequest {
+            key: "replication_status_test".to_string(),
+            value: "value".to_string(),
+        };
+        let data = StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        let index = leader.propose(data).await.unwrap().index;
+        assert!(index > before_matched);
+
+        // the entry is committed, so the follower has acknowledged it
+        let status = leader.replication_status().await.unwrap();
+        assert_eq!(status.peers.len(), 2);
+        let progress = status
+            .peers
+            .iter()
+            .find(|peer| peer.node_id == follower_id)
+            .unwrap();
+        assert!(progress.matched >= index);
+        assert!(progress.next_index > progress.matched);
+        assert!(!progress.learner);
+        assert!(status.quorum_commit_index >= index);
+        assert!(status.commit_index >= index);
+
+        stop_send_1.send(true).unwrap();
+        stop_send_2.send(true).unwrap();
+        remove_dir_all(data_path_1).await.unwrap();
+        remove_dir_all(data_path_2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn campaign_test() {
+        init_test_conf();
+        let node_ids = [1, 2];
+        let data_path_1 = format!("/tmp/robustmq_{}", unique_id());
+        let data_path_2 = format!("/tmp/robustmq_{}", unique_id());
+        let (apply_1, stop_send_1, peer_recv_1, _, _) =
+            start_cluster_node(1, &node_ids, &data_path_1);
+        let (apply_2, stop_send_2, peer_recv_2, _, _) =
+            start_cluster_node(2, &node_ids, &data_path_2);
+
+        let mut nodes = HashMap::new();
+        nodes.insert(1, apply_1.clone());
+        nodes.insert(2, apply_2.clone());
+        let connected = Arc::new(AtomicBool::new(true));
+        forward_peer_messages(peer_recv_1, nodes.clone(), connected.clone());
+        forward_peer_messages(peer_recv_2, nodes, connected);
+
+        // the follower has to have the whole log, a quorum does not vote for it otherwise
+        let mut leader_status = None;
+        for _ in 0..100 {
+            let status_1 = apply_1.status().await.unwrap();
+            let status_2 = apply_2.status().await.unwrap();
+            if status_1.leader_id != 0
+                && status_1.leader_id == status_2.leader_id
+                && status_1.last_index == status_2.last_index
+                && status_1.commit_index == status_2.commit_index
+            {
+                leader_status = Some(if status_1.leader_id == 1 { status_1 } else { status_2 });
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        let leader_status = leader_status.unwrap();
+        let (leader, follower, follower_id) = if leader_status.node_id == 1 {
+            (apply_1.clone(), apply_2.clone(), 2)
+        } else {
+            (apply_2.clone(), apply_1.clone(), 1)
+        };
+
+        // the leader does not campaign again
+        leader.campaign().await.unwrap();
+        assert_eq!(leader.status().await.unwrap().term, leader_status.term);
+
+        follower.campaign().await.unwrap();
+        let mut status = follower.status().await.unwrap();
+        for _ in 0..100 {
+            if status.role == StateRole::Leader {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+            status = follower.status().await.unwrap();
+        }
+        assert_eq!(status.role, StateRole::Leader);
+        assert_eq!(status.leader_id, follower_id);
+        assert!(status.term > leader_status.term);
+
+        let mut old_leader_status = leader.status().await.unwrap();
+        for _ in 0..100 {
+            if old_leader_status.leader_id == follower_id {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+            old_leader_status = leader.status().await.unwrap();
+        }
+        assert_eq!(old_leader_status.role, StateRole::Follower);
+        assert_eq!(old_leader_status.leader_id, follower_id);
+        assert_eq!(old_leader_status.term, status.term);
+
+        stop_send_1.send(true).unwrap();
+        stop_send_2.send(true).unwrap();
+        remove_dir_all(data_path_1).await.unwrap();
+        remove_dir_all(data_path_2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn propose_to_follower_test() {
+        init_test_conf();
+        let node_ids = [1, 2];
+        let data_path_1 = format!("/tmp/robustmq_{}", unique_id());
+        let data_path_2 = format!("/tmp/robustmq_{}", unique_id());
+        let (apply_1, stop_send_1, peer_recv_1, _, _) =
+            start_cluster_node(1, &node_ids, &data_path_1);
+        let (apply_2, stop_send_2, peer_recv_2, _, _) =
+            start_cluster_node(2, &node_ids, &data_path_2);
+
+        let mut nodes = HashMap::new();
+        nodes.insert(1, apply_1.clone());
+        nodes.insert(2, apply_2.clone());
+        let connected = Arc::new(AtomicBool::new(true));
+        forward_peer_messages(peer_recv_1, nodes.clone(), connected.clone());
+        forward_peer_messages(peer_recv_2, nodes, connected);
+
+        let mut leader_id = 0;
+        for _ in 0..100 {
+            let status = apply_1.status().await.unwrap();
+            if status.leader_id != 0 && status.leader_id == apply_2.status().await.unwrap().leader_id
+            {
+                leader_id = status.leader_id;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert_ne!(leader_id, 0);
+        let follower = if leader_id == 1 { apply_2.clone() } else { apply_1.clone() };
+
+        let req = SetRequest {
+            key: "propose_to_follower_test".to_string(),
+            value: "value".to_string(),
+        };
+        let data = StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        match follower.propose(data).await {
+            Err(CommonError::PlacementCenterError(PlacementCenterError::NotLeader {
+                leader_id: hint_id,
+                leader_addr,
+            })) => {
+                assert_eq!(hint_id, leader_id);
+                assert_eq!(leader_addr, format!("127.0.0.1:{}", 1228 + leader_id));
+            }
+            _ => panic!("a follower should reject the proposal with a leader hint"),
+        }
+
+        stop_send_1.send(true).unwrap();
+        stop_send_2.send(true).unwrap();
+        remove_dir_all(data_path_1).await.unwrap();
+        remove_dir_all(data_path_2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn quorum_loss_step_down_test() {
+        init_test_conf();
+        let node_ids = [1, 2];
+        let data_path_1 = format!("/tmp/robustmq_{}", unique_id());
+        let data_path_2 = format!("/tmp/robustmq_{}", unique_id());
+        let (apply_1, stop_send_1, peer_recv_1, _, _) =
+            start_cluster_node(1, &node_ids, &data_path_1);
+        let (apply_2, stop_send_2, peer_recv_2, _, _) =
+            start_cluster_node(2, &node_ids, &data_path_2);
+
+        let mut nodes = HashMap::new();
+        nodes.insert(1, apply_1.clone());
+        nodes.insert(2, apply_2.clone());
+        let connected = Arc::new(AtomicBool::new(true));
+        forward_peer_messages(peer_recv_1, nodes.clone(), connected.clone());
+        forward_peer_messages(peer_recv_2, nodes.clone(), connected.clone());
+
+        let mut leader_id = 0;
+        for _ in 0..100 {
+            let status = apply_1.status().await.unwrap();
+            if status.leader_id != 0 && status.leader_id == apply_2.status().await.unwrap().leader_id
+            {
+                leader_id = status.leader_id;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert_ne!(leader_id, 0);
+        let leader = nodes.get(&leader_id).unwrap().clone();
+
+        let status = leader.status().await.unwrap();
+        assert_eq!(status.role, StateRole::Leader);
+        assert!(status.quorum_ack_age.unwrap() <= quorum_loss_timeout());
+        assert_eq!(status.quorum_loss_step_downs, 0);
+
+        // the follower no longer answers, the leader has to give up after the election timeout
+        connected.store(false, Ordering::Relaxed);
+        let partitioned_at = Instant::now();
+        let mut status = leader.status().await.unwrap();
+        while status.role == StateRole::Leader {
+            assert!(partitioned_at.elapsed() < quorum_loss_timeout() * 3);
+            sleep(Duration::from_millis(50)).await;
+            status = leader.status().await.unwrap();
+        }
+        assert!(partitioned_at.elapsed() >= leader_lease_duration());
+        assert_eq!(status.quorum_loss_step_downs, 1);
+        assert_eq!(status.quorum_ack_age, None);
+
+        // alone it cannot win an election again
+        sleep(quorum_loss_timeout() * 2).await;
+        let status = leader.status().await.unwrap();
+        assert_ne!(status.role, StateRole::Leader);
+        assert_eq!(status.quorum_loss_step_downs, 1);
+
+        stop_send_1.send(true).unwrap();
+        stop_send_2.send(true).unwrap();
+        remove_dir_all(data_path_1).await.unwrap();
+        remove_dir_all(data_path_2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn propose_timeout_test() {
+        init_test_conf();
+        let node_ids = [1, 2];
+        let data_path_1 = format!("/tmp/robustmq_{}", unique_id());
+        let data_path_2 = format!("/tmp/robustmq_{}", unique_id());
+        let (apply_1, stop_send_1, peer_recv_1, _, _) =
+            start_cluster_node(1, &node_ids, &data_path_1);
+        let (apply_2, stop_send_2, peer_recv_2, _, _) =
+            start_cluster_node(2, &node_ids, &data_path_2);
+
+        let mut nodes = HashMap::new();
+        nodes.insert(1, apply_1.clone());
+        nodes.insert(2, apply_2.clone());
+        let connected = Arc::new(AtomicBool::new(true));
+        forward_peer_messages(peer_recv_1, nodes.clone(), connected.clone());
+        forward_peer_messages(peer_recv_2, nodes.clone(), connected.clone());
+
+        let mut leader_id = 0;
+        for _ in 0..100 {
+            let status = apply_1.status().await.unwrap();
+            if status.leader_id != 0 && status.leader_id == apply_2.status().await.unwrap().leader_id
+            {
+                leader_id = status.leader_id;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert_ne!(leader_id, 0);
+        let leader = nodes.get(&leader_id).unwrap().clone();
+
+        // without the follower the entry cannot be committed, the deadline passes while
+        // the node is still the leader
+        connected.store(false, Ordering::Relaxed);
+        let req = SetRequest {
+            key: "propose_timeout_test".to_string(),
+            value: "value".to_string(),
+        };
+        let data = StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        let propose_timeout = Duration::from_millis(300);
+        let start = Instant::now();
+        match leader.propose_with_timeout(data, propose_timeout).await {
+            Err(CommonError::PlacementCenterError(PlacementCenterError::RaftProposalTimeout(
+                _,
+                ms,
+            ))) => assert_eq!(ms, 300),
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert!(start.elapsed() >= propose_timeout);
+        assert!(start.elapsed() < quorum_loss_timeout());
+
+        let status = leader.status().await.unwrap();
+        assert_eq!(status.role, StateRole::Leader);
+        assert_eq!(status.pending_proposals, 0);
+        // the entry of the proposal stays in the log until the follower is back
+        assert_eq!(status.uncommitted_entries, 1);
+
+        stop_send_1.send(true).unwrap();
+        stop_send_2.send(true).unwrap();
+        remove_dir_all(data_path_1).await.unwrap();
+        remove_dir_all(data_path_2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn replicated_delete_test() {
+        init_test_conf();
+        let node_ids = [1, 2];
+        let data_path_1 = format!("/tmp/robustmq_{}", unique_id());
+        let data_path_2 = format!("/tmp/robustmq_{}", unique_id());
+        let (apply_1, stop_send_1, peer_recv_1, _, rocksdb_engine_1) =
+            start_cluster_node(1, &node_ids, &data_path_1);
+        let (apply_2, stop_send_2, peer_recv_2, _, rocksdb_engine_2) =
+            start_cluster_node(2, &node_ids, &data_path_2);
+
+        let mut nodes = HashMap::new();
+        nodes.insert(1, apply_1.clone());
+        nodes.insert(2, apply_2.clone());
+        let connected = Arc::new(AtomicBool::new(true));
+        forward_peer_messages(peer_recv_1, nodes.clone(), connected.clone());
+        forward_peer_messages(peer_recv_2, nodes, connected);
+
+        let mut leader_id = 0;
+        for _ in 0..100 {
+            let status = apply_1.status().await.unwrap();
+            if status.leader_id != 0 && status.leader_id == apply_2.status().await.unwrap().leader_id
+            {
+                leader_id = status.leader_id;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert_ne!(leader_id, 0);
+        let (leader, follower) = if leader_id == 1 {
+            (apply_1.clone(), apply_2.clone())
+        } else {
+            (apply_2.clone(), apply_1.clone())
+        };
+
+        let key = "replicated_delete_test".to_string();
+        let req = SetRequest {
+            key: key.clone(),
+            value: "value".to_string(),
+        };
+        let data = StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        leader.propose(data).await.unwrap();
+        let req = DeleteRequest { key: key.clone() };
+        let data = StorageData::new(StorageDataType::KvDelete, DeleteRequest::encode_to_vec(&req));
+        let index = leader.propose(data).await.unwrap().index;
+
+        // the follower applies the same delete once it has caught up with the leader
+        let mut follower_applied = 0;
+        for _ in 0..100 {
+            follower_applied = follower.status().await.unwrap().applied_index;
+            if follower_applied >= index {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert!(follower_applied >= index);
+
+        for engine in [rocksdb_engine_1, rocksdb_engine_2] {
+            let kv_storage = KvStorage::new(engine);
+            assert!(!kv_storage.exists(key.clone()).unwrap());
+            assert_eq!(kv_storage.get(key.clone()).unwrap(), None);
+        }
+
+        stop_send_1.send(true).unwrap();
+        stop_send_2.send(true).unwrap();
+        remove_dir_all(data_path_1).await.unwrap();
+        remove_dir_all(data_path_2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn last_write_wins_test() {
+        init_test_conf();
+        let node_ids = [1, 2, 3];
+        let mut nodes = HashMap::new();
+        let mut peer_recvs = Vec::new();
+        let mut stops = Vec::new();
+        let mut engines = Vec::new();
+        let mut data_paths = Vec::new();
+        for id in node_ids {
+            let data_path = format!("/tmp/robustmq_{}", unique_id());
+            let (apply, stop_send, peer_recv, _, engine) =
+                start_cluster_node(id, &node_ids, &data_path);
+            nodes.insert(id, apply);
+            peer_recvs.push(peer_recv);
+            stops.push(stop_send);
+            engines.push(engine);
+            data_paths.push(data_path);
+        }
+        let connected = Arc::new(AtomicBool::new(true));
+        for peer_recv in peer_recvs {
+            forward_peer_messages(peer_recv, nodes.clone(), connected.clone());
+        }
+        let mut leader_id = 0;
+        for _ in 0..100 {
+            let status = nodes[&1].status().await.unwrap();
+            if status.leader_id != 0 {
+                leader_id = status.leader_id;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert_ne!(leader_id, 0);
+        let leader = nodes[&leader_id].clone();
+
+        let key = "last_write_wins_test".to_string();
+        let write = |value: &str| {
+            let req = SetRequest {
+                key: key.clone(),
+                value: value.to_string(),
+            };
+            return StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        };
+        let (first, second) = tokio::join!(
+            leader.propose(write("first")),
+            leader.propose(write("second"))
+        );
+        let (first, second) = (first.unwrap().index, second.unwrap().index);
+        assert_ne!(first, second);
+        let expected = if second > first { "second" } else { "first" };
+        let last_index = first.max(second);
+
+        for (id, engine) in node_ids.iter().zip(engines) {
+            for _ in 0..100 {
+                if nodes[id].status().await.unwrap().applied_index >= last_index {
+                    break;
+                }
+                sleep(Duration::from_millis(100)).await;
+            }
+            let kv_storage = KvStorage::new(engine);
+            assert_eq!(kv_storage.get(key.clone()).unwrap(), Some(expected.to_string()));
+        }
+
+        for stop_send in stops {
+            stop_send.send(true).unwrap();
+        }
+        for data_path in data_paths {
+            remove_dir_all(data_path).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn propose_result_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (mut machine, raft_message_send, stop_send, _peer_recv, engine) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+        tokio::spawn(async move {
+            machine.run().await.unwrap();
+        });
+        let apply = RaftMachineApply::new(raft_message_send);
+        wait_for_leader(&apply).await;
+
+        let req = SetRequest {
+            key: "propose_result_test".to_string(),
+            value: "value".to_string(),
+        };
+        let data = StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        let result = apply.propose(data).await.unwrap();
+        assert!(result.index > 0);
+        assert!(apply.status().await.unwrap().applied_index >= result.index);
+
+        // the entry has been applied once the result is returned
+        let kv_storage = KvStorage::new(engine);
+        assert_eq!(
+            kv_storage.get("propose_result_test".to_string()).unwrap(),
+            Some("value".to_string())
+        );
+
+        stop_send.send(true).unwrap();
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn slow_apply_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (mut machine, raft_message_send, stop_send, _peer_recv, engine) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+        Arc::get_mut(&mut machine.applier).unwrap().apply_delay = Duration::from_millis(500);
+        tokio::spawn(async move {
+            machine.run().await.unwrap();
+        });
+        let apply = Arc::new(RaftMachineApply::new(raft_message_send));
+        let status = wait_for_leader(&apply).await;
+
+        let req = SetRequest {
+            key: "slow_apply_test".to_string(),
+            value: "value".to_string(),
+        };
+        let data = StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        let propose_apply = apply.clone();
+        let propose = tokio::spawn(async move { propose_apply.propose(data).await });
+
+        // the ready loop keeps answering and ticking while the entry is applied
+        let mut applying = false;
+        while !propose.is_finished() {
+            let start = Instant::now();
+            let current = apply.status().await.unwrap();
+            assert!(start.elapsed() < RAFT_TICK_INTERVAL);
+            assert_eq!(current.role, StateRole::Leader);
+            assert_eq!(current.term, status.term);
+            if current.commit_index > current.applied_index {
+                applying = true;
+            }
+            sleep(Duration::from_millis(50)).await;
+        }
+        assert!(applying);
+
+        let result = propose.await.unwrap().unwrap();
+        assert!(apply.status().await.unwrap().applied_index >= result.index);
+        let kv_storage = KvStorage::new(engine);
+        assert_eq!(
+            kv_storage.get("slow_apply_test".to_string()).unwrap(),
+            Some("value".to_string())
+        );
+
+        stop_send.send(true).unwrap();
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn snapshot_behind_apply_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (mut machine, raft_message_send, stop_send, _peer_recv, _) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+        Arc::get_mut(&mut machine.applier).unwrap().apply_delay = Duration::from_millis(500);
+        let raft_storage = machine.raft_storage.clone();
+        tokio::spawn(async move {
+            machine.run().await.unwrap();
+        });
+        let apply = Arc::new(RaftMachineApply::new(raft_message_send));
+        wait_for_leader(&apply).await;
+
+        let req = SetRequest {
+            key: "snapshot_behind_apply_test".to_string(),
+            value: "value".to_string(),
+        };
+        let data = StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        let propose_apply = apply.clone();
+        let propose = tokio::spawn(async move { propose_apply.propose(data).await });
+        loop {
+            let status = apply.status().await.unwrap();
+            if status.commit_index > status.applied_index {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        // the entry is committed but still being applied, the snapshot stops before it
+        let index = apply.trigger_snapshot().await.unwrap();
+        let result = propose.await.unwrap().unwrap();
+        assert!(index < result.index);
+        let snapshot = raft_storage.read().unwrap().saved_snapshot().unwrap();
+        assert_eq!(snapshot.get_metadata().index, index);
+        assert!(index <= raft_storage.read().unwrap().applied_index());
+
+        stop_send.send(true).unwrap();
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn flush_interval_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (mut machine, raft_message_send, stop_send, _peer_recv, engine) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+        machine.flush_interval = Duration::from_millis(200);
+        let machine_task = tokio::spawn(async move {
+            machine.run().await.unwrap();
+        });
+        let apply = RaftMachineApply::new(raft_message_send);
+        wait_for_leader(&apply).await;
+
+        for i in 0..10 {
+            let req = SetRequest {
+                key: format!("flush_interval_test_{}", i),
+                value: "value".to_string(),
+            };
+            let data = StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+            apply.propose(data).await.unwrap();
+        }
+
+        // nothing flushes the memtables but the background task
+        sleep(Duration::from_millis(600)).await;
+        assert!(engine.properties()["rocksdb.num-files-at-level0"] >= 1);
+
+        stop_send.send(true).unwrap();
+        timeout(Duration::from_secs(5), machine_task)
+            .await
+            .unwrap()
+            .unwrap();
+        drop(engine);
+        sleep(Duration::from_millis(100)).await;
+
+        let engine = Arc::new(RocksDBEngine::new(&data_path, 10, column_family_list()));
+        let kv_storage = KvStorage::new(engine.clone());
+        for i in 0..10 {
+            assert_eq!(
+                kv_storage.get(format!("flush_interval_test_{}", i)).unwrap(),
+                Some("value".to_string())
+            );
+        }
+
+        drop(kv_storage);
+        drop(engine);
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn duplicate_request_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (mut machine, raft_message_send, stop_send, _peer_recv, engine) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+        let mut events = machine.subscribe();
+        tokio::spawn(async move {
+            machine.run().await.unwrap();
+        });
+        let apply = RaftMachineApply::new(raft_message_send);
+        wait_for_leader(&apply).await;
+
+        let set_request = |value: &str, request_id: u64| {
+            let req = SetRequest {
+                key: "duplicate_request_test".to_string(),
+                value: value.to_string(),
+            };
+            return StorageData::new_request(
+                StorageDataType::KvSet,
+                SetRequest::encode_to_vec(&req),
+                "client-1".to_string(),
+                request_id,
+            );
+        };
+        let kv_storage = KvStorage::new(engine);
+        let first = apply.propose(set_request("v1", 1)).await.unwrap();
+        let event = timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.index, first.index);
+
+        // a write of another client in between would be undone if the retry were applied again
+        let req = SetRequest {
+            key: "duplicate_request_test".to_string(),
+            value: "v2".to_string(),
+        };
+        let data = StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        let second = apply.propose(data).await.unwrap();
+        let event = timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.index, second.index);
+
+        let retry = apply.propose(set_request("v1", 1)).await.unwrap();
+        assert_eq!(retry.index, first.index);
+        assert_eq!(
+            kv_storage.get("duplicate_request_test".to_string()).unwrap(),
+            Some("v2".to_string())
+        );
+        assert!(events.try_recv().is_err());
+
+        // the next request of the client is applied, an older one is rejected
+        let third = apply.propose(set_request("v3", 2)).await.unwrap();
+        assert!(third.index > second.index);
+        assert_eq!(
+            kv_storage.get("duplicate_request_test".to_string()).unwrap(),
+            Some("v3".to_string())
+        );
+        match apply.propose(set_request("v1", 1)).await {
+            Err(CommonError::PlacementCenterError(PlacementCenterError::RaftRequestOutdated(
+                client_id,
+                request_id,
+                applied_id,
+            ))) => {
+                assert_eq!(client_id, "client-1".to_string());
+                assert_eq!(request_id, 1);
+                assert_eq!(applied_id, 2);
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        // the retry of a compare-and-swap that did not match fails the same way again
+        let req = CompareAndSwapRequest {
+            cf: DB_COLUMN_FAMILY_CLUSTER.to_string(),
+            key: "duplicate_request_test".to_string(),
+            expected: Some("v1".to_string()),
+            new: "v4".to_string(),
+        };
+        for _ in 0..2 {
+            let data = StorageData::new_request(
+                StorageDataType::KvCompareAndSwap,
+                req.encode_to_vec(),
+                "client-1".to_string(),
+                3,
+            );
+            match apply.propose(data).await {
+                Err(CommonError::PlacementCenterError(
+                    PlacementCenterError::CompareAndSwapMismatch(key),
+                )) => assert_eq!(key, "duplicate_request_test".to_string()),
+                res => panic!("unexpected result {:?}", res),
+            }
+        }
+
+        stop_send.send(true).unwrap();
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn apply_entry_batch_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (machine, _raft_send, _stop_send, _peer_recv, engine) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+        let kv_storage = KvStorage::new(engine);
+
+        // the writes of a command are only collected, they reach RocksDB with the batch
+        let (result, operations) = collect_writes(|| {
+            kv_storage.set("apply_entry_batch_test".to_string(), "v0".to_string())
+        });
+        result.unwrap();
+        assert_eq!(operations.len(), 1);
+        assert_eq!(kv_storage.get("apply_entry_batch_test".to_string()).unwrap(), None);
+
+        let req = SetRequest {
+            key: "apply_entry_batch_test".to_string(),
+            value: "v1".to_string(),
+        };
+        let data = StorageData::new_request(
+            StorageDataType::KvSet,
+            SetRequest::encode_to_vec(&req),
+            "client-1".to_string(),
+            1,
+        );
+        let mut entry = Entry::default();
+        entry.index = 7;
+        entry.term = 1;
+        entry.data = data.encode();
+        assert!(machine.applier.apply_normal_entry(&entry).unwrap().is_none());
+
+        assert_eq!(
+            kv_storage.get("apply_entry_batch_test".to_string()).unwrap(),
+            Some("v1".to_string())
+        );
+        let raft_storage = machine.raft_storage.read().unwrap();
+        assert_eq!(raft_storage.applied_index(), 7);
+        assert_eq!(raft_storage.applied_request("client-1").unwrap().index, 7);
+        drop(raft_storage);
+
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn apply_empty_entry_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (mut machine, raft_message_send, stop_send, _peer_recv, _) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+        let raft_storage = machine.raft_storage.clone();
+        tokio::spawn(async move {
+            machine.run().await.unwrap();
+        });
+        let apply = RaftMachineApply::new(raft_message_send);
+        let status = wait_for_leader(&apply).await;
+
+        // the entry the leader appended on election is applied on its own
+        let noop_index = status.last_index;
+        let noop = raft_storage.read().unwrap().entry_by_idx(noop_index).unwrap();
+        assert_eq!(noop.get_entry_type(), EntryType::EntryNormal);
+        assert!(noop.data.is_empty());
+        for _ in 0..50 {
+            if raft_storage.read().unwrap().applied_index() >= noop_index {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(raft_storage.read().unwrap().applied_index(), noop_index);
+
+        let req = SetRequest {
+            key: "apply_empty_entry_test".to_string(),
+            value: "value".to_string(),
+        };
+        let data = StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        let result = apply.propose(data).await.unwrap();
+        assert_eq!(result.index, noop_index + 1);
+        assert_eq!(raft_storage.read().unwrap().applied_index(), result.index);
+        assert!(raft_storage.read().unwrap().uncommit_index().is_empty());
+
+        stop_send.send(true).unwrap();
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn message_channel_full_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (mut machine, raft_message_send, stop_send, _peer_recv, _) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+        let capacity = placement_center_conf().raft.message_channel_size as usize;
+        assert_eq!(raft_message_send.max_capacity(), capacity);
+
+        // the node is not running yet, nothing takes the messages off the channel
+        for _ in 0..capacity {
+            let (sx, _) = oneshot::channel();
+            raft_message_send.try_send(RaftMessage::Status { chan: sx }).unwrap();
+        }
+        let (sx, _) = oneshot::channel();
+        match raft_message_send.try_send(RaftMessage::Status { chan: sx }) {
+            Err(TrySendError::Full(_)) => {}
+            _ => panic!("the full channel must reject the message"),
+        }
+
+        // a sender waits for room until the node takes messages off the channel
+        let apply = RaftMachineApply::new(raft_message_send.clone());
+        let blocked = tokio::spawn(async move { apply.status().await });
+        sleep(Duration::from_millis(300)).await;
+        assert!(!blocked.is_finished());
+
+        tokio::spawn(async move {
+            machine.run().await.unwrap();
+        });
+        let status = timeout(Duration::from_secs(10), blocked)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(status.node_id, 1);
+
+        stop_send.send(true).unwrap();
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_only_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (mut machine, raft_message_send, stop_send, _peer_recv, engine) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+        tokio::spawn(async move {
+            machine.run().await.unwrap();
+        });
+        let apply = RaftMachineApply::new(raft_message_send);
+        wait_for_leader(&apply).await;
+
+        let key = "read_only_test".to_string();
+        let set = |value: &str| {
+            let req = SetRequest {
+                key: key.clone(),
+                value: value.to_string(),
+            };
+            return StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        };
+        let read = || {
+            let apply = &apply;
+            let engine = engine.clone();
+            let key = key.clone();
+            return async move {
+                let data = apply
+                    .linearizable_get(engine, DB_COLUMN_FAMILY_CLUSTER, key)
+                    .await
+                    .unwrap();
+                return data.map(|data| serde_json::from_slice::<String>(&data).unwrap());
+            };
+        };
+        apply.propose(set("before")).await.unwrap();
+
+        apply.set_read_only(true).await.unwrap();
+        assert!(apply.status().await.unwrap().read_only);
+        match apply.propose(set("during")).await {
+            Err(CommonError::PlacementCenterError(PlacementCenterError::RaftReadOnly(_))) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert_eq!(read().await, Some("before".to_string()));
+
+        apply.set_read_only(false).await.unwrap();
+        assert!(!apply.status().await.unwrap().read_only);
+        apply.propose(set("after")).await.unwrap();
+        assert_eq!(read().await, Some("after".to_string()));
+
+        stop_send.send(true).unwrap();
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn undecodable_entry_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (mut machine, raft_message_send, stop_send, _peer_recv, engine) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+        let raft_storage = machine.raft_storage.clone();
+        tokio::spawn(async move {
+            machine.run().await.unwrap();
+        });
+        let apply = RaftMachineApply::new(raft_message_send.clone());
+        wait_for_leader(&apply).await;
+
+        // the data is too short to be decoded as StorageData
+        let skipped = raft_undecodable_entries("EntryNormal");
+        let (sx, rx) = oneshot::channel();
+        raft_message_send
+            .send(RaftMessage::Propose {
+                data: vec![0xff, 0xff, 0xff],
+                chan: sx,
+                deadline: Instant::now() + Duration::from_secs(30),
+            })
+            .await
+            .unwrap();
+        let garbage_index = match rx.await.unwrap() {
+            RaftResponseMesage::ApplyFailed(e) => {
+                assert!(e.to_string().contains("cannot be decoded"));
+                raft_storage.read().unwrap().applied_index()
+            }
+            _ => panic!("the undecodable entry must fail to apply"),
+        };
+        assert!(raft_undecodable_entries("EntryNormal") > skipped);
+
+        // the entries behind it are still applied
+        let req = SetRequest {
+            key: "undecodable_entry_test".to_string(),
+            value: "value".to_string(),
+        };
+        let data = StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        let result = apply.propose(data).await.unwrap();
+        assert_eq!(result.index, garbage_index + 1);
+        let kv_storage = KvStorage::new(engine);
+        assert_eq!(
+            kv_storage.get("undecodable_entry_test".to_string()).unwrap(),
+            Some("value".to_string())
+        );
+
+        stop_send.send(true).unwrap();
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn legacy_entry_format_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (machine, _raft_send, _stop_send, _peer_recv, engine) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+
+        // the layout of the entries written before client_id and request_id were added
+        #[derive(serde::Serialize)]
+        struct LegacyStorageData {
+            data_type: StorageDataType,
+            value: Vec<u8>,
+        }
+        let req = SetRequest {
+            key: "legacy_entry_format_test".to_string(),
+            value: "value".to_string(),
+        };
+        let legacy = serialize(&LegacyStorageData {
+            data_type: StorageDataType::KvSet,
+            value: SetRequest::encode_to_vec(&req),
+        })
+        .unwrap();
+        // KvSet has kept the index it had in those entries
+        assert_eq!(legacy[..4], [11, 0, 0, 0]);
+
+        let data = StorageData::decode(&legacy).unwrap();
+        assert_eq!(data.data_type, StorageDataType::KvSet);
+        assert_eq!(data.value, SetRequest::encode_to_vec(&req));
+        assert!(data.client_id.is_empty());
+        assert_eq!(data.propose_time, 0);
+
+        let mut entry = Entry::default();
+        entry.index = 1;
+        entry.term = 1;
+        entry.data = legacy;
+        assert!(machine.applier.apply_normal_entry(&entry).unwrap().is_none());
+        let kv_storage = KvStorage::new(engine);
+        assert_eq!(
+            kv_storage.get("legacy_entry_format_test".to_string()).unwrap(),
+            Some("value".to_string())
+        );
+
+        // the current layout keeps the client of the request
+        let data = StorageData::new_request(
+            StorageDataType::KvSet,
+            vec![1],
+            "client-1".to_string(),
+            3,
+        );
+        let data = StorageData::decode(&data.encode()).unwrap();
+        assert_eq!(data.client_id, "client-1".to_string());
+        assert_eq!(data.request_id, 3);
+
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn compare_and_swap_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (mut machine, raft_message_send, stop_send, _peer_recv, engine) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+        tokio::spawn(async move {
+            machine.run().await.unwrap();
+        });
+        let apply = RaftMachineApply::new(raft_message_send);
+        wait_for_leader(&apply).await;
+
+        let cf = DB_COLUMN_FAMILY_CLUSTER.to_string();
+        let key = "compare_and_swap_test".to_string();
+        assert!(apply
+            .compare_and_swap(cf.clone(), key.clone(), None, "1".to_string())
+            .await
+            .unwrap());
+
+        // both proposals expect "1", whichever is applied first wins
+        let (first, second) = tokio::join!(
+            apply.compare_and_swap(
+                cf.clone(),
+                key.clone(),
+                Some("1".to_string()),
+                "first".to_string()
+            ),
+            apply.compare_and_swap(
+                cf.clone(),
+                key.clone(),
+                Some("1".to_string()),
+                "second".to_string()
+            )
+        );
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert!(first != second);
+
+        let kv_storage = KvStorage::new(engine);
+        let winner = if first { "first" } else { "second" };
+        assert_eq!(kv_storage.get(key.clone()).unwrap(), Some(winner.to_string()));
+
+        // a missing key only matches None
+        assert!(!apply
+            .compare_and_swap(cf, "not_exist".to_string(), Some("1".to_string()), "2".to_string())
+            .await
+            .unwrap());
+        assert_eq!(kv_storage.get("not_exist".to_string()).unwrap(), None);
+
+        // the gRPC writes see the rejection of the state machine as well
+        let req = CompareAndSwapRequest {
+            cf: DB_COLUMN_FAMILY_CLUSTER.to_string(),
+            key: "not_exist".to_string(),
+            expected: Some("1".to_string()),
+            new: "2".to_string(),
+        };
+        let data = StorageData::new(StorageDataType::KvCompareAndSwap, req.encode_to_vec());
+        match apply
+            .apply_propose_message(data, "compare_and_swap".to_string())
+            .await
+        {
+            Err(CommonError::PlacementCenterError(
+                PlacementCenterError::CompareAndSwapMismatch(_),
+            )) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        stop_send.send(true).unwrap();
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn election_tick_range_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (mut machine, _raft_send, _stop_send, _peer_recv, _) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+        machine.min_election_tick = 15;
+        machine.max_election_tick = 30;
+        let mut raft_node = machine.new_node().await.unwrap();
+
+        for _ in 0..50 {
+            raft_node.raft.reset_randomized_election_timeout();
+            let election_timeout = raft_node.raft.randomized_election_timeout();
+            assert!((15..30).contains(&election_timeout));
+        }
+
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[test]
+    fn elapsed_ticks_test() {
+        let interval = RAFT_TICK_INTERVAL;
+        assert_eq!(elapsed_ticks(Duration::from_millis(0), interval), 0);
+        assert_eq!(elapsed_ticks(interval - Duration::from_millis(1), interval), 0);
+        assert_eq!(elapsed_ticks(interval, interval), 1);
+        assert_eq!(elapsed_ticks(interval * 3 + Duration::from_millis(50), interval), 3);
+        assert_eq!(elapsed_ticks(Duration::from_secs(60), interval), MAX_COALESCED_TICKS);
+    }
+
+    #[test]
+    fn quorum_index_test() {
+        assert_eq!(quorum_index(vec![]), 0);
+        assert_eq!(quorum_index(vec![7]), 7);
+        // both voters of a two node group have to match the index
+        assert_eq!(quorum_index(vec![3, 9]), 3);
+        assert_eq!(quorum_index(vec![5, 9, 2]), 5);
+        assert_eq!(quorum_index(vec![4, 8, 6, 1, 9]), 6);
+    }
+
+    #[tokio::test]
+    async fn advance_ticks_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (mut machine, _raft_send, _stop_send, _peer_recv, _) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+        let mut raft_node = machine.new_node().await.unwrap();
+        assert_eq!(raft_node.raft.state, StateRole::Follower);
+
+        // an iteration delayed by three and a half intervals makes up all three ticks
+        let ticks = machine.advance_ticks(&mut raft_node, RAFT_TICK_INTERVAL * 7 / 2);
+        assert_eq!(ticks, 3);
+        assert_eq!(raft_node.raft.election_elapsed, 3);
+
+        assert_eq!(machine.advance_ticks(&mut raft_node, RAFT_TICK_INTERVAL / 2), 0);
+        assert_eq!(raft_node.raft.election_elapsed, 3);
+
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn new_node_start_error_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (mut machine, _raft_send, _stop_send, _peer_recv, _) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+
+        // an election range the Raft config rejects
+        machine.max_election_tick = machine.min_election_tick;
+        match machine.new_node().await {
+            Err(PlacementCenterError::RaftNodeStartFailed(node_id, _)) => assert_eq!(node_id, 1),
+            _ => panic!("the Raft node should fail to start"),
+        }
+
+        // an applied index beyond anything that was ever committed
+        machine.max_election_tick = machine.min_election_tick * 2;
+        machine
+            .raft_storage
+            .write()
+            .unwrap()
+            .save_applied_index(10)
+            .unwrap();
+        assert!(matches!(
+            machine.run().await,
+            Err(PlacementCenterError::RaftNodeStartFailed(1, _))
+        ));
+
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn trigger_snapshot_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (mut machine, raft_message_send, stop_send, _peer_recv, _) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+        let raft_storage = machine.raft_storage.clone();
+        tokio::spawn(async move {
+            machine.run().await.unwrap();
+        });
+        let apply = RaftMachineApply::new(raft_message_send);
+        wait_for_leader(&apply).await;
+
+        let mut last_index = 0;
+        for i in 0..5 {
+            let req = SetRequest {
+                key: format!("trigger_snapshot_test-{}", i),
+                value: "value".to_string(),
+            };
+            let data = StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+            last_index = apply.propose(data).await.unwrap().index;
+        }
+
+        let index = apply.trigger_snapshot().await.unwrap();
+        assert!(index >= last_index);
+        let storage = raft_storage.read().unwrap();
+        assert_eq!(storage.first_index(), index);
+        assert_eq!(storage.snapshot_metadata.index, index);
+        drop(storage);
+
+        stop_send.send(true).unwrap();
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn pre_vote_config_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (machine, _raft_send, _stop_send, _peer_recv, _) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+
+        let conf = machine.build_config(0);
+        assert!(conf.pre_vote);
+
+        let raft_node = machine.new_node().await.unwrap();
+        assert!(raft_node.raft.pre_vote);
+
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_index_test() {
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let (apply, stop_send, _peer_recv) = start_single_node(&data_path);
+        wait_for_leader(&apply).await;
+
+        let req = SetRequest {
+            key: "read_index_test".to_string(),
+            value: "value".to_string(),
+        };
+        let data = StorageData::new(StorageDataType::KvSet, SetRequest::encode_to_vec(&req));
+        apply
+            .apply_propose_message(data, "set".to_string())
+            .await
+            .unwrap();
+
+        // without lease reads every read is confirmed through ReadIndex
+        timeout(Duration::from_secs(5), apply.read_index())
+            .await
+            .unwrap()
+            .unwrap();
+        let status = apply.status().await.unwrap();
+        assert!(status.applied_index >= status.commit_index);
+
+        stop_send.send(true).unwrap();
+        remove_dir_all(data_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn bootstrap_restart_test() {
+        init_test_conf();
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let term = {
+            let (mut machine, raft_message_send, stop_send, _peer_recv, _) =
+                build_machine(&data_path, RaftGroupMetadata::new());
+            assert!(machine.bootstrap);
+            let handle = tokio::spawn(async move {
+                machine.run().await.unwrap();
+            });
+            let apply = RaftMachineApply::new(raft_message_send);
+            let status = wait_for_leader(&apply).await;
+            stop_send.send(true).unwrap();
+            handle.await.unwrap();
+            status.term
+        };
+        assert!(term >= 1);
+
+        // restarting without bootstrap recovers the persisted state
+        let (mut machine, _raft_send, _stop_send, _peer_recv, _) =
+            build_machine(&data_path, RaftGroupMetadata::new());
+        machine.bootstrap = false;
+        let raft_node = machine.new_node().await.unwrap();
+        assert!(raft_node.raft.term >= term);
+        assert_eq!(raft_node.raft.prs().conf().to_conf_state().voters, vec![1]);
+        drop(raft_node);
+        drop(machine);
+
+        // a fresh node without bootstrap waits to be added instead of forming a cluster
+        let fresh_path = format!("/tmp/robustmq_{}", unique_id());
+        let (mut machine, _raft_send, _stop_send, _peer_recv, _) =
+            build_machine(&fresh_path, RaftGroupMetadata::new());
+        machine.bootstrap = false;
+        let raft_node = machine.new_node().await.unwrap();
+        assert_eq!(raft_node.raft.term, 0);
+        assert!(raft_node
+            .raft
+            .prs()
+            .conf()
+            .to_conf_state()
+            .voters
+            .is_empty());
+
+        remove_dir_all(data_path).await.unwrap();
+        remove_dir_all(fresh_path).await.unwrap();
+    }
+    // Serve the placement gRPC service of a running node on a free local port
+    async fn serve_placement_service(
+        apply: Arc<RaftMachineApply>,
+        raft_storage: Arc<RwLock<RaftMachineStorage>>,
+        engine: Arc<RocksDBEngine>,
+    ) -> String {
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let service = GrpcPlacementService::new(
+            apply,
+            Arc::new(RwLock::new(RaftGroupMetadata::new())),
+            Arc::new(PlacementCacheManager::new(engine.clone())),
+            engine,
+            Arc::new(ClientPool::new(10)),
+            raft_storage,
+            Arc::new(PeerClients::new()),
+        );
+        tokio::spawn(async move {
+            Server::builder()
+                .add_service(PlacementCenterServiceServer::new(service))
+                .serve(addr)
+                .await
+                .unwrap();
+        });
+        for _ in 0..100 {
+            if PlacementCenterServiceClient::connect(format!("http://{}", addr))
+                .await
+                .is_ok()
+            {
+                break;
+            }
+            sleep(Duration::from_millis(50)).await;
+        }
+        return addr.to_string();
+    }
+
+    #[tokio::test]
+    async fn discover_leader_test() {
+        init_test_conf();
+        let data_path_1 = format!("/tmp/robustmq_{}", unique_id());
+        let data_path_2 = format!("/tmp/robustmq_{}", unique_id());
+
+        // node 2 already leads a Raft group of its own
+        let (apply_2, stop_send_2, _peer_recv_2, raft_storage_2, engine_2) =
+            start_cluster_node(2, &[2], &data_path_2);
+        let status = wait_for_leader(&apply_2).await;
+        let addr_2 = serve_placement_service(apply_2.clone(), raft_storage_2, engine_2).await;
+
+        // a fresh node 1 listing node 2 finds the leader instead of bootstrapping
+        let mut metadata = cluster_metadata(1, &[1, 2]);
+        metadata.peers.get_mut(&2).unwrap().node_inner_addr = addr_2;
+        let (machine, _raft_send, _stop_send, _peer_recv, _) = build_machine(&data_path_1, metadata);
+        assert!(machine.bootstrap);
+        let raft_node = machine.new_node().await.unwrap();
+        assert!(raft_node
+            .raft
+            .prs()
+            .conf()
+            .to_conf_state()
+            .voters
+            .is_empty());
+        let leader = machine.placement_cluster.read().unwrap().leader.clone().unwrap();
+        assert_eq!(leader.node_id, 2);
+
+        // without a reachable peer it forms the group on its own
+        let fresh_path = format!("/tmp/robustmq_{}", unique_id());
+        let (machine, _raft_send, _stop_send, _peer_recv, _) =
+            build_machine(&fresh_path, cluster_metadata(1, &[1, 3]));
+        let raft_node = machine.new_node().await.unwrap();
+        let mut voters = raft_node.raft.prs().conf().to_conf_state().voters;
+        voters.sort();
+        assert_eq!(voters, vec![1, 3]);
+        assert!(status.term >= 1);
+
+        drop(raft_node);
+        drop(machine);
+        stop_send_2.send(true).unwrap();
+        remove_dir_all(data_path_1).await.unwrap();
+        remove_dir_all(data_path_2).await.unwrap();
+        remove_dir_all(fresh_path).await.unwrap();
+    }
+}
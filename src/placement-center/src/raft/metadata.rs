@@ -45,7 +45,7 @@ impl RaftGroupMetadata {
         let mut local = BrokerNode::default();
         local.cluster_type = ClusterType::PlacementCenter.as_str_name().to_string();
         local.cluster_name = config.cluster_name.clone();
-        local.node_inner_addr = format!("{}:{}", config.node.addr.clone(), config.network.grpc_port);
+        local.node_inner_addr = config.local_addr();
         local.node_ip = config.node.addr.clone();
         local.node_id = config.node.node_id;
 
@@ -27,9 +27,16 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
+pub mod applier;
+pub mod codec;
 pub mod apply;
+pub mod election;
+pub mod lease;
+pub mod logger;
 pub mod machine;
 pub mod metadata;
 pub mod peer;
 pub mod route;
-pub mod storage;
\ No newline at end of file
+pub mod snapshot;
+pub mod storage;
+pub mod tail;
\ No newline at end of file
@@ -12,31 +12,229 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use clients::{placement::placement::call::send_raft_message, poll::ClientPool};
-use log::{debug, error, info};
+use super::codec::decode_message;
+use common_base::config::default_placement_center::default_dns_cache_ttl_ms;
+use common_base::error::common::CommonError;
+use common_base::tools::now_mills;
+use dashmap::DashMap;
+use log::{debug, error, info, warn};
+use protocol::placement_center::generate::placement::placement_center_service_client::PlacementCenterServiceClient;
 use protocol::placement_center::generate::placement::SendRaftMessageRequest;
+use raft::eraftpb::Message as raftPreludeMessage;
+use std::net::ToSocketAddrs;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
+use tonic::transport::{Channel, Endpoint};
 
 #[derive(Debug, Clone)]
 pub struct PeerMessage {
+    pub node_id: u64,
     pub to: String,
     pub data: Vec<u8>,
 }
 
+impl PeerMessage {
+    // Type and target of the Raft message for the transport logs
+    pub fn describe(&self) -> String {
+        let msg_type = match decode_message::<raftPreludeMessage>("Message", self.data.as_ref()) {
+            Ok(message) => format!("{:?}", message.get_msg_type()),
+            Err(_) => "undecodable message".to_string(),
+        };
+        return format!("{} to node {} at {}", msg_type, self.node_id, self.to);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerHealth {
+    pub node_id: u64,
+    // Time of the last successful send in milliseconds, 0 if the peer was never reached
+    pub last_contact_ms: u64,
+    pub consecutive_failures: u32,
+}
+
+impl PeerHealth {
+    pub fn unreachable(&self) -> bool {
+        return self.consecutive_failures >= PEER_UNREACHABLE_FAILURES;
+    }
+}
+
+// Resolves the configured host:port of a peer to the ip:port that is connected to
+pub trait PeerResolver: Send + Sync {
+    fn resolve(&self, addr: &str) -> Result<String, CommonError>;
+}
+
+pub struct DnsResolver;
+
+impl PeerResolver for DnsResolver {
+    // A blocking lookup, it only happens when a channel to the peer is created
+    fn resolve(&self, addr: &str) -> Result<String, CommonError> {
+        match addr.to_socket_addrs()?.next() {
+            Some(socket_addr) => return Ok(socket_addr.to_string()),
+            None => {
+                return Err(CommonError::CommmonError(format!(
+                    "{} does not resolve to any address",
+                    addr
+                )))
+            }
+        }
+    }
+}
+
+// A peer is reported unreachable after this many failed sends in a row
+pub const PEER_UNREACHABLE_FAILURES: u32 = 3;
+
+// gRPC clients of the other Raft nodes, keyed by node id. A tonic Channel multiplexes
+// requests over a single HTTP/2 connection, so one channel per peer is enough.
+pub struct PeerClients {
+    clients: DashMap<u64, PlacementCenterServiceClient<Channel>>,
+    channel_num: AtomicUsize,
+    health: DashMap<u64, PeerHealth>,
+    resolver: Arc<dyn PeerResolver>,
+    dns_cache_ttl: Duration,
+    // configured address => resolved address and when it was resolved
+    dns_cache: DashMap<String, (String, Instant)>,
+    // node id => configured and resolved address of its channel
+    client_addrs: DashMap<u64, (String, String)>,
+}
+
+impl PeerClients {
+    pub fn new() -> Self {
+        return PeerClients::new_with_resolver(
+            Arc::new(DnsResolver),
+            Duration::from_millis(default_dns_cache_ttl_ms()),
+        );
+    }
+
+    pub fn new_with_resolver(resolver: Arc<dyn PeerResolver>, dns_cache_ttl: Duration) -> Self {
+        return PeerClients {
+            clients: DashMap::with_capacity(8),
+            channel_num: AtomicUsize::new(0),
+            health: DashMap::with_capacity(8),
+            resolver,
+            dns_cache_ttl,
+            dns_cache: DashMap::with_capacity(8),
+            client_addrs: DashMap::with_capacity(8),
+        };
+    }
+
+    pub fn get(
+        &self,
+        node_id: u64,
+        addr: &String,
+    ) -> Result<PlacementCenterServiceClient<Channel>, CommonError> {
+        if let Some(client) = self.clients.get(&node_id) {
+            return Ok(client.clone());
+        }
+        let resolved = self.resolve(addr);
+        let channel = Endpoint::from_shared(format!("http://{}", resolved))?.connect_lazy();
+        let client = PlacementCenterServiceClient::new(channel);
+        self.clients.insert(node_id, client.clone());
+        self.client_addrs.insert(node_id, (addr.clone(), resolved));
+        self.channel_num.fetch_add(1, Ordering::Relaxed);
+        return Ok(client);
+    }
+
+    // The resolved address of addr, from the cache while it is not older than the TTL.
+    // An address that fails to resolve is connected to as it is.
+    fn resolve(&self, addr: &String) -> String {
+        if let Some(entry) = self.dns_cache.get(addr) {
+            let (resolved, resolved_at) = entry.value();
+            if resolved_at.elapsed() < self.dns_cache_ttl {
+                return resolved.clone();
+            }
+        }
+        match self.resolver.resolve(addr) {
+            Ok(resolved) => {
+                self.dns_cache
+                    .insert(addr.clone(), (resolved.clone(), Instant::now()));
+                return resolved;
+            }
+            Err(e) => {
+                warn!("Failed to resolve {}, connect to it as it is: {}", addr, e);
+                return addr.clone();
+            }
+        }
+    }
+
+    /// The address the channel to node_id connects to, after resolution
+    pub fn client_addr(&self, node_id: u64) -> Option<String> {
+        return self
+            .client_addrs
+            .get(&node_id)
+            .map(|entry| entry.value().1.clone());
+    }
+
+    // Drop the cached channel so that the next call reconnects. Its address is
+    // resolved again, the peer may have moved to another IP.
+    pub fn remove(&self, node_id: u64) {
+        self.clients.remove(&node_id);
+        if let Some((_, (addr, _))) = self.client_addrs.remove(&node_id) {
+            self.dns_cache.remove(&addr);
+        }
+    }
+
+    // The node left the Raft group, forget its channel and health.
+    pub fn remove_peer(&self, node_id: u64) {
+        self.remove(node_id);
+        self.health.remove(&node_id);
+    }
+
+    pub fn record_success(&self, node_id: u64) {
+        let mut health = self.health.entry(node_id).or_insert_with(|| PeerHealth {
+            node_id,
+            last_contact_ms: 0,
+            consecutive_failures: 0,
+        });
+        health.last_contact_ms = now_mills() as u64;
+        health.consecutive_failures = 0;
+    }
+
+    pub fn record_failure(&self, node_id: u64) {
+        let mut health = self.health.entry(node_id).or_insert_with(|| PeerHealth {
+            node_id,
+            last_contact_ms: 0,
+            consecutive_failures: 0,
+        });
+        health.consecutive_failures += 1;
+        if health.consecutive_failures == PEER_UNREACHABLE_FAILURES {
+            warn!(
+                "Raft node {} is unreachable after {} failed sends",
+                node_id, health.consecutive_failures
+            );
+        }
+    }
+
+    /// Health of every peer a message was sent to, ordered by node id
+    pub fn peer_health(&self) -> Vec<PeerHealth> {
+        let mut results: Vec<PeerHealth> = self
+            .health
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect();
+        results.sort_by_key(|health| health.node_id);
+        return results;
+    }
+
+    pub fn channel_num(&self) -> usize {
+        self.channel_num.load(Ordering::Relaxed)
+    }
+}
+
 pub struct PeersManager {
     peer_message_recv: mpsc::Receiver<PeerMessage>,
-    client_poll: Arc<ClientPool>,
+    peer_clients: Arc<PeerClients>,
 }
 
 impl PeersManager {
     pub fn new(
         peer_message_recv: mpsc::Receiver<PeerMessage>,
-        client_poll: Arc<ClientPool>,
+        peer_clients: Arc<PeerClients>,
     ) -> PeersManager {
         let pm = PeersManager {
             peer_message_recv,
-            client_poll,
+            peer_clients,
         };
         return pm;
     }
@@ -48,18 +246,191 @@ impl PeersManager {
         );
         loop {
             if let Some(data) = self.peer_message_recv.recv().await {
-                let addr = data.to;
+                let mut client = match self.peer_clients.get(data.node_id, &data.to) {
+                    Ok(client) => client,
+                    Err(e) => {
+                        self.peer_clients.record_failure(data.node_id);
+                        error!(
+                            "Failed to create client for Raft {}, error message: {}",
+                            data.describe(),
+                            e.to_string()
+                        );
+                        continue;
+                    }
+                };
+                let description = data.describe();
                 let request = SendRaftMessageRequest { message: data.data };
-                match send_raft_message(self.client_poll.clone(), vec![addr.clone()], request).await
-                {
-                    Ok(_) => debug!("Send Raft message to node {} Successful.", addr),
-                    Err(e) => error!(
-                        "Failed to send data to {}, error message: {}",
-                        addr,
-                        e.to_string()
-                    ),
+                match client.send_raft_message(request).await {
+                    Ok(_) => {
+                        self.peer_clients.record_success(data.node_id);
+                        debug!("Sent Raft {}", description);
+                    }
+                    Err(e) => {
+                        self.peer_clients.record_failure(data.node_id);
+                        self.peer_clients.remove(data.node_id);
+                        error!(
+                            "Failed to send Raft {}, error message: {}",
+                            description,
+                            e.to_string()
+                        );
+                    }
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        PeerClients, PeerMessage, PeerResolver, PeersManager, PEER_UNREACHABLE_FAILURES,
+    };
+    use crate::raft::codec::encode_message;
+    use common_base::error::common::CommonError;
+    use raft::eraftpb::{Message as raftPreludeMessage, MessageType};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+    use tokio::time::sleep;
+
+    #[test]
+    fn describe_message_test() {
+        let mut message = raftPreludeMessage::default();
+        message.set_msg_type(MessageType::MsgHeartbeat);
+        message.to = 2;
+        let data = PeerMessage {
+            node_id: 2,
+            to: "127.0.0.1:1230".to_string(),
+            data: encode_message(&message),
+        };
+        assert_eq!(data.describe(), "MsgHeartbeat to node 2 at 127.0.0.1:1230");
+
+        let data = PeerMessage {
+            node_id: 3,
+            to: "127.0.0.1:1231".to_string(),
+            data: vec![0xff, 0xff, 0xff],
+        };
+        assert_eq!(
+            data.describe(),
+            "undecodable message to node 3 at 127.0.0.1:1231"
+        );
+    }
+
+    // Resolves every address to 10.0.0.{n}, n is the number of lookups so far
+    struct MovingResolver {
+        lookups: AtomicUsize,
+    }
+
+    impl PeerResolver for MovingResolver {
+        fn resolve(&self, addr: &str) -> Result<String, CommonError> {
+            let n = self.lookups.fetch_add(1, Ordering::Relaxed) + 1;
+            let port = addr.rsplit(':').next().unwrap();
+            return Ok(format!("10.0.0.{}:{}", n, port));
+        }
+    }
+
+    #[tokio::test]
+    async fn peer_dns_resolve_test() {
+        let resolver = Arc::new(MovingResolver {
+            lookups: AtomicUsize::new(0),
+        });
+        let peer_clients =
+            PeerClients::new_with_resolver(resolver.clone(), Duration::from_secs(60));
+        let addr = "placement-center-2:1228".to_string();
+        peer_clients.get(2, &addr).unwrap();
+        assert_eq!(peer_clients.client_addr(2), Some("10.0.0.1:1228".to_string()));
+
+        // the cached resolution is used while it is fresh
+        peer_clients.clients.remove(&2);
+        peer_clients.get(2, &addr).unwrap();
+        assert_eq!(peer_clients.client_addr(2), Some("10.0.0.1:1228".to_string()));
+        assert_eq!(resolver.lookups.load(Ordering::Relaxed), 1);
+
+        // a failed connection resolves the address again
+        peer_clients.remove(2);
+        peer_clients.get(2, &addr).unwrap();
+        assert_eq!(peer_clients.client_addr(2), Some("10.0.0.2:1228".to_string()));
+
+        // without a TTL every new connection resolves the address
+        let peer_clients = PeerClients::new_with_resolver(resolver.clone(), Duration::ZERO);
+        peer_clients.get(3, &addr).unwrap();
+        peer_clients.clients.remove(&3);
+        peer_clients.get(3, &addr).unwrap();
+        assert_eq!(peer_clients.client_addr(3), Some("10.0.0.4:1228".to_string()));
+    }
+
+    #[tokio::test]
+    async fn peer_channel_reuse_test() {
+        let peer_clients = PeerClients::new();
+        let addr = "127.0.0.1:2228".to_string();
+        for _ in 0..100 {
+            let client = peer_clients.get(2, &addr);
+            assert!(client.is_ok());
+        }
+        assert_eq!(peer_clients.channel_num(), 1);
+
+        peer_clients.remove(2);
+        peer_clients.get(2, &addr).unwrap();
+        assert_eq!(peer_clients.channel_num(), 2);
+    }
+
+    #[test]
+    fn peer_health_test() {
+        let peer_clients = PeerClients::new();
+        assert!(peer_clients.peer_health().is_empty());
+
+        peer_clients.record_success(2);
+        for _ in 0..PEER_UNREACHABLE_FAILURES - 1 {
+            peer_clients.record_failure(2);
+        }
+        let health = peer_clients.peer_health();
+        assert_eq!(health.len(), 1);
+        assert!(health[0].last_contact_ms > 0);
+        assert!(!health[0].unreachable());
+
+        peer_clients.record_failure(2);
+        assert!(peer_clients.peer_health()[0].unreachable());
+
+        // one successful send makes the peer reachable again
+        peer_clients.record_success(2);
+        assert_eq!(peer_clients.peer_health()[0].consecutive_failures, 0);
+
+        peer_clients.remove_peer(2);
+        assert!(peer_clients.peer_health().is_empty());
+    }
+
+    #[tokio::test]
+    async fn peer_unreachable_test() {
+        let peer_clients = Arc::new(PeerClients::new());
+        let (peer_message_send, peer_message_recv) = mpsc::channel::<PeerMessage>(100);
+        let mut peers_manager = PeersManager::new(peer_message_recv, peer_clients.clone());
+        tokio::spawn(async move {
+            peers_manager.start().await;
+        });
+
+        // nothing listens on this port, every send fails
+        for _ in 0..PEER_UNREACHABLE_FAILURES {
+            peer_message_send
+                .send(PeerMessage {
+                    node_id: 2,
+                    to: "127.0.0.1:1".to_string(),
+                    data: Vec::new(),
+                })
+                .await
+                .unwrap();
+        }
+
+        let mut unreachable = false;
+        for _ in 0..100 {
+            let health = peer_clients.peer_health();
+            if health.len() == 1 && health[0].unreachable() {
+                unreachable = true;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert!(unreachable);
+        assert_eq!(peer_clients.peer_health()[0].last_contact_ms, 0);
+    }
+}
@@ -17,12 +17,13 @@ use crate::{
     storage::{
         mqtt::{acl::AclStorage, blacklist::MQTTBlackListStorage},
         placement::{
-            cluster::ClusterStorage, config::ResourceConfigStorage, idempotent::IdempotentStorage,
-            node::NodeStorage,
+            clock::ClusterClockStorage, cluster::ClusterStorage, config::ResourceConfigStorage,
+            idempotent::IdempotentStorage, node::NodeStorage,
         },
         rocksdb::RocksDBEngine,
     },
 };
+use bincode::deserialize;
 use common_base::{
     error::common::CommonError,
     tools::{now_mills, now_second, unique_id},
@@ -58,7 +59,9 @@ impl DataRouteCluster {
         };
     }
 
-    pub fn register_node(&self, value: Vec<u8>) -> Result<(), CommonError> {
+    // `propose_time` is when the leader proposed the registration, which counts as the
+    // first heartbeat of the node
+    pub fn register_node(&self, value: Vec<u8>, propose_time: u64) -> Result<(), CommonError> {
         let req: RegisterNodeRequest = RegisterNodeRequest::decode(value.as_ref())?;
         let cluster_type = req.cluster_type();
         let cluster_name = req.cluster_name;
@@ -89,7 +92,8 @@ impl DataRouteCluster {
 
         // update node
         self.cluster_cache.add_node(node.clone());
-        return node_storage.save(&node);
+        node_storage.save(&node)?;
+        return node_storage.heartbeat(&cluster_name, node.node_id, heartbeat_time(propose_time));
     }
 
     pub fn unregister_node(&self, value: Vec<u8>) -> Result<(), CommonError> {
@@ -102,10 +106,31 @@ impl DataRouteCluster {
         return node_storage.delete(&cluster_name, node_id);
     }
 
-    pub fn heartbeat(&self, value: Vec<u8>) -> Result<(), CommonError> {
+    pub fn heartbeat(&self, value: Vec<u8>, propose_time: u64) -> Result<(), CommonError> {
         let req = HeartbeatRequest::decode(value.as_ref())?;
+        let time = heartbeat_time(propose_time);
         self.cluster_cache
-            .heart_time(&req.cluster_name, req.node_id, now_second());
+            .heart_time(&req.cluster_name, req.node_id, time);
+        let node_storage = NodeStorage::new(self.rocksdb_engine_handler.clone());
+        return node_storage.heartbeat(&req.cluster_name, req.node_id, time);
+    }
+
+    // Every entry carries the time it was proposed at on the leader, applying it moves the
+    // cluster clock. Entries written before the time was recorded leave it as is.
+    pub fn advance_clock(&self, propose_time: u64) -> Result<(), CommonError> {
+        if propose_time == 0 {
+            return Ok(());
+        }
+        let clock_storage = ClusterClockStorage::new(self.rocksdb_engine_handler.clone());
+        clock_storage.advance(propose_time)?;
+        return Ok(());
+    }
+
+    pub fn clock_tick(&self, value: Vec<u8>) -> Result<(), CommonError> {
+        let tick = deserialize::<u64>(value.as_ref())
+            .map_err(|e| CommonError::CommmonError(e.to_string()))?;
+        let clock_storage = ClusterClockStorage::new(self.rocksdb_engine_handler.clone());
+        clock_storage.advance(tick)?;
         return Ok(());
     }
 
@@ -165,6 +190,14 @@ impl DataRouteCluster {
     }
 }
 
+// The entries written before the leader recorded the propose time only have the local clock
+fn heartbeat_time(propose_time: u64) -> u64 {
+    if propose_time == 0 {
+        return now_second();
+    }
+    return propose_time;
+}
+
 #[cfg(test)]
 mod tests {
     use std::{fs::remove_dir_all, sync::Arc};
@@ -178,10 +211,14 @@ mod tests {
             rocksdb::{column_family_list, RocksDBEngine},
         },
     };
-    use common_base::{config::placement_center::PlacementCenterConfig, tools::unique_id};
+    use common_base::{
+        config::placement_center::PlacementCenterConfig,
+        tools::{now_second, unique_id},
+    };
     use prost::Message as _;
     use protocol::placement_center::generate::{
-        common::ClusterType, placement::RegisterNodeRequest,
+        common::ClusterType,
+        placement::{HeartbeatRequest, RegisterNodeRequest, UnRegisterNodeRequest},
     };
 
     #[test]
@@ -209,7 +246,7 @@ mod tests {
         let cluster_cache = Arc::new(PlacementCacheManager::new(rocksdb_engine.clone()));
 
         let route = DataRouteCluster::new(rocksdb_engine.clone(), cluster_cache);
-        let _ = route.register_node(data);
+        let _ = route.register_node(data, now_second());
 
         let node_storage = NodeStorage::new(rocksdb_engine.clone());
         let cluster_storage = ClusterStorage::new(rocksdb_engine.clone());
@@ -243,4 +280,70 @@ mod tests {
 
         remove_dir_all(config.rocksdb.data_path).unwrap();
     }
+
+    #[test]
+    fn node_heartbeat_stale() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+        config.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine = Arc::new(RocksDBEngine::new(
+            &config.rocksdb.data_path,
+            config.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let cluster_cache = Arc::new(PlacementCacheManager::new(rocksdb_engine.clone()));
+        let route = DataRouteCluster::new(rocksdb_engine.clone(), cluster_cache);
+        let node_storage = NodeStorage::new(rocksdb_engine.clone());
+
+        let cluster_name = "test-cluster".to_string();
+        for node_id in 1..=2 {
+            let mut req = RegisterNodeRequest::default();
+            req.node_id = node_id;
+            req.node_ip = "127.0.0.1".to_string();
+            req.cluster_type = ClusterType::MqttBrokerServer.into();
+            req.cluster_name = cluster_name.clone();
+            req.extend_info = "{}".to_string();
+            route
+                .register_node(RegisterNodeRequest::encode_to_vec(&req), now_second())
+                .unwrap();
+        }
+        assert_eq!(node_storage.list(Some(cluster_name.clone())).unwrap().len(), 2);
+        assert!(node_storage.last_heartbeat(&cluster_name, 1).unwrap().is_some());
+        assert!(node_storage.stale_nodes(&cluster_name, 30).unwrap().is_empty());
+
+        // the heartbeat keeps the time the leader proposed it at, a minute ago
+        let mut req = HeartbeatRequest::default();
+        req.cluster_name = cluster_name.clone();
+        req.node_id = 2;
+        let proposed = now_second() - 60;
+        route
+            .heartbeat(HeartbeatRequest::encode_to_vec(&req), proposed)
+            .unwrap();
+        assert_eq!(
+            node_storage.last_heartbeat(&cluster_name, 2).unwrap(),
+            Some(proposed)
+        );
+        let stale = node_storage.stale_nodes(&cluster_name, 30).unwrap();
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].node_id, 2);
+
+        // a newer heartbeat refreshes the last seen time
+        route
+            .heartbeat(HeartbeatRequest::encode_to_vec(&req), now_second())
+            .unwrap();
+        assert!(node_storage.stale_nodes(&cluster_name, 30).unwrap().is_empty());
+
+        // removing the node also drops its heartbeat
+        let mut req = UnRegisterNodeRequest::default();
+        req.cluster_name = cluster_name.clone();
+        req.node_id = 2;
+        route
+            .unregister_node(UnRegisterNodeRequest::encode_to_vec(&req))
+            .unwrap();
+        assert_eq!(node_storage.list(Some(cluster_name.clone())).unwrap().len(), 1);
+        assert!(node_storage.last_heartbeat(&cluster_name, 2).unwrap().is_none());
+
+        remove_dir_all(config.rocksdb.data_path).unwrap();
+    }
 }
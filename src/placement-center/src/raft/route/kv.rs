@@ -15,7 +15,7 @@
 use crate::storage::{placement::kv::KvStorage, rocksdb::RocksDBEngine};
 use common_base::error::common::CommonError;
 use prost::Message as _;
-use protocol::placement_center::generate::kv::{DeleteRequest, SetRequest};
+use protocol::placement_center::generate::kv::{CompareAndSwapRequest, DeleteRequest, SetRequest};
 use std::sync::Arc;
 pub struct DataRouteKv {
     kv_storage: KvStorage,
@@ -37,4 +37,9 @@ impl DataRouteKv {
         let req: DeleteRequest = DeleteRequest::decode(value.as_ref())?;
         return self.kv_storage.delete(req.key);
     }
+
+    pub fn compare_and_swap(&self, value: Vec<u8>) -> Result<(), CommonError> {
+        let req: CompareAndSwapRequest = CompareAndSwapRequest::decode(value.as_ref())?;
+        return self.kv_storage.compare_and_swap(req.cf, req.key, req.expected, req.new);
+    }
 }
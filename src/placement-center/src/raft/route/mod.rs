@@ -27,7 +27,6 @@ use crate::{
     cache::{journal::JournalCacheManager, placement::PlacementCacheManager},
     storage::rocksdb::RocksDBEngine,
 };
-use bincode::deserialize;
 use common_base::error::common::CommonError;
 use std::sync::Arc;
 
@@ -62,17 +61,40 @@ impl DataRoute {
     }
 
     //Receive write operations performed by the Raft state machine and write subsequent service data after Raft state machine synchronization is complete.
-    pub fn route(&self, data: Vec<u8>) -> Result<(), CommonError> {
-        let storage_data: StorageData = deserialize(data.as_ref()).unwrap();
+    //Returns the type of the data that was applied.
+    pub fn route(&self, data: Vec<u8>) -> Result<StorageDataType, CommonError> {
+        let storage_data = match StorageData::decode(data.as_ref()) {
+            Ok(data) => data,
+            Err(e) => return Err(CommonError::CommmonError(e.to_string())),
+        };
+        return self.route_storage_data(storage_data);
+    }
+
+    pub fn route_storage_data(
+        &self,
+        storage_data: StorageData,
+    ) -> Result<StorageDataType, CommonError> {
+        let data_type = storage_data.data_type.clone();
+        let propose_time = storage_data.propose_time;
+        self.route_data(storage_data)?;
+        self.route_cluster.advance_clock(propose_time)?;
+        return Ok(data_type);
+    }
+
+    fn route_data(&self, storage_data: StorageData) -> Result<(), CommonError> {
         match storage_data.data_type {
             StorageDataType::ClusterRegisterNode => {
-                return self.route_cluster.register_node(storage_data.value);
+                return self
+                    .route_cluster
+                    .register_node(storage_data.value, storage_data.propose_time);
             }
             StorageDataType::ClusterUngisterNode => {
                 return self.route_cluster.unregister_node(storage_data.value);
             }
             StorageDataType::ClusterNodeHeartbeat => {
-                return self.route_cluster.heartbeat(storage_data.value);
+                return self
+                    .route_cluster
+                    .heartbeat(storage_data.value, storage_data.propose_time);
             }
 
             StorageDataType::ClusterSetResourceConfig => {
@@ -91,6 +113,9 @@ impl DataRoute {
                     .route_cluster
                     .delete_idempotent_data(storage_data.value);
             }
+            StorageDataType::ClusterClockTick => {
+                return self.route_cluster.clock_tick(storage_data.value);
+            }
             StorageDataType::MQTTCreateAcl => {
                 return self.route_cluster.create_acl(storage_data.value);
             }
@@ -122,6 +147,9 @@ impl DataRoute {
             StorageDataType::KvDelete => {
                 return self.route_kv.delete(storage_data.value);
             }
+            StorageDataType::KvCompareAndSwap => {
+                return self.route_kv.compare_and_swap(storage_data.value);
+            }
             StorageDataType::MQTTCreateUser => {
                 return self.route_mqtt.create_user(storage_data.value);
             }
@@ -0,0 +1,405 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use common_base::error::common::CommonError;
+use common_base::error::placement_center::PlacementCenterError;
+use log::error;
+use protocol::placement_center::generate::placement::placement_center_service_client::PlacementCenterServiceClient;
+use protocol::placement_center::generate::placement::{
+    InstallSnapshotReply, InstallSnapshotRequest,
+};
+use raft::prelude::SnapshotMetadata;
+use tonic::transport::Channel;
+
+// A transfer interrupted by a broken stream is resumed from the received offset this many times
+const SNAPSHOT_FETCH_RETRIES: u32 = 3;
+
+/// Split the snapshot data into chunks of `chunk_size` bytes, starting at `offset`.
+/// An empty snapshot still yields one empty chunk so that the receiver learns the total size.
+pub fn snapshot_chunks(
+    data: Vec<u8>,
+    term: u64,
+    index: u64,
+    offset: u64,
+    chunk_size: u64,
+) -> impl Iterator<Item = InstallSnapshotReply> {
+    let total_size = data.len() as u64;
+    let chunk_size = chunk_size.max(1);
+    let start = offset.min(total_size);
+    let mut offsets: Vec<u64> = (start..total_size).step_by(chunk_size as usize).collect();
+    if offsets.is_empty() {
+        offsets.push(start);
+    }
+
+    return offsets.into_iter().map(move |offset| {
+        let end = (offset + chunk_size).min(total_size);
+        InstallSnapshotReply {
+            term,
+            index,
+            offset,
+            total_size,
+            data: data[offset as usize..end as usize].to_vec(),
+        }
+    });
+}
+
+/// Reassemble the chunks of one snapshot on the receiving node.
+pub struct SnapshotAssembler {
+    term: u64,
+    index: u64,
+    total_size: Option<u64>,
+    data: Vec<u8>,
+}
+
+impl SnapshotAssembler {
+    pub fn new(term: u64, index: u64) -> Self {
+        return SnapshotAssembler {
+            term,
+            index,
+            total_size: None,
+            data: Vec::new(),
+        };
+    }
+
+    // The offset to resume the transfer from
+    pub fn offset(&self) -> u64 {
+        return self.data.len() as u64;
+    }
+
+    pub fn push(&mut self, chunk: InstallSnapshotReply) -> Result<(), PlacementCenterError> {
+        if chunk.term != self.term || chunk.index != self.index {
+            return Err(PlacementCenterError::SnapshotChunkMismatch(
+                chunk.term,
+                chunk.index,
+            ));
+        }
+
+        let received = self.offset();
+        if chunk.offset > received {
+            return Err(PlacementCenterError::SnapshotChunkOutOfOrder(
+                received,
+                chunk.offset,
+            ));
+        }
+
+        // A resumed transfer may resend bytes we already have, keep only the new part
+        let skip = (received - chunk.offset) as usize;
+        if skip < chunk.data.len() {
+            self.data.extend_from_slice(&chunk.data[skip..]);
+        }
+        self.total_size = Some(chunk.total_size);
+        return Ok(());
+    }
+
+    pub fn is_complete(&self) -> bool {
+        match self.total_size {
+            Some(total_size) => return self.offset() >= total_size,
+            None => return false,
+        }
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        return self.data;
+    }
+}
+
+/// Pull the data of the snapshot described by `metadata` from the node that sent it.
+pub async fn fetch_snapshot_data(
+    mut client: PlacementCenterServiceClient<Channel>,
+    local_node_id: u64,
+    from_node_id: u64,
+    metadata: &SnapshotMetadata,
+) -> Result<Vec<u8>, CommonError> {
+    let mut assembler = SnapshotAssembler::new(metadata.term, metadata.index);
+    for _ in 0..SNAPSHOT_FETCH_RETRIES {
+        let request = InstallSnapshotRequest {
+            node_id: local_node_id,
+            term: metadata.term,
+            index: metadata.index,
+            offset: assembler.offset(),
+        };
+        match client.install_snapshot(request).await {
+            Ok(response) => {
+                let mut stream = response.into_inner();
+                loop {
+                    match stream.message().await {
+                        Ok(Some(chunk)) => assembler.push(chunk)?,
+                        Ok(None) => break,
+                        Err(e) => {
+                            error!(
+                                "Snapshot stream from node {} broke at offset {}, error message: {}",
+                                from_node_id,
+                                assembler.offset(),
+                                e
+                            );
+                            break;
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                error!(
+                    "Failed to request snapshot from node {}, error message: {}",
+                    from_node_id, e
+                );
+            }
+        }
+
+        if assembler.is_complete() {
+            return Ok(assembler.into_data());
+        }
+    }
+    return Err(PlacementCenterError::SnapshotTransferFailed(metadata.index, from_node_id).into());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fetch_snapshot_data, snapshot_chunks, SnapshotAssembler};
+    use crate::cache::placement::PlacementCacheManager;
+    use crate::raft::apply::{RaftMachineApply, RaftMessage, RaftResponseMesage};
+    use crate::raft::codec::encode_message;
+    use crate::raft::metadata::RaftGroupMetadata;
+    use crate::raft::peer::PeerClients;
+    use crate::server::grpc::service_placement::GrpcPlacementService;
+    use crate::storage::placement::raft::RaftMachineStorage;
+    use crate::storage::rocksdb::{column_family_list, RocksDBEngine};
+    use clients::poll::ClientPool;
+    use common_base::config::default_placement_center::default_raft;
+    use common_base::config::placement_center::{
+        init_placement_center_conf_by_config, PlacementCenterConfig,
+    };
+    use common_base::error::placement_center::PlacementCenterError;
+    use common_base::tools::unique_id;
+    use metadata_struct::placement::broker_node::BrokerNode;
+    use protocol::placement_center::generate::placement::placement_center_service_client::PlacementCenterServiceClient;
+    use protocol::placement_center::generate::placement::placement_center_service_server::PlacementCenterServiceServer;
+    use protocol::placement_center::generate::placement::SendRaftMessageRequest;
+    use raft::eraftpb::{Entry, HardState, Message as raftPreludeMessage, MessageType};
+    use std::fs::remove_dir_all;
+    use std::net::TcpListener;
+    use std::sync::{Arc, RwLock};
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+    use tokio::time::{sleep, timeout};
+    use tonic::transport::Server;
+
+    fn new_storage(data_path: &String) -> RaftMachineStorage {
+        let rocksdb_engine_handler = Arc::new(RocksDBEngine::new(
+            data_path,
+            10,
+            column_family_list(),
+        ));
+        return RaftMachineStorage::new(rocksdb_engine_handler);
+    }
+
+    #[test]
+    fn snapshot_chunks_test() {
+        let data: Vec<u8> = (0..250).map(|i| i as u8).collect();
+        let chunks: Vec<_> = snapshot_chunks(data.clone(), 2, 10, 0, 100).collect();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].offset, 0);
+        assert_eq!(chunks[1].offset, 100);
+        assert_eq!(chunks[2].offset, 200);
+        assert_eq!(chunks[2].data.len(), 50);
+        assert!(chunks.iter().all(|c| c.total_size == 250));
+
+        // resume from the middle of a chunk
+        let chunks: Vec<_> = snapshot_chunks(data.clone(), 2, 10, 150, 100).collect();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].data, data[150..].to_vec());
+
+        let chunks: Vec<_> = snapshot_chunks(Vec::new(), 2, 10, 0, 100).collect();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].total_size, 0);
+    }
+
+    #[test]
+    fn snapshot_assembler_test() {
+        let data: Vec<u8> = (0..250).map(|i| i as u8).collect();
+        let chunks: Vec<_> = snapshot_chunks(data.clone(), 2, 10, 0, 100).collect();
+
+        let mut assembler = SnapshotAssembler::new(2, 10);
+        match assembler.push(chunks[1].clone()) {
+            Err(PlacementCenterError::SnapshotChunkOutOfOrder(expected, got)) => {
+                assert_eq!(expected, 0);
+                assert_eq!(got, 100);
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        let mut other = chunks[0].clone();
+        other.index = 9;
+        assert!(assembler.push(other).is_err());
+
+        assembler.push(chunks[0].clone()).unwrap();
+        // a duplicated chunk after a resumed transfer is ignored
+        assembler.push(chunks[0].clone()).unwrap();
+        assert_eq!(assembler.offset(), 100);
+        assert!(!assembler.is_complete());
+
+        for chunk in snapshot_chunks(data.clone(), 2, 10, assembler.offset(), 100) {
+            assembler.push(chunk).unwrap();
+        }
+        assert!(assembler.is_complete());
+        assert_eq!(assembler.into_data(), data);
+    }
+
+    fn init_test_conf() {
+        let mut config = PlacementCenterConfig::default();
+        config.raft = default_raft();
+        config.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        config.log.log_path = format!("/tmp/robustmq_{}/logs", unique_id());
+        init_placement_center_conf_by_config(config);
+    }
+
+    // Serve the placement service of a node on a free local port, returns its address
+    async fn serve_node(
+        raft_machine_apply: Arc<RaftMachineApply>,
+        raft_metadata: RaftGroupMetadata,
+        raft_storage: Arc<RwLock<RaftMachineStorage>>,
+    ) -> String {
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let engine = raft_storage.read().unwrap().rocksdb_engine_handler.clone();
+        let service = GrpcPlacementService::new(
+            raft_machine_apply,
+            Arc::new(RwLock::new(raft_metadata)),
+            Arc::new(PlacementCacheManager::new(engine.clone())),
+            engine,
+            Arc::new(ClientPool::new(10)),
+            raft_storage,
+            Arc::new(PeerClients::new()),
+        );
+        tokio::spawn(async move {
+            Server::builder()
+                .add_service(PlacementCenterServiceServer::new(service))
+                .serve(addr)
+                .await
+                .unwrap();
+        });
+        for _ in 0..100 {
+            if PlacementCenterServiceClient::connect(format!("http://{}", addr))
+                .await
+                .is_ok()
+            {
+                break;
+            }
+            sleep(Duration::from_millis(50)).await;
+        }
+        return addr.to_string();
+    }
+
+    #[tokio::test]
+    async fn snapshot_transfer_test() {
+        init_test_conf();
+        let leader_path = format!("/tmp/robustmq_{}", unique_id());
+        let follower_path = format!("/tmp/robustmq_{}", unique_id());
+
+        let mut leader = new_storage(&leader_path);
+        let mut entries = Vec::new();
+        for idx in 1..=10 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            entry.data = vec![idx as u8; 64];
+            entries.push(entry);
+        }
+        leader.append(&entries).unwrap();
+        let mut hs = HardState::default();
+        hs.term = 1;
+        hs.commit = 10;
+        leader.save_hard_state(hs).unwrap();
+        leader.save_applied_index(10).unwrap();
+        leader.create_snapshot();
+        let snapshot = leader.saved_snapshot().unwrap();
+        let metadata = snapshot.get_metadata().clone();
+
+        // node 1 serves the snapshot, its own Raft machine is not needed for that
+        let (leader_send, _leader_recv) = mpsc::channel(10);
+        let leader_addr = serve_node(
+            Arc::new(RaftMachineApply::new(leader_send)),
+            RaftGroupMetadata::default(),
+            Arc::new(RwLock::new(leader)),
+        )
+        .await;
+
+        // node 2 knows node 1 and hands the messages it receives to its Raft machine
+        let mut follower_metadata = RaftGroupMetadata::default();
+        follower_metadata.local.node_id = 2;
+        let mut leader_node = BrokerNode::default();
+        leader_node.node_id = 1;
+        leader_node.node_inner_addr = leader_addr.clone();
+        follower_metadata.add_peer(1, leader_node);
+        let (follower_send, mut follower_recv) = mpsc::channel(10);
+        let follower = Arc::new(RwLock::new(new_storage(&follower_path)));
+        let follower_addr = serve_node(
+            Arc::new(RaftMachineApply::new(follower_send)),
+            follower_metadata,
+            follower.clone(),
+        )
+        .await;
+
+        // the snapshot data is left out of the message, the follower pulls it from node 1
+        let fetched = fetch_snapshot_data(
+            PlacementCenterServiceClient::connect(format!("http://{}", leader_addr))
+                .await
+                .unwrap(),
+            2,
+            1,
+            &metadata,
+        )
+        .await
+        .unwrap();
+        assert_eq!(fetched, snapshot.data);
+
+        let mut message = raftPreludeMessage::default();
+        message.set_msg_type(MessageType::MsgSnapshot);
+        message.from = 1;
+        message.to = 2;
+        message.mut_snapshot().set_metadata(metadata.clone());
+        let mut client = PlacementCenterServiceClient::connect(format!("http://{}", follower_addr))
+            .await
+            .unwrap();
+        let request = SendRaftMessageRequest {
+            message: encode_message(&message),
+        };
+        // acknowledged before the data is fetched, the leader does not wait for the transfer
+        client.send_raft_message(request).await.unwrap();
+
+        let received = match timeout(Duration::from_secs(5), follower_recv.recv())
+            .await
+            .unwrap()
+            .unwrap()
+        {
+            RaftMessage::Raft { mut message, chan } => {
+                assert!(chan.send(RaftResponseMesage::Success).is_ok());
+                message.take_snapshot()
+            }
+            _ => panic!("the snapshot was not handed to the Raft machine"),
+        };
+        assert_eq!(received.get_metadata(), &metadata);
+        assert_eq!(received.data, snapshot.data);
+
+        // the follower installs the transferred snapshot
+        let mut follower = follower.write().unwrap();
+        follower.apply_snapshot(received).unwrap();
+        assert_eq!(follower.hard_state().unwrap().commit, metadata.index);
+        for entry in entries {
+            assert_eq!(follower.entry_by_idx(entry.index), Some(entry));
+        }
+        drop(follower);
+
+        remove_dir_all(leader_path).unwrap();
+        remove_dir_all(follower_path).unwrap();
+    }
+}
@@ -56,9 +56,7 @@ impl RaftRocksDBStorage {
         ConfState: From<T>,
     {
         assert!(!self.initial_state().unwrap().initialized());
-        let _ = self
-            .write_lock()
-            .save_conf_state(ConfState::from(conf_state));
+        let _ = self.write_lock().bootstrap(ConfState::from(conf_state));
     }
 
     pub fn read_lock(&self) -> RwLockReadGuard<'_, RaftMachineStorage> {
@@ -73,44 +71,50 @@ impl RaftRocksDBStorage {
 impl RaftRocksDBStorage {
     pub fn apply_snapshot(&mut self, snapshot: Snapshot) -> RaftResult<()> {
         let mut store = self.core.write().unwrap();
-        let _ = store.apply_snapshot(snapshot);
-        Ok(())
+        return store.apply_snapshot(snapshot);
     }
 
     pub fn append(&mut self, entrys: &Vec<Entry>) -> RaftResult<()> {
         let mut store = self.core.write().unwrap();
-        let _ = store.append(entrys);
-        return Ok(());
+        return store.append(entrys);
     }
 
     pub fn commmit_index(&mut self, idx: u64) -> RaftResult<()> {
         let mut store = self.core.write().unwrap();
-        let _ = store.commmit_index(idx);
-        return Ok(());
+        return store.commmit_index(idx);
+    }
+
+    pub fn commit_entries(&mut self, indexes: &[u64]) -> RaftResult<()> {
+        let mut store = self.core.write().unwrap();
+        return store
+            .commit_entries(indexes)
+            .map_err(|e| Error::Store(StorageError::Other(e.into())));
     }
 
     pub fn set_hard_state(&mut self, hs: HardState) -> RaftResult<()> {
         let store = self.core.write().unwrap();
-        let _ = store.save_hard_state(hs.clone());
-        return Ok(());
+        return store
+            .save_hard_state(hs)
+            .map_err(|e| Error::Store(StorageError::Other(e.into())));
     }
 
-    pub fn set_hard_state_comit(&mut self, hs: u64) -> RaftResult<()> {
+    pub fn set_hard_state_commit(&mut self, commit: u64) -> RaftResult<()> {
         let store = self.core.write().unwrap();
-        let _ = store.set_hard_state_commit(hs);
-        return Ok(());
+        return store
+            .set_hard_state_commit(commit)
+            .map_err(|e| Error::Store(StorageError::Other(e.into())));
     }
 
     pub fn set_conf_state(&mut self, cs: ConfState) -> RaftResult<()> {
         let store = self.core.write().unwrap();
-        let _ = store.save_conf_state(cs);
-        return Ok(());
+        return store
+            .save_conf_state(cs)
+            .map_err(|e| Error::Store(StorageError::Other(e.into())));
     }
 
-    pub fn create_snapshot(&mut self) -> RaftResult<()> {
+    pub fn create_snapshot(&mut self) -> Option<u64> {
         let mut store = self.core.write().unwrap();
-        let _ = store.create_snapshot();
-        return Ok(());
+        return store.create_snapshot();
     }
 }
 
@@ -122,7 +126,7 @@ impl RaftStorage for RaftRocksDBStorage {
     /// created with a configuration, and its last index and term should be greater than 0.
     fn initial_state(&self) -> RaftResult<RaftState> {
         let core = self.read_lock();
-        return Ok(core.raft_state());
+        return core.raft_state();
     }
 
     /// Returns a slice of log entries in the range `[low, high)`.
@@ -143,34 +147,11 @@ impl RaftStorage for RaftRocksDBStorage {
         &self,
         low: u64,
         high: u64,
-        _: impl Into<Option<u64>>,
+        max_size: impl Into<Option<u64>>,
         _: raft::GetEntriesContext,
     ) -> RaftResult<Vec<Entry>> {
         let core = self.read_lock();
-        if low < core.first_index() {
-            return Err(Error::Store(StorageError::Compacted));
-        }
-
-        if high > core.last_index() + 1 {
-            panic!(
-                "index out of bound (last: {}, high: {})",
-                core.last_index() + 1,
-                high
-            )
-        }
-
-        let mut entry_list: Vec<Entry> = Vec::new();
-        for idx in low..=high {
-            let sret = core.entry_by_idx(idx);
-            if sret == None {
-                continue;
-            }
-            entry_list.push(sret.unwrap());
-        }
-
-        // todo limit size
-
-        return Ok(entry_list);
+        return core.entries(low, high, max_size.into());
     }
 
     /// Returns the term of entry idx, which must be in the range
@@ -179,24 +160,7 @@ impl RaftStorage for RaftRocksDBStorage {
     /// rest of that entry may not be available.
     fn term(&self, idx: u64) -> RaftResult<u64> {
         let core = self.read_lock();
-
-        if idx == core.snapshot_metadata.index {
-            return Ok(core.snapshot_metadata.index);
-        }
-
-        if idx < core.first_index() {
-            return Err(Error::Store(StorageError::Compacted));
-        }
-
-        if idx > core.last_index() {
-            return Err(Error::Store(StorageError::Unavailable));
-        }
-
-        if let Some(value) = core.entry_by_idx(idx) {
-            return Ok(value.term);
-        }
-
-        return Ok(core.snapshot_metadata.term);
+        return core.term(idx);
     }
 
     /// Returns the index of the first log entry that is possible available via entries, which will
@@ -226,15 +190,279 @@ impl RaftStorage for RaftRocksDBStorage {
     /// `to` indicates which peer is requesting the snapshot.
     fn snapshot(&self, request_index: u64, to: u64) -> RaftResult<Snapshot> {
         info!("Node {} requests snapshot data", to);
-        let mut core = self.write_lock();
+        let core = self.read_lock();
         if core.trigger_snap_unavailable {
             return Err(Error::Store(StorageError::SnapshotTemporarilyUnavailable));
         } else {
-            let mut snap = core.snapshot();
+            let snap = core.snapshot();
+            // nothing has been applied yet, there is no snapshot to send
+            if snap.get_metadata().index == 0 {
+                return Err(Error::Store(StorageError::SnapshotTemporarilyUnavailable));
+            }
+            // the data only covers the entries up to the index of the snapshot, a follower
+            // installing it must not believe it holds the entries up to request_index. The
+            // applier takes a newer snapshot once it has applied enough entries.
             if snap.get_metadata().index < request_index {
-                snap.mut_metadata().index = request_index;
+                return Err(Error::Store(StorageError::SnapshotTemporarilyUnavailable));
             }
             Ok(snap)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::RaftRocksDBStorage;
+    use crate::storage::placement::raft::RaftMachineStorage;
+    use crate::storage::rocksdb::{column_family_list, RocksDBEngine};
+    use common_base::tools::unique_id;
+    use raft::eraftpb::{Entry, HardState, Message, MessageType};
+    use raft::{Config, Error, GetEntriesContext, RawNode, StateRole, Storage, StorageError};
+    use std::fs::remove_dir_all;
+    use std::sync::{Arc, RwLock};
+
+    // Persist and apply everything the node has ready, as RaftMachine::on_ready does
+    fn drive(node: &mut RawNode<RaftRocksDBStorage>, committed: &mut Vec<Entry>) {
+        while node.has_ready() {
+            let mut ready = node.ready();
+            if !ready.entries().is_empty() {
+                node.mut_store().append(ready.entries()).unwrap();
+            }
+            if let Some(hs) = ready.hs() {
+                node.mut_store().set_hard_state(hs.clone()).unwrap();
+            }
+            committed.extend(ready.take_committed_entries());
+
+            let mut light_rd = node.advance(ready);
+            if let Some(commit) = light_rd.commit_index() {
+                node.mut_store().set_hard_state_commit(commit).unwrap();
+            }
+            committed.extend(light_rd.take_committed_entries());
+            node.advance_apply();
+        }
+    }
+
+    #[test]
+    fn set_hard_state_test() {
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let rocksdb_engine_handler = Arc::new(RocksDBEngine::new(
+            &data_path,
+            10,
+            column_family_list(),
+        ));
+        let mut storage = RaftRocksDBStorage::new(Arc::new(RwLock::new(
+            RaftMachineStorage::new(rocksdb_engine_handler),
+        )));
+
+        let mut hs = HardState::default();
+        hs.term = 2;
+        hs.vote = 1;
+        storage.set_hard_state(hs.clone()).unwrap();
+        assert_eq!(storage.initial_state().unwrap().hard_state, hs);
+
+        storage.set_hard_state_commit(7).unwrap();
+        let hs = storage.initial_state().unwrap().hard_state;
+        assert_eq!(hs.term, 2);
+        assert_eq!(hs.vote, 1);
+        assert_eq!(hs.commit, 7);
+
+        remove_dir_all(data_path).unwrap();
+    }
+
+    #[test]
+    fn raw_node_propose_commit_test() {
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let rocksdb_engine_handler = Arc::new(RocksDBEngine::new(
+            &data_path,
+            10,
+            column_family_list(),
+        ));
+        let storage = RaftRocksDBStorage::new_with_conf_state(
+            Arc::new(RwLock::new(RaftMachineStorage::new(rocksdb_engine_handler))),
+            (vec![1], vec![]),
+        );
+        let conf = Config {
+            id: 1,
+            ..Default::default()
+        };
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let mut node = RawNode::new(&conf, storage, &logger).unwrap();
+
+        let mut committed = Vec::new();
+        node.campaign().unwrap();
+        drive(&mut node, &mut committed);
+        assert_eq!(node.raft.state, StateRole::Leader);
+
+        node.propose(vec![], b"value".to_vec()).unwrap();
+        drive(&mut node, &mut committed);
+
+        // the empty entry of the new leader, then the proposal
+        assert_eq!(committed.len(), 2);
+        assert_eq!(committed[1].index, 2);
+        assert_eq!(committed[1].data, b"value".to_vec());
+        let store = node.store();
+        assert_eq!(store.initial_state().unwrap().hard_state.commit, 2);
+        assert_eq!(store.first_index().unwrap(), 1);
+        assert_eq!(store.last_index().unwrap(), 2);
+        assert_eq!(store.term(0).unwrap(), 0);
+        assert_eq!(store.term(2).unwrap(), 1);
+        assert_eq!(
+            store.term(3).unwrap_err(),
+            Error::Store(StorageError::Unavailable)
+        );
+
+        // [low, high) and at least one entry whatever max_size is
+        let entries = store
+            .entries(1, 3, None, GetEntriesContext::empty(false))
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+        let entries = store
+            .entries(1, 3, Some(0), GetEntriesContext::empty(false))
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].index, 1);
+
+        // the snapshot compacts the log, the term before first_index is still known
+        node.mut_store().commit_entries(&[1, 2]).unwrap();
+        assert_eq!(node.mut_store().create_snapshot(), Some(2));
+        let snapshot = node.store().snapshot(0, 1).unwrap();
+        assert_eq!(snapshot.get_metadata().index, 2);
+        assert_eq!(snapshot.get_metadata().term, 1);
+        assert_eq!(
+            node.store().snapshot(3, 1).unwrap_err(),
+            Error::Store(StorageError::SnapshotTemporarilyUnavailable)
+        );
+        let store = node.store();
+        assert_eq!(store.first_index().unwrap(), 2);
+        assert_eq!(store.term(2).unwrap(), 1);
+        assert_eq!(
+            store.term(1).unwrap_err(),
+            Error::Store(StorageError::Compacted)
+        );
+        assert_eq!(
+            store
+                .entries(1, 3, None, GetEntriesContext::empty(false))
+                .unwrap_err(),
+            Error::Store(StorageError::Compacted)
+        );
+
+        remove_dir_all(data_path).unwrap();
+    }
+
+    // Step a vote request from the candidate and return whether the node granted it
+    fn request_vote(node: &mut RawNode<RaftRocksDBStorage>, candidate: u64, term: u64) -> bool {
+        let mut msg = Message::default();
+        msg.set_msg_type(MessageType::MsgRequestVote);
+        msg.from = candidate;
+        msg.to = 1;
+        msg.term = term;
+        node.step(msg).unwrap();
+
+        let mut ready = node.ready();
+        let mut msgs = ready.take_messages();
+        msgs.extend(ready.take_persisted_messages());
+        node.advance(ready);
+        let resp = msgs
+            .into_iter()
+            .find(|m| m.get_msg_type() == MessageType::MsgRequestVoteResponse && m.to == candidate)
+            .unwrap();
+        return !resp.reject;
+    }
+
+    #[test]
+    fn persisted_vote_test() {
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        {
+            let rocksdb_engine_handler = Arc::new(RocksDBEngine::new(
+                &data_path,
+                10,
+                column_family_list(),
+            ));
+            let mut storage = RaftRocksDBStorage::new_with_conf_state(
+                Arc::new(RwLock::new(RaftMachineStorage::new(rocksdb_engine_handler))),
+                (vec![1, 2, 3], vec![]),
+            );
+            let mut hs = HardState::default();
+            hs.term = 5;
+            hs.vote = 2;
+            storage.set_hard_state(hs).unwrap();
+        }
+
+        // The node restarts on the same data path after voting for 2 in term 5.
+        let rocksdb_engine_handler = Arc::new(RocksDBEngine::new(
+            &data_path,
+            10,
+            column_family_list(),
+        ));
+        let storage = RaftRocksDBStorage::new(Arc::new(RwLock::new(RaftMachineStorage::new(
+            rocksdb_engine_handler,
+        ))));
+        let conf = Config {
+            id: 1,
+            ..Default::default()
+        };
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let mut node = RawNode::new(&conf, storage, &logger).unwrap();
+        assert_eq!(node.raft.term, 5);
+        assert_eq!(node.raft.vote, 2);
+
+        // a second vote in the same term goes to the same candidate only
+        assert!(!request_vote(&mut node, 3, 5));
+        assert!(request_vote(&mut node, 2, 5));
+        assert_eq!(node.raft.vote, 2);
+
+        remove_dir_all(data_path).unwrap();
+    }
+
+    #[test]
+    fn initial_state_fresh_and_restarted_test() {
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        {
+            let rocksdb_engine_handler = Arc::new(RocksDBEngine::new(
+                &data_path,
+                10,
+                column_family_list(),
+            ));
+            let storage = RaftRocksDBStorage::new(Arc::new(RwLock::new(
+                RaftMachineStorage::new(rocksdb_engine_handler),
+            )));
+
+            // a fresh node has nothing persisted
+            assert!(!storage.read_lock().is_bootstrapped());
+            let state = storage.initial_state().unwrap();
+            assert!(!state.initialized());
+            assert_eq!(state.hard_state, HardState::default());
+
+            storage.initialize_with_conf_state((vec![1], vec![]));
+            let conf = Config {
+                id: 1,
+                ..Default::default()
+            };
+            let logger = slog::Logger::root(slog::Discard, slog::o!());
+            let mut node = RawNode::new(&conf, storage, &logger).unwrap();
+            let mut committed = Vec::new();
+            node.campaign().unwrap();
+            drive(&mut node, &mut committed);
+            assert_eq!(node.raft.state, StateRole::Leader);
+        }
+
+        // The node restarts on the same data path with the state it persisted as the leader.
+        let rocksdb_engine_handler = Arc::new(RocksDBEngine::new(
+            &data_path,
+            10,
+            column_family_list(),
+        ));
+        let storage = RaftRocksDBStorage::new(Arc::new(RwLock::new(RaftMachineStorage::new(
+            rocksdb_engine_handler,
+        ))));
+        assert!(storage.read_lock().is_bootstrapped());
+        let state = storage.initial_state().unwrap();
+        assert!(state.initialized());
+        assert_eq!(state.conf_state.voters, vec![1]);
+        assert_eq!(state.hard_state.term, 1);
+        assert_eq!(state.hard_state.vote, 1);
+        assert_eq!(state.hard_state.commit, 1);
+
+        remove_dir_all(data_path).unwrap();
+    }
+}
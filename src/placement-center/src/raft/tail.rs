@@ -0,0 +1,188 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::apply::{MetaEvent, StorageData};
+use crate::storage::placement::raft::RaftMachineStorage;
+use common_base::error::placement_center::PlacementCenterError;
+use futures::stream::{self, Stream};
+use log::{debug, error};
+use raft::eraftpb::EntryType;
+use std::collections::VecDeque;
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+struct TailState {
+    raft_storage: Arc<RwLock<RaftMachineStorage>>,
+    events: broadcast::Receiver<MetaEvent>,
+    next_index: u64,
+    pending: VecDeque<(u64, StorageData)>,
+    // Set once the entries to stream have been compacted, the stream then ends
+    compacted: bool,
+}
+
+/// Stream the service data of the committed entries starting at `from_index`. The entries
+/// already applied are read from the Raft log, after that the stream follows the applied
+/// entries. Entries that have been compacted into the snapshot can no longer be replayed,
+/// the stream then ends with RaftEntriesCompacted and the reader has to start over from the
+/// state machine, which holds the state of the snapshot.
+pub fn tail(
+    raft_storage: Arc<RwLock<RaftMachineStorage>>,
+    events: broadcast::Receiver<MetaEvent>,
+    from_index: u64,
+) -> impl Stream<Item = Result<(u64, StorageData), PlacementCenterError>> {
+    let state = TailState {
+        raft_storage,
+        events,
+        next_index: from_index.max(1),
+        pending: VecDeque::new(),
+        compacted: false,
+    };
+    return stream::unfold(state, |mut state| async move {
+        let item = state.next().await?;
+        return Some((item, state));
+    });
+}
+
+impl TailState {
+    async fn next(&mut self) -> Option<Result<(u64, StorageData), PlacementCenterError>> {
+        if self.compacted {
+            return None;
+        }
+        loop {
+            if let Some(item) = self.pending.pop_front() {
+                return Some(Ok(item));
+            }
+
+            let applied_index = self.raft_storage.read().unwrap().applied_index();
+            if self.next_index <= applied_index {
+                match self.read_up_to(applied_index) {
+                    Ok(true) => continue,
+                    Ok(false) => {}
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+
+            // The event is only a wake up, the entries themselves are read from the log
+            match self.events.recv().await {
+                Ok(event) => {
+                    if event.index >= self.next_index {
+                        if let Err(e) = self.read_up_to(event.index) {
+                            return Some(Err(e));
+                        }
+                    }
+                }
+                Err(RecvError::Lagged(n)) => {
+                    debug!("Tail lagged {} events behind, continue from the Raft log", n);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    // Reads the entries from next_index to last_index, returns whether any of them was read.
+    // Fails when the entries from next_index on have been compacted, skipping them would
+    // hide their writes from the reader.
+    fn read_up_to(&mut self, last_index: u64) -> Result<bool, PlacementCenterError> {
+        let raft_storage = self.raft_storage.read().unwrap();
+        let first_index = raft_storage.first_index();
+        if self.next_index < first_index {
+            self.compacted = true;
+            return Err(PlacementCenterError::RaftEntriesCompacted(
+                self.next_index,
+                first_index - 1,
+            ));
+        }
+
+        let start = self.next_index;
+        while self.next_index <= last_index {
+            let entry = match raft_storage.entry_by_idx(self.next_index) {
+                Some(entry) => entry,
+                None => break,
+            };
+            self.next_index += 1;
+
+            if entry.get_entry_type() != EntryType::EntryNormal || entry.get_data().is_empty() {
+                continue;
+            }
+            match StorageData::decode(entry.get_data()) {
+                Ok(data) => self.pending.push_back((entry.get_index(), data)),
+                Err(e) => {
+                    error!(
+                        "Failed to decode the entry at index {} for the tail, error message: {}",
+                        entry.get_index(),
+                        e
+                    );
+                }
+            }
+        }
+        return Ok(self.next_index > start);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tail;
+    use crate::raft::apply::{StorageData, StorageDataType};
+    use crate::storage::placement::raft::RaftMachineStorage;
+    use crate::storage::rocksdb::{column_family_list, RocksDBEngine};
+    use common_base::error::placement_center::PlacementCenterError;
+    use common_base::tools::unique_id;
+    use futures::StreamExt;
+    use raft::eraftpb::Entry;
+    use std::fs::remove_dir_all;
+    use std::sync::{Arc, RwLock};
+    use tokio::sync::broadcast;
+
+    #[tokio::test]
+    async fn tail_compacted_entries_test() {
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let engine = Arc::new(RocksDBEngine::new(&data_path, 10, column_family_list()));
+        let mut raft_storage = RaftMachineStorage::new(engine);
+        let mut entries = Vec::new();
+        for idx in 1..=10 {
+            let data = StorageData::new(StorageDataType::KvSet, vec![idx as u8]);
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            entry.data = data.encode();
+            entries.push(entry);
+        }
+        raft_storage.append(&entries).unwrap();
+        let indexes: Vec<u64> = (1..=10).collect();
+        raft_storage.commit_entries(&indexes).unwrap();
+        raft_storage.compact(6).unwrap();
+        let raft_storage = Arc::new(RwLock::new(raft_storage));
+        let (event_sender, _) = broadcast::channel(16);
+
+        // the entries still in the log are replayed
+        let mut stream = Box::pin(tail(raft_storage.clone(), event_sender.subscribe(), 6));
+        let (index, data) = stream.next().await.unwrap().unwrap();
+        assert_eq!(index, 6);
+        assert_eq!(data.value, vec![6]);
+
+        // the compacted ones end the stream instead of being skipped
+        let mut stream = Box::pin(tail(raft_storage, event_sender.subscribe(), 2));
+        match stream.next().await {
+            Some(Err(PlacementCenterError::RaftEntriesCompacted(from, to))) => {
+                assert_eq!(from, 2);
+                assert_eq!(to, 5);
+            }
+            _ => assert!(false),
+        }
+        assert!(stream.next().await.is_none());
+
+        remove_dir_all(data_path).unwrap();
+    }
+}
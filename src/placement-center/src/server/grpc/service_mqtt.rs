@@ -24,6 +24,7 @@ use crate::{
         rocksdb::RocksDBEngine,
     },
 };
+use log::error;
 use prost::Message;
 use protocol::placement_center::generate::{
     common::CommonReply,
@@ -500,7 +501,7 @@ impl MqttService for GrpcMqttService {
                 return Ok(Response::new(ListBlacklistReply { blacklists }));
             }
             Err(e) => {
-                println!("error:{:?}", e);
+                error!("Failed to list the blacklist, error message: {}", e);
                 return Err(Status::internal(e.to_string()));
             }
         }
@@ -29,26 +29,40 @@
  */
 use crate::cache::placement::PlacementCacheManager;
 use crate::raft::apply::{RaftMachineApply, StorageData, StorageDataType};
+use crate::raft::codec::decode_message;
 use crate::raft::metadata::RaftGroupMetadata;
+use crate::raft::peer::PeerClients;
+use crate::raft::snapshot::{fetch_snapshot_data, snapshot_chunks};
 use crate::storage::placement::config::ResourceConfigStorage;
 use crate::storage::placement::idempotent::IdempotentStorage;
+use crate::storage::placement::raft::RaftMachineStorage;
 use crate::storage::rocksdb::RocksDBEngine;
 use clients::placement::placement::call::{heartbeat, register_node, un_register_node};
 use clients::poll::ClientPool;
+use common_base::config::placement_center::placement_center_conf;
+use common_base::error::common::CommonError;
 use common_base::error::placement_center::PlacementCenterError;
+use futures::stream::{self, BoxStream, StreamExt};
+use log::{error, info};
 use prost::Message;
 use protocol::placement_center::generate::common::CommonReply;
 use protocol::placement_center::generate::placement::placement_center_service_server::PlacementCenterService;
 use protocol::placement_center::generate::placement::{
-    ClusterStatusReply, ClusterStatusRequest, DeleteIdempotentDataRequest,
+    ClusterConfigReply, ClusterConfigRequest, ClusterStatusReply, ClusterStatusRequest,
+    CompactStorageReply, CompactStorageRequest, DeleteIdempotentDataRequest,
     DeleteResourceConfigRequest, ExistsIdempotentDataReply, ExistsIdempotentDataRequest,
-    GetResourceConfigReply, GetResourceConfigRequest, HeartbeatRequest, NodeListReply,
-    NodeListRequest, RegisterNodeRequest, ReportMonitorRequest, SendRaftConfChangeReply,
+    FlushStorageReply, FlushStorageRequest, GetResourceConfigReply, GetResourceConfigRequest,
+    HeartbeatRequest, InstallSnapshotReply, InstallSnapshotRequest, NodeListReply,
+    NodeListRequest, PeerHealth, PeerProgress, RegisterNodeRequest, ReplicationStatusReply,
+    ReplicationStatusRequest, ReportMonitorRequest, SendRaftConfChangeReply,
     SendRaftConfChangeRequest, SendRaftMessageReply, SendRaftMessageRequest,
     SetIdempotentDataRequest, SetResourceConfigRequest, UnRegisterNodeRequest,
 };
-use raft::eraftpb::{ConfChange, Message as raftPreludeMessage};
+use raft::eraftpb::{ConfChange, Message as raftPreludeMessage, MessageType};
+use rocksdb_engine::StorageError;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
+use std::time::Instant;
 use tonic::{Request, Response, Status};
 
 pub struct GrpcPlacementService {
@@ -57,6 +71,10 @@ pub struct GrpcPlacementService {
     cluster_cache: Arc<PlacementCacheManager>,
     rocksdb_engine_handler: Arc<RocksDBEngine>,
     client_poll: Arc<ClientPool>,
+    raft_machine_storage: Arc<RwLock<RaftMachineStorage>>,
+    peer_clients: Arc<PeerClients>,
+    // Set while a FlushStorage or CompactStorage request is running
+    storage_maintenance: AtomicBool,
 }
 
 impl GrpcPlacementService {
@@ -66,6 +84,8 @@ impl GrpcPlacementService {
         cluster_cache: Arc<PlacementCacheManager>,
         rocksdb_engine_handler: Arc<RocksDBEngine>,
         client_poll: Arc<ClientPool>,
+        raft_machine_storage: Arc<RwLock<RaftMachineStorage>>,
+        peer_clients: Arc<PeerClients>,
     ) -> Self {
         GrpcPlacementService {
             placement_center_storage: raft_machine_apply,
@@ -73,6 +93,35 @@ impl GrpcPlacementService {
             cluster_cache,
             rocksdb_engine_handler,
             client_poll,
+            raft_machine_storage,
+            peer_clients,
+            storage_maintenance: AtomicBool::new(false),
+        }
+    }
+
+    // Runs a flush or compaction of RocksDB off the async runtime and returns how long it took
+    // in milliseconds. Only one of them runs at a time, a concurrent request is rejected.
+    async fn run_storage_maintenance<F>(&self, task: F) -> Result<u64, Status>
+    where
+        F: FnOnce(Arc<RocksDBEngine>) -> Result<(), StorageError> + Send + 'static,
+    {
+        if self
+            .storage_maintenance
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(Status::aborted("A flush or compaction of the storage is already running"));
+        }
+
+        let engine = self.rocksdb_engine_handler.clone();
+        let start = Instant::now();
+        let result = tokio::task::spawn_blocking(move || task(engine)).await;
+        self.storage_maintenance.store(false, Ordering::SeqCst);
+
+        match result {
+            Ok(Ok(())) => return Ok(start.elapsed().as_millis() as u64),
+            Ok(Err(e)) => return Err(Status::internal(e.to_string())),
+            Err(e) => return Err(Status::internal(e.to_string())),
         }
     }
 
@@ -81,6 +130,33 @@ impl GrpcPlacementService {
     }
 }
 
+// The snapshot data was left out of the message because it is too large, pull it from the sender
+async fn fill_snapshot_data(
+    raft_metadata: &Arc<RwLock<RaftGroupMetadata>>,
+    peer_clients: &Arc<PeerClients>,
+    message: &mut raftPreludeMessage,
+) -> Result<(), CommonError> {
+    let from = message.get_from();
+    let (local_node_id, addr) = {
+        let metadata = raft_metadata.read().unwrap();
+        match metadata.get_node_by_id(from) {
+            Some(node) => (metadata.local.node_id, node.node_inner_addr.clone()),
+            None => {
+                return Err(CommonError::CommmonError(format!(
+                    "Raft node {} is not found, the snapshot cannot be fetched",
+                    from
+                )))
+            }
+        }
+    };
+
+    let client = peer_clients.get(from, &addr)?;
+    let metadata = message.get_snapshot().get_metadata().clone();
+    let data = fetch_snapshot_data(client, local_node_id, from, &metadata).await?;
+    message.mut_snapshot().data = data;
+    return Ok(());
+}
+
 #[tonic::async_trait]
 impl PlacementCenterService for GrpcPlacementService {
     async fn cluster_status(
@@ -106,9 +182,53 @@ impl PlacementCenterService for GrpcPlacementService {
             }
         }
 
+        match self.placement_center_storage.status().await {
+            Ok(status) => {
+                reply.node_id = status.node_id;
+                reply.role = format!("{:?}", status.role);
+                reply.term = status.term;
+                reply.leader_id = status.leader_id;
+                reply.commit_index = status.commit_index;
+                reply.last_index = status.last_index;
+                reply.applied_index = status.applied_index;
+                reply.peers = status
+                    .peers
+                    .iter()
+                    .map(|peer| PeerHealth {
+                        node_id: peer.node_id,
+                        last_contact_ms: peer.last_contact_ms,
+                        consecutive_failures: peer.consecutive_failures,
+                        unreachable: peer.unreachable(),
+                    })
+                    .collect();
+            }
+            Err(e) => {
+                return Err(Status::cancelled(e.to_string()));
+            }
+        }
+
         return Ok(Response::new(reply));
     }
 
+    async fn cluster_config(
+        &self,
+        _: Request<ClusterConfigRequest>,
+    ) -> Result<Response<ClusterConfigReply>, Status> {
+        match self.placement_center_storage.cluster_config().await {
+            Ok(config) => {
+                return Ok(Response::new(ClusterConfigReply {
+                    voters: config.conf_state.voters,
+                    learners: config.conf_state.learners,
+                    voters_outgoing: config.conf_state.voters_outgoing,
+                    leader_id: config.leader_id,
+                }));
+            }
+            Err(e) => {
+                return Err(Status::cancelled(e.to_string()));
+            }
+        }
+    }
+
     async fn node_list(
         &self,
         request: Request<NodeListRequest>,
@@ -224,8 +344,42 @@ impl PlacementCenterService for GrpcPlacementService {
         &self,
         request: Request<SendRaftMessageRequest>,
     ) -> Result<Response<SendRaftMessageReply>, Status> {
-        let message = raftPreludeMessage::decode(request.into_inner().message.as_ref())
-            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let mut message =
+            decode_message::<raftPreludeMessage>("Message", request.into_inner().message.as_ref())
+                .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        // Fetching the snapshot data takes a while and the leader sends its messages one at a
+        // time, so the message is acknowledged now and stepped once the data is in. A failed
+        // fetch is only logged, the leader sends the snapshot again.
+        if message.get_msg_type() == MessageType::MsgSnapshot
+            && message.get_snapshot().get_data().is_empty()
+            && message.get_snapshot().get_metadata().get_index() > 0
+        {
+            let raft_metadata = self.raft_metadata.clone();
+            let peer_clients = self.peer_clients.clone();
+            let raft_machine_apply = self.placement_center_storage.clone();
+            tokio::spawn(async move {
+                let from = message.get_from();
+                let fetched = fill_snapshot_data(&raft_metadata, &peer_clients, &mut message).await;
+                if let Err(e) = fetched {
+                    error!(
+                        "Failed to fetch the snapshot data from node {}, error message: {}",
+                        from, e
+                    );
+                    return;
+                }
+                if let Err(e) = raft_machine_apply
+                    .apply_raft_message(message, "send_raft_message".to_string())
+                    .await
+                {
+                    error!(
+                        "Failed to step the snapshot from node {}, error message: {}",
+                        from, e
+                    );
+                }
+            });
+            return Ok(Response::new(SendRaftMessageReply::default()));
+        }
 
         match self
             .placement_center_storage
@@ -241,11 +395,44 @@ impl PlacementCenterService for GrpcPlacementService {
         }
     }
 
+    type InstallSnapshotStream = BoxStream<'static, Result<InstallSnapshotReply, Status>>;
+
+    async fn install_snapshot(
+        &self,
+        request: Request<InstallSnapshotRequest>,
+    ) -> Result<Response<Self::InstallSnapshotStream>, Status> {
+        let req = request.into_inner();
+        let snapshot = match self.raft_machine_storage.read().unwrap().saved_snapshot() {
+            Some(snapshot) => snapshot,
+            None => {
+                return Err(Status::failed_precondition(
+                    PlacementCenterError::SnapshotNotAvailable(req.term, req.index).to_string(),
+                ))
+            }
+        };
+
+        let metadata = snapshot.get_metadata();
+        if metadata.get_term() != req.term || metadata.get_index() != req.index {
+            return Err(Status::failed_precondition(
+                PlacementCenterError::SnapshotNotAvailable(req.term, req.index).to_string(),
+            ));
+        }
+
+        let chunks = snapshot_chunks(
+            snapshot.data,
+            req.term,
+            req.index,
+            req.offset,
+            placement_center_conf().raft.snapshot_chunk_size,
+        );
+        return Ok(Response::new(stream::iter(chunks.map(Ok)).boxed()));
+    }
+
     async fn send_raft_conf_change(
         &self,
         request: Request<SendRaftConfChangeRequest>,
     ) -> Result<Response<SendRaftConfChangeReply>, Status> {
-        let change = ConfChange::decode(request.into_inner().message.as_ref())
+        let change = decode_message::<ConfChange>("ConfChange", request.into_inner().message.as_ref())
             .map_err(|e| Status::invalid_argument(e.to_string()))?;
 
         match self
@@ -385,4 +572,74 @@ impl PlacementCenterService for GrpcPlacementService {
             }
         }
     }
+
+    async fn flush_storage(
+        &self,
+        _: Request<FlushStorageRequest>,
+    ) -> Result<Response<FlushStorageReply>, Status> {
+        let duration_ms = self.run_storage_maintenance(|engine| engine.flush()).await?;
+        info!("FlushStorage finished in {}ms", duration_ms);
+        return Ok(Response::new(FlushStorageReply { duration_ms }));
+    }
+
+    async fn compact_storage(
+        &self,
+        request: Request<CompactStorageRequest>,
+    ) -> Result<Response<CompactStorageReply>, Status> {
+        let cf_name = request.into_inner().cf_name;
+        if !cf_name.is_empty() && self.rocksdb_engine_handler.cf_handle(&cf_name).is_none() {
+            return Err(Status::invalid_argument(format!(
+                "Column family {} does not exist",
+                cf_name
+            )));
+        }
+
+        // An empty name compacts all column families
+        let target = cf_name.clone();
+        let duration_ms = self
+            .run_storage_maintenance(move |engine| {
+                if target.is_empty() {
+                    engine.compact_all();
+                    return Ok(());
+                }
+                return engine.compact_cf(&target);
+            })
+            .await?;
+        info!(
+            "CompactStorage of column family '{}' finished in {}ms",
+            cf_name, duration_ms
+        );
+        return Ok(Response::new(CompactStorageReply { duration_ms }));
+    }
+
+    async fn replication_status(
+        &self,
+        _: Request<ReplicationStatusRequest>,
+    ) -> Result<Response<ReplicationStatusReply>, Status> {
+        // only the leader tracks the progress of the other nodes
+        let status = match self.placement_center_storage.replication_status().await {
+            Ok(status) => status,
+            Err(CommonError::PlacementCenterError(e @ PlacementCenterError::NotLeader { .. })) => {
+                return Err(Status::failed_precondition(e.to_string()));
+            }
+            Err(e) => return Err(Status::cancelled(e.to_string())),
+        };
+
+        let peers = status
+            .peers
+            .iter()
+            .map(|peer| PeerProgress {
+                node_id: peer.node_id,
+                matched: peer.matched,
+                next_index: peer.next_index,
+                learner: peer.learner,
+            })
+            .collect();
+        return Ok(Response::new(ReplicationStatusReply {
+            term: status.term,
+            commit_index: status.commit_index,
+            quorum_commit_index: status.quorum_commit_index,
+            peers,
+        }));
+    }
 }
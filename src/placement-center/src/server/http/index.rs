@@ -29,8 +29,12 @@
  */
 use super::server::HttpServerState;
 use axum::extract::State;
-use common_base::{http_response::success_response, metrics::dump_metrics};
+use common_base::{
+    http_response::{error_response, success_response},
+    metrics::dump_metrics,
+};
 use dashmap::DashMap;
+use log::error;
 use metadata_struct::placement::{broker_node::BrokerNode, cluster::ClusterInfo};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -61,8 +65,20 @@ pub struct RaftInfo {
 pub async fn index(State(state): State<HttpServerState>) -> String {
     let storage = state.raft_storage.read().unwrap();
     let placement_cache = state.raft_metadata.read().unwrap();
-    let hs = storage.hard_state();
-    let cs = storage.conf_state();
+    let hs = match storage.hard_state() {
+        Ok(hs) => hs,
+        Err(e) => {
+            error!("Failed to read the HardState, error message: {}", e);
+            return error_response();
+        }
+    };
+    let cs = match storage.conf_state() {
+        Ok(cs) => cs,
+        Err(e) => {
+            error!("Failed to read the ConfState, error message: {}", e);
+            return error_response();
+        }
+    };
     let uncommit_index = storage.uncommit_index();
 
     let raft_info = RaftInfo {
@@ -17,9 +17,38 @@ use super::{
     StorageDataWrap,
 };
 use common_base::error::common::CommonError;
+use rocksdb_engine::BatchOperation;
 use serde::Serialize;
+use std::cell::RefCell;
 use std::sync::Arc;
 
+thread_local! {
+    // Set while collect_writes runs, the saves and deletes of the thread are kept here
+    static COLLECTED_WRITES: RefCell<Option<Vec<BatchOperation>>> = const { RefCell::new(None) };
+}
+
+/// Run `f` with the saves and deletes it makes on this thread collected instead of written,
+/// the caller writes them in one batch together with its own writes.
+pub fn collect_writes<R>(f: impl FnOnce() -> R) -> (R, Vec<BatchOperation>) {
+    let outer = COLLECTED_WRITES.with(|writes| writes.replace(Some(Vec::new())));
+    let result = f();
+    let operations = COLLECTED_WRITES
+        .with(|writes| writes.replace(outer))
+        .unwrap_or_default();
+    return (result, operations);
+}
+
+// Keep the operation when collect_writes runs on this thread, it is handed back otherwise
+fn collect_write(operation: BatchOperation) -> Option<BatchOperation> {
+    return COLLECTED_WRITES.with(|writes| match writes.borrow_mut().as_mut() {
+        Some(operations) => {
+            operations.push(operation);
+            return None;
+        }
+        None => return Some(operation),
+    });
+}
+
 pub fn engine_save_by_cluster<T>(
     rocksdb_engine_handler: Arc<RocksDBEngine>,
     key_name: String,
@@ -36,6 +65,26 @@ where
     );
 }
 
+pub fn engine_save_by_cf<T>(
+    rocksdb_engine_handler: Arc<RocksDBEngine>,
+    cf: &str,
+    key_name: String,
+    value: T,
+) -> Result<(), CommonError>
+where
+    T: Serialize,
+{
+    return engine_save(rocksdb_engine_handler, cf, key_name, value);
+}
+
+pub fn engine_get_by_cf(
+    rocksdb_engine_handler: Arc<RocksDBEngine>,
+    cf: &str,
+    key_name: String,
+) -> Result<Option<StorageDataWrap>, CommonError> {
+    return engine_get(rocksdb_engine_handler, cf, key_name);
+}
+
 pub fn engine_get_by_cluster(
     rocksdb_engine_handler: Arc<RocksDBEngine>,
     key_name: String,
@@ -88,12 +137,17 @@ where
     };
 
     let data = StorageDataWrap::new(content);
-    match rocksdb_engine_handler.write(cf, &key_name, &data) {
+    let value = rocksdb_engine_handler.codec().encode(&data)?;
+    let value = match collect_write(BatchOperation::put(rocksdb_cluster, &key_name, value)) {
+        Some(BatchOperation::Put { value, .. }) => value,
+        _ => return Ok(()),
+    };
+    match rocksdb_engine_handler.write_raw(cf, &key_name, &value) {
         Ok(_) => {
             return Ok(());
         }
         Err(e) => {
-            return Err(CommonError::CommmonError(e));
+            return Err(e.into());
         }
     }
 }
@@ -116,7 +170,7 @@ fn engine_get(
             return Ok(None);
         }
         Err(e) => {
-            return Err(CommonError::CommmonError(e));
+            return Err(e.into());
         }
     }
 }
@@ -132,7 +186,10 @@ fn engine_delete(
         return Err(CommonError::ClusterNoAvailableNode);
     };
 
-    rocksdb_engine_handler.delete(cf, &key_name)
+    if collect_write(BatchOperation::delete(rocksdb_cluster, &key_name)).is_none() {
+        return Ok(());
+    }
+    return Ok(rocksdb_engine_handler.delete(cf, &key_name)?);
 }
 
 fn engine_exists(
@@ -25,12 +25,25 @@ pub fn key_name_by_hard_state() -> String {
     return "/raft/hard_state".to_string();
 }
 
+pub fn key_name_by_applied_index() -> String {
+    return "/raft/applied_index".to_string();
+}
+
 pub fn key_name_by_conf_state() -> String {
     return "/raft/conf_state".to_string();
 }
 
+// Zero padded, so that the keys sort in the order of the indexes
 pub fn key_name_by_entry(idx: u64) -> String {
-    return format!("/raft/entry/{}", idx);
+    return format!("/raft/entry/{:020}", idx);
+}
+
+pub fn key_name_entry_prefix() -> String {
+    return "/raft/entry/".to_string();
+}
+
+pub fn key_name_storage_format() -> String {
+    return "/raft/storage_format".to_string();
 }
 
 pub fn key_name_uncommit() -> String {
@@ -41,6 +54,27 @@ pub fn key_name_snapshot() -> String {
     return "/raft/snapshot".to_string();
 }
 
+pub fn key_name_snapshot_metadata() -> String {
+    return "/raft/snapshot_metadata".to_string();
+}
+
+// Index of the snapshot persisted but not yet restored into the state machine
+pub fn key_name_snapshot_pending() -> String {
+    return "/raft/snapshot_pending".to_string();
+}
+
+pub fn key_name_raft_prefix() -> String {
+    return "/raft/".to_string();
+}
+
+pub fn key_name_client_request(client_id: &str) -> String {
+    return format!("/raft/client_request/{}", client_id);
+}
+
+pub fn key_name_client_request_prefix() -> String {
+    return "/raft/client_request/".to_string();
+}
+
 /** ===========Cluster========== */
 pub fn key_cluster(cluster_type: &String, cluster_name: &String) -> String {
     return format!("/clusters/{}/{}", cluster_type, cluster_name);
@@ -66,6 +100,10 @@ pub fn key_node_prefix_all() -> String {
     return format!("/clusters/node/");
 }
 
+pub fn key_node_heartbeat(cluster_name: &String, node_id: u64) -> String {
+    return format!("/heartbeat/node/{}/{}", cluster_name, node_id);
+}
+
 pub fn key_resource_config(cluster_name: String, resource_key: String) -> String {
     return format!("/config/{}/{}", cluster_name, resource_key);
 }
@@ -74,6 +112,10 @@ pub fn key_resource_idempotent(cluster_name: &String, produce_id: &String, seq_n
     return format!("/idempotent/{}/{}/{}", cluster_name, produce_id, seq_num);
 }
 
+pub fn key_cluster_clock() -> String {
+    return "/clock/cluster".to_string();
+}
+
 /** ===========Journal========== */
 pub fn key_shard(cluster_name: &String, shard_name: &String) -> String {
     return format!("/journal/shard/{}/{}", cluster_name, shard_name);
@@ -118,6 +160,10 @@ pub fn storage_key_mqtt_topic_cluster_prefix(cluster_name: &String) -> String {
     return format!("/mqtt/topic/{}", cluster_name);
 }
 
+pub fn storage_key_mqtt_topic_name_prefix(cluster_name: &String, topic_prefix: &String) -> String {
+    return format!("/mqtt/topic/{}/{}", cluster_name, topic_prefix);
+}
+
 pub fn storage_key_mqtt_session(cluster_name: &String, client_id: &String) -> String {
     return format!("/mqtt/session/{}/{}", cluster_name, client_id);
 }
@@ -36,8 +36,11 @@ pub mod keys;
 pub mod mqtt;
 pub mod placement;
 pub mod rocksdb;
+pub mod self_check;
 mod engine;
 
+pub use engine::{collect_writes, engine_get_by_cf};
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct StorageDataWrap {
     pub data: Vec<u8>,
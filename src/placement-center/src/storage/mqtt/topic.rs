@@ -17,7 +17,10 @@ use crate::storage::{
         engine_delete_by_cluster, engine_get_by_cluster, engine_prefix_list_by_cluster,
         engine_save_by_cluster,
     },
-    keys::{storage_key_mqtt_topic, storage_key_mqtt_topic_cluster_prefix},
+    keys::{
+        storage_key_mqtt_topic, storage_key_mqtt_topic_cluster_prefix,
+        storage_key_mqtt_topic_name_prefix,
+    },
     rocksdb::RocksDBEngine,
 };
 use common_base::error::{common::CommonError, mqtt_broker::MQTTBrokerError};
@@ -47,6 +50,20 @@ impl MQTTTopicStorage {
 
     pub fn list(&self, cluster_name: &String) -> Result<Vec<MQTTTopic>, CommonError> {
         let prefix_key = storage_key_mqtt_topic_cluster_prefix(&cluster_name);
+        return self.list_by_key_prefix(prefix_key);
+    }
+
+    /// List the topics of the cluster whose name starts with topic_prefix, ordered by topic name
+    pub fn list_by_prefix(
+        &self,
+        cluster_name: &String,
+        topic_prefix: &String,
+    ) -> Result<Vec<MQTTTopic>, CommonError> {
+        let prefix_key = storage_key_mqtt_topic_name_prefix(cluster_name, topic_prefix);
+        return self.list_by_key_prefix(prefix_key);
+    }
+
+    fn list_by_key_prefix(&self, prefix_key: String) -> Result<Vec<MQTTTopic>, CommonError> {
         match engine_prefix_list_by_cluster(self.rocksdb_engine_handler.clone(), prefix_key) {
             Ok(data) => {
                 let mut results = Vec::new();
@@ -185,4 +202,48 @@ mod tests {
 
         remove_dir_all(config.rocksdb.data_path).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn topic_list_by_prefix_test() {
+        let mut config = PlacementCenterConfig::default();
+        config.rocksdb.data_path = format!("/tmp/{}", unique_id());
+        config.rocksdb.max_open_files = Some(10);
+
+        let rs = Arc::new(RocksDBEngine::new(
+            &config.rocksdb.data_path.as_str(),
+            config.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let topic_storage = MQTTTopicStorage::new(rs);
+        let cluster_name = "test_cluster".to_string();
+        for topic_name in ["sensor/2", "device/1", "sensor/1"] {
+            let topic = MQTTTopic {
+                topic_id: unique_id(),
+                topic_name: topic_name.to_string(),
+                retain_message: None,
+                retain_message_expired_at: None,
+            };
+            topic_storage
+                .save(&cluster_name, &topic_name.to_string(), topic)
+                .unwrap();
+        }
+
+        let res = topic_storage
+            .list_by_prefix(&cluster_name, &"sensor/".to_string())
+            .unwrap();
+        let names: Vec<String> = res.iter().map(|topic| topic.topic_name.clone()).collect();
+        assert_eq!(names, vec!["sensor/1".to_string(), "sensor/2".to_string()]);
+
+        topic_storage
+            .delete(&cluster_name, &"sensor/1".to_string())
+            .unwrap();
+        let res = topic_storage
+            .list_by_prefix(&cluster_name, &"sensor/".to_string())
+            .unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].topic_name, "sensor/2".to_string());
+        assert_eq!(topic_storage.list(&cluster_name).unwrap().len(), 2);
+
+        remove_dir_all(config.rocksdb.data_path).await.unwrap();
+    }
 }
@@ -0,0 +1,81 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::storage::{
+    engine::{engine_get_by_cluster, engine_save_by_cluster},
+    keys::key_cluster_clock,
+    rocksdb::RocksDBEngine,
+};
+use common_base::error::common::CommonError;
+use std::sync::Arc;
+
+// The cluster clock in seconds. It only moves with the propose time of the applied entries,
+// so every node reads the same time after applying the same entries, whatever its own
+// wall clock says.
+pub struct ClusterClockStorage {
+    rocksdb_engine_handler: Arc<RocksDBEngine>,
+}
+
+impl ClusterClockStorage {
+    pub fn new(rocksdb_engine_handler: Arc<RocksDBEngine>) -> Self {
+        ClusterClockStorage {
+            rocksdb_engine_handler,
+        }
+    }
+
+    /// Time of the cluster clock, 0 until the first entry stamped by a leader has been applied
+    pub fn now(&self) -> Result<u64, CommonError> {
+        match engine_get_by_cluster(self.rocksdb_engine_handler.clone(), key_cluster_clock())? {
+            Some(data) => return Ok(serde_json::from_slice::<u64>(&data.data)?),
+            None => return Ok(0),
+        }
+    }
+
+    /// Move the clock to tick and return its time. The clock never goes back, a tick
+    /// behind it, as stamped by a new leader whose wall clock is late, leaves it as is.
+    pub fn advance(&self, tick: u64) -> Result<u64, CommonError> {
+        let now = self.now()?;
+        if tick <= now {
+            return Ok(now);
+        }
+        engine_save_by_cluster(self.rocksdb_engine_handler.clone(), key_cluster_clock(), tick)?;
+        return Ok(tick);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClusterClockStorage;
+    use crate::storage::rocksdb::{column_family_list, RocksDBEngine};
+    use common_base::tools::unique_id;
+    use std::{fs::remove_dir_all, sync::Arc};
+
+    #[test]
+    fn advance_test() {
+        let data_path = format!("/tmp/{}", unique_id());
+        let rocksdb_engine_handler =
+            Arc::new(RocksDBEngine::new(&data_path, 10, column_family_list()));
+        let clock_storage = ClusterClockStorage::new(rocksdb_engine_handler);
+
+        assert_eq!(clock_storage.now().unwrap(), 0);
+
+        assert_eq!(clock_storage.advance(100).unwrap(), 100);
+        assert_eq!(clock_storage.advance(50).unwrap(), 100);
+        assert_eq!(clock_storage.now().unwrap(), 100);
+        assert_eq!(clock_storage.advance(101).unwrap(), 101);
+
+        remove_dir_all(data_path).unwrap();
+    }
+}
@@ -14,12 +14,12 @@
 
 use crate::storage::{
     engine::{
-        engine_delete_by_cluster, engine_exists_by_cluster, engine_get_by_cluster,
-        engine_save_by_cluster,
+        engine_delete_by_cluster, engine_exists_by_cluster, engine_get_by_cf,
+        engine_get_by_cluster, engine_save_by_cf, engine_save_by_cluster,
     },
     rocksdb::RocksDBEngine,
 };
-use common_base::error::common::CommonError;
+use common_base::error::{common::CommonError, placement_center::PlacementCenterError};
 use std::sync::Arc;
 
 pub struct KvStorage {
@@ -58,6 +58,24 @@ impl KvStorage {
         }
     }
 
+    pub fn compare_and_swap(
+        &self,
+        cf: String,
+        key: String,
+        expected: Option<String>,
+        new: String,
+    ) -> Result<(), CommonError> {
+        let data = engine_get_by_cf(self.rocksdb_engine_handler.clone(), &cf, key.clone())?;
+        let current = match data {
+            Some(data) => Some(serde_json::from_slice::<String>(&data.data)?),
+            None => None,
+        };
+        if current != expected {
+            return Err(PlacementCenterError::CompareAndSwapMismatch(key).into());
+        }
+        return engine_save_by_cf(self.rocksdb_engine_handler.clone(), &cf, key, new);
+    }
+
     pub fn exists(&self, key: String) -> Result<bool, CommonError> {
         return engine_exists_by_cluster(self.rocksdb_engine_handler.clone(), key);
     }
@@ -17,10 +17,11 @@ use crate::storage::{
         engine_delete_by_cluster, engine_get_by_cluster, engine_prefix_list_by_cluster,
         engine_save_by_cluster,
     },
-    keys::{key_node, key_node_prefix, key_node_prefix_all},
+    keys::{key_node, key_node_heartbeat, key_node_prefix, key_node_prefix_all},
     rocksdb::RocksDBEngine,
 };
 use common_base::error::common::CommonError;
+use common_base::tools::now_second;
 use metadata_struct::placement::broker_node::BrokerNode;
 use std::sync::Arc;
 
@@ -41,10 +42,65 @@ impl NodeStorage {
     }
 
     pub fn delete(&self, cluster_name: &String, node_id: u64) -> Result<(), CommonError> {
+        let heartbeat_key = key_node_heartbeat(cluster_name, node_id);
+        engine_delete_by_cluster(self.rocksdb_engine_handler.clone(), heartbeat_key)?;
         let node_key = key_node(cluster_name, node_id);
         return engine_delete_by_cluster(self.rocksdb_engine_handler.clone(), node_key);
     }
 
+    /// Record the last time (in seconds) the node was seen
+    pub fn heartbeat(
+        &self,
+        cluster_name: &String,
+        node_id: u64,
+        time: u64,
+    ) -> Result<(), CommonError> {
+        let heartbeat_key = key_node_heartbeat(cluster_name, node_id);
+        return engine_save_by_cluster(self.rocksdb_engine_handler.clone(), heartbeat_key, time);
+    }
+
+    pub fn last_heartbeat(
+        &self,
+        cluster_name: &String,
+        node_id: u64,
+    ) -> Result<Option<u64>, CommonError> {
+        let heartbeat_key = key_node_heartbeat(cluster_name, node_id);
+        match engine_get_by_cluster(self.rocksdb_engine_handler.clone(), heartbeat_key) {
+            Ok(Some(data)) => match serde_json::from_slice::<u64>(&data.data) {
+                Ok(time) => {
+                    return Ok(Some(time));
+                }
+                Err(e) => {
+                    return Err(e.into());
+                }
+            },
+            Ok(None) => {
+                return Ok(None);
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Nodes of the cluster whose last heartbeat is at least ttl seconds old.
+    /// Nodes that never reported a heartbeat are not considered stale.
+    #[allow(dead_code)]
+    pub fn stale_nodes(
+        &self,
+        cluster_name: &String,
+        ttl: u64,
+    ) -> Result<Vec<BrokerNode>, CommonError> {
+        let now = now_second();
+        let mut results = Vec::new();
+        for node in self.list(Some(cluster_name.clone()))? {
+            if let Some(time) = self.last_heartbeat(&node.cluster_name, node.node_id)? {
+                if now.saturating_sub(time) >= ttl {
+                    results.push(node);
+                }
+            }
+        }
+        return Ok(results);
+    }
+
     #[allow(dead_code)]
     pub fn get(
         &self,
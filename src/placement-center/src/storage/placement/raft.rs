@@ -12,18 +12,31 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::raft::codec::{decode_message, encode_message};
+use crate::storage::keys::key_name_by_applied_index;
+use crate::storage::keys::key_name_client_request;
+use crate::storage::keys::key_name_client_request_prefix;
 use crate::storage::keys::key_name_by_conf_state;
 use crate::storage::keys::key_name_by_entry;
 use crate::storage::keys::key_name_by_first_index;
 use crate::storage::keys::key_name_by_hard_state;
 use crate::storage::keys::key_name_by_last_index;
+use crate::storage::keys::key_name_entry_prefix;
+use crate::storage::keys::key_name_raft_prefix;
 use crate::storage::keys::key_name_snapshot;
+use crate::storage::keys::key_name_snapshot_metadata;
+use crate::storage::keys::key_name_snapshot_pending;
+use crate::storage::keys::key_name_storage_format;
 use crate::storage::keys::key_name_uncommit;
-use crate::storage::rocksdb::RocksDBEngine;
+use crate::storage::rocksdb::{RocksDBEngine, DB_COLUMN_FAMILY_CLUSTER};
 use bincode::{deserialize, serialize};
+use common_base::config::default_placement_center::default_entry_cache_size;
+use common_base::error::common::CommonError;
+use common_base::error::placement_center::PlacementCenterError;
 use log::debug;
 use log::error;
 use log::info;
+use log::warn;
 use prost::Message as _;
 use raft::eraftpb::HardState;
 use raft::prelude::ConfState;
@@ -34,19 +47,222 @@ use raft::Error;
 use raft::RaftState;
 use raft::Result as RaftResult;
 use raft::StorageError;
+use raft::util::limit_size;
+use rocksdb_engine::BatchOperation;
+use rocksdb_engine::StorageError as EngineError;
+use rocksdb_engine::WriteBatch;
+use serde::{Deserialize, Serialize};
 use std::cmp;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+// Result of the last request of a client, returned again when the request is proposed twice
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppliedRequest {
+    pub request_id: u64,
+    pub index: u64,
+    // Error of the request, None when it succeeded
+    pub error: Option<AppliedError>,
+}
+
+// The error a request failed with, a retry of the request fails with the same error
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AppliedError {
+    // The compare-and-swap did not match, the proposer answers false instead of failing
+    CompareAndSwapMismatch(String),
+    // Any other error of the state machine, only its message is kept
+    Message(String),
+}
+
+impl AppliedError {
+    pub fn from_error(e: &CommonError) -> Self {
+        match e {
+            CommonError::PlacementCenterError(PlacementCenterError::CompareAndSwapMismatch(
+                key,
+            )) => return AppliedError::CompareAndSwapMismatch(key.clone()),
+            e => return AppliedError::Message(e.to_string()),
+        }
+    }
+
+    pub fn into_error(self) -> CommonError {
+        match self {
+            AppliedError::CompareAndSwapMismatch(key) => {
+                return PlacementCenterError::CompareAndSwapMismatch(key).into();
+            }
+            AppliedError::Message(message) => return CommonError::CommmonError(message),
+        }
+    }
+}
+
+// Prost encoded Raft state is stored as raw bytes, older versions wrapped them in JSON arrays
+const RAFT_STORAGE_FORMAT_RAW: u64 = 1;
+// Entries are stored as the CRC32 of the encoded entry followed by the entry itself
+const RAFT_STORAGE_FORMAT_CHECKSUM: u64 = 2;
+// Every prost encoded value starts with the version byte of its encoding
+const RAFT_STORAGE_FORMAT_VERSIONED: u64 = 3;
+// The index in the entry keys is zero padded, the keys sort in index order
+const RAFT_STORAGE_FORMAT_PADDED_KEYS: u64 = 4;
+const ENTRY_CHECKSUM_LEN: usize = 4;
+// Version 1 is the prost encoded value, the entries also carry their checksum. A
+// change of the encoding gets the next version, the read path keeps decoding the
+// older ones so that values written before the change can still be read.
+const RAFT_VALUE_VERSION_1: u8 = 1;
+
+fn checksum_value(data: &[u8]) -> Vec<u8> {
+    let mut value = Vec::with_capacity(ENTRY_CHECKSUM_LEN + data.len());
+    value.extend_from_slice(&crc32fast::hash(data).to_be_bytes());
+    value.extend_from_slice(data);
+    return value;
+}
+
+fn versioned_value(data: &[u8]) -> Vec<u8> {
+    let mut value = Vec::with_capacity(1 + data.len());
+    value.push(RAFT_VALUE_VERSION_1);
+    value.extend_from_slice(data);
+    return value;
+}
+
+// Splits the version byte off the stored value
+fn value_version<'a>(key: &str, value: &'a [u8]) -> Result<(u8, &'a [u8]), EngineError> {
+    match value.split_first() {
+        Some((version, data)) => return Ok((*version, data)),
+        None => {
+            return Err(EngineError::Deserialize(format!(
+                "The value stored at key {} is empty, it has no format version",
+                key
+            )))
+        }
+    }
+}
+
+fn encode_value<M: prost::Message>(message: &M) -> Vec<u8> {
+    return versioned_value(&encode_message(message));
+}
+
+fn decode_value<M: prost::Message + Default>(key: &str, value: &[u8]) -> Result<M, EngineError> {
+    match value_version(key, value)? {
+        (RAFT_VALUE_VERSION_1, data) => {
+            return decode_message::<M>(key, data)
+                .map_err(|e| EngineError::Deserialize(e.to_string()))
+        }
+        (version, _) => return Err(EngineError::UnknownFormatVersion(key.to_string(), version)),
+    }
+}
+
+pub(crate) fn encode_entry(entry: &Entry) -> Vec<u8> {
+    return versioned_value(&checksum_value(&encode_message(entry)));
+}
+
+pub(crate) fn decode_entry(key: &str, value: &[u8]) -> Result<Entry, EngineError> {
+    match value_version(key, value)? {
+        (RAFT_VALUE_VERSION_1, data) => return decode_checksum_entry(key, data),
+        (version, _) => return Err(EngineError::UnknownFormatVersion(key.to_string(), version)),
+    }
+}
+
+fn decode_checksum_entry(key: &str, value: &[u8]) -> Result<Entry, EngineError> {
+    if value.len() < ENTRY_CHECKSUM_LEN {
+        return Err(EngineError::ChecksumMismatch(key.to_string()));
+    }
+    let (checksum, data) = value.split_at(ENTRY_CHECKSUM_LEN);
+    if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]])
+        != crc32fast::hash(data)
+    {
+        return Err(EngineError::ChecksumMismatch(key.to_string()));
+    }
+    return decode_message::<Entry>(key, data).map_err(|e| EngineError::Deserialize(e.to_string()));
+}
+
 pub struct RaftMachineStorage {
     pub uncommit_index: HashMap<u64, i8>,
     pub trigger_snap_unavailable: bool,
     pub snapshot_metadata: SnapshotMetadata,
     pub rocksdb_engine_handler: Arc<RocksDBEngine>,
+    // In-memory copies of the persisted first/last index, kept in sync by every write
+    cache_first_index: u64,
+    cache_last_index: u64,
+    // The most recently appended entries, replication reads them again right after the append
+    entry_cache: BTreeMap<u64, Entry>,
+    entry_cache_size: usize,
+    // Entries kept below the snapshot index by create_snapshot
+    snapshot_retain_entries: u64,
+    // Directory of the snapshot data files, None keeps the data in RocksDB
+    snapshot_path: Option<PathBuf>,
+    // Makes the entry batch of append fail, as if RocksDB rejected the write
+    #[cfg(test)]
+    fail_append: bool,
+}
+
+// The Raft state of the node that took a snapshot is part of its data, it must not replace
+// the one of this node. The applied requests of the clients belong to the state machine.
+fn is_raft_state_key(key: &str) -> bool {
+    return key.starts_with(&key_name_raft_prefix())
+        && !key.starts_with(&key_name_client_request_prefix());
+}
+
+fn is_raft_state_raw_key(key: &[u8]) -> bool {
+    return std::str::from_utf8(key).map_or(false, is_raft_state_key);
+}
+
+// The data of a snapshot, the key/value pairs of every ColumnFamily as they are stored
+type SnapshotData = HashMap<String, Vec<(Vec<u8>, Vec<u8>)>>;
+
+// The data of the snapshots taken when the values were read as strings
+type LegacySnapshotData = HashMap<String, Vec<HashMap<String, String>>>;
+
+fn legacy_snapshot_data(data: LegacySnapshotData) -> SnapshotData {
+    return data
+        .into_iter()
+        .map(|(cf_name, raws)| {
+            let raws = raws
+                .into_iter()
+                .flatten()
+                .map(|(key, val)| (key.into_bytes(), val.into_bytes()))
+                .collect();
+            (cf_name, raws)
+        })
+        .collect();
+}
+
+// The data of the snapshot at term and index, the snapshot saved in RocksDB refers to it
+fn snapshot_file_name(term: u64, index: u64) -> String {
+    return format!("snapshot-{}-{}.data", term, index);
+}
+
+// Files written by an earlier snapshot, including the ones a crash left half written
+fn is_snapshot_file(name: &str) -> bool {
+    return name.starts_with("snapshot-") && (name.ends_with(".data") || name.ends_with(".tmp"));
+}
+
+fn snapshot_file_error(path: &Path, e: std::io::Error) -> EngineError {
+    return EngineError::SnapshotFile(path.display().to_string(), e);
+}
+
+// The error names the first entry that has not been persisted
+fn append_error(index: u64, e: EngineError) -> Error {
+    error!("Failed to append the Raft log at index {}, error message: {}", index, e);
+    return Error::Store(StorageError::Other(Box::new(
+        PlacementCenterError::RaftLogAppendFailed(index, e.to_string()),
+    )));
 }
 
 impl RaftMachineStorage {
+    /// Create the storage with the default entry cache size
     pub fn new(rocksdb_engine_handler: Arc<RocksDBEngine>) -> Self {
+        return RaftMachineStorage::new_with_entry_cache(
+            rocksdb_engine_handler,
+            default_entry_cache_size(),
+        );
+    }
+
+    /// Create the storage keeping up to `entry_cache_size` recently appended entries in memory
+    pub fn new_with_entry_cache(
+        rocksdb_engine_handler: Arc<RocksDBEngine>,
+        entry_cache_size: u64,
+    ) -> Self {
         let uncommit_index = HashMap::new();
 
         let mut rc = RaftMachineStorage {
@@ -54,66 +270,350 @@ impl RaftMachineStorage {
             trigger_snap_unavailable: false,
             uncommit_index,
             rocksdb_engine_handler,
+            cache_first_index: 0,
+            cache_last_index: 0,
+            entry_cache: BTreeMap::new(),
+            entry_cache_size: entry_cache_size as usize,
+            snapshot_retain_entries: 0,
+            snapshot_path: None,
+            #[cfg(test)]
+            fail_append: false,
         };
+        if let Err(e) = rc.migrate_storage_format() {
+            error!(
+                "Failed to migrate the Raft state to the current storage format, error message: {}",
+                e
+            );
+        }
         rc.uncommit_index = rc.uncommit_index();
-        rc.snapshot_metadata = rc.create_snapshot_metadata();
+        rc.snapshot_metadata = rc.persisted_snapshot_metadata();
+        rc.cache_first_index = rc.read_first_index();
+        rc.cache_last_index = rc.read_last_index();
         return rc;
     }
 
+    /// Rewrite the values stored by older versions in the current format: the values wrapped
+    /// in JSON arrays are unwrapped, a checksum is added to the entries and every value gets
+    /// its format version, the entries are moved to zero padded keys. Runs once per data
+    /// path, the storage format marker is saved in the same batch.
+    fn migrate_storage_format(&self) -> Result<(), EngineError> {
+        let engine = &self.rocksdb_engine_handler;
+        let cf = engine.cf_cluster();
+        let format_key = key_name_storage_format();
+        let format = engine.read::<u64>(cf, &format_key)?.unwrap_or(0);
+        if format >= RAFT_STORAGE_FORMAT_PADDED_KEYS {
+            return Ok(());
+        }
+
+        // values that are not JSON arrays are already raw
+        let unwrap_json = |value: Vec<u8>| -> Vec<u8> {
+            if format >= RAFT_STORAGE_FORMAT_RAW {
+                return value;
+            }
+            return serde_json::from_slice::<Vec<u8>>(&value).unwrap_or(value);
+        };
+
+        let mut batch = WriteBatch::default();
+        let mut migrated = 0;
+        if format < RAFT_STORAGE_FORMAT_VERSIONED {
+            for key in [
+                key_name_by_hard_state(),
+                key_name_by_conf_state(),
+                key_name_snapshot(),
+                key_name_snapshot_metadata(),
+            ] {
+                if let Some(value) = engine.read_raw(cf, &key)? {
+                    batch.put_cf(cf, key, versioned_value(&unwrap_json(value)));
+                    migrated += 1;
+                }
+            }
+        }
+        if format < RAFT_STORAGE_FORMAT_RAW {
+            if let Some(value) = engine.read_raw(cf, &key_name_uncommit())? {
+                batch.put_cf(cf, key_name_uncommit(), unwrap_json(value));
+                migrated += 1;
+            }
+        }
+
+        let prefix = key_name_entry_prefix();
+        for raw in engine.read_prefix(cf, &prefix) {
+            for (key, value) in raw {
+                let mut data = value;
+                if format < RAFT_STORAGE_FORMAT_VERSIONED {
+                    data = unwrap_json(data);
+                    if format < RAFT_STORAGE_FORMAT_CHECKSUM {
+                        data = checksum_value(&data);
+                    }
+                    data = versioned_value(&data);
+                }
+                let padded_key = match key[prefix.len()..].parse::<u64>() {
+                    Ok(idx) => key_name_by_entry(idx),
+                    Err(_) => {
+                        warn!("Skip the Raft entry key {} that has no index", key);
+                        continue;
+                    }
+                };
+                if padded_key != key {
+                    batch.delete_cf(cf, &key);
+                }
+                batch.put_cf(cf, padded_key, data);
+                migrated += 1;
+            }
+        }
+
+        engine.batch_put(&mut batch, cf, &format_key, &RAFT_STORAGE_FORMAT_PADDED_KEYS)?;
+        engine.write_batch(batch)?;
+        if migrated > 0 {
+            info!(
+                "Migrated {} Raft values from storage format {} to {}",
+                migrated, format, RAFT_STORAGE_FORMAT_PADDED_KEYS
+            );
+        }
+        return Ok(());
+    }
+
+    /// Wipe the Raft log and state of this node so that it can join the group again as a new
+    /// member: the entries, indexes, HardState, ConfState, snapshot and request records are
+    /// deleted in one batch. The other keys and column families are left as they are.
+    /// Nothing happens unless `confirm` is set.
+    pub fn reset(&mut self, confirm: bool) -> Result<(), CommonError> {
+        if !confirm {
+            return Err(PlacementCenterError::RaftStorageResetNotConfirmed.into());
+        }
+
+        let engine = &self.rocksdb_engine_handler;
+        let cf = engine.cf_cluster();
+        let mut batch = WriteBatch::default();
+        // every Raft key starts with /raft/, '0' is the byte right after '/'
+        batch.delete_range_cf(cf, "/raft/", "/raft0");
+        let format_key = key_name_storage_format();
+        engine
+            .batch_put(&mut batch, cf, &format_key, &RAFT_STORAGE_FORMAT_PADDED_KEYS)
+            .map_err(|e| CommonError::CommmonError(e.to_string()))?;
+        engine
+            .write_batch(batch)
+            .map_err(|e| CommonError::CommmonError(e.to_string()))?;
+
+        self.uncommit_index.clear();
+        self.trigger_snap_unavailable = false;
+        self.snapshot_metadata = SnapshotMetadata::default();
+        self.entry_cache.clear();
+        self.cache_first_index = self.read_first_index();
+        self.cache_last_index = self.read_last_index();
+        self.remove_superseded_snapshot_files(&SnapshotMetadata::default());
+        info!("The Raft storage has been reset");
+        return Ok(());
+    }
+
+    /// Metadata of the last snapshot persisted to RocksDB, empty if no snapshot has been taken yet
+    pub fn saved_snapshot_metadata(&self) -> SnapshotMetadata {
+        match self.saved_snapshot() {
+            Some(mut snapshot) => return snapshot.take_metadata(),
+            None => return SnapshotMetadata::default(),
+        }
+    }
+
+    /// Metadata of the last snapshot taken or applied, it survives a restart. Data paths
+    /// written before the metadata was saved on its own fall back to the saved snapshot.
+    pub fn persisted_snapshot_metadata(&self) -> SnapshotMetadata {
+        match self.read_value::<SnapshotMetadata>(&key_name_snapshot_metadata()) {
+            Ok(Some(meta)) => return meta,
+            Ok(None) => {}
+            Err(e) => {
+                error!("Failed to read the snapshot metadata, error message: {}", e);
+            }
+        }
+        return self.saved_snapshot_metadata();
+    }
+
+    // The snapshot and its metadata in one batch, a crash can not leave one without the other
+    fn save_snapshot(&self, snapshot: &Snapshot) -> Result<(), EngineError> {
+        let engine = &self.rocksdb_engine_handler;
+        let cf = engine.cf_cluster();
+        let mut batch = WriteBatch::default();
+        batch.put_cf(cf, key_name_snapshot(), self.encode_snapshot(snapshot)?);
+        batch.put_cf(cf, key_name_snapshot_metadata(), encode_value(snapshot.get_metadata()));
+        return engine.write_batch_sync(batch);
+    }
+
+    /// The last snapshot persisted to RocksDB, without taking a new one. With a snapshot
+    /// path its data is read from the snapshot file.
+    pub fn saved_snapshot(&self) -> Option<Snapshot> {
+        let mut snapshot = match self.read_value::<Snapshot>(&key_name_snapshot()) {
+            Ok(Some(snapshot)) => snapshot,
+            Ok(None) => return None,
+            Err(e) => {
+                error!("Failed to read the saved snapshot, error message: {}", e);
+                return None;
+            }
+        };
+        if let Err(e) = self.load_snapshot_data(&mut snapshot) {
+            error!("Failed to read the saved snapshot, error message: {}", e);
+            return None;
+        }
+        return Some(snapshot);
+    }
+
+    // Snapshots saved while the data was kept in RocksDB still have it
+    fn load_snapshot_data(&self, snapshot: &mut Snapshot) -> Result<(), EngineError> {
+        let dir = match &self.snapshot_path {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+        if !snapshot.data.is_empty() {
+            return Ok(());
+        }
+        let meta = snapshot.get_metadata();
+        let path = dir.join(snapshot_file_name(meta.term, meta.index));
+        let data = fs::read(&path).map_err(|e| snapshot_file_error(&path, e))?;
+        snapshot.set_data(data);
+        return Ok(());
+    }
+
+    // The value saved at the snapshot key. With a snapshot path the data is written to its
+    // file first and only the metadata goes to RocksDB.
+    fn encode_snapshot(&self, snapshot: &Snapshot) -> Result<Vec<u8>, EngineError> {
+        let dir = match &self.snapshot_path {
+            Some(dir) => dir,
+            None => return Ok(encode_value(snapshot)),
+        };
+        fs::create_dir_all(dir).map_err(|e| snapshot_file_error(dir, e))?;
+
+        // a crash while writing leaves the tmp file, never a truncated data file
+        let meta = snapshot.get_metadata();
+        let path = dir.join(snapshot_file_name(meta.term, meta.index));
+        let tmp_path = path.with_extension("tmp");
+        let mut file = fs::File::create(&tmp_path).map_err(|e| snapshot_file_error(&tmp_path, e))?;
+        file.write_all(snapshot.get_data())
+            .and_then(|_| file.sync_all())
+            .map_err(|e| snapshot_file_error(&tmp_path, e))?;
+        fs::rename(&tmp_path, &path).map_err(|e| snapshot_file_error(&path, e))?;
+
+        let mut stored = Snapshot::default();
+        stored.set_metadata(meta.clone());
+        return Ok(encode_value(&stored));
+    }
+
+    // Delete the snapshot files other than the one of the snapshot at meta, they are
+    // superseded once it has been saved
+    fn remove_superseded_snapshot_files(&self, meta: &SnapshotMetadata) {
+        let dir = match &self.snapshot_path {
+            Some(dir) => dir,
+            None => return,
+        };
+        let read_dir = match fs::read_dir(dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    error!(
+                        "Failed to list the snapshot files in {}, error message: {}",
+                        dir.display(),
+                        e
+                    );
+                }
+                return;
+            }
+        };
+
+        let current = snapshot_file_name(meta.term, meta.index);
+        for file in read_dir.flatten() {
+            let name = file.file_name().to_string_lossy().to_string();
+            if name == current || !is_snapshot_file(&name) {
+                continue;
+            }
+            match fs::remove_file(file.path()) {
+                Ok(_) => debug!("Removed the superseded snapshot file {}", name),
+                Err(e) => error!(
+                    "Failed to remove the snapshot file {}, error message: {}",
+                    name, e
+                ),
+            }
+        }
+    }
+
+    // Reads the prost encoded value stored at key, None when there is none
+    fn read_value<M: prost::Message + Default>(&self, key: &str) -> Result<Option<M>, EngineError> {
+        let engine = &self.rocksdb_engine_handler;
+        match engine.read_raw(engine.cf_cluster(), key)? {
+            Some(value) => return Ok(Some(decode_value::<M>(key, &value)?)),
+            None => return Ok(None),
+        }
+    }
+
     /// Save HardState information to RocksDB
-    pub fn save_conf_state(&self, cs: ConfState) -> Result<(), String> {
+    pub fn save_conf_state(&self, cs: ConfState) -> Result<(), EngineError> {
         let key = key_name_by_conf_state();
-        let value = ConfState::encode_to_vec(&cs);
+        let value = encode_value(&cs);
         self.rocksdb_engine_handler
-            .write(self.rocksdb_engine_handler.cf_cluster(), &key, &value)
+            .write_raw(self.rocksdb_engine_handler.cf_cluster(), &key, &value)
     }
 
-    // Return RaftState
-    pub fn raft_state(&self) -> RaftState {
-        let shs = self.hard_state();
-        let scs = self.conf_state();
-        RaftState {
+    // Return RaftState, the defaults until the node has been bootstrapped or has joined a cluster
+    pub fn raft_state(&self) -> RaftResult<RaftState> {
+        if !self.is_bootstrapped() {
+            return Ok(RaftState::default());
+        }
+        let shs = match self.hard_state() {
+            Ok(hs) => hs,
+            Err(e) => return Err(Error::Store(StorageError::Other(Box::new(e)))),
+        };
+        let mut scs = match self.conf_state() {
+            Ok(cs) => cs,
+            Err(e) => return Err(Error::Store(StorageError::Other(Box::new(e)))),
+        };
+        // the ConfState of a snapshot applied without one saved on its own
+        if scs == ConfState::default() {
+            scs = self.persisted_snapshot_metadata().take_conf_state();
+        }
+        Ok(RaftState {
             hard_state: shs,
             conf_state: scs,
+        })
+    }
+
+    /// Whether any Raft state has been persisted, a fresh node has none of it
+    pub fn is_bootstrapped(&self) -> bool {
+        let engine = &self.rocksdb_engine_handler;
+        for key in [key_name_by_hard_state(), key_name_by_conf_state()] {
+            if let Ok(Some(_)) = engine.read_raw(engine.cf_cluster(), &key) {
+                return true;
+            }
         }
+        return self.last_index() > 0 || self.persisted_snapshot_metadata().index > 0;
+    }
+
+    /// Persist the initial HardState and ConfState of a new cluster in one batch, so a crash
+    /// can not leave a node that is bootstrapped with only one of them
+    pub fn bootstrap(&self, cs: ConfState) -> Result<(), EngineError> {
+        let engine = &self.rocksdb_engine_handler;
+        let cf = engine.cf_cluster();
+        let mut batch = WriteBatch::default();
+        batch.put_cf(cf, key_name_by_hard_state(), encode_value(&self.hard_state()?));
+        batch.put_cf(cf, key_name_by_conf_state(), encode_value(&cs));
+        return engine.write_batch_sync(batch);
     }
 
     // Save HardState information to RocksDB
-    pub fn hard_state(&self) -> HardState {
-        let key = key_name_by_hard_state();
-        let value = self
-            .rocksdb_engine_handler
-            .read::<Vec<u8>>(self.rocksdb_engine_handler.cf_cluster(), &key)
-            .unwrap();
-        if value == None {
-            HardState::default()
-        } else {
-            return HardState::decode(value.unwrap().as_ref()).unwrap();
-        }
+    pub fn hard_state(&self) -> Result<HardState, EngineError> {
+        let hs = self.read_value::<HardState>(&key_name_by_hard_state())?;
+        return Ok(hs.unwrap_or_default());
     }
 
     /// Save HardState information to RocksDB
-    pub fn conf_state(&self) -> ConfState {
-        let key = key_name_by_conf_state();
-        let value = self
-            .rocksdb_engine_handler
-            .read::<Vec<u8>>(self.rocksdb_engine_handler.cf_cluster(), &key)
-            .unwrap();
-        if value.is_none() {
-            ConfState::default()
-        } else {
-            return ConfState::decode(value.unwrap().as_ref())
-                .map_err(|e| tonic::Status::invalid_argument(e.to_string()))
-                .unwrap();
-        }
+    pub fn conf_state(&self) -> Result<ConfState, EngineError> {
+        let cs = self.read_value::<ConfState>(&key_name_by_conf_state())?;
+        return Ok(cs.unwrap_or_default());
     }
 
     // todo
     pub fn commmit_index(&mut self, idx: u64) -> RaftResult<()> {
-        let entry = self.entry_by_idx(idx);
-        if entry.is_none() {
-            info!("commit_to {} but the entry does not exist", idx);
-        }
+        let entry = match self.entry_by_idx(idx) {
+            Some(entry) => entry,
+            None => {
+                info!("commit_to {} but the entry does not exist", idx);
+                return Err(Error::Store(StorageError::Unavailable));
+            }
+        };
 
         debug!(">> commit entry index:{}", idx);
         // update uncommit index
@@ -121,13 +621,44 @@ impl RaftMachineStorage {
         self.save_uncommit_index();
 
         // update hs
-        let mut hs = self.hard_state();
+        let mut hs = match self.hard_state() {
+            Ok(hs) => hs,
+            Err(e) => return Err(Error::Store(StorageError::Other(Box::new(e)))),
+        };
         hs.commit = idx;
-        hs.term = entry.unwrap().get_term();
-        let _ = self.save_hard_state(hs);
+        hs.term = entry.get_term();
+        if let Err(e) = self.save_hard_state(hs) {
+            return Err(Error::Store(StorageError::Other(Box::new(e))));
+        }
+
+        // the entry has been applied to the state machine
+        if let Err(e) = self.save_applied_index(idx) {
+            return Err(Error::Store(StorageError::Other(Box::new(e))));
+        }
         return Ok(());
     }
 
+    /// Record a run of applied entries as committed. The uncommit index and the applied
+    /// index are written once for the whole run, in a single batch. The ready loop has
+    /// already persisted the term and commit index before the entries reached the applier.
+    pub fn commit_entries(&mut self, indexes: &[u64]) -> Result<(), EngineError> {
+        let last_idx = match indexes.last() {
+            Some(idx) => *idx,
+            None => return Ok(()),
+        };
+        debug!(">> commit entries up to index:{}", last_idx);
+        for idx in indexes {
+            self.uncommit_index.remove(idx);
+        }
+
+        let engine = &self.rocksdb_engine_handler;
+        let cf = engine.cf_cluster();
+        let mut batch = WriteBatch::default();
+        batch.put_cf(cf, key_name_uncommit(), serialize(&self.uncommit_index).unwrap());
+        engine.batch_put(&mut batch, cf, &key_name_by_applied_index(), &last_idx)?;
+        return engine.write_batch(batch);
+    }
+
     pub fn append(&mut self, entrys: &Vec<Entry>) -> RaftResult<()> {
         if entrys.len() == 0 {
             return Ok(());
@@ -152,37 +683,195 @@ impl RaftMachineStorage {
             );
         }
 
+        // The entries go in a single batch and the last index is written after it, a crash
+        // in between leaves the previous last index and so a shorter but consistent log.
+        // The in-memory state only changes once the batch is persisted.
+        let engine = &self.rocksdb_engine_handler;
+        let cf = engine.cf_cluster();
+        let mut batch = WriteBatch::default();
+        let mut uncommit_index = self.uncommit_index.clone();
         for entry in entrys {
             debug!(">> save entry index:{}, value:{:?}", entry.index, entry);
-            let data: Vec<u8> = Entry::encode_to_vec(&entry);
-            let key = key_name_by_entry(entry.index);
-            self.rocksdb_engine_handler
-                .write(self.rocksdb_engine_handler.cf_cluster(), &key, &data)
-                .unwrap();
-            self.uncommit_index.insert(entry.index, 1);
-            self.save_last_index(entry.index).unwrap();
+            batch.put_cf(cf, key_name_by_entry(entry.index), encode_entry(&entry));
+            uncommit_index.insert(entry.index, 1);
         }
+        batch.put_cf(cf, key_name_uncommit(), serialize(&uncommit_index).unwrap());
+        if let Err(e) = self.write_entries(batch) {
+            return Err(append_error(entry_first_index, e));
+        }
+        self.uncommit_index = uncommit_index;
+        self.cache_entries(entrys);
 
-        self.save_uncommit_index();
+        // entries after the last appended one are overwritten, so the last index always moves to it
+        let entry_last_index = entrys[entrys.len() - 1].index;
+        if let Err(e) = self.save_last_index(entry_last_index) {
+            return Err(append_error(entry_last_index, e));
+        }
 
         return Ok(());
     }
 
-    #[allow(dead_code)]
-    pub fn entrys(&self, low: u64, high: u64) -> Vec<Entry> {
+    fn write_entries(&self, batch: WriteBatch) -> Result<(), EngineError> {
+        #[cfg(test)]
+        if self.fail_append {
+            return Err(EngineError::ColumnFamilyNotFound("cluster".to_string()));
+        }
+        return self.rocksdb_engine_handler.write_batch(batch);
+    }
+
+    // Entries from the first appended one on are overwritten, drop their cached copies
+    // before caching the new ones, then evict the oldest entries beyond the cache size.
+    fn cache_entries(&mut self, entrys: &[Entry]) {
+        if self.entry_cache_size == 0 {
+            return;
+        }
+        let _ = self.entry_cache.split_off(&entrys[0].index);
+        for entry in entrys {
+            self.entry_cache.insert(entry.index, entry.clone());
+        }
+        while self.entry_cache.len() > self.entry_cache_size {
+            self.entry_cache.pop_first();
+        }
+    }
+
+    /// Entries in [low, high), limited to max_size bytes but always at least one entry.
+    /// Same semantics as raft::Storage::entries, compacted entries fail with Compacted.
+    pub fn entries(&self, low: u64, high: u64, max_size: Option<u64>) -> RaftResult<Vec<Entry>> {
+        if low < self.first_index() {
+            return Err(Error::Store(StorageError::Compacted));
+        }
+
+        if high > self.last_index() + 1 {
+            panic!(
+                "index out of bound (last: {}, high: {})",
+                self.last_index() + 1,
+                high
+            )
+        }
+
+        // The entries before the cache are read with one forward iterator from low on,
+        // it stops at the size budget. The cache holds the newest entries without gaps.
+        let max_size = max_size.unwrap_or(u64::MAX);
         let mut entry_list: Vec<Entry> = Vec::new();
-        for idx in low..=high {
-            if let Some(sret) = self.entry_by_idx(idx) {
-                entry_list.push(sret);
-            }
+        let mut size = 0;
+        let cache_start = match self.entry_cache.range(low..high).next() {
+            Some((idx, _)) => *idx,
+            None => high,
+        };
+        let mut next_idx = low;
+        let mut failure: Option<Error> = None;
+        let engine = &self.rocksdb_engine_handler;
+        let scanned = engine.scan_range_while(
+            engine.cf_cluster(),
+            &key_name_by_entry(low),
+            &key_name_by_entry(cache_start),
+            |key, value| {
+                let expected = key_name_by_entry(next_idx);
+                if key != expected.as_bytes() {
+                    // the log has a hole, the entry is not available instead of silently skipped
+                    failure = Some(Error::Store(StorageError::Unavailable));
+                    return false;
+                }
+                match decode_entry(&expected, value) {
+                    Ok(entry) => {
+                        size += entry.encoded_len() as u64;
+                        entry_list.push(entry);
+                    }
+                    // a corrupted entry must not be replicated, fail instead of skipping it
+                    Err(e) => {
+                        failure = Some(Error::Store(StorageError::Other(Box::new(e))));
+                        return false;
+                    }
+                }
+                next_idx += 1;
+                return size <= max_size;
+            },
+        );
+        if let Err(e) = scanned {
+            return Err(Error::Store(StorageError::Other(Box::new(e))));
+        }
+        if let Some(e) = failure {
+            return Err(e);
+        }
+        if size <= max_size && next_idx < cache_start {
+            return Err(Error::Store(StorageError::Unavailable));
+        }
+
+        while size <= max_size && next_idx < high {
+            let entry = match self.read_entry(next_idx) {
+                Ok(Some(entry)) => entry,
+                Ok(None) => return Err(Error::Store(StorageError::Unavailable)),
+                Err(e) => return Err(Error::Store(StorageError::Other(Box::new(e)))),
+            };
+            size += entry.encoded_len() as u64;
+            entry_list.push(entry);
+            next_idx += 1;
+        }
+        limit_size(&mut entry_list, Some(max_size));
+        return Ok(entry_list);
+    }
+
+    /// Term of the entry at idx, which must be in [first_index() - 1, last_index()].
+    /// The term of the entry before first_index is kept in the snapshot metadata.
+    pub fn term(&self, idx: u64) -> RaftResult<u64> {
+        if idx == self.snapshot_metadata.index {
+            return Ok(self.snapshot_metadata.term);
+        }
+
+        if idx < self.first_index() {
+            return Err(Error::Store(StorageError::Compacted));
+        }
+
+        if idx > self.last_index() {
+            return Err(Error::Store(StorageError::Unavailable));
+        }
+
+        match self.read_entry(idx) {
+            Ok(Some(entry)) => return Ok(entry.term),
+            Ok(None) => return Err(Error::Store(StorageError::Unavailable)),
+            Err(e) => return Err(Error::Store(StorageError::Other(Box::new(e)))),
         }
-        return entry_list;
     }
 }
 
 impl RaftMachineStorage {
-    /// Get the index of the first Entry from RocksDB
+    /// Keep this many entries below the snapshot index when a snapshot compacts the log
+    pub fn set_snapshot_retain_entries(&mut self, retain_entries: u64) {
+        self.snapshot_retain_entries = retain_entries;
+    }
+
+    /// Write the snapshot data to files in this directory instead of RocksDB, an empty path
+    /// keeps it in RocksDB. The directory has to be kept, the saved snapshot refers to it.
+    pub fn set_snapshot_path(&mut self, snapshot_path: &str) {
+        let snapshot_path = snapshot_path.trim();
+        if snapshot_path.is_empty() {
+            self.snapshot_path = None;
+        } else {
+            self.snapshot_path = Some(PathBuf::from(snapshot_path));
+        }
+    }
+
+    /// Bytes the log entries take in RocksDB, each entry counts its encoding and checksum.
+    /// Unlike the entry count this tells how much a snapshot would reclaim.
+    pub fn log_size_bytes(&self) -> u64 {
+        return self.rocksdb_engine_handler.prefix_value_size(
+            self.rocksdb_engine_handler.cf_cluster(),
+            &key_name_entry_prefix(),
+        );
+    }
+
+    /// Get the index of the first Entry
     pub fn first_index(&self) -> u64 {
+        return self.cache_first_index;
+    }
+
+    /// Gets the index of the last Entry
+    pub fn last_index(&self) -> u64 {
+        return self.cache_last_index;
+    }
+
+    /// Get the index of the first Entry from RocksDB
+    pub fn read_first_index(&self) -> u64 {
         let key = key_name_by_first_index();
         match self
             .rocksdb_engine_handler
@@ -203,7 +892,7 @@ impl RaftMachineStorage {
     }
 
     /// Gets the index of the last Entry from RocksDB
-    pub fn last_index(&self) -> u64 {
+    pub fn read_last_index(&self) -> u64 {
         let key = key_name_by_last_index();
         match self
             .rocksdb_engine_handler
@@ -225,49 +914,89 @@ impl RaftMachineStorage {
 
     /// Obtain the Entry based on the index ID
     pub fn entry_by_idx(&self, idx: u64) -> Option<Entry> {
+        match self.read_entry(idx) {
+            Ok(entry) => return entry,
+            Err(e) => {
+                error!(
+                    "Failed to read entry. The failure information is {}, and the current index is {}",
+                    e, idx
+                );
+                return None;
+            }
+        }
+    }
+
+    /// Obtain the Entry based on the index ID, a corrupted value is reported as
+    /// StorageError::ChecksumMismatch instead of being decoded.
+    pub fn read_entry(&self, idx: u64) -> Result<Option<Entry>, EngineError> {
+        if let Some(entry) = self.entry_cache.get(&idx) {
+            return Ok(Some(entry.clone()));
+        }
+
         let key = key_name_by_entry(idx);
         match self
             .rocksdb_engine_handler
-            .read::<Vec<u8>>(self.rocksdb_engine_handler.cf_cluster(), &key)
+            .read_raw(self.rocksdb_engine_handler.cf_cluster(), &key)?
+        {
+            Some(value) => return Ok(Some(decode_entry(&key, &value)?)),
+            None => return Ok(None),
+        }
+    }
+
+    /// Gets the index of the last Entry applied to the state machine from RocksDB
+    pub fn applied_index(&self) -> u64 {
+        let key = key_name_by_applied_index();
+        match self
+            .rocksdb_engine_handler
+            .read::<u64>(self.rocksdb_engine_handler.cf_cluster(), &key)
         {
             Ok(value) => {
-                if let Some(vl) = value {
-                    let et = Entry::decode(vl.as_ref())
-                        .map_err(|e| tonic::Status::invalid_argument(e.to_string()))
-                        .unwrap();
-                    return Some(et);
+                if let Some(ai) = value {
+                    cmp::max(ai, self.snapshot_metadata.index)
+                } else {
+                    self.snapshot_metadata.index
                 }
             }
-            Err(e) => error!(
-                "Failed to read entry. The failure information is {}, and the current index is {}",
-                e, idx
-            ),
+            Err(e) => {
+                error!("Failed to read the applied index. The failure message is {}, and the current snapshot index is {}",e, self.snapshot_metadata.index);
+                self.snapshot_metadata.index
+            }
         }
-        return None;
     }
 
-    pub fn save_last_index(&self, index: u64) -> Result<(), String> {
-        let key = key_name_by_last_index();
+    pub fn save_applied_index(&self, index: u64) -> Result<(), EngineError> {
+        let key = key_name_by_applied_index();
         self.rocksdb_engine_handler
             .write(self.rocksdb_engine_handler.cf_cluster(), &key, &index)
     }
 
-    pub fn save_first_index(&self, index: u64) -> Result<(), String> {
+    pub fn save_last_index(&mut self, index: u64) -> Result<(), EngineError> {
+        let key = key_name_by_last_index();
+        self.rocksdb_engine_handler
+            .write(self.rocksdb_engine_handler.cf_cluster(), &key, &index)?;
+        self.cache_last_index = index;
+        return Ok(());
+    }
+
+    pub fn save_first_index(&mut self, index: u64) -> Result<(), EngineError> {
         let key = key_name_by_first_index();
         self.rocksdb_engine_handler
-            .write(self.rocksdb_engine_handler.cf_cluster(), &key, &index)
+            .write(self.rocksdb_engine_handler.cf_cluster(), &key, &index)?;
+        self.cache_first_index = index;
+        return Ok(());
     }
 
-    /// Save HardState information to RocksDB
-    pub fn save_hard_state(&self, hs: HardState) -> Result<(), String> {
+    /// Save HardState information to RocksDB.
+    /// HardState must be durable before Raft acks, so this write is synced.
+    pub fn save_hard_state(&self, hs: HardState) -> Result<(), EngineError> {
         let key = key_name_by_hard_state();
-        let val = HardState::encode_to_vec(&hs);
+        let val = encode_value(&hs);
         self.rocksdb_engine_handler
-            .write(self.rocksdb_engine_handler.cf_cluster(), &key, &val)
+            .write_raw_sync(self.rocksdb_engine_handler.cf_cluster(), &key, &val)
     }
 
-    pub fn set_hard_state_commit(&self, commit: u64) -> Result<(), String> {
-        let mut hs = self.hard_state();
+    pub fn set_hard_state_commit(&self, commit: u64) -> Result<(), EngineError> {
+        let mut hs = self.hard_state()?;
         hs.commit = commit;
         self.save_hard_state(hs)
     }
@@ -278,22 +1007,14 @@ impl RaftMachineStorage {
         let key = key_name_uncommit();
         let _ =
             self.rocksdb_engine_handler
-                .write(self.rocksdb_engine_handler.cf_cluster(), &key, &val);
-    }
-
-    pub fn save_snapshot_data(&self, snapshot: Snapshot) {
-        let val = Snapshot::encode_to_vec(&snapshot);
-        let key = key_name_snapshot();
-        let _ =
-            self.rocksdb_engine_handler
-                .write(self.rocksdb_engine_handler.cf_cluster(), &key, &val);
+                .write_raw(self.rocksdb_engine_handler.cf_cluster(), &key, &val);
     }
 
     pub fn uncommit_index(&self) -> HashMap<u64, i8> {
         let key = key_name_uncommit();
         match self
             .rocksdb_engine_handler
-            .read::<Vec<u8>>(self.rocksdb_engine_handler.cf_cluster(), &key)
+            .read_raw(self.rocksdb_engine_handler.cf_cluster(), &key)
         {
             Ok(data) => {
                 if let Some(value) = data {
@@ -308,38 +1029,104 @@ impl RaftMachineStorage {
         return HashMap::new();
     }
 
-    pub fn write_all(&self, data: &[u8]) {
-        if data.len() == 0 {
-            return;
+    /// Indices of the appended entries that have not been committed yet, in ascending order
+    pub fn uncommitted_indices(&self) -> Vec<u64> {
+        let mut indices: Vec<u64> = self.uncommit_index.keys().copied().collect();
+        indices.sort_unstable();
+        return indices;
+    }
+
+    /// The last request of the client applied to the state machine
+    pub fn applied_request(&self, client_id: &str) -> Option<AppliedRequest> {
+        let key = key_name_client_request(client_id);
+        match self
+            .rocksdb_engine_handler
+            .read::<AppliedRequest>(self.rocksdb_engine_handler.cf_cluster(), &key)
+        {
+            Ok(request) => return request,
+            Err(e) => {
+                error!(
+                    "Failed to read the last applied request of client {}, error message: {}",
+                    client_id, e
+                );
+                return None;
+            }
         }
+    }
 
-        match deserialize::<HashMap<String, Vec<HashMap<String, String>>>>(data) {
-            Ok(data) => {
-                for (_, value) in data {
-                    let cf = self.rocksdb_engine_handler.get_column_family();
-                    for raw in value {
-                        for (key, val) in &raw {
-                            info!("key:{:?},val{:?}", key, val.to_string());
-                            match self
-                                .rocksdb_engine_handler
-                                .write_str(cf, key, val.to_string())
-                            {
-                                Ok(_) => {}
-                                Err(err) => {
-                                    error!(
-                                        "Error occurred during apply snapshot. Error message: {}",
-                                        err
-                                    );
-                                }
-                            }
-                        }
+    // The write saving the last applied request of the client, for the batch of its entry
+    pub fn applied_request_operation(
+        &self,
+        client_id: &str,
+        request: &AppliedRequest,
+    ) -> Result<BatchOperation, EngineError> {
+        let key = key_name_client_request(client_id);
+        let value = self.rocksdb_engine_handler.codec().encode(request)?;
+        return Ok(BatchOperation::put(DB_COLUMN_FAMILY_CLUSTER, &key, value));
+    }
+
+    // The write saving the applied index, for the batch of the entry it was applied by
+    pub fn applied_index_operation(&self, index: u64) -> Result<BatchOperation, EngineError> {
+        let value = self.rocksdb_engine_handler.codec().encode(&index)?;
+        return Ok(BatchOperation::put(
+            DB_COLUMN_FAMILY_CLUSTER,
+            &key_name_by_applied_index(),
+            value,
+        ));
+    }
+
+    /// The writes replacing the state machine with the key/values of a snapshot, the data is
+    /// grouped by ColumnFamily name. The keys of a ColumnFamily that are not in the snapshot
+    /// are deleted, they may have been deleted in the entries the snapshot covers.
+    pub fn snapshot_data_operations(&self, data: &[u8]) -> Result<Vec<BatchOperation>, EngineError> {
+        let mut operations = Vec::new();
+        if data.len() == 0 {
+            return Ok(operations);
+        }
+
+        let data = match deserialize::<SnapshotData>(data) {
+            Ok(data) => data,
+            // a snapshot taken before the values were kept as bytes
+            Err(e) => match deserialize::<LegacySnapshotData>(data) {
+                Ok(data) => legacy_snapshot_data(data),
+                Err(_) => {
+                    return Err(EngineError::Deserialize(format!(
+                        "Failed to parse the snapshot data, error message: {}",
+                        e
+                    )));
+                }
+            },
+        };
+
+        for (cf_name, raws) in data {
+            // an unknown ColumnFamily rejects the snapshot before anything is written
+            let cf = self.rocksdb_engine_handler.cf_by_name(&cf_name)?;
+            // deleted first, the puts of the same batch write the keys the snapshot keeps
+            for (key, _) in self.rocksdb_engine_handler.iter_cf(cf) {
+                if let Ok(key) = String::from_utf8(key) {
+                    if !is_raft_state_key(&key) {
+                        operations.push(BatchOperation::delete(&cf_name, &key));
                     }
                 }
             }
-            Err(err) => {
-                error!("Failed to parse the snapshot data during snapshot data recovery, error message :{}",err.to_string());
+            for (key, val) in raws {
+                let key = match String::from_utf8(key) {
+                    Ok(key) => key,
+                    Err(e) => {
+                        return Err(EngineError::Deserialize(format!(
+                            "The snapshot data has a key that is not valid UTF-8, error message: {}",
+                            e
+                        )));
+                    }
+                };
+                if is_raft_state_key(&key) {
+                    continue;
+                }
+                debug!("key:{:?},val{:?}", key, val);
+                operations.push(BatchOperation::put(&cf_name, &key, val));
             }
         }
+        return Ok(operations);
     }
 }
 
@@ -351,88 +1138,379 @@ impl RaftMachineStorage {
     /// Panics if the snapshot index is less than the storage's first index.
 
     pub fn apply_snapshot(&mut self, mut snapshot: Snapshot) -> RaftResult<()> {
-        let mut meta = snapshot.take_metadata();
+        let meta = snapshot.take_metadata();
         let index = meta.index;
 
         if self.first_index() > index {
             return Err(Error::Store(StorageError::SnapshotOutOfDate));
         }
 
-        self.snapshot_metadata = meta.clone();
+        // A snapshot behind the commit index would roll back entries that are already committed
+        let commit = match self.hard_state() {
+            Ok(hs) => hs.commit,
+            Err(e) => return Err(Error::Store(StorageError::Other(Box::new(e)))),
+        };
+        if index < commit {
+            warn!(
+                "Reject snapshot index {}, it is behind the commit index {}",
+                index, commit
+            );
+            return Err(Error::Store(StorageError::SnapshotOutOfDate));
+        }
+
+        // A snapshot whose data cannot be restored is rejected before anything is written
+        let operations = match self.snapshot_data_operations(snapshot.data.as_ref()) {
+            Ok(operations) => operations,
+            Err(e) => {
+                error!(
+                    "Failed to apply snapshot index {}, error message: {}",
+                    index, e
+                );
+                return Err(Error::Store(StorageError::Other(Box::new(e))));
+            }
+        };
+
+        // The snapshot and the Raft state it implies are persisted first, a crash before the
+        // state machine is restored is completed by recover_snapshot on restart.
+        snapshot.set_metadata(meta.clone());
+        if let Err(e) = self.persist_snapshot(&snapshot) {
+            error!(
+                "Failed to persist snapshot index {}, error message: {}",
+                index, e
+            );
+            return Err(Error::Store(StorageError::Other(Box::new(e))));
+        }
+        self.remove_superseded_snapshot_files(&meta);
+        self.snapshot_metadata = meta;
+        self.cache_first_index = index + 1;
+        self.cache_last_index = index;
+        self.entry_cache.clear();
+
+        if let Err(e) = self.restore_snapshot_data(index, operations) {
+            error!(
+                "Failed to restore snapshot index {} into the state machine, error message: {}",
+                index, e
+            );
+            return Err(Error::Store(StorageError::Other(Box::new(e))));
+        }
+        return Ok(());
+    }
+
+    /// Complete the apply of a snapshot that was persisted before the node stopped, returns
+    /// its index or None when no snapshot is waiting to be restored.
+    pub fn recover_snapshot(&mut self) -> Result<Option<u64>, EngineError> {
+        let engine = &self.rocksdb_engine_handler;
+        let index = match engine.read::<u64>(engine.cf_cluster(), &key_name_snapshot_pending())? {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        let snapshot = match self.saved_snapshot() {
+            Some(snapshot) if snapshot.get_metadata().index == index => snapshot,
+            _ => {
+                error!(
+                    "Snapshot index {} was persisted to be applied, but it is no longer saved",
+                    index
+                );
+                return Ok(None);
+            }
+        };
+
+        info!("Restore snapshot index {} persisted before the restart", index);
+        let operations = self.snapshot_data_operations(snapshot.get_data())?;
+        self.restore_snapshot_data(index, operations)?;
+        return Ok(Some(index));
+    }
+
+    // The snapshot itself, the HardState and ConfState it implies and the truncated log in
+    // one batch, along with the index of the snapshot waiting to be restored
+    fn persist_snapshot(&self, snapshot: &Snapshot) -> Result<(), EngineError> {
+        let engine = &self.rocksdb_engine_handler;
+        let cf = engine.cf_cluster();
+        let meta = snapshot.get_metadata();
+        let mut batch = WriteBatch::default();
 
-        // Restore snapshot data to persistent storage
-        self.write_all(snapshot.data.as_ref());
+        batch.put_cf(cf, key_name_snapshot(), self.encode_snapshot(snapshot)?);
+        batch.put_cf(cf, key_name_snapshot_metadata(), encode_value(meta));
 
         // update HardState
-        let mut hs = self.hard_state();
+        let mut hs = self.hard_state()?;
         hs.set_term(cmp::max(hs.term, meta.term));
-        hs.set_commit(index);
-        let _ = self.save_hard_state(hs);
+        hs.set_commit(meta.index);
+        batch.put_cf(cf, key_name_by_hard_state(), encode_value(&hs));
 
         // update ConfState
-        let _ = self.save_conf_state(meta.take_conf_state());
-        return Ok(());
-    }
+        batch.put_cf(cf, key_name_by_conf_state(), encode_value(meta.get_conf_state()));
 
-    // Obtain the Entry based on the index ID
-    pub fn snapshot(&mut self) -> Snapshot {
-        self.create_snapshot();
-        let key = key_name_snapshot();
-        let value = self
-            .rocksdb_engine_handler
-            .read::<Vec<u8>>(self.rocksdb_engine_handler.cf_cluster(), &key)
-            .unwrap();
-        if value.is_none() {
-            Snapshot::default()
-        } else {
-            return Snapshot::decode(value.unwrap().as_ref())
-                .map_err(|e| tonic::Status::invalid_argument(e.to_string()))
-                .unwrap();
+        // the local entries are replaced by the snapshot, the log now starts right after it
+        for idx in self.first_index()..=self.last_index() {
+            batch.delete_cf(cf, key_name_by_entry(idx));
         }
+        engine.batch_put(&mut batch, cf, &key_name_by_first_index(), &(meta.index + 1))?;
+        engine.batch_put(&mut batch, cf, &key_name_by_last_index(), &meta.index)?;
+        engine.batch_put(&mut batch, cf, &key_name_snapshot_pending(), &meta.index)?;
+
+        return engine.write_batch_sync(batch);
+    }
+
+    // Restore the data of the snapshot into the state machine, the data may span several
+    // ColumnFamilies and is written in one batch with the applied index it brings the node to
+    fn restore_snapshot_data(
+        &self,
+        index: u64,
+        mut operations: Vec<BatchOperation>,
+    ) -> Result<(), EngineError> {
+        let engine = &self.rocksdb_engine_handler;
+        operations.push(BatchOperation::put(
+            DB_COLUMN_FAMILY_CLUSTER,
+            &key_name_by_applied_index(),
+            engine.codec().encode(&index)?,
+        ));
+        operations.push(BatchOperation::delete(
+            DB_COLUMN_FAMILY_CLUSTER,
+            &key_name_snapshot_pending(),
+        ));
+        return engine.write_batch_multi_cf(&operations);
     }
 
-    // Example Create a data snapshot for the current system
-    pub fn create_snapshot(&mut self) {
+    /// The last snapshot saved, raft asks for it to catch up a lagging follower. A new
+    /// snapshot is only taken by the applier, serving one never dumps the state machine.
+    pub fn snapshot(&self) -> Snapshot {
+        return self.saved_snapshot().unwrap_or_default();
+    }
+
+    /// Snapshot the state machine at the last applied entry and compact the log up to it,
+    /// returns the index of the snapshot or None when nothing has been applied yet or
+    /// the snapshot could not be saved.
+    pub fn create_snapshot(&mut self) -> Option<u64> {
         let mut sns = Snapshot::default();
 
         // create snapshot metadata
-        let meta = self.create_snapshot_metadata();
+        let meta = match self.create_snapshot_metadata() {
+            Ok(Some(meta)) => meta,
+            Ok(None) => {
+                debug!("There are no applied entries to take a snapshot of");
+                return None;
+            }
+            Err(e) => {
+                error!("Failed to create snapshot metadata, error message: {}", e);
+                return None;
+            }
+        };
         sns.set_metadata(meta.clone());
 
         // create snapshot data
 
-        let all_data = self.rocksdb_engine_handler.read_all();
+        // the values are copied as stored, the raft state of this node is left out
+        let mut all_data: SnapshotData = self.rocksdb_engine_handler.read_all();
+        for raws in all_data.values_mut() {
+            raws.retain(|(key, _)| !is_raft_state_raw_key(key));
+        }
         sns.set_data(serialize(&all_data).unwrap());
 
-        // update value
-        let _ = self.save_first_index(meta.get_index());
+        // the snapshot must be durable before the entries it covers are deleted
+        if let Err(e) = self.save_snapshot(&sns) {
+            error!(
+                "Failed to save snapshot index {}, error message: {}",
+                meta.get_index(),
+                e
+            );
+            return None;
+        }
 
-        //todo clear < first_index entry log
+        // clear the entry log covered by the snapshot, except the most recent entries
+        // retained for lagging followers, compact never removes entries past last_index
+        let compact_index = cmp::min(
+            meta.get_index().saturating_sub(self.snapshot_retain_entries),
+            self.last_index(),
+        );
+        if let Err(e) = self.compact(compact_index) {
+            error!(
+                "Failed to compact raft log to index {}, error message: {}",
+                compact_index, e
+            );
+        }
 
-        self.save_snapshot_data(sns);
+        self.remove_superseded_snapshot_files(&meta);
         self.snapshot_metadata = meta.clone();
+        return Some(meta.get_index());
+    }
+
+    /// Delete the entries before compact_index, they are already covered by the snapshot.
+    /// Entries past the applied index are never deleted, recovery still has to apply them,
+    /// so compact_index is clamped to the entry after the applied index.
+    pub fn compact(&mut self, compact_index: u64) -> Result<(), EngineError> {
+        let applied_index = self.applied_index();
+        let compact_index = if compact_index > applied_index + 1 {
+            warn!(
+                "Compact index {} is beyond the applied index {}, the log is only compacted up to the applied index",
+                compact_index, applied_index
+            );
+            applied_index + 1
+        } else {
+            compact_index
+        };
+
+        let first_index = self.first_index();
+        if compact_index <= first_index {
+            return Ok(());
+        }
+
+        // documents the clamp above, every entry deleted has been applied
+        let delete_range = first_index..compact_index;
+        debug_assert!(
+            delete_range.end <= applied_index + 1,
+            "deleting the entries {:?} would delete entries that have not been applied, applied index {}",
+            delete_range,
+            applied_index
+        );
+
+        // the entries and the first index in one batch, a crash can not leave a hole
+        // below the persisted first index
+        let engine = &self.rocksdb_engine_handler;
+        let cf = engine.cf_cluster();
+        let mut batch = WriteBatch::default();
+        for idx in delete_range {
+            batch.delete_cf(cf, key_name_by_entry(idx));
+        }
+        engine.batch_put(&mut batch, cf, &key_name_by_first_index(), &compact_index)?;
+        engine.write_batch_sync(batch)?;
+
+        self.entry_cache = self.entry_cache.split_off(&compact_index);
+        self.cache_first_index = compact_index;
+        return Ok(());
     }
 
-    pub fn create_snapshot_metadata(&self) -> SnapshotMetadata {
-        let hard_state = self.hard_state();
-        let conf_state = self.conf_state();
+    /// Metadata of a snapshot at the last applied entry still in the log.
+    /// Returns None when nothing has been applied yet or the entry is missing.
+    pub fn create_snapshot_metadata(&self) -> Result<Option<SnapshotMetadata>, EngineError> {
+        // The commit index runs ahead of the apply worker, the data of the snapshot is the
+        // state machine as applied. An entry is applied in one batch under the read lock, so
+        // the applied index matches the data read under the write lock.
+        let index = cmp::min(self.applied_index(), self.last_index());
+        if index == 0 {
+            return Ok(None);
+        }
+
+        // the entry may already be compacted, the last snapshot then knows its term
+        let term = if index <= self.snapshot_metadata.index {
+            if index < self.snapshot_metadata.index {
+                return Ok(Some(self.snapshot_metadata.clone()));
+            }
+            self.snapshot_metadata.term
+        } else {
+            match self.entry_by_idx(index) {
+                Some(entry) => entry.term,
+                None => {
+                    error!(
+                        "Failed to create snapshot metadata, the entry at index {} does not exist",
+                        index
+                    );
+                    return Ok(None);
+                }
+            }
+        };
 
         let mut meta: SnapshotMetadata = SnapshotMetadata::default();
-        meta.set_conf_state(conf_state);
-        meta.set_index(hard_state.commit);
-        meta.set_term(hard_state.term);
-        return meta;
+        meta.set_conf_state(self.conf_state()?);
+        meta.set_index(index);
+        meta.set_term(term);
+        return Ok(Some(meta));
+    }
+
+    /// Scan the Raft state persisted in RocksDB and return every invariant it violates.
+    /// This reads RocksDB directly and does not trust the cached indexes.
+    pub fn verify(&self) -> Result<(), Vec<String>> {
+        let mut violations = Vec::new();
+        let first_index = self.read_first_index();
+        let last_index = self.read_last_index();
+
+        if first_index > last_index + 1 {
+            violations.push(format!(
+                "first index {} is greater than last index {} + 1",
+                first_index, last_index
+            ));
+        }
+
+        let cf = self.rocksdb_engine_handler.cf_cluster();
+        for idx in first_index..=last_index {
+            let key = key_name_by_entry(idx);
+            match self.rocksdb_engine_handler.read_raw(cf, &key) {
+                Ok(Some(value)) => match decode_entry(&key, &value) {
+                    Ok(entry) => {
+                        if entry.index != idx {
+                            violations.push(format!(
+                                "entry stored at index {} has index {}",
+                                idx, entry.index
+                            ));
+                        }
+                    }
+                    Err(e) => {
+                        violations.push(format!("entry at index {} cannot be decoded: {}", idx, e))
+                    }
+                },
+                Ok(None) => violations.push(format!("entry at index {} does not exist", idx)),
+                Err(e) => violations.push(format!("failed to read entry at index {}: {}", idx, e)),
+            }
+        }
+
+        // the entries up to the snapshot are compacted, the commit may be right below first index
+        match self.hard_state() {
+            Ok(hs) => {
+                if hs.commit > last_index || hs.commit + 1 < first_index {
+                    violations.push(format!(
+                        "hard state commit {} is outside of [{}, {}]",
+                        hs.commit,
+                        first_index - 1,
+                        last_index
+                    ));
+                }
+            }
+            Err(e) => violations.push(format!("hard state cannot be read, {}", e)),
+        }
+
+        // a fresh node has no voters until it joins or bootstraps a cluster
+        match self.conf_state() {
+            Ok(cs) => {
+                if last_index > 0 && cs.voters.is_empty() {
+                    violations.push("conf state has no voters".to_string());
+                }
+            }
+            Err(e) => violations.push(format!("conf state cannot be read, {}", e)),
+        }
+
+        if violations.is_empty() {
+            return Ok(());
+        }
+        return Err(violations);
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{fs::remove_dir_all, sync::Arc};
+    use std::fs::{read, remove_dir_all, remove_file};
+    use std::path::Path;
+    use std::sync::Arc;
 
-    use crate::storage::rocksdb::{column_family_list, RocksDBEngine};
+    use crate::storage::rocksdb::{column_family_list, RocksDBEngine, RocksDBTuning};
 
-    use super::RaftMachineStorage;
+    use super::{
+        checksum_value, decode_value, encode_entry, encode_value, RaftMachineStorage,
+        RAFT_STORAGE_FORMAT_CHECKSUM, RAFT_STORAGE_FORMAT_VERSIONED, RAFT_VALUE_VERSION_1,
+    };
+    use crate::storage::keys::{
+        key_name_by_entry, key_name_by_first_index, key_name_by_hard_state,
+        key_name_by_last_index, key_name_entry_prefix, key_name_snapshot,
+        key_name_storage_format,
+    };
+    use bincode::serialize;
+    use common_base::error::placement_center::PlacementCenterError;
     use common_base::{config::placement_center::PlacementCenterConfig, tools::unique_id};
+    use prost::Message as _;
+    use raft::eraftpb::{ConfState, Entry, HardState, Snapshot, SnapshotMetadata};
+    use raft::{Error, StorageError as RaftStorageError};
+    use rocksdb_engine::{StorageError, ValueCodec, WriteBatch};
+    use std::collections::HashMap;
+    use std::time::Instant;
 
     #[test]
     fn write_read_test() {
@@ -445,7 +1523,7 @@ mod tests {
             conf.rocksdb.max_open_files.unwrap(),
             column_family_list(),
         ));
-        let rds = RaftMachineStorage::new(rocksdb_engine_handler);
+        let mut rds = RaftMachineStorage::new(rocksdb_engine_handler);
 
         let first_index = 1;
         let _ = rds.save_first_index(first_index);
@@ -459,4 +1537,1414 @@ mod tests {
 
         remove_dir_all(conf.rocksdb.data_path).unwrap();
     }
+
+    #[test]
+    fn hard_state_sync_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        {
+            let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+                &conf.rocksdb.data_path,
+                conf.rocksdb.max_open_files.unwrap(),
+                column_family_list(),
+            ));
+            let rds = RaftMachineStorage::new(rocksdb_engine_handler);
+            let mut hs = HardState::default();
+            hs.term = 3;
+            hs.vote = 2;
+            hs.commit = 5;
+            rds.save_hard_state(hs).unwrap();
+        }
+
+        // The previous instance has been dropped, reopen the same data path.
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let rds = RaftMachineStorage::new(rocksdb_engine_handler);
+        let hs = rds.hard_state().unwrap();
+        assert_eq!(hs.term, 3);
+        assert_eq!(hs.vote, 2);
+        assert_eq!(hs.commit, 5);
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn create_snapshot_compact_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let mut rds = RaftMachineStorage::new(rocksdb_engine_handler);
+
+        let mut entries = Vec::new();
+        for idx in 1..=20 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            entries.push(entry);
+        }
+        rds.append(&entries).unwrap();
+        // an entry that is not in the log can not be committed
+        assert_eq!(
+            rds.commmit_index(21).unwrap_err(),
+            Error::Store(RaftStorageError::Unavailable)
+        );
+        assert_eq!(rds.applied_index(), 0);
+        rds.commmit_index(15).unwrap();
+        assert_eq!(rds.first_index(), 1);
+
+        rds.create_snapshot();
+        assert_eq!(rds.first_index(), 15);
+        assert!(rds.entry_by_idx(14).is_none());
+        assert!(rds.entry_by_idx(15).is_some());
+        assert_eq!(rds.last_index(), 20);
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn log_size_bytes_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let mut rds = RaftMachineStorage::new(rocksdb_engine_handler);
+        assert_eq!(rds.log_size_bytes(), 0);
+
+        let mut entries = Vec::new();
+        for idx in 1..=100 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            entry.data = vec![1; 1000];
+            entries.push(entry);
+        }
+        rds.append(&entries).unwrap();
+
+        // the data of the entries plus their index, term, framing and checksum
+        let size = rds.log_size_bytes();
+        assert!(size >= 100 * 1000);
+        assert!(size <= 100 * 1020);
+
+        rds.commmit_index(50).unwrap();
+        rds.create_snapshot();
+        let remaining = rds.last_index() - rds.first_index() + 1;
+        let compacted = rds.log_size_bytes();
+        assert!(compacted >= remaining * 1000);
+        assert!(compacted <= remaining * 1020);
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn snapshot_retain_entries_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let mut rds = RaftMachineStorage::new(rocksdb_engine_handler);
+        rds.set_snapshot_retain_entries(5);
+
+        let mut entries = Vec::new();
+        for idx in 1..=20 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            entries.push(entry);
+        }
+        rds.append(&entries).unwrap();
+        rds.commmit_index(15).unwrap();
+
+        // exactly 5 entries are kept below the snapshot index
+        assert_eq!(rds.create_snapshot(), Some(15));
+        assert_eq!(rds.first_index(), 10);
+        assert!(rds.entry_by_idx(9).is_none());
+        for idx in 10..15 {
+            assert!(rds.entry_by_idx(idx).is_some());
+        }
+        assert_eq!(rds.term(10).unwrap(), 1);
+        assert_eq!(rds.last_index(), 20);
+
+        // more than the log holds, nothing else is compacted
+        rds.set_snapshot_retain_entries(100);
+        rds.commmit_index(20).unwrap();
+        assert_eq!(rds.create_snapshot(), Some(20));
+        assert_eq!(rds.first_index(), 10);
+        assert_eq!(rds.last_index(), 20);
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn snapshot_file_test() {
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let snapshot_path = format!("/tmp/robustmq_{}", unique_id());
+        let rocksdb_engine_handler: Arc<RocksDBEngine> =
+            Arc::new(RocksDBEngine::new(&data_path, 10, column_family_list()));
+        let mut rds = RaftMachineStorage::new(rocksdb_engine_handler.clone());
+        rds.set_snapshot_path(&snapshot_path);
+
+        let cf = rocksdb_engine_handler.cf_cluster();
+        rocksdb_engine_handler
+            .write_raw(cf, "snapshot_key", "snapshot_value".as_bytes())
+            .unwrap();
+        let mut entries = Vec::new();
+        for idx in 1..=10 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            entries.push(entry);
+        }
+        rds.append(&entries).unwrap();
+        rds.commmit_index(5).unwrap();
+
+        // only the metadata of the snapshot is kept in RocksDB
+        assert_eq!(rds.create_snapshot(), Some(5));
+        let first_file = format!("{}/snapshot-1-5.data", snapshot_path);
+        assert!(Path::new(&first_file).exists());
+        let stored = rocksdb_engine_handler
+            .read_raw(cf, &key_name_snapshot())
+            .unwrap()
+            .unwrap();
+        let stored = decode_value::<Snapshot>(&key_name_snapshot(), &stored).unwrap();
+        assert_eq!(stored.get_metadata().index, 5);
+        assert!(stored.get_data().is_empty());
+        let snapshot = rds.saved_snapshot().unwrap();
+        assert_eq!(snapshot.get_data().to_vec(), read(&first_file).unwrap());
+
+        // the next snapshot supersedes the file of the first one
+        rds.commmit_index(8).unwrap();
+        assert_eq!(rds.create_snapshot(), Some(8));
+        let second_file = format!("{}/snapshot-1-8.data", snapshot_path);
+        assert!(Path::new(&second_file).exists());
+        assert!(!Path::new(&first_file).exists());
+        let snapshot = rds.snapshot();
+        assert_eq!(snapshot.get_metadata().index, 8);
+        assert!(!snapshot.get_data().is_empty());
+
+        // another node persists the snapshot and restores it from its file after a restart
+        let restore_data_path = format!("/tmp/robustmq_{}", unique_id());
+        let restore_snapshot_path = format!("/tmp/robustmq_{}", unique_id());
+        let restore_engine: Arc<RocksDBEngine> =
+            Arc::new(RocksDBEngine::new(&restore_data_path, 10, column_family_list()));
+        let mut restore_rds = RaftMachineStorage::new(restore_engine.clone());
+        restore_rds.set_snapshot_path(&restore_snapshot_path);
+        restore_rds.persist_snapshot(&snapshot).unwrap();
+        drop(restore_rds);
+        drop(restore_engine);
+
+        let restore_engine: Arc<RocksDBEngine> =
+            Arc::new(RocksDBEngine::new(&restore_data_path, 10, column_family_list()));
+        let mut restore_rds = RaftMachineStorage::new(restore_engine.clone());
+        restore_rds.set_snapshot_path(&restore_snapshot_path);
+        assert_eq!(restore_rds.recover_snapshot().unwrap(), Some(8));
+        assert_eq!(
+            restore_engine
+                .read_raw(restore_engine.cf_cluster(), "snapshot_key")
+                .unwrap(),
+            Some("snapshot_value".as_bytes().to_vec())
+        );
+
+        // a snapshot applied from the leader supersedes the restored one
+        let mut newer = snapshot.clone();
+        newer.mut_metadata().index = 12;
+        newer.mut_metadata().term = 2;
+        restore_rds.apply_snapshot(newer).unwrap();
+        assert!(Path::new(&format!("{}/snapshot-2-12.data", restore_snapshot_path)).exists());
+        assert!(!Path::new(&format!("{}/snapshot-1-8.data", restore_snapshot_path)).exists());
+        assert_eq!(restore_rds.saved_snapshot().unwrap().get_data(), snapshot.get_data());
+
+        // without its file the saved snapshot cannot be read
+        remove_file(format!("{}/snapshot-2-12.data", restore_snapshot_path)).unwrap();
+        assert!(restore_rds.saved_snapshot().is_none());
+
+        drop(restore_rds);
+        drop(restore_engine);
+        remove_dir_all(restore_data_path).unwrap();
+        remove_dir_all(restore_snapshot_path).unwrap();
+        remove_dir_all(data_path).unwrap();
+        remove_dir_all(snapshot_path).unwrap();
+    }
+
+    #[test]
+    fn cache_index_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        {
+            let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+                &conf.rocksdb.data_path,
+                conf.rocksdb.max_open_files.unwrap(),
+                column_family_list(),
+            ));
+            let mut rds = RaftMachineStorage::new(rocksdb_engine_handler);
+            assert_eq!(rds.first_index(), 1);
+            assert_eq!(rds.last_index(), 0);
+
+            for batch in 0..4 {
+                let mut entries = Vec::new();
+                for idx in 1..=5 {
+                    let mut entry = Entry::default();
+                    entry.index = batch * 5 + idx;
+                    entry.term = 1;
+                    entries.push(entry);
+                }
+                rds.append(&entries).unwrap();
+                assert_eq!(rds.last_index(), (batch + 1) * 5);
+                assert_eq!(rds.last_index(), rds.read_last_index());
+            }
+
+            rds.save_applied_index(20).unwrap();
+            rds.compact(12).unwrap();
+            assert_eq!(rds.first_index(), 12);
+            assert_eq!(rds.first_index(), rds.read_first_index());
+            assert_eq!(rds.last_index(), 20);
+            assert_eq!(rds.last_index(), rds.read_last_index());
+        }
+
+        // a reopened storage loads the cache from the persisted values
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let rds = RaftMachineStorage::new(rocksdb_engine_handler);
+        assert_eq!(rds.first_index(), 12);
+        assert_eq!(rds.last_index(), 20);
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn apply_snapshot_atomic_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let mut rds = RaftMachineStorage::new(rocksdb_engine_handler.clone());
+
+        let mut entries = Vec::new();
+        for idx in 1..=5 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            entries.push(entry);
+        }
+        rds.append(&entries).unwrap();
+        let mut hs = HardState::default();
+        hs.term = 1;
+        hs.commit = 3;
+        rds.save_hard_state(hs).unwrap();
+
+        let build_snapshot = |cf_name: &str| {
+            let raw = (b"snapshot_key".to_vec(), b"snapshot_value".to_vec());
+            let mut data = HashMap::new();
+            data.insert(cf_name.to_string(), vec![raw]);
+
+            let mut snapshot = Snapshot::default();
+            snapshot.mut_metadata().index = 10;
+            snapshot.mut_metadata().term = 2;
+            snapshot.mut_metadata().mut_conf_state().voters = vec![1, 2, 3];
+            snapshot.set_data(serialize(&data).unwrap());
+            return snapshot;
+        };
+
+        // writing to a ColumnFamily that does not exist fails the whole batch
+        assert!(rds.apply_snapshot(build_snapshot("not_exist_cf")).is_err());
+        let hs = rds.hard_state().unwrap();
+        assert_eq!(hs.term, 1);
+        assert_eq!(hs.commit, 3);
+        assert!(rds.conf_state().unwrap().voters.is_empty());
+        assert_eq!(rds.first_index(), 1);
+        assert_eq!(rds.last_index(), 5);
+        assert!(rds.entry_by_idx(5).is_some());
+        let cf = rocksdb_engine_handler.cf_cluster();
+        assert!(rocksdb_engine_handler
+            .db
+            .get_cf(cf, "snapshot_key")
+            .unwrap()
+            .is_none());
+
+        rds.apply_snapshot(build_snapshot("cluster")).unwrap();
+        let hs = rds.hard_state().unwrap();
+        assert_eq!(hs.term, 2);
+        assert_eq!(hs.commit, 10);
+        assert_eq!(rds.conf_state().unwrap().voters, vec![1, 2, 3]);
+        assert_eq!(rds.first_index(), 11);
+        assert_eq!(rds.read_first_index(), 11);
+        assert_eq!(rds.last_index(), 10);
+        assert_eq!(rds.read_last_index(), 10);
+        assert!(rds.entry_by_idx(5).is_none());
+        assert_eq!(
+            rocksdb_engine_handler.db.get_cf(cf, "snapshot_key").unwrap(),
+            Some("snapshot_value".as_bytes().to_vec())
+        );
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn snapshot_round_trip_test() {
+        for codec in [ValueCodec::Json, ValueCodec::Bincode] {
+            let tuning = RocksDBTuning {
+                value_codec: codec,
+                ..Default::default()
+            };
+            let leader_path = format!("/tmp/robustmq_{}", unique_id());
+            let leader_engine: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new_with_tuning(
+                &leader_path,
+                10,
+                column_family_list(),
+                &tuning,
+            ));
+            let mut leader = RaftMachineStorage::new(leader_engine.clone());
+
+            let cf = leader_engine.cf_cluster();
+            leader_engine.write(cf, "/cluster/node/1", &"node-1".to_string()).unwrap();
+            leader_engine.write(cf, "/cluster/tick", &42u64).unwrap();
+
+            let mut entries = Vec::new();
+            for idx in 1..=3 {
+                let mut entry = Entry::default();
+                entry.index = idx;
+                entry.term = 1;
+                entries.push(entry);
+            }
+            leader.append(&entries).unwrap();
+            let mut hs = HardState::default();
+            hs.term = 1;
+            hs.commit = 3;
+            leader.save_hard_state(hs).unwrap();
+            leader.save_applied_index(3).unwrap();
+            assert_eq!(leader.create_snapshot(), Some(3));
+            let snapshot = leader.snapshot();
+
+            let follower_path = format!("/tmp/robustmq_{}", unique_id());
+            let follower_engine: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new_with_tuning(
+                &follower_path,
+                10,
+                column_family_list(),
+                &tuning,
+            ));
+            let mut follower = RaftMachineStorage::new(follower_engine.clone());
+            follower.apply_snapshot(snapshot).unwrap();
+
+            let cf = follower_engine.cf_cluster();
+            assert_eq!(
+                follower_engine.read::<String>(cf, "/cluster/node/1").unwrap(),
+                Some("node-1".to_string())
+            );
+            assert_eq!(
+                follower_engine.read::<u64>(cf, "/cluster/tick").unwrap(),
+                Some(42)
+            );
+            assert_eq!(follower.applied_index(), 3);
+
+            drop(leader);
+            drop(leader_engine);
+            drop(follower);
+            drop(follower_engine);
+            remove_dir_all(leader_path).unwrap();
+            remove_dir_all(follower_path).unwrap();
+        }
+    }
+
+    #[test]
+    fn apply_snapshot_replaces_state_test() {
+        let leader_path = format!("/tmp/robustmq_{}", unique_id());
+        let leader_engine: Arc<RocksDBEngine> =
+            Arc::new(RocksDBEngine::new(&leader_path, 10, column_family_list()));
+        let mut leader = RaftMachineStorage::new(leader_engine.clone());
+        let follower_path = format!("/tmp/robustmq_{}", unique_id());
+        let follower_engine: Arc<RocksDBEngine> =
+            Arc::new(RocksDBEngine::new(&follower_path, 10, column_family_list()));
+        let mut follower = RaftMachineStorage::new(follower_engine.clone());
+
+        // both nodes applied the writes, the follower stopped before the delete
+        for engine in [&leader_engine, &follower_engine] {
+            let cf = engine.cf_cluster();
+            engine.write(cf, "/cluster/node/1", &1u64).unwrap();
+            engine.write(cf, "/cluster/node/2", &2u64).unwrap();
+        }
+        leader_engine
+            .delete(leader_engine.cf_cluster(), "/cluster/node/2")
+            .unwrap();
+
+        let mut entries = Vec::new();
+        for idx in 1..=3 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            entries.push(entry);
+        }
+        leader.append(&entries).unwrap();
+        let mut hs = HardState::default();
+        hs.term = 1;
+        hs.commit = 3;
+        leader.save_hard_state(hs).unwrap();
+        leader.save_applied_index(3).unwrap();
+        assert_eq!(leader.create_snapshot(), Some(3));
+
+        follower.apply_snapshot(leader.snapshot()).unwrap();
+        let cf = follower_engine.cf_cluster();
+        assert_eq!(follower_engine.read::<u64>(cf, "/cluster/node/1").unwrap(), Some(1));
+        assert!(follower_engine
+            .read::<u64>(cf, "/cluster/node/2")
+            .unwrap()
+            .is_none());
+        // the Raft state of the follower is its own
+        assert_eq!(follower.hard_state().unwrap().commit, 3);
+        assert_eq!(follower.first_index(), 4);
+
+        drop(leader);
+        drop(leader_engine);
+        drop(follower);
+        drop(follower_engine);
+        remove_dir_all(leader_path).unwrap();
+        remove_dir_all(follower_path).unwrap();
+    }
+
+    #[test]
+    fn apply_stale_snapshot_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let mut rds = RaftMachineStorage::new(rocksdb_engine_handler);
+
+        let mut entries = Vec::new();
+        for idx in 1..=5 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            entries.push(entry);
+        }
+        rds.append(&entries).unwrap();
+        let mut hs = HardState::default();
+        hs.term = 1;
+        hs.commit = 5;
+        rds.save_hard_state(hs).unwrap();
+
+        // the snapshot is still in the log, but behind the commit index
+        let mut snapshot = Snapshot::default();
+        snapshot.mut_metadata().index = 3;
+        snapshot.mut_metadata().term = 1;
+        let data: HashMap<String, Vec<HashMap<String, String>>> = HashMap::new();
+        snapshot.set_data(serialize(&data).unwrap());
+        assert!(matches!(
+            rds.apply_snapshot(snapshot),
+            Err(Error::Store(RaftStorageError::SnapshotOutOfDate))
+        ));
+
+        assert_eq!(rds.hard_state().unwrap().commit, 5);
+        assert_eq!(rds.first_index(), 1);
+        assert_eq!(rds.last_index(), 5);
+        assert!(rds.entry_by_idx(4).is_some());
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn snapshot_metadata_restart_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let mut rds = RaftMachineStorage::new(rocksdb_engine_handler.clone());
+
+        let mut snapshot = Snapshot::default();
+        snapshot.mut_metadata().index = 10;
+        snapshot.mut_metadata().term = 2;
+        snapshot.mut_metadata().mut_conf_state().voters = vec![1];
+        let data: HashMap<String, Vec<HashMap<String, String>>> = HashMap::new();
+        snapshot.set_data(serialize(&data).unwrap());
+        rds.apply_snapshot(snapshot).unwrap();
+        drop(rds);
+        drop(rocksdb_engine_handler);
+
+        // the node restarts on the same data path
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let rds = RaftMachineStorage::new(rocksdb_engine_handler);
+        assert_eq!(rds.snapshot_metadata.index, 10);
+        assert_eq!(rds.snapshot_metadata.term, 2);
+        assert_eq!(rds.first_index(), 11);
+        assert_eq!(rds.last_index(), 10);
+        assert_eq!(rds.term(10).unwrap(), 2);
+        assert_eq!(rds.applied_index(), 10);
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn recover_snapshot_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let rds = RaftMachineStorage::new(rocksdb_engine_handler.clone());
+
+        // a snapshot in the layout taken before the values were kept as bytes
+        let mut raw = HashMap::new();
+        raw.insert("snapshot_key".to_string(), "snapshot_value".to_string());
+        // the Raft state of the sender is not restored
+        raw.insert(key_name_by_first_index(), "1".to_string());
+        let mut data = HashMap::new();
+        data.insert("cluster".to_string(), vec![raw]);
+        let mut snapshot = Snapshot::default();
+        snapshot.mut_metadata().index = 10;
+        snapshot.mut_metadata().term = 2;
+        snapshot.mut_metadata().mut_conf_state().voters = vec![1];
+        snapshot.set_data(serialize(&data).unwrap());
+
+        // the node stops after the snapshot is persisted, before its data is restored
+        rds.persist_snapshot(&snapshot).unwrap();
+        drop(rds);
+        drop(rocksdb_engine_handler);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let mut rds = RaftMachineStorage::new(rocksdb_engine_handler.clone());
+        let cf = rocksdb_engine_handler.cf_cluster();
+        assert_eq!(rds.first_index(), 11);
+        assert_eq!(rds.hard_state().unwrap().commit, 10);
+        assert!(rocksdb_engine_handler
+            .db
+            .get_cf(cf, "snapshot_key")
+            .unwrap()
+            .is_none());
+
+        assert_eq!(rds.recover_snapshot().unwrap(), Some(10));
+        assert_eq!(
+            rocksdb_engine_handler.db.get_cf(cf, "snapshot_key").unwrap(),
+            Some("snapshot_value".as_bytes().to_vec())
+        );
+        assert_eq!(rds.read_first_index(), 11);
+        assert_eq!(rds.applied_index(), 10);
+        assert_eq!(rds.conf_state().unwrap().voters, vec![1]);
+
+        // nothing is left to restore
+        assert_eq!(rds.recover_snapshot().unwrap(), None);
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn reset_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let mut rds = RaftMachineStorage::new(rocksdb_engine_handler.clone());
+
+        let mut entries = Vec::new();
+        for idx in 1..=5 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            entries.push(entry);
+        }
+        rds.append(&entries).unwrap();
+        let mut hs = HardState::default();
+        hs.term = 1;
+        hs.commit = 5;
+        rds.save_hard_state(hs).unwrap();
+        let mut cs = ConfState::default();
+        cs.voters = vec![1, 2];
+        rds.save_conf_state(cs).unwrap();
+        rds.save_applied_index(5).unwrap();
+        rds.create_snapshot().unwrap();
+        let cf = rocksdb_engine_handler.cf_cluster();
+        rocksdb_engine_handler
+            .write_str(cf, "/clusters/reset_test", "cluster".to_string())
+            .unwrap();
+
+        // the reset has to be confirmed
+        assert!(rds.reset(false).is_err());
+        assert_eq!(rds.hard_state().unwrap().commit, 5);
+
+        rds.reset(true).unwrap();
+        assert_eq!(rds.first_index(), 1);
+        assert_eq!(rds.last_index(), 0);
+        assert_eq!(rds.read_first_index(), 1);
+        assert_eq!(rds.read_last_index(), 0);
+        assert_eq!(rds.hard_state().unwrap(), HardState::default());
+        assert!(rds.conf_state().unwrap().voters.is_empty());
+        assert_eq!(rds.applied_index(), 0);
+        assert_eq!(rds.snapshot_metadata, SnapshotMetadata::default());
+        assert!(rds.saved_snapshot().is_none());
+        assert!(rds.entry_by_idx(1).is_none());
+        assert!(rds.uncommit_index().is_empty());
+
+        // the data outside of the Raft keys is kept
+        assert_eq!(
+            rocksdb_engine_handler.read_raw(cf, "/clusters/reset_test").unwrap(),
+            Some("cluster".as_bytes().to_vec())
+        );
+
+        // a restart does not bring the old state back
+        let rds = RaftMachineStorage::new(rocksdb_engine_handler);
+        assert_eq!(rds.first_index(), 1);
+        assert_eq!(rds.last_index(), 0);
+        assert_eq!(rds.snapshot_metadata, SnapshotMetadata::default());
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn snapshot_empty_log_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let mut rds = RaftMachineStorage::new(rocksdb_engine_handler);
+        assert!(rds.create_snapshot_metadata().unwrap().is_none());
+
+        let snapshot = rds.snapshot();
+        assert_eq!(snapshot.get_metadata().index, 0);
+        assert!(rds.saved_snapshot().is_none());
+
+        // the entries committed but not applied yet are not in the data of the snapshot
+        let mut entries = Vec::new();
+        for idx in 1..=2 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 2;
+            entries.push(entry);
+        }
+        rds.append(&entries).unwrap();
+        let mut hs = HardState::default();
+        hs.term = 3;
+        hs.commit = 2;
+        rds.save_hard_state(hs).unwrap();
+        assert!(rds.create_snapshot_metadata().unwrap().is_none());
+        rds.save_applied_index(1).unwrap();
+        let meta = rds.create_snapshot_metadata().unwrap().unwrap();
+        assert_eq!(meta.index, 1);
+        assert_eq!(meta.term, 2);
+
+        // an applied index ahead of the log is capped at the last entry
+        rds.save_applied_index(5).unwrap();
+        let meta = rds.create_snapshot_metadata().unwrap().unwrap();
+        assert_eq!(meta.index, 2);
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn snapshot_after_compaction_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let mut rds = RaftMachineStorage::new(rocksdb_engine_handler);
+
+        let mut entries = Vec::new();
+        for idx in 1..=20 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = if idx <= 10 { 1 } else { 2 };
+            entries.push(entry);
+        }
+        rds.append(&entries).unwrap();
+        rds.commmit_index(15).unwrap();
+
+        rds.create_snapshot();
+        assert_eq!(rds.snapshot_metadata.index, 15);
+        assert_eq!(rds.snapshot_metadata.term, 2);
+
+        // the entry at the snapshot index is gone, its term comes from the snapshot
+        rds.compact(16).unwrap();
+        assert!(rds.entry_by_idx(15).is_none());
+        let meta = rds.create_snapshot_metadata().unwrap().unwrap();
+        assert_eq!(meta.index, 15);
+        assert_eq!(meta.term, 2);
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn migrate_storage_format_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let cf = rocksdb_engine_handler.cf_cluster();
+
+        // the layout of older versions, prost bytes serialized as JSON arrays
+        let mut entry = Entry::default();
+        entry.index = 1;
+        entry.term = 2;
+        entry.data = b"json-wrapped".to_vec();
+        rocksdb_engine_handler
+            .write(cf, &key_name_by_entry(1), &Entry::encode_to_vec(&entry))
+            .unwrap();
+        rocksdb_engine_handler.write(cf, &key_name_by_last_index(), &1u64).unwrap();
+        let mut hs = HardState::default();
+        hs.term = 2;
+        hs.commit = 1;
+        rocksdb_engine_handler
+            .write(cf, &key_name_by_hard_state(), &HardState::encode_to_vec(&hs))
+            .unwrap();
+
+        let rds = RaftMachineStorage::new(rocksdb_engine_handler.clone());
+        let migrated = rds.entry_by_idx(1).unwrap();
+        assert_eq!(migrated, entry);
+        assert_eq!(rds.hard_state().unwrap(), hs);
+        assert_eq!(rds.last_index(), 1);
+        assert_eq!(
+            rocksdb_engine_handler.read_raw(cf, &key_name_by_entry(1)).unwrap(),
+            Some(encode_entry(&entry))
+        );
+
+        // values are left alone once the storage is migrated
+        let rds = RaftMachineStorage::new(rocksdb_engine_handler.clone());
+        assert_eq!(rds.entry_by_idx(1).unwrap(), entry);
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn migrate_entry_keys_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let cf = rocksdb_engine_handler.cf_cluster();
+
+        // the previous format, /raft/entry/10 sorts before /raft/entry/9
+        let mut entries = Vec::new();
+        for idx in 1..=12 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            let key = format!("{}{}", key_name_entry_prefix(), idx);
+            rocksdb_engine_handler.write_raw(cf, &key, &encode_entry(&entry)).unwrap();
+            entries.push(entry);
+        }
+        rocksdb_engine_handler.write(cf, &key_name_by_last_index(), &12u64).unwrap();
+        rocksdb_engine_handler
+            .write(cf, &key_name_storage_format(), &RAFT_STORAGE_FORMAT_VERSIONED)
+            .unwrap();
+
+        let rds = RaftMachineStorage::new_with_entry_cache(rocksdb_engine_handler.clone(), 0);
+        assert_eq!(rds.entries(1, 13, None).unwrap(), entries);
+        assert!(rocksdb_engine_handler
+            .read_raw(cf, &format!("{}9", key_name_entry_prefix()))
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            rocksdb_engine_handler.read_raw(cf, &key_name_by_entry(9)).unwrap(),
+            Some(encode_entry(&entries[8]))
+        );
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn value_format_version_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let cf = rocksdb_engine_handler.cf_cluster();
+
+        // a data path of the previous format, values without the version byte
+        let mut entry = Entry::default();
+        entry.index = 1;
+        entry.term = 1;
+        entry.data = b"unversioned".to_vec();
+        rocksdb_engine_handler
+            .write_raw(cf, &key_name_by_entry(1), &checksum_value(&Entry::encode_to_vec(&entry)))
+            .unwrap();
+        rocksdb_engine_handler.write(cf, &key_name_by_last_index(), &1u64).unwrap();
+        let mut hs = HardState::default();
+        hs.term = 1;
+        hs.commit = 1;
+        rocksdb_engine_handler
+            .write_raw(cf, &key_name_by_hard_state(), &HardState::encode_to_vec(&hs))
+            .unwrap();
+        rocksdb_engine_handler
+            .write(cf, &key_name_storage_format(), &RAFT_STORAGE_FORMAT_CHECKSUM)
+            .unwrap();
+
+        let rds = RaftMachineStorage::new_with_entry_cache(rocksdb_engine_handler.clone(), 0);
+        assert_eq!(rds.read_entry(1).unwrap(), Some(entry.clone()));
+        assert_eq!(rds.hard_state().unwrap(), hs);
+
+        // values are written and read in version 1
+        hs.commit = 2;
+        rds.save_hard_state(hs.clone()).unwrap();
+        let value = rocksdb_engine_handler
+            .read_raw(cf, &key_name_by_hard_state())
+            .unwrap()
+            .unwrap();
+        assert_eq!(value[0], RAFT_VALUE_VERSION_1);
+        assert_eq!(value, encode_value(&hs));
+        assert_eq!(rds.hard_state().unwrap(), hs);
+        let entry_value = rocksdb_engine_handler
+            .read_raw(cf, &key_name_by_entry(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry_value, encode_entry(&entry));
+
+        // a version this build does not know is reported instead of decoded
+        let mut unknown = encode_entry(&entry);
+        unknown[0] = 9;
+        rocksdb_engine_handler.write_raw(cf, &key_name_by_entry(1), &unknown).unwrap();
+        match rds.read_entry(1) {
+            Err(StorageError::UnknownFormatVersion(key, version)) => {
+                assert_eq!(key, key_name_by_entry(1));
+                assert_eq!(version, 9);
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+        let mut unknown = encode_value(&hs);
+        unknown[0] = 9;
+        let err = decode_value::<HardState>(&key_name_by_hard_state(), &unknown).unwrap_err();
+        assert!(err.to_string().contains("unknown format version 9"));
+        assert!(decode_value::<HardState>(&key_name_by_hard_state(), &[]).is_err());
+
+        // the stored HardState of an unknown version fails the read instead of the node
+        rocksdb_engine_handler.write_raw(cf, &key_name_by_hard_state(), &unknown).unwrap();
+        match rds.hard_state() {
+            Err(StorageError::UnknownFormatVersion(key, version)) => {
+                assert_eq!(key, key_name_by_hard_state());
+                assert_eq!(version, 9);
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert!(rds.raft_state().is_err());
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn commit_entries_batch_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let mut rds = RaftMachineStorage::new(rocksdb_engine_handler.clone());
+
+        let mut entries = Vec::new();
+        for idx in 1..=500 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            entries.push(entry);
+        }
+        rds.append(&entries).unwrap();
+        // the ready loop records the term and commit index before the entries are applied
+        let mut hs = HardState::default();
+        hs.term = 1;
+        hs.commit = 500;
+        rds.save_hard_state(hs).unwrap();
+
+        let apply_batch_size = 100;
+        let before = rocksdb_engine_handler.write_calls();
+        for run in entries.chunks(apply_batch_size) {
+            let indexes: Vec<u64> = run.iter().map(|entry| entry.index).collect();
+            rds.commit_entries(&indexes).unwrap();
+            assert_eq!(rds.applied_index(), *indexes.last().unwrap());
+            assert_eq!(rds.hard_state().unwrap().commit, 500);
+        }
+
+        // one batch per run, committing the entries one by one takes three writes each
+        assert_eq!(rocksdb_engine_handler.write_calls() - before, 5);
+        assert_eq!(rds.applied_index(), 500);
+        assert_eq!(rds.hard_state().unwrap().commit, 500);
+        assert_eq!(rds.hard_state().unwrap().term, 1);
+        assert!(rds.uncommit_index.is_empty());
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn compact_beyond_applied_index_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let mut rds = RaftMachineStorage::new(rocksdb_engine_handler);
+
+        let mut entries = Vec::new();
+        for idx in 1..=20 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            entries.push(entry);
+        }
+        rds.append(&entries).unwrap();
+        let indexes: Vec<u64> = (1..=10).collect();
+        rds.commit_entries(&indexes).unwrap();
+
+        // only the applied entries are deleted, the rest is still there for recovery
+        rds.compact(18).unwrap();
+        assert_eq!(rds.first_index(), 11);
+        assert!(rds.entry_by_idx(10).is_none());
+        for idx in 11..=20 {
+            assert_eq!(rds.entry_by_idx(idx).unwrap().index, idx);
+        }
+
+        // nothing new has been applied, compacting again removes nothing
+        rds.compact(20).unwrap();
+        assert_eq!(rds.first_index(), 11);
+        assert_eq!(rds.entry_by_idx(11).unwrap().index, 11);
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn uncommitted_indices_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let mut rds = RaftMachineStorage::new(rocksdb_engine_handler.clone());
+        assert!(rds.uncommitted_indices().is_empty());
+
+        let mut entries = Vec::new();
+        for idx in 1..=20 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            entries.push(entry);
+        }
+        rds.append(&entries).unwrap();
+        assert_eq!(rds.uncommitted_indices(), (1..=20).collect::<Vec<u64>>());
+
+        rds.commit_entries(&[1, 2, 3, 4, 5]).unwrap();
+        assert_eq!(rds.uncommitted_indices(), (6..=20).collect::<Vec<u64>>());
+
+        // the list is rebuilt from RocksDB when the storage is opened again
+        let rds = RaftMachineStorage::new(rocksdb_engine_handler);
+        assert_eq!(rds.uncommitted_indices(), (6..=20).collect::<Vec<u64>>());
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn append_batch_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let mut rds = RaftMachineStorage::new(rocksdb_engine_handler.clone());
+
+        let mut entries = Vec::new();
+        for idx in 1..=100 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            entries.push(entry);
+        }
+        let before = rocksdb_engine_handler.write_calls();
+        rds.append(&entries).unwrap();
+
+        // one batch for the entries, then exactly one write of the last index
+        assert_eq!(rocksdb_engine_handler.write_calls() - before, 2);
+        assert_eq!(rds.last_index(), 100);
+        assert_eq!(rds.uncommit_index.len(), 100);
+        assert!(rds.verify().is_ok());
+
+        // a crash after the entry batch but before the last index leaves the shorter log
+        let cf = rocksdb_engine_handler.cf_cluster();
+        let mut batch = WriteBatch::default();
+        for idx in 101..=110 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            batch.put_cf(cf, key_name_by_entry(idx), encode_entry(&entry));
+        }
+        rocksdb_engine_handler.write_batch(batch).unwrap();
+
+        let rds = RaftMachineStorage::new(rocksdb_engine_handler);
+        assert_eq!(rds.last_index(), 100);
+        assert!(rds.verify().is_ok());
+        assert_eq!(rds.entry_by_idx(100).unwrap().index, 100);
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn append_error_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let mut rds = RaftMachineStorage::new(rocksdb_engine_handler.clone());
+
+        let build_entries = |low: u64, high: u64| -> Vec<Entry> {
+            let mut entries = Vec::new();
+            for idx in low..=high {
+                let mut entry = Entry::default();
+                entry.index = idx;
+                entry.term = 1;
+                entries.push(entry);
+            }
+            return entries;
+        };
+        rds.append(&build_entries(1, 5)).unwrap();
+
+        // the failure is returned with the first index that was not persisted
+        rds.fail_append = true;
+        match rds.append(&build_entries(6, 10)) {
+            Err(Error::Store(RaftStorageError::Other(e))) => {
+                match e.downcast_ref::<PlacementCenterError>() {
+                    Some(PlacementCenterError::RaftLogAppendFailed(index, _)) => {
+                        assert_eq!(*index, 6)
+                    }
+                    _ => panic!("append should fail with RaftLogAppendFailed, got {}", e),
+                }
+            }
+            _ => panic!("append should fail when RocksDB rejects the write"),
+        }
+        assert_eq!(rds.last_index(), 5);
+        assert_eq!(rds.uncommit_index.len(), 5);
+        assert!(rds.entry_by_idx(6).is_none());
+        assert!(rds.verify().is_ok());
+
+        // nothing is left behind, the same entries can be appended again
+        rds.fail_append = false;
+        rds.append(&build_entries(6, 10)).unwrap();
+        assert_eq!(rds.last_index(), 10);
+        assert_eq!(rds.uncommit_index.len(), 10);
+        assert!(rds.verify().is_ok());
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn verify_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let mut rds = RaftMachineStorage::new(rocksdb_engine_handler.clone());
+        assert!(rds.verify().is_ok());
+
+        let mut entries = Vec::new();
+        for idx in 1..=10 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            entries.push(entry);
+        }
+        rds.append(&entries).unwrap();
+        let mut cs = ConfState::default();
+        cs.voters = vec![1];
+        rds.save_conf_state(cs).unwrap();
+        let mut hs = HardState::default();
+        hs.term = 1;
+        hs.commit = 8;
+        rds.save_hard_state(hs.clone()).unwrap();
+        assert!(rds.verify().is_ok());
+
+        // a missing entry in the middle of the log
+        rocksdb_engine_handler
+            .delete(rocksdb_engine_handler.cf_cluster(), &key_name_by_entry(5))
+            .unwrap();
+        let violations = rds.verify().unwrap_err();
+        assert_eq!(violations, vec!["entry at index 5 does not exist".to_string()]);
+
+        // the commit beyond the end of the log is reported along with the missing entry
+        hs.commit = 11;
+        rds.save_hard_state(hs).unwrap();
+        let violations = rds.verify().unwrap_err();
+        assert_eq!(violations.len(), 2);
+        assert!(violations[1].starts_with("hard state commit 11"));
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn entry_cache_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let mut rds = RaftMachineStorage::new_with_entry_cache(rocksdb_engine_handler.clone(), 5);
+
+        let mut entries = Vec::new();
+        for idx in 1..=10 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            entries.push(entry);
+        }
+        rds.append(&entries).unwrap();
+
+        // the last five entries are served from memory, older ones from RocksDB
+        let reads = rocksdb_engine_handler.read_calls();
+        for idx in 6..=10 {
+            assert_eq!(rds.entry_by_idx(idx).unwrap().index, idx);
+        }
+        assert_eq!(rocksdb_engine_handler.read_calls(), reads);
+        assert_eq!(rds.entry_by_idx(5).unwrap().index, 5);
+        assert_eq!(rocksdb_engine_handler.read_calls(), reads + 1);
+
+        // the compacted entries are gone from the cache as well
+        rds.save_applied_index(10).unwrap();
+        rds.compact(8).unwrap();
+        let reads = rocksdb_engine_handler.read_calls();
+        assert!(rds.entry_by_idx(7).is_none());
+        assert_eq!(rocksdb_engine_handler.read_calls(), reads + 1);
+        assert_eq!(rds.entry_by_idx(8).unwrap().index, 8);
+        assert_eq!(rocksdb_engine_handler.read_calls(), reads + 1);
+
+        // an overwritten entry is not served from the cache
+        let mut entry = Entry::default();
+        entry.index = 9;
+        entry.term = 2;
+        rds.append(&vec![entry]).unwrap();
+        assert_eq!(rds.entry_by_idx(9).unwrap().term, 2);
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn entries_batch_read_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let mut rds = RaftMachineStorage::new_with_entry_cache(rocksdb_engine_handler.clone(), 10);
+        let mut entries = Vec::new();
+        for idx in 1..=200 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            entry.data = vec![1; 16];
+            entries.push(entry);
+        }
+        rds.append(&entries).unwrap();
+
+        // the window spans several batches and the entry cache
+        assert_eq!(rds.entries(1, 201, None).unwrap(), entries);
+        assert_eq!(rds.entries(60, 140, None).unwrap(), entries[59..139].to_vec());
+
+        // the size budget, at least one entry is returned
+        let entry_size = entries[0].encoded_len() as u64;
+        assert_eq!(rds.entries(1, 201, Some(entry_size * 3)).unwrap().len(), 3);
+        assert_eq!(rds.entries(1, 201, Some(0)).unwrap().len(), 1);
+
+        // a hole in the log is reported instead of skipped
+        rocksdb_engine_handler
+            .delete(rocksdb_engine_handler.cf_cluster(), &key_name_by_entry(100))
+            .unwrap();
+        match rds.entries(90, 110, None) {
+            Err(Error::Store(RaftStorageError::Unavailable)) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    // Reads a 1000 entry window entry by entry and with entries(), run with --ignored
+    #[test]
+    #[ignore]
+    fn entries_read_bench() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        // without the entry cache every read goes to RocksDB
+        let mut rds = RaftMachineStorage::new_with_entry_cache(rocksdb_engine_handler, 0);
+        let mut entries = Vec::new();
+        for idx in 1..=1000 {
+            let mut entry = Entry::default();
+            entry.index = idx;
+            entry.term = 1;
+            entry.data = vec![1; 256];
+            entries.push(entry);
+        }
+        rds.append(&entries).unwrap();
+
+        let rounds = 100;
+        let start = Instant::now();
+        for _ in 0..rounds {
+            let mut window = Vec::new();
+            for idx in 1..=1000 {
+                window.push(rds.entry_by_idx(idx).unwrap());
+            }
+            assert_eq!(window.len(), 1000);
+        }
+        let point_lookup = start.elapsed();
+
+        let start = Instant::now();
+        for _ in 0..rounds {
+            assert_eq!(rds.entries(1, 1001, None).unwrap(), entries);
+        }
+        let batched = start.elapsed();
+        assert!(
+            batched < point_lookup,
+            "1000 entries x {}: point lookups {:?}, entries() {:?}",
+            rounds,
+            point_lookup,
+            batched
+        );
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
+
+    #[test]
+    fn entry_checksum_test() {
+        let mut conf = PlacementCenterConfig::default();
+        conf.rocksdb.data_path = format!("/tmp/robustmq_{}", unique_id());
+        conf.rocksdb.max_open_files = Some(10);
+
+        let rocksdb_engine_handler: Arc<RocksDBEngine> = Arc::new(RocksDBEngine::new(
+            &conf.rocksdb.data_path,
+            conf.rocksdb.max_open_files.unwrap(),
+            column_family_list(),
+        ));
+        let cf = rocksdb_engine_handler.cf_cluster();
+        // no entry cache, every read goes to RocksDB
+        let mut rds = RaftMachineStorage::new_with_entry_cache(rocksdb_engine_handler.clone(), 0);
+
+        let mut entry = Entry::default();
+        entry.index = 1;
+        entry.term = 1;
+        entry.data = b"checksum".to_vec();
+        rds.append(&vec![entry.clone()]).unwrap();
+        assert_eq!(rds.read_entry(1).unwrap(), Some(entry));
+
+        // flip a byte of the stored entry
+        let key = key_name_by_entry(1);
+        let mut value = rocksdb_engine_handler.read_raw(cf, &key).unwrap().unwrap();
+        let last = value.len() - 1;
+        value[last] ^= 0xff;
+        rocksdb_engine_handler.write_raw(cf, &key, &value).unwrap();
+
+        match rds.read_entry(1) {
+            Err(StorageError::ChecksumMismatch(corrupted)) => assert_eq!(corrupted, key),
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert!(rds.entry_by_idx(1).is_none());
+
+        remove_dir_all(conf.rocksdb.data_path).unwrap();
+    }
 }
@@ -12,12 +12,81 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-pub use rocksdb_engine::RocksDBEngine;
+pub use rocksdb_engine::{RocksDBEngine, RocksDBTuning};
 
 pub const DB_COLUMN_FAMILY_CLUSTER: &str = "cluster";
 
+/// The column families the storage is opened with. The default ones always come first,
+/// subsystems register their own on top of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnFamilyRegistry {
+    names: Vec<String>,
+}
+
+impl ColumnFamilyRegistry {
+    pub fn new() -> Self {
+        return ColumnFamilyRegistry {
+            names: vec![DB_COLUMN_FAMILY_CLUSTER.to_string()],
+        };
+    }
+
+    // Registering a name twice keeps a single column family
+    pub fn register(mut self, name: &str) -> Self {
+        if !self.names.iter().any(|family| family == name) {
+            self.names.push(name.to_string());
+        }
+        return self;
+    }
+
+    pub fn register_all(self, names: &[&str]) -> Self {
+        return names.iter().fold(self, |registry, name| registry.register(name));
+    }
+
+    pub fn build(self) -> Vec<String> {
+        return self.names;
+    }
+}
+
+impl Default for ColumnFamilyRegistry {
+    fn default() -> Self {
+        return ColumnFamilyRegistry::new();
+    }
+}
+
 pub fn column_family_list() -> Vec<String> {
-    let mut list = Vec::new();
-    list.push(DB_COLUMN_FAMILY_CLUSTER.to_string());
-    return list;
+    return ColumnFamilyRegistry::new().build();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{column_family_list, ColumnFamilyRegistry, RocksDBEngine, DB_COLUMN_FAMILY_CLUSTER};
+    use common_base::tools::unique_id;
+    use std::fs::remove_dir_all;
+
+    #[test]
+    fn register_column_family_test() {
+        let cf_list = ColumnFamilyRegistry::new()
+            .register("journal")
+            .register_all(&["journal", DB_COLUMN_FAMILY_CLUSTER, "mqtt_record"])
+            .build();
+        assert_eq!(cf_list, vec!["cluster", "journal", "mqtt_record"]);
+        assert_eq!(ColumnFamilyRegistry::default().build(), column_family_list());
+
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let engine = RocksDBEngine::new(&data_path, 10, cf_list);
+        let journal_cf = engine.cf_by_name("journal").unwrap();
+        engine.write(journal_cf, "/journal/1", &1u64).unwrap();
+        engine.write(engine.cf_cluster(), "/cluster/1", &2u64).unwrap();
+        assert_eq!(engine.read::<u64>(journal_cf, "/journal/1").unwrap(), Some(1));
+        assert!(engine.read::<u64>(journal_cf, "/cluster/1").unwrap().is_none());
+
+        let all = engine.read_all();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all["journal"][0].0, b"/journal/1".to_vec());
+        assert_eq!(all["cluster"][0].0, b"/cluster/1".to_vec());
+        assert!(all["mqtt_record"].is_empty());
+
+        drop(engine);
+        remove_dir_all(data_path).unwrap();
+    }
 }
@@ -0,0 +1,127 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::storage::placement::raft::{decode_entry, encode_entry};
+use crate::storage::rocksdb::RocksDBEngine;
+use common_base::error::placement_center::PlacementCenterError;
+use log::info;
+use raft::eraftpb::{Entry, EntryType};
+use rocksdb_engine::StorageError as EngineError;
+
+// Key the encoded canary entry is written to in the cluster column family
+const SELF_CHECK_ENTRY_KEY: &str = "/self_check/entry";
+
+/// Round trip checks of the storage run at startup when rocksdb.self_check is set: a
+/// canary value in every column family and a Raft entry through the entry encoding.
+pub fn storage_self_check(engine: &RocksDBEngine) -> Result<(), PlacementCenterError> {
+    engine
+        .self_check()
+        .map_err(|e| PlacementCenterError::StorageSelfCheckFailed(e.to_string()))?;
+    check_entry_codec(engine, encode_entry, decode_entry)?;
+    info!("Storage self-check passed");
+    return Ok(());
+}
+
+// Writes an entry encoded with encode, then reads it back and decodes it with decode
+fn check_entry_codec(
+    engine: &RocksDBEngine,
+    encode: fn(&Entry) -> Vec<u8>,
+    decode: fn(&str, &[u8]) -> Result<Entry, EngineError>,
+) -> Result<(), PlacementCenterError> {
+    let mut entry = Entry::default();
+    entry.set_entry_type(EntryType::EntryNormal);
+    entry.index = 1;
+    entry.term = 1;
+    entry.data = b"self-check".to_vec();
+    entry.context = b"context".to_vec();
+
+    let failed = |reason: String| {
+        return PlacementCenterError::StorageSelfCheckFailed(format!(
+            "the Raft entry does not round trip, {}",
+            reason
+        ));
+    };
+    let cf = engine.cf_cluster();
+    engine
+        .write_raw(cf, SELF_CHECK_ENTRY_KEY, &encode(&entry))
+        .map_err(|e| failed(e.to_string()))?;
+    let value = engine.read_raw(cf, SELF_CHECK_ENTRY_KEY);
+    engine
+        .delete(cf, SELF_CHECK_ENTRY_KEY)
+        .map_err(|e| failed(e.to_string()))?;
+    let value = match value.map_err(|e| failed(e.to_string()))? {
+        Some(value) => value,
+        None => return Err(failed("the written value is missing".to_string())),
+    };
+
+    let decoded = decode(SELF_CHECK_ENTRY_KEY, &value).map_err(|e| failed(e.to_string()))?;
+    if decoded != entry {
+        return Err(failed(format!("wrote {:?} but read back {:?}", entry, decoded)));
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_entry_codec, storage_self_check, SELF_CHECK_ENTRY_KEY};
+    use crate::storage::placement::raft::{decode_entry, encode_entry};
+    use crate::storage::rocksdb::{column_family_list, RocksDBEngine};
+    use common_base::error::placement_center::PlacementCenterError;
+    use common_base::tools::unique_id;
+    use raft::eraftpb::Entry;
+    use std::fs::remove_dir_all;
+
+    #[test]
+    fn storage_self_check_test() {
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let engine = RocksDBEngine::new(&data_path, 10, column_family_list());
+
+        storage_self_check(&engine).unwrap();
+        assert!(!engine.exist(engine.cf_cluster(), SELF_CHECK_ENTRY_KEY));
+
+        drop(engine);
+        remove_dir_all(data_path).unwrap();
+    }
+
+    #[test]
+    fn broken_entry_codec_test() {
+        let data_path = format!("/tmp/robustmq_{}", unique_id());
+        let engine = RocksDBEngine::new(&data_path, 10, column_family_list());
+
+        // the last byte of the entry gets lost, its checksum no longer matches
+        fn truncated(entry: &Entry) -> Vec<u8> {
+            let mut value = encode_entry(entry);
+            value.pop();
+            return value;
+        }
+        match check_entry_codec(&engine, truncated, decode_entry) {
+            Err(PlacementCenterError::StorageSelfCheckFailed(reason)) => {
+                assert!(reason.contains("Raft entry"));
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        // an encoding the decoder does not know
+        fn unversioned(entry: &Entry) -> Vec<u8> {
+            let mut value = encode_entry(entry);
+            value[0] = 9;
+            return value;
+        }
+        assert!(check_entry_codec(&engine, unversioned, decode_entry).is_err());
+        assert!(!engine.exist(engine.cf_cluster(), SELF_CHECK_ENTRY_KEY));
+
+        drop(engine);
+        remove_dir_all(data_path).unwrap();
+    }
+}
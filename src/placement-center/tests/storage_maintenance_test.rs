@@ -0,0 +1,93 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use protocol::placement_center::generate::{
+        kv::{kv_service_client::KvServiceClient, GetRequest, SetRequest},
+        placement::{
+            placement_center_service_client::PlacementCenterServiceClient, CompactStorageRequest,
+            FlushStorageRequest,
+        },
+    };
+
+    use crate::common::pc_addr;
+
+    #[tokio::test]
+    async fn flush_storage() {
+        let mut kv_client = KvServiceClient::connect(pc_addr()).await.unwrap();
+        let key = "flush_storage_test".to_string();
+        let value = "flush_storage_value".to_string();
+        let set_req = SetRequest {
+            key: key.clone(),
+            value: value.clone(),
+        };
+        kv_client.set(set_req).await.unwrap();
+
+        let mut client = PlacementCenterServiceClient::connect(pc_addr())
+            .await
+            .unwrap();
+        match client
+            .flush_storage(tonic::Request::new(FlushStorageRequest::default()))
+            .await
+        {
+            Ok(reply) => println!("flush finished in {}ms", reply.into_inner().duration_ms),
+            Err(e) => {
+                println!("{}", e.to_string());
+                assert!(false)
+            }
+        }
+
+        let get_req = GetRequest { key: key.clone() };
+        let get_rep = kv_client.get(get_req).await.unwrap().into_inner();
+        assert_eq!(value, get_rep.value);
+    }
+
+    #[tokio::test]
+    async fn compact_storage() {
+        let mut client = PlacementCenterServiceClient::connect(pc_addr())
+            .await
+            .unwrap();
+        let request = CompactStorageRequest {
+            cf_name: "cluster".to_string(),
+        };
+        match client
+            .compact_storage(tonic::Request::new(request))
+            .await
+        {
+            Ok(reply) => println!("compaction finished in {}ms", reply.into_inner().duration_ms),
+            Err(e) => {
+                println!("{}", e.to_string());
+                assert!(false)
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn compact_unknown_column_family() {
+        let mut client = PlacementCenterServiceClient::connect(pc_addr())
+            .await
+            .unwrap();
+        let request = CompactStorageRequest {
+            cf_name: "unknown".to_string(),
+        };
+        let status = client
+            .compact_storage(tonic::Request::new(request))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+}
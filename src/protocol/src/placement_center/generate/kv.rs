@@ -50,6 +50,19 @@ pub struct ExistsReply {
     #[prost(bool, tag = "1")]
     pub flag: bool,
 }
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CompareAndSwapRequest {
+    #[prost(string, tag = "1")]
+    pub cf: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub key: ::prost::alloc::string::String,
+    /// when not set the key must not exist yet
+    #[prost(string, optional, tag = "3")]
+    pub expected: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, tag = "4")]
+    pub new: ::prost::alloc::string::String,
+}
 /// Generated client implementations.
 pub mod kv_service_client {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
@@ -22,6 +22,49 @@ pub struct ClusterStatusReply {
     pub leader: ::prost::alloc::string::String,
     #[prost(string, repeated, tag = "2")]
     pub nodes: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(uint64, tag = "3")]
+    pub node_id: u64,
+    #[prost(string, tag = "4")]
+    pub role: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "5")]
+    pub term: u64,
+    #[prost(uint64, tag = "6")]
+    pub leader_id: u64,
+    #[prost(uint64, tag = "7")]
+    pub commit_index: u64,
+    #[prost(uint64, tag = "8")]
+    pub last_index: u64,
+    #[prost(uint64, tag = "9")]
+    pub applied_index: u64,
+    #[prost(message, repeated, tag = "10")]
+    pub peers: ::prost::alloc::vec::Vec<PeerHealth>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PeerHealth {
+    #[prost(uint64, tag = "1")]
+    pub node_id: u64,
+    #[prost(uint64, tag = "2")]
+    pub last_contact_ms: u64,
+    #[prost(uint32, tag = "3")]
+    pub consecutive_failures: u32,
+    #[prost(bool, tag = "4")]
+    pub unreachable: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ClusterConfigRequest {}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ClusterConfigReply {
+    #[prost(uint64, repeated, tag = "1")]
+    pub voters: ::prost::alloc::vec::Vec<u64>,
+    #[prost(uint64, repeated, tag = "2")]
+    pub learners: ::prost::alloc::vec::Vec<u64>,
+    #[prost(uint64, repeated, tag = "3")]
+    pub voters_outgoing: ::prost::alloc::vec::Vec<u64>,
+    #[prost(uint64, tag = "4")]
+    pub leader_id: u64,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -53,6 +96,32 @@ pub struct SendRaftConfChangeRequest {
 pub struct SendRaftConfChangeReply {}
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct InstallSnapshotRequest {
+    #[prost(uint64, tag = "1")]
+    pub node_id: u64,
+    #[prost(uint64, tag = "2")]
+    pub term: u64,
+    #[prost(uint64, tag = "3")]
+    pub index: u64,
+    #[prost(uint64, tag = "4")]
+    pub offset: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct InstallSnapshotReply {
+    #[prost(uint64, tag = "1")]
+    pub term: u64,
+    #[prost(uint64, tag = "2")]
+    pub index: u64,
+    #[prost(uint64, tag = "3")]
+    pub offset: u64,
+    #[prost(uint64, tag = "4")]
+    pub total_size: u64,
+    #[prost(bytes = "vec", tag = "5")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct NodeListRequest {
     #[prost(string, tag = "1")]
     pub cluster_name: ::prost::alloc::string::String,
@@ -173,6 +242,54 @@ pub struct DeleteIdempotentDataRequest {
     #[prost(uint64, tag = "3")]
     pub seq_num: u64,
 }
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FlushStorageRequest {}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FlushStorageReply {
+    #[prost(uint64, tag = "1")]
+    pub duration_ms: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CompactStorageRequest {
+    #[prost(string, tag = "1")]
+    pub cf_name: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CompactStorageReply {
+    #[prost(uint64, tag = "1")]
+    pub duration_ms: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReplicationStatusRequest {}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PeerProgress {
+    #[prost(uint64, tag = "1")]
+    pub node_id: u64,
+    #[prost(uint64, tag = "2")]
+    pub matched: u64,
+    #[prost(uint64, tag = "3")]
+    pub next_index: u64,
+    #[prost(bool, tag = "4")]
+    pub learner: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReplicationStatusReply {
+    #[prost(uint64, tag = "1")]
+    pub term: u64,
+    #[prost(uint64, tag = "2")]
+    pub commit_index: u64,
+    #[prost(uint64, tag = "3")]
+    pub quorum_commit_index: u64,
+    #[prost(message, repeated, tag = "4")]
+    pub peers: ::prost::alloc::vec::Vec<PeerProgress>,
+}
 /// Generated client implementations.
 pub mod placement_center_service_client {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
@@ -287,6 +404,28 @@ pub mod placement_center_service_client {
                 );
             self.inner.unary(req, path, codec).await
         }
+        pub async fn cluster_config(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ClusterConfigRequest>,
+        ) -> std::result::Result<tonic::Response<super::ClusterConfigReply>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/placement.PlacementCenterService/ClusterConfig",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("placement.PlacementCenterService", "ClusterConfig"));
+            self.inner.unary(req, path, codec).await
+        }
         pub async fn node_list(
             &mut self,
             request: impl tonic::IntoRequest<super::NodeListRequest>,
@@ -477,6 +616,36 @@ pub mod placement_center_service_client {
                 );
             self.inner.unary(req, path, codec).await
         }
+        pub async fn install_snapshot(
+            &mut self,
+            request: impl tonic::IntoRequest<super::InstallSnapshotRequest>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::InstallSnapshotReply>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/placement.PlacementCenterService/InstallSnapshot",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "placement.PlacementCenterService",
+                        "InstallSnapshot",
+                    ),
+                );
+            self.inner.server_streaming(req, path, codec).await
+        }
         pub async fn set_resource_config(
             &mut self,
             request: impl tonic::IntoRequest<super::SetResourceConfigRequest>,
@@ -657,6 +826,72 @@ pub mod placement_center_service_client {
                 );
             self.inner.unary(req, path, codec).await
         }
+        pub async fn flush_storage(
+            &mut self,
+            request: impl tonic::IntoRequest<super::FlushStorageRequest>,
+        ) -> std::result::Result<tonic::Response<super::FlushStorageReply>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/placement.PlacementCenterService/FlushStorage",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("placement.PlacementCenterService", "FlushStorage"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn compact_storage(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CompactStorageRequest>,
+        ) -> std::result::Result<tonic::Response<super::CompactStorageReply>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/placement.PlacementCenterService/CompactStorage",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("placement.PlacementCenterService", "CompactStorage"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn replication_status(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ReplicationStatusRequest>,
+        ) -> std::result::Result<tonic::Response<super::ReplicationStatusReply>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/placement.PlacementCenterService/ReplicationStatus",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("placement.PlacementCenterService", "ReplicationStatus"));
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -673,6 +908,10 @@ pub mod placement_center_service_server {
             tonic::Response<super::ClusterStatusReply>,
             tonic::Status,
         >;
+        async fn cluster_config(
+            &self,
+            request: tonic::Request<super::ClusterConfigRequest>,
+        ) -> std::result::Result<tonic::Response<super::ClusterConfigReply>, tonic::Status>;
         async fn node_list(
             &self,
             request: tonic::Request<super::NodeListRequest>,
@@ -719,6 +958,19 @@ pub mod placement_center_service_server {
             tonic::Response<super::SendRaftConfChangeReply>,
             tonic::Status,
         >;
+        /// Server streaming response type for the InstallSnapshot method.
+        type InstallSnapshotStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<super::InstallSnapshotReply, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        async fn install_snapshot(
+            &self,
+            request: tonic::Request<super::InstallSnapshotRequest>,
+        ) -> std::result::Result<
+            tonic::Response<Self::InstallSnapshotStream>,
+            tonic::Status,
+        >;
         async fn set_resource_config(
             &self,
             request: tonic::Request<super::SetResourceConfigRequest>,
@@ -761,6 +1013,18 @@ pub mod placement_center_service_server {
             tonic::Response<super::super::common::CommonReply>,
             tonic::Status,
         >;
+        async fn flush_storage(
+            &self,
+            request: tonic::Request<super::FlushStorageRequest>,
+        ) -> std::result::Result<tonic::Response<super::FlushStorageReply>, tonic::Status>;
+        async fn compact_storage(
+            &self,
+            request: tonic::Request<super::CompactStorageRequest>,
+        ) -> std::result::Result<tonic::Response<super::CompactStorageReply>, tonic::Status>;
+        async fn replication_status(
+            &self,
+            request: tonic::Request<super::ReplicationStatusRequest>,
+        ) -> std::result::Result<tonic::Response<super::ReplicationStatusReply>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct PlacementCenterServiceServer<T: PlacementCenterService> {
@@ -892,6 +1156,53 @@ pub mod placement_center_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/placement.PlacementCenterService/ClusterConfig" => {
+                    #[allow(non_camel_case_types)]
+                    struct ClusterConfigSvc<T: PlacementCenterService>(pub Arc<T>);
+                    impl<
+                        T: PlacementCenterService,
+                    > tonic::server::UnaryService<super::ClusterConfigRequest>
+                    for ClusterConfigSvc<T> {
+                        type Response = super::ClusterConfigReply;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ClusterConfigRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as PlacementCenterService>::cluster_config(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ClusterConfigSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/placement.PlacementCenterService/NodeList" => {
                     #[allow(non_camel_case_types)]
                     struct NodeListSvc<T: PlacementCenterService>(pub Arc<T>);
@@ -1236,6 +1547,58 @@ pub mod placement_center_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/placement.PlacementCenterService/InstallSnapshot" => {
+                    #[allow(non_camel_case_types)]
+                    struct InstallSnapshotSvc<T: PlacementCenterService>(pub Arc<T>);
+                    impl<
+                        T: PlacementCenterService,
+                    > tonic::server::ServerStreamingService<
+                        super::InstallSnapshotRequest,
+                    > for InstallSnapshotSvc<T> {
+                        type Response = super::InstallSnapshotReply;
+                        type ResponseStream = T::InstallSnapshotStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::InstallSnapshotRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as PlacementCenterService>::install_snapshot(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = InstallSnapshotSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/placement.PlacementCenterService/SetResourceConfig" => {
                     #[allow(non_camel_case_types)]
                     struct SetResourceConfigSvc<T: PlacementCenterService>(pub Arc<T>);
@@ -1542,6 +1905,147 @@ pub mod placement_center_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/placement.PlacementCenterService/FlushStorage" => {
+                    #[allow(non_camel_case_types)]
+                    struct FlushStorageSvc<T: PlacementCenterService>(pub Arc<T>);
+                    impl<
+                        T: PlacementCenterService,
+                    > tonic::server::UnaryService<super::FlushStorageRequest>
+                    for FlushStorageSvc<T> {
+                        type Response = super::FlushStorageReply;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::FlushStorageRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as PlacementCenterService>::flush_storage(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = FlushStorageSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/placement.PlacementCenterService/CompactStorage" => {
+                    #[allow(non_camel_case_types)]
+                    struct CompactStorageSvc<T: PlacementCenterService>(pub Arc<T>);
+                    impl<
+                        T: PlacementCenterService,
+                    > tonic::server::UnaryService<super::CompactStorageRequest>
+                    for CompactStorageSvc<T> {
+                        type Response = super::CompactStorageReply;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CompactStorageRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as PlacementCenterService>::compact_storage(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = CompactStorageSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/placement.PlacementCenterService/ReplicationStatus" => {
+                    #[allow(non_camel_case_types)]
+                    struct ReplicationStatusSvc<T: PlacementCenterService>(pub Arc<T>);
+                    impl<
+                        T: PlacementCenterService,
+                    > tonic::server::UnaryService<super::ReplicationStatusRequest>
+                    for ReplicationStatusSvc<T> {
+                        type Response = super::ReplicationStatusReply;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ReplicationStatusRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as PlacementCenterService>::replication_status(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ReplicationStatusSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => {
                     Box::pin(async move {
                         Ok(
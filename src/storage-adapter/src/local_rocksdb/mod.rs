@@ -82,27 +82,27 @@ impl StorageAdapter for RocksDBStorageAdapter {
         let key = self.offset_shard_key(shard_name);
         self.db
             .write(cf, key.as_str(), &0_u128)
-            .map_err(CommonError::CommmonError)
+            .map_err(CommonError::from)
     }
 
     async fn delete_shard(&self, shard_name: String) -> Result<(), CommonError> {
         let cf = self.db.cf_handle(DB_COLUMN_FAMILY_RECORD).unwrap();
-        self.db.delete_prefix(cf, shard_name.as_str())
+        Ok(self.db.delete_prefix(cf, shard_name.as_str())?)
     }
 
     async fn set(&self, key: String, value: Record) -> Result<(), CommonError> {
         let cf = self.db.cf_handle(DB_COLUMN_FAMILY_KV).unwrap();
         self.db
             .write(cf, key.as_str(), &value)
-            .map_err(CommonError::CommmonError)
+            .map_err(CommonError::from)
     }
     async fn get(&self, key: String) -> Result<Option<Record>, CommonError> {
         let cf = self.db.cf_handle(DB_COLUMN_FAMILY_KV).unwrap();
-        self.db.read(cf, &key).map_err(CommonError::CommmonError)
+        self.db.read(cf, &key).map_err(CommonError::from)
     }
     async fn delete(&self, key: String) -> Result<(), CommonError> {
         let cf = self.db.cf_handle(DB_COLUMN_FAMILY_KV).unwrap();
-        self.db.delete(cf, key.as_str())
+        Ok(self.db.delete(cf, key.as_str())?)
     }
     async fn exists(&self, key: String) -> Result<bool, CommonError> {
         let cf = self.db.cf_handle(DB_COLUMN_FAMILY_KV).unwrap();
@@ -119,7 +119,7 @@ impl StorageAdapter for RocksDBStorageAdapter {
         let offset = self
             .db
             .read::<u128>(cf, key_shard_offset.as_str())
-            .map_err(CommonError::CommmonError)?
+            .map_err(CommonError::from)?
             .unwrap_or(0);
 
         let mut start_offset = offset;
@@ -135,13 +135,13 @@ impl StorageAdapter for RocksDBStorageAdapter {
                     format!("{}_record_{}", shard_name, start_offset).as_str(),
                     &msg,
                 )
-                .map_err(CommonError::CommmonError)?;
+                .map_err(CommonError::from)?;
             start_offset += 1;
         }
 
         self.db
             .write(cf, key_shard_offset.as_str(), &start_offset)
-            .map_err(CommonError::CommmonError)?;
+            .map_err(CommonError::from)?;
 
         return Ok(offset_res);
     }
@@ -158,7 +158,7 @@ impl StorageAdapter for RocksDBStorageAdapter {
         let offset = self
             .db
             .read::<u128>(cf, group_offset_key.as_str())
-            .map_err(CommonError::CommmonError)?
+            .map_err(CommonError::from)?
             .unwrap_or(0);
 
         let num = if let Some(num) = record_num { num } else { 10 };
@@ -169,7 +169,7 @@ impl StorageAdapter for RocksDBStorageAdapter {
             let value = self
                 .db
                 .read::<Record>(cf, self.record_key(&shard_name, i).as_str())
-                .map_err(CommonError::CommmonError)?;
+                .map_err(CommonError::from)?;
 
             if let Some(value) = value {
                 result.push(value.clone());
@@ -182,7 +182,7 @@ impl StorageAdapter for RocksDBStorageAdapter {
         if cur_offset > 0 {
             self.db
                 .write(cf, group_offset_key.as_str(), &(offset + cur_offset))
-                .map_err(CommonError::CommmonError)?;
+                .map_err(CommonError::from)?;
         }
         return Ok(Some(result));
     }
@@ -197,7 +197,7 @@ impl StorageAdapter for RocksDBStorageAdapter {
         let key = self.offset_key(group_id, shard_name);
         self.db
             .write(cf, key.as_str(), &offset)
-            .map_err(CommonError::CommmonError)?;
+            .map_err(CommonError::from)?;
         return Ok(true);
     }
 
@@ -209,7 +209,7 @@ impl StorageAdapter for RocksDBStorageAdapter {
         let cf = self.db.cf_handle(DB_COLUMN_FAMILY_RECORD).unwrap();
         self.db
             .read::<Record>(cf, self.record_key(shard_name, offset as u128).as_str())
-            .map_err(CommonError::CommmonError)
+            .map_err(CommonError::from)
     }
 
     async fn stream_read_by_timestamp(